@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compares the allocations of mutations with and without a reused
+//! [`TraversalScratch`].
+//!
+//! Run with `cargo bench --features serde --bench scratch`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use rpds_pathtree::{NodeValue, SimpleJsonTree, SimplePath, TraversalScratch};
+use serde_json::json;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[allow(unsafe_code)]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const SUBTREES_COUNT: usize = 100;
+const SUBTREE_LEAVES_COUNT: usize = 50;
+const ROUNDS_COUNT: usize = 20;
+
+fn new_tree() -> SimpleJsonTree {
+    let mut tree = SimpleJsonTree::new(Default::default(), NodeValue::Inner(json!(null)));
+    for i in 0..SUBTREES_COUNT {
+        for j in 0..SUBTREE_LEAVES_COUNT {
+            tree.insert_or_update_node_value(
+                &SimplePath::new([format!("{i}"), format!("{j}")]),
+                NodeValue::Leaf(json!(j)),
+                &mut || json!(null),
+                |_| None,
+            )
+            .unwrap();
+        }
+    }
+    tree
+}
+
+fn subtree_node_ids(tree: &SimpleJsonTree) -> Vec<u64> {
+    tree.child_nodes(tree.root_node())
+        .map(|child| child.node.id)
+        .collect()
+}
+
+fn measure(label: &str, mut remove_subtrees: impl FnMut(&mut SimpleJsonTree, Vec<u64>)) {
+    let mut allocations = 0;
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ROUNDS_COUNT {
+        let mut tree = new_tree();
+        let node_ids = subtree_node_ids(&tree);
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let started = Instant::now();
+        remove_subtrees(&mut tree, node_ids);
+        elapsed += started.elapsed();
+        allocations += ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+        black_box(tree);
+    }
+    println!(
+        "{label}: {allocations} allocation(s), {elapsed:?}",
+        allocations = allocations / ROUNDS_COUNT,
+        elapsed = elapsed / u32::try_from(ROUNDS_COUNT).unwrap(),
+    );
+}
+
+fn main() {
+    measure("remove_subtree_by_id", |tree, node_ids| {
+        for node_id in node_ids {
+            black_box(tree.remove_subtree_by_id(node_id));
+        }
+    });
+    let mut scratch = TraversalScratch::new();
+    measure("remove_subtree_by_id_with_scratch", |tree, node_ids| {
+        for node_id in node_ids {
+            black_box(tree.remove_subtree_by_id_with_scratch(node_id, &mut scratch));
+        }
+    });
+    measure("retain_nodes", |tree, _| {
+        tree.retain_nodes(|node| node.node.leaf_value() != Some(&json!(0)));
+    });
+    let mut scratch = TraversalScratch::new();
+    measure("retain_nodes_with_scratch", |tree, _| {
+        tree.retain_nodes_with_scratch(
+            |node| node.node.leaf_value() != Some(&json!(0)),
+            &mut scratch,
+        );
+    });
+}
@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compare two tree snapshots and print the textual patch.
+//!
+//! Usage:
+//!
+//! ```sh
+//! # Write a snapshot from a textual patch that is applied to an empty tree
+//! pathtree-diff --from-patch <SNAPSHOT> < patch.txt
+//! # Print the patch that transforms the old into the new tree
+//! pathtree-diff <OLD_SNAPSHOT> <NEW_SNAPSHOT>
+//! ```
+//!
+//! Requires the feature "mmap".
+
+use std::{
+    borrow::Cow,
+    io::{self, Read as _, Write as _},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use rpds_pathtree::{
+    AnyNewNodeId, AnyPath, MappedSnapshot, NodeValue, PathTree, PathTreeTypes, SnapshotCodec,
+    TreePatch,
+};
+
+/// Trees with string segments and string values.
+#[derive(Debug, Clone, Default)]
+struct StringTreeTypes;
+
+impl PathTreeTypes for StringTreeTypes {
+    type NodeId = usize;
+    type NewNodeId = AnyNewNodeId;
+    type InnerValue = String;
+    type LeafValue = String;
+    type PathSegmentOwned = Arc<str>;
+    type PathSegment = str;
+    type RootPath = AnyPath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.into()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+impl SnapshotCodec for StringTreeTypes {
+    fn encode_path_segment(path_segment: &Self::PathSegment, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(path_segment.as_bytes());
+    }
+
+    fn decode_path_segment(bytes: &[u8]) -> Option<Self::PathSegmentOwned> {
+        std::str::from_utf8(bytes).ok().map(Into::into)
+    }
+
+    fn encode_inner_value(value: &Self::InnerValue, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn decode_inner_value(bytes: &[u8]) -> Option<Self::InnerValue> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    fn encode_leaf_value(value: &Self::LeafValue, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn decode_leaf_value(bytes: &[u8]) -> Option<Self::LeafValue> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+type StringTree = PathTree<StringTreeTypes>;
+
+fn load_snapshot(path: &str) -> Result<StringTree, String> {
+    // SAFETY: Snapshot files are not modified while this tool is running.
+    #[allow(unsafe_code)]
+    let snapshot = unsafe { MappedSnapshot::<StringTreeTypes>::open(path) }
+        .map_err(|err| format!("failed to open snapshot {path}: {err}"))?;
+    snapshot
+        .view()
+        .to_path_tree(AnyNewNodeId::default())
+        .map_err(|err| format!("failed to load snapshot {path}: {err}"))
+}
+
+fn write_snapshot_from_patch(snapshot_path: &str) -> Result<(), String> {
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|err| format!("failed to read patch: {err}"))?;
+    let patch = text
+        .parse::<TreePatch<StringTreeTypes>>()
+        .map_err(|err| format!("failed to parse patch: {err}"))?;
+    let mut tree = StringTree::new(AnyNewNodeId::default(), NodeValue::Inner(String::new()));
+    tree.apply_patch(&patch)
+        .map_err(|err| format!("failed to apply patch: {err}"))?;
+    let file = std::fs::File::create(snapshot_path)
+        .map_err(|err| format!("failed to create snapshot {snapshot_path}: {err}"))?;
+    tree.write_snapshot(io::BufWriter::new(file))
+        .map_err(|err| format!("failed to write snapshot {snapshot_path}: {err}"))
+}
+
+fn print_diff(old_path: &str, new_path: &str) -> Result<(), String> {
+    let old_tree = load_snapshot(old_path)?;
+    let new_tree = load_snapshot(new_path)?;
+    let patch = old_tree.diff_patch(&new_tree);
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{patch}").map_err(|err| format!("failed to print patch: {err}"))
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let result = match args.as_slice() {
+        ["--from-patch", path] => write_snapshot_from_patch(path),
+        [old_path, new_path] => print_diff(old_path, new_path),
+        _ => {
+            eprintln!("usage: pathtree-diff <OLD_SNAPSHOT> <NEW_SNAPSHOT>");
+            eprintln!("       pathtree-diff --from-patch <SNAPSHOT> < <PATCH>");
+            return ExitCode::from(2);
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,209 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Applies random sequences of operations and validates the tree after each operation.
+
+#![no_main]
+
+use std::{borrow::Cow, sync::Arc};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rpds_pathtree::{AnyNewNodeId, AnyPath, Node, NodeValue, PathTree, PathTreeTypes};
+
+#[derive(Debug, Clone, Default)]
+struct FuzzPathTreeTypes;
+
+impl PathTreeTypes for FuzzPathTreeTypes {
+    type NodeId = usize;
+    type NewNodeId = AnyNewNodeId;
+    type InnerValue = u8;
+    type LeafValue = u8;
+    type PathSegmentOwned = Arc<str>;
+    type PathSegment = str;
+    type RootPath = AnyPath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.into()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+/// Few distinct segments for provoking collisions.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+enum Segment {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Segment {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::A => "a",
+            Self::B => "b",
+            Self::C => "c",
+            Self::D => "d",
+        }
+    }
+}
+
+fn path(segments: &[Segment]) -> AnyPath {
+    AnyPath(
+        segments
+            .iter()
+            .map(|segment| segment.as_str().into())
+            .collect(),
+    )
+}
+
+#[derive(Debug, Arbitrary)]
+enum Value {
+    Inner(u8),
+    Leaf(u8),
+}
+
+impl From<Value> for NodeValue<FuzzPathTreeTypes> {
+    fn from(from: Value) -> Self {
+        match from {
+            Value::Inner(value) => Self::Inner(value),
+            Value::Leaf(value) => Self::Leaf(value),
+        }
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+enum Operation {
+    InsertOrUpdateNodeValue {
+        path: Vec<Segment>,
+        value: Value,
+        clone_leaf: bool,
+    },
+    InsertOrUpdateChildNodeValue {
+        parent_path: Vec<Segment>,
+        child: Segment,
+        old_child: Option<Segment>,
+        value: Value,
+    },
+    RemoveSubtree {
+        path: Vec<Segment>,
+    },
+    RemovePaths {
+        paths: Vec<Vec<Segment>>,
+    },
+    RewritePrefix {
+        old_prefix: Vec<Segment>,
+        new_prefix: Vec<Segment>,
+    },
+    InsertOrReplaceSubtree {
+        subtree_path: Vec<Segment>,
+        parent_path: Vec<Segment>,
+        child: Segment,
+        old_child: Option<Segment>,
+    },
+    RetainNodes {
+        modulus: u8,
+    },
+}
+
+fn apply(tree: &mut PathTree<FuzzPathTreeTypes>, operation: Operation) {
+    match operation {
+        Operation::InsertOrUpdateNodeValue {
+            path: segments,
+            value,
+            clone_leaf,
+        } => {
+            let _ = tree.insert_or_update_node_value(
+                &path(&segments),
+                value.into(),
+                &mut || 0,
+                |leaf_value| clone_leaf.then_some(*leaf_value),
+            );
+        }
+        Operation::InsertOrUpdateChildNodeValue {
+            parent_path,
+            child,
+            old_child,
+            value,
+        } => {
+            let Some(parent_node) = tree.find_node(&path(&parent_path)).map(Arc::clone) else {
+                return;
+            };
+            if !matches!(parent_node.node, Node::Inner(_)) {
+                return;
+            }
+            let _ = tree.insert_or_update_child_node_value(
+                &parent_node,
+                child.as_str(),
+                old_child.map(Segment::as_str),
+                value.into(),
+            );
+        }
+        Operation::RemoveSubtree { path: segments } => {
+            let Some(node_id) = tree.find_node(&path(&segments)).map(|node| node.id) else {
+                return;
+            };
+            tree.remove_subtree_by_id(node_id);
+        }
+        Operation::RemovePaths { paths } => {
+            let paths = paths
+                .iter()
+                .map(|segments| path(segments))
+                .collect::<Vec<_>>();
+            tree.remove_paths(&paths);
+        }
+        Operation::RewritePrefix {
+            old_prefix,
+            new_prefix,
+        } => {
+            let _ = tree.rewrite_prefix(&path(&old_prefix), &path(&new_prefix), &mut || 0);
+        }
+        Operation::InsertOrReplaceSubtree {
+            subtree_path,
+            parent_path,
+            child,
+            old_child,
+        } => {
+            let Some(subtree_node_id) = tree.find_node(&path(&subtree_path)).map(|node| node.id)
+            else {
+                return;
+            };
+            let mut source_tree = tree.clone();
+            let Some(removed) = source_tree.remove_subtree_by_id(subtree_node_id) else {
+                return;
+            };
+            let Some(parent_node) = tree.find_node(&path(&parent_path)).map(Arc::clone) else {
+                return;
+            };
+            if !matches!(parent_node.node, Node::Inner(_)) {
+                return;
+            }
+            let _ = tree.insert_or_replace_subtree(
+                &parent_node,
+                child.as_str(),
+                old_child.map(Segment::as_str),
+                removed.removed_subtree,
+            );
+        }
+        Operation::RetainNodes { modulus } => {
+            let modulus = usize::from(modulus.max(2));
+            tree.retain_nodes(|node| node.id % modulus != 0);
+        }
+    }
+}
+
+fuzz_target!(|operations: Vec<Operation>| {
+    let mut tree = PathTree::<FuzzPathTreeTypes>::new(AnyNewNodeId::default(), NodeValue::Inner(0));
+    for operation in operations {
+        apply(&mut tree, operation);
+        if let Err(err) = tree.validate() {
+            panic!("{err}: {tree:?}");
+        }
+    }
+});
@@ -0,0 +1,110 @@
+/*
+ * SPDX-FileCopyrightText: The rpds-pathtree authors
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+/*
+ * C API of rpds-pathtree, enabled by the "capi" feature.
+ *
+ * Must be kept in sync with src/capi.rs.
+ */
+
+#ifndef RPDS_PATHTREE_H
+#define RPDS_PATHTREE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque tree handle. */
+typedef struct RpdsPathTree RpdsPathTree;
+
+/* Result of a C API function. */
+typedef enum RpdsPathTreeStatus {
+    RPDS_PATHTREE_STATUS_OK = 0,
+    /* A required pointer argument is NULL. */
+    RPDS_PATHTREE_STATUS_NULL_POINTER = 1,
+    /* A string argument is not UTF-8 encoded. */
+    RPDS_PATHTREE_STATUS_INVALID_UTF8 = 2,
+    /* The node or a parent node does not exist. */
+    RPDS_PATHTREE_STATUS_NOT_FOUND = 3,
+    /* The path conflicts with an existing leaf node or
+     * an inner node with children should become a leaf node. */
+    RPDS_PATHTREE_STATUS_CONFLICT = 4,
+    /* A value or path segment contains an interior NUL byte and
+     * could not be returned as a NUL-terminated string. */
+    RPDS_PATHTREE_STATUS_INTERIOR_NUL = 5,
+} RpdsPathTreeStatus;
+
+/* Callback for visiting child nodes.
+ *
+ * The path_segment is only valid during the invocation. */
+typedef void (*RpdsPathTreeChildCallback)(void *user_data, size_t node_id,
+                                          const char *path_segment);
+
+/* Create a new tree with an inner root node.
+ *
+ * Returns NULL if root_value is NULL or not UTF-8 encoded. */
+RpdsPathTree *rpds_pathtree_new(const char *root_value);
+
+/* Create an independent copy of a tree in constant time. */
+RpdsPathTree *rpds_pathtree_clone(const RpdsPathTree *tree);
+
+/* Release a tree. */
+void rpds_pathtree_free(RpdsPathTree *tree);
+
+/* Release a string that has been returned by the API. */
+void rpds_pathtree_string_free(char *value);
+
+/* Insert or update the value of a node.
+ *
+ * Missing ancestor nodes are created as inner nodes with an empty value.
+ * The id of the inserted or updated node is stored in node_id unless NULL. */
+RpdsPathTreeStatus rpds_pathtree_insert(RpdsPathTree *tree, const char *path,
+                                        const char *value, bool leaf,
+                                        size_t *node_id);
+
+/* Find a node by path.
+ *
+ * The id of the node is stored in node_id unless NULL. */
+RpdsPathTreeStatus rpds_pathtree_find(const RpdsPathTree *tree,
+                                      const char *path, size_t *node_id);
+
+/* Remove a node with all its descendants.
+ *
+ * The number of removed nodes is stored in removed_count unless NULL.
+ * The root node could not be removed. */
+RpdsPathTreeStatus rpds_pathtree_remove(RpdsPathTree *tree, size_t node_id,
+                                        size_t *removed_count);
+
+/* Get the value of a node.
+ *
+ * The value is stored in value and must be released by
+ * rpds_pathtree_string_free(). The kind of the node is stored in leaf
+ * unless NULL. */
+RpdsPathTreeStatus rpds_pathtree_node_value(const RpdsPathTree *tree,
+                                            size_t node_id, char **value,
+                                            bool *leaf);
+
+/* Invoke a callback for each child of a node.
+ *
+ * The order of children is undefined. Returns
+ * RPDS_PATHTREE_STATUS_NULL_POINTER if callback is NULL. */
+RpdsPathTreeStatus rpds_pathtree_children(const RpdsPathTree *tree,
+                                          size_t node_id,
+                                          RpdsPathTreeChildCallback callback,
+                                          void *user_data);
+
+/* The total number of nodes.
+ *
+ * Returns 0 if tree is NULL. */
+size_t rpds_pathtree_nodes_count(const RpdsPathTree *tree);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RPDS_PATHTREE_H */
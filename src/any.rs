@@ -0,0 +1,199 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{
+    any::Any,
+    borrow::{Borrow as _, Cow},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use derive_more::{Display, Error};
+
+use crate::{NodeValue, PathTree, PathTreeTypes, RootPath, SegmentedPath};
+
+/// Type-erased value.
+pub type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// Sequential node ids of an [`AnyPathTree`].
+#[derive(Debug, Clone, Default)]
+pub struct AnyNewNodeId {
+    next_node_id: usize,
+}
+
+impl crate::NewNodeId<usize> for AnyNewNodeId {
+    fn new_node_id(&mut self) -> usize {
+        let next_node_id = self.next_node_id;
+        self.next_node_id = self.next_node_id.checked_add(1).expect("node id overflow");
+        next_node_id
+    }
+}
+
+/// Path of an [`AnyPathTree`].
+///
+/// The root path has no segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AnyPath(pub Vec<Arc<str>>);
+
+impl SegmentedPath<str> for AnyPath {
+    fn segments(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        let Self(segments) = self;
+        Box::new(segments.iter().map(AsRef::as_ref))
+    }
+
+    fn parent_child_segments(&self) -> (Box<dyn Iterator<Item = &str> + '_>, Option<&str>) {
+        let Self(segments) = self;
+        let Some((child_segment, parent_segments)) = segments.split_last() else {
+            return (Box::new(std::iter::empty()), None);
+        };
+        (
+            Box::new(parent_segments.iter().map(AsRef::as_ref)),
+            Some(child_segment),
+        )
+    }
+}
+
+impl RootPath<str> for AnyPath {
+    fn is_root(&self) -> bool {
+        let Self(segments) = self;
+        segments.is_empty()
+    }
+}
+
+/// Type system of an [`AnyPathTree`].
+#[derive(Debug, Clone, Default)]
+pub struct AnyPathTreeTypes;
+
+impl PathTreeTypes for AnyPathTreeTypes {
+    type NodeId = usize;
+    type NewNodeId = AnyNewNodeId;
+    type InnerValue = AnyValue;
+    type LeafValue = AnyValue;
+    type PathSegmentOwned = Arc<str>;
+    type PathSegment = str;
+    type RootPath = AnyPath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.into()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+/// Dynamically typed tree.
+///
+/// Erases the concrete [`PathTreeTypes`] for passing trees across plugin or
+/// scripting boundaries. Values are stored as [`AnyValue`] and path segments
+/// as `Arc<str>`.
+pub type AnyPathTree = PathTree<AnyPathTreeTypes>;
+
+/// A value of an [`AnyPathTree`] could not be converted into the requested type.
+#[derive(Debug, Display, Error)]
+#[display("value of node {node_id} has an unexpected type")]
+pub struct AnyValueTypeMismatch {
+    /// The id of the node in the [`AnyPathTree`].
+    pub node_id: usize,
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Convert into a dynamically typed tree.
+    ///
+    /// Node ids are reassigned sequentially.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn to_any(&self) -> AnyPathTree
+    where
+        T::PathSegmentOwned: AsRef<str>,
+        T::InnerValue: Any + Send + Sync,
+        T::LeafValue: Any + Send + Sync,
+    {
+        self.convert_nodes(
+            AnyNewNodeId::default(),
+            |path_segment| Arc::from(path_segment.as_ref()),
+            |_, value| -> Result<_, std::convert::Infallible> {
+                Ok(match value {
+                    NodeValue::Inner(value) => NodeValue::Inner(Arc::new(value) as AnyValue),
+                    NodeValue::Leaf(value) => NodeValue::Leaf(Arc::new(value) as AnyValue),
+                })
+            },
+        )
+        .expect("infallible")
+    }
+
+    /// Copy all nodes into a tree with different types.
+    ///
+    /// Parent nodes are converted before their children.
+    fn convert_nodes<U, E>(
+        &self,
+        new_node_id: U::NewNodeId,
+        mut convert_path_segment: impl FnMut(&T::PathSegmentOwned) -> U::PathSegmentOwned,
+        mut convert_value: impl FnMut(T::NodeId, NodeValue<T>) -> Result<NodeValue<U>, E>,
+    ) -> Result<PathTree<U>, E>
+    where
+        U: PathTreeTypes,
+    {
+        let root_node = self.root_node();
+        let root_value = convert_value(root_node.id.clone(), root_node.node.to_value())?;
+        let mut tree = PathTree::new(new_node_id, root_value);
+        let mut node_ids = HashMap::new();
+        node_ids.insert(root_node.id.clone(), tree.root_node_id());
+        for child in self.descendant_nodes(root_node) {
+            let node = self.get_node(child.node_id);
+            let parent = node.parent.as_ref().expect("has parent");
+            let new_parent_node = Arc::clone(tree.get_node(node_ids[&parent.node_id].clone()));
+            let new_path_segment = convert_path_segment(&parent.path_segment);
+            let new_value = convert_value(node.id.clone(), node.node.to_value())?;
+            let inserted = tree
+                .insert_or_update_child_node_value(
+                    &new_parent_node,
+                    new_path_segment.borrow(),
+                    None,
+                    new_value,
+                )
+                .expect("parent node is an inner node");
+            node_ids.insert(node.id.clone(), inserted.node.id.clone());
+        }
+        Ok(tree)
+    }
+}
+
+impl AnyPathTree {
+    /// Convert into a statically typed tree.
+    ///
+    /// Fails if any value could not be downcast into the requested type.
+    pub fn to_typed<T>(
+        &self,
+        new_node_id: T::NewNodeId,
+    ) -> Result<PathTree<T>, AnyValueTypeMismatch>
+    where
+        T: PathTreeTypes,
+        T::PathSegmentOwned: From<String>,
+        T::InnerValue: Any,
+        T::LeafValue: Any,
+    {
+        self.convert_nodes(
+            new_node_id,
+            |path_segment| path_segment.to_string().into(),
+            |node_id, value| {
+                let value = match value {
+                    NodeValue::Inner(value) => value
+                        .downcast_ref::<T::InnerValue>()
+                        .cloned()
+                        .map(NodeValue::Inner),
+                    NodeValue::Leaf(value) => value
+                        .downcast_ref::<T::LeafValue>()
+                        .cloned()
+                        .map(NodeValue::Leaf),
+                };
+                value.ok_or(AnyValueTypeMismatch { node_id })
+            },
+        )
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{collections::VecDeque, future::Future};
+
+use crate::{
+    InsertOrUpdateNodeValueError, MatchNodePath, Node, NodeInsertedOrUpdated, NodePathResolved,
+    NodeValue, PathTree, PathTreeTypes, RootPath as _, SegmentedPath as _,
+};
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Insert or update a node in the tree with async value factories.
+    ///
+    /// Same as [`Self::insert_or_update_node_value()`], but the values of missing
+    /// parent nodes are created asynchronously.
+    ///
+    /// All values are awaited one after another before the tree is modified.
+    /// The tree remains unchanged if the returned future is dropped before
+    /// completion.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub async fn insert_or_update_node_value_async<NewInnerValue, CloneLeafIntoInnerValue>(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        mut new_inner_value: impl FnMut() -> NewInnerValue,
+        try_clone_leaf_into_inner_value: impl FnOnce(T::LeafValue) -> CloneLeafIntoInnerValue,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>>
+    where
+        NewInnerValue: Future<Output = T::InnerValue>,
+        CloneLeafIntoInnerValue: Future<Output = Option<T::InnerValue>>,
+    {
+        let parent_segments_count = path.segments().count().saturating_sub(1);
+        let (matched_node, matched_segments_count) = self
+            .resolve_node_path(path, MatchNodePath::PartialOrFull)
+            .map_or(
+                (self.root_node(), 0),
+                |NodePathResolved { node, matched_path }| {
+                    (node, matched_path.number_of_matched_segments())
+                },
+            );
+        let mut leaf_into_inner_value = None;
+        if matched_segments_count <= parent_segments_count && !path.is_root() {
+            // The matched node becomes a parent node.
+            if let Node::Leaf(leaf) = &matched_node.node {
+                let leaf_value = leaf.value.clone();
+                leaf_into_inner_value = try_clone_leaf_into_inner_value(leaf_value).await;
+                if leaf_into_inner_value.is_none() {
+                    // Fails with a path conflict.
+                    return self.insert_or_update_node_value(
+                        path,
+                        new_value,
+                        &mut || unreachable!(),
+                        |_| None,
+                    );
+                }
+            }
+        }
+        let missing_ancestors_count = parent_segments_count.saturating_sub(matched_segments_count);
+        let mut new_inner_values = VecDeque::with_capacity(missing_ancestors_count);
+        for _ in 0..missing_ancestors_count {
+            new_inner_values.push_back(new_inner_value().await);
+        }
+        self.insert_or_update_node_value(
+            path,
+            new_value,
+            &mut || {
+                new_inner_values
+                    .pop_front()
+                    .expect("all missing values have been created")
+            },
+            |_| leaf_into_inner_value.take(),
+        )
+    }
+}
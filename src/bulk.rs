@@ -0,0 +1,108 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{
+    HalfEdgeOwned, InsertOrUpdateNodeValueError, NodeValue, PathTree, PathTreeTypes,
+    SegmentedPath as _,
+};
+
+/// Check if both paths address siblings, i.e. children of the same parent node.
+fn have_same_parent<T>(lhs: &T::RootPath, rhs: &T::RootPath) -> bool
+where
+    T: PathTreeTypes,
+{
+    let (lhs_parent_segments, lhs_child_segment) = lhs.parent_child_segments();
+    let (rhs_parent_segments, rhs_child_segment) = rhs.parent_child_segments();
+    lhs_child_segment.is_some()
+        && rhs_child_segment.is_some()
+        && lhs_parent_segments.eq(rhs_parent_segments)
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Insert or update many nodes at once.
+    ///
+    /// The entries are sorted by their paths before inserting them. Siblings
+    /// are thereby inserted consecutively and the parent node that has been
+    /// resolved for the first sibling is reused for all subsequent siblings.
+    /// Entries with equal paths are applied in their original order, i.e.
+    /// the last value wins.
+    ///
+    /// Each entry is inserted or updated like by
+    /// [`Self::insert_or_update_node_value()`].
+    ///
+    /// The tree remains unchanged if any entry could not be inserted. The
+    /// value of this entry is returned back to the caller.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn extend_from_iter(
+        &mut self,
+        entries: impl IntoIterator<Item = (T::RootPath, NodeValue<T>)>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        mut try_clone_leaf_into_inner_value: impl FnMut(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<(), InsertOrUpdateNodeValueError<T>>
+    where
+        T::PathSegment: Ord,
+    {
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by(|(lhs, _), (rhs, _)| lhs.segments().cmp(rhs.segments()));
+        log::debug!(
+            "Inserting or updating {count} node(s)",
+            count = entries.len()
+        );
+        self.with_rollback(|tree| {
+            // The path of the last inserted or updated node and the id of its parent node.
+            let mut last_resolved_parent: Option<(T::RootPath, T::NodeId)> = None;
+            for (path, value) in entries {
+                let parent_node = last_resolved_parent
+                    .as_ref()
+                    .filter(|(last_path, _)| have_same_parent::<T>(last_path, &path))
+                    .map(|(_, parent_node_id)| Arc::clone(tree.get_node(parent_node_id.clone())));
+                let inserted_or_updated = if let Some(parent_node) = parent_node {
+                    let (_, child_path_segment) = path.parent_child_segments();
+                    tree.insert_or_update_child_node_value(
+                        &parent_node,
+                        child_path_segment.expect("not the root path"),
+                        None,
+                        value,
+                    )?
+                } else {
+                    tree.insert_or_update_node_value(
+                        &path,
+                        value,
+                        new_inner_value,
+                        &mut try_clone_leaf_into_inner_value,
+                    )?
+                };
+                last_resolved_parent = inserted_or_updated
+                    .node
+                    .parent
+                    .as_ref()
+                    .map(|HalfEdgeOwned { node_id, .. }| (path, node_id.clone()));
+            }
+            Ok(())
+        })
+    }
+
+    /// Create a new path tree from many nodes.
+    ///
+    /// Creates a new tree with the given root node and inserts all entries
+    /// by [`Self::extend_from_iter()`].
+    pub fn try_from_iter(
+        new_node_id: T::NewNodeId,
+        root_node_value: NodeValue<T>,
+        entries: impl IntoIterator<Item = (T::RootPath, NodeValue<T>)>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        try_clone_leaf_into_inner_value: impl FnMut(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<Self, InsertOrUpdateNodeValueError<T>>
+    where
+        T::PathSegment: Ord,
+    {
+        let mut tree = Self::new(new_node_id, root_node_value);
+        tree.extend_from_iter(entries, new_inner_value, try_clone_leaf_into_inner_value)?;
+        Ok(tree)
+    }
+}
@@ -0,0 +1,361 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! C API
+//!
+//! A concrete instantiation of [`PathTree`] for embedding in non-Rust hosts.
+//!
+//! - Node ids are `size_t` values.
+//! - Paths are NUL-terminated, UTF-8 encoded strings with segments separated by `/`.
+//!   Empty segments are ignored, i.e. both `""` and `"/"` denote the root node.
+//! - Values are NUL-terminated, UTF-8 encoded strings.
+//!
+//! Trees are passed as opaque pointers that are created by [`rpds_pathtree_new()`]
+//! and must be released by [`rpds_pathtree_free()`]. Strings returned by the API
+//! must be released by [`rpds_pathtree_string_free()`].
+//!
+//! The declarations for C are provided by the header `include/rpds_pathtree.h`.
+
+#![allow(unsafe_code)] // FFI
+
+use std::{
+    borrow::Cow,
+    ffi::{c_char, c_void, CStr, CString},
+    sync::Arc,
+};
+
+use crate::{AnyNewNodeId, AnyPath, Node, NodeValue, PathTree, PathTreeTypes};
+
+/// Type system of the C API.
+#[derive(Debug, Clone, Default)]
+pub struct CApiPathTreeTypes;
+
+impl PathTreeTypes for CApiPathTreeTypes {
+    type NodeId = usize;
+    type NewNodeId = AnyNewNodeId;
+    type InnerValue = String;
+    type LeafValue = String;
+    type PathSegmentOwned = String;
+    type PathSegment = str;
+    type RootPath = AnyPath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.to_owned()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+/// Opaque tree handle.
+#[derive(Debug)]
+pub struct RpdsPathTree {
+    tree: PathTree<CApiPathTreeTypes>,
+}
+
+/// Result of a C API function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpdsPathTreeStatus {
+    Ok = 0,
+    /// A required pointer argument is `NULL`.
+    NullPointer = 1,
+    /// A string argument is not UTF-8 encoded.
+    InvalidUtf8 = 2,
+    /// The node or a parent node does not exist.
+    NotFound = 3,
+    /// The path conflicts with an existing leaf node or
+    /// an inner node with children should become a leaf node.
+    Conflict = 4,
+    /// A value or path segment contains an interior NUL byte and
+    /// could not be returned as a NUL-terminated string.
+    InteriorNul = 5,
+}
+
+/// Callback for visiting child nodes.
+///
+/// The `path_segment` is only valid during the invocation.
+/// `None` corresponds to a `NULL` function pointer.
+pub type RpdsPathTreeChildCallback =
+    Option<extern "C" fn(user_data: *mut c_void, node_id: usize, path_segment: *const c_char)>;
+
+/// # Safety
+///
+/// `ptr` must either be `NULL` or point to a valid, NUL-terminated string.
+unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Result<&'a str, RpdsPathTreeStatus> {
+    if ptr.is_null() {
+        return Err(RpdsPathTreeStatus::NullPointer);
+    }
+    // SAFETY: Guaranteed by the caller.
+    let c_str = unsafe { CStr::from_ptr(ptr) };
+    c_str.to_str().map_err(|_| RpdsPathTreeStatus::InvalidUtf8)
+}
+
+fn parse_path(path: &str) -> AnyPath {
+    AnyPath(
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(Arc::from)
+            .collect(),
+    )
+}
+
+const fn node_value(value: String, leaf: bool) -> NodeValue<CApiPathTreeTypes> {
+    if leaf {
+        NodeValue::Leaf(value)
+    } else {
+        NodeValue::Inner(value)
+    }
+}
+
+/// Create a new tree with an inner root node.
+///
+/// Returns `NULL` if `root_value` is `NULL` or not UTF-8 encoded.
+///
+/// # Safety
+///
+/// `root_value` must either be `NULL` or point to a valid, NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_new(root_value: *const c_char) -> *mut RpdsPathTree {
+    // SAFETY: Guaranteed by the caller.
+    let Ok(root_value) = (unsafe { str_from_ptr(root_value) }) else {
+        return std::ptr::null_mut();
+    };
+    let tree = PathTree::new(
+        AnyNewNodeId::default(),
+        NodeValue::Inner(root_value.to_owned()),
+    );
+    Box::into_raw(Box::new(RpdsPathTree { tree }))
+}
+
+/// Create an independent copy of a tree in constant time.
+///
+/// # Safety
+///
+/// `tree` must either be `NULL` or a valid tree handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_clone(tree: *const RpdsPathTree) -> *mut RpdsPathTree {
+    // SAFETY: Guaranteed by the caller.
+    let Some(tree) = (unsafe { tree.as_ref() }) else {
+        return std::ptr::null_mut();
+    };
+    Box::into_raw(Box::new(RpdsPathTree {
+        tree: tree.tree.clone(),
+    }))
+}
+
+/// Release a tree.
+///
+/// # Safety
+///
+/// `tree` must either be `NULL` or a valid tree handle that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_free(tree: *mut RpdsPathTree) {
+    if tree.is_null() {
+        return;
+    }
+    // SAFETY: Guaranteed by the caller.
+    drop(unsafe { Box::from_raw(tree) });
+}
+
+/// Release a string that has been returned by the API.
+///
+/// # Safety
+///
+/// `value` must either be `NULL` or a string returned by the API that is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_string_free(value: *mut c_char) {
+    if value.is_null() {
+        return;
+    }
+    // SAFETY: Guaranteed by the caller.
+    drop(unsafe { CString::from_raw(value) });
+}
+
+/// Insert or update the value of a node.
+///
+/// Missing ancestor nodes are created as inner nodes with an empty value.
+/// The id of the inserted or updated node is stored in `node_id` unless `NULL`.
+///
+/// # Safety
+///
+/// `tree` must be a valid tree handle, `path` and `value` must point to valid,
+/// NUL-terminated strings, and `node_id` must either be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_insert(
+    tree: *mut RpdsPathTree,
+    path: *const c_char,
+    value: *const c_char,
+    leaf: bool,
+    node_id: *mut usize,
+) -> RpdsPathTreeStatus {
+    // SAFETY: Guaranteed by the caller.
+    let Some(tree) = (unsafe { tree.as_mut() }) else {
+        return RpdsPathTreeStatus::NullPointer;
+    };
+    // SAFETY: Guaranteed by the caller.
+    let (path, value) = match unsafe { (str_from_ptr(path), str_from_ptr(value)) } {
+        (Ok(path), Ok(value)) => (parse_path(path), value.to_owned()),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+    let Ok(inserted) = tree.tree.insert_or_update_node_value(
+        &path,
+        node_value(value, leaf),
+        &mut String::new,
+        |_| None,
+    ) else {
+        return RpdsPathTreeStatus::Conflict;
+    };
+    // SAFETY: Guaranteed by the caller.
+    if let Some(node_id) = unsafe { node_id.as_mut() } {
+        *node_id = inserted.node.id;
+    }
+    RpdsPathTreeStatus::Ok
+}
+
+/// Find a node by path.
+///
+/// The id of the node is stored in `node_id` unless `NULL`.
+///
+/// # Safety
+///
+/// `tree` must be a valid tree handle, `path` must point to a valid,
+/// NUL-terminated string, and `node_id` must either be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_find(
+    tree: *const RpdsPathTree,
+    path: *const c_char,
+    node_id: *mut usize,
+) -> RpdsPathTreeStatus {
+    // SAFETY: Guaranteed by the caller.
+    let Some(tree) = (unsafe { tree.as_ref() }) else {
+        return RpdsPathTreeStatus::NullPointer;
+    };
+    // SAFETY: Guaranteed by the caller.
+    let path = match unsafe { str_from_ptr(path) } {
+        Ok(path) => parse_path(path),
+        Err(status) => return status,
+    };
+    let Some(node) = tree.tree.find_node(&path) else {
+        return RpdsPathTreeStatus::NotFound;
+    };
+    // SAFETY: Guaranteed by the caller.
+    if let Some(node_id) = unsafe { node_id.as_mut() } {
+        *node_id = node.id;
+    }
+    RpdsPathTreeStatus::Ok
+}
+
+/// Remove a node with all its descendants.
+///
+/// The number of removed nodes is stored in `removed_count` unless `NULL`.
+/// The root node could not be removed.
+///
+/// # Safety
+///
+/// `tree` must be a valid tree handle and `removed_count` must either be `NULL`
+/// or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_remove(
+    tree: *mut RpdsPathTree,
+    node_id: usize,
+    removed_count: *mut usize,
+) -> RpdsPathTreeStatus {
+    // SAFETY: Guaranteed by the caller.
+    let Some(tree) = (unsafe { tree.as_mut() }) else {
+        return RpdsPathTreeStatus::NullPointer;
+    };
+    let Some(removed) = tree.tree.remove_subtree_by_id(node_id) else {
+        return RpdsPathTreeStatus::NotFound;
+    };
+    // SAFETY: Guaranteed by the caller.
+    if let Some(removed_count) = unsafe { removed_count.as_mut() } {
+        *removed_count = removed.removed_subtree.nodes_count().get();
+    }
+    RpdsPathTreeStatus::Ok
+}
+
+/// Get the value of a node.
+///
+/// The value is stored in `value` and must be released by [`rpds_pathtree_string_free()`].
+/// The kind of the node is stored in `leaf` unless `NULL`.
+///
+/// # Safety
+///
+/// `tree` must be a valid tree handle, `value` must be valid for writes, and
+/// `leaf` must either be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_node_value(
+    tree: *const RpdsPathTree,
+    node_id: usize,
+    value: *mut *mut c_char,
+    leaf: *mut bool,
+) -> RpdsPathTreeStatus {
+    // SAFETY: Guaranteed by the caller.
+    let (Some(tree), Some(value)) = (unsafe { (tree.as_ref(), value.as_mut()) }) else {
+        return RpdsPathTreeStatus::NullPointer;
+    };
+    let Some(node) = tree.tree.lookup_node(node_id) else {
+        return RpdsPathTreeStatus::NotFound;
+    };
+    let (node_value, is_leaf) = match &node.node {
+        Node::Inner(inner) => (inner.value.as_str(), false),
+        Node::Leaf(leaf) => (leaf.value.as_str(), true),
+    };
+    let Ok(node_value) = CString::new(node_value) else {
+        return RpdsPathTreeStatus::InteriorNul;
+    };
+    *value = node_value.into_raw();
+    // SAFETY: Guaranteed by the caller.
+    if let Some(leaf) = unsafe { leaf.as_mut() } {
+        *leaf = is_leaf;
+    }
+    RpdsPathTreeStatus::Ok
+}
+
+/// Invoke a callback for each child of a node.
+///
+/// The order of children is undefined.
+///
+/// # Safety
+///
+/// `tree` must be a valid tree handle and `callback` must not be `NULL`.
+/// `user_data` is passed to the callback as is.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_children(
+    tree: *const RpdsPathTree,
+    node_id: usize,
+    callback: RpdsPathTreeChildCallback,
+    user_data: *mut c_void,
+) -> RpdsPathTreeStatus {
+    // SAFETY: Guaranteed by the caller.
+    let (Some(tree), Some(callback)) = (unsafe { tree.as_ref() }, callback) else {
+        return RpdsPathTreeStatus::NullPointer;
+    };
+    let Some(node) = tree.tree.lookup_node(node_id) else {
+        return RpdsPathTreeStatus::NotFound;
+    };
+    for child in node.node.children() {
+        let Ok(path_segment) = CString::new(child.path_segment) else {
+            return RpdsPathTreeStatus::InteriorNul;
+        };
+        callback(user_data, child.node_id, path_segment.as_ptr());
+    }
+    RpdsPathTreeStatus::Ok
+}
+
+/// The total number of nodes.
+///
+/// Returns 0 if `tree` is `NULL`.
+///
+/// # Safety
+///
+/// `tree` must either be `NULL` or a valid tree handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpds_pathtree_nodes_count(tree: *const RpdsPathTree) -> usize {
+    // SAFETY: Guaranteed by the caller.
+    unsafe { tree.as_ref() }.map_or(0, |tree| tree.tree.nodes_count().get())
+}
@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Storage for the children of inner nodes.
+
+#[cfg(not(feature = "insertion-order"))]
+pub(crate) type ChildrenMap<K, V> = crate::HashMap<K, V>;
+
+#[cfg(not(feature = "insertion-order"))]
+pub(crate) fn new_children_map<K: std::hash::Hash + Eq, V>() -> ChildrenMap<K, V> {
+    crate::new_hash_map()
+}
+
+#[cfg(feature = "insertion-order")]
+pub(crate) use self::ordered::{new_children_map, InsertionOrderedMap as ChildrenMap};
+
+#[cfg(feature = "insertion-order")]
+mod ordered {
+    use std::{borrow::Borrow, hash::Hash};
+
+    use crate::{new_hash_map, HashMap, SharedPointerKind};
+
+    type Index = u64;
+
+    /// Persistent hash map that iterates over its entries in insertion order.
+    ///
+    /// Replacing the value of an existing key retains its position.
+    /// Removed keys that are inserted again are appended at the end.
+    #[derive(Debug, Clone)]
+    pub(crate) struct InsertionOrderedMap<K, V>
+    where
+        K: Hash + Eq,
+    {
+        entries: HashMap<K, (Index, V)>,
+        order: rpds::RedBlackTreeMap<Index, K, SharedPointerKind>,
+        next_index: Index,
+    }
+
+    pub(crate) fn new_children_map<K: Hash + Eq, V>() -> InsertionOrderedMap<K, V> {
+        InsertionOrderedMap {
+            entries: new_hash_map(),
+            order: rpds::RedBlackTreeMap::new_with_ptr_kind(),
+            next_index: 0,
+        }
+    }
+
+    impl<K, V> InsertionOrderedMap<K, V>
+    where
+        K: Clone + Hash + Eq,
+    {
+        pub(crate) fn size(&self) -> usize {
+            debug_assert_eq!(self.entries.size(), self.order.size());
+            self.entries.size()
+        }
+
+        pub(crate) fn is_empty(&self) -> bool {
+            self.entries.is_empty()
+        }
+
+        pub(crate) fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.entries.get(key).map(|(_, value)| value)
+        }
+
+        pub(crate) fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.entries.contains_key(key)
+        }
+
+        pub(crate) fn insert_mut(&mut self, key: K, value: V) {
+            let index = if let Some((index, _)) = self.entries.get(&key) {
+                *index
+            } else {
+                let index = self.next_index;
+                self.next_index += 1;
+                self.order.insert_mut(index, key.clone());
+                index
+            };
+            self.entries.insert_mut(key, (index, value));
+        }
+
+        pub(crate) fn remove_mut<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let Some(index) = self.entries.get(key).map(|(index, _)| *index) else {
+                return false;
+            };
+            let removed = self.order.remove_mut(&index);
+            debug_assert!(removed);
+            self.entries.remove_mut(key)
+        }
+
+        /// In insertion order.
+        pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = (&K, &V)> + '_ {
+            self.order.values().map(|key| {
+                let (key, (_, value)) = self.entries.get_key_value(key).expect("entry exists");
+                (key, value)
+            })
+        }
+    }
+
+    impl<K, V> FromIterator<(K, V)> for InsertionOrderedMap<K, V>
+    where
+        K: Clone + Hash + Eq,
+    {
+        fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+            let mut map = new_children_map();
+            for (key, value) in iter {
+                map.insert_mut(key, value);
+            }
+            map
+        }
+    }
+}
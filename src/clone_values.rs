@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, sync::Arc};
+
+use crate::{
+    InsertOrUpdateNodeValueError, Node, NodeValue, PathTree, PathTreeTypes,
+    TreeNodeParentChildPathConflict,
+};
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Clone the values of nodes from another tree.
+    ///
+    /// Visits all nodes of `other`, parent nodes before their children. The
+    /// path of each node is passed to `map_path` as a sequence of path segments,
+    /// starting at the root node. The node is skipped if `None` is returned.
+    /// Otherwise its value is inserted or updated at the returned path in
+    /// this tree. An empty path denotes the root node. Missing ancestor nodes
+    /// are created with values provided by `new_inner_value`.
+    ///
+    /// Nodes are not shared with `other`, because their ids and parents differ
+    /// in this tree. Instead the values are cloned and the structure of this
+    /// tree is created from scratch. The cost of cloning depends on the value
+    /// types. Large payloads should be wrapped into an `Arc`, which is cloned by
+    /// incrementing a reference count, for sharing them between both trees.
+    ///
+    /// Returns the number of cloned values.
+    ///
+    /// The tree remains unchanged on error. In case of an error, the value
+    /// that could not be inserted or updated is returned back to the caller.
+    pub fn clone_values_from(
+        &mut self,
+        other: &Self,
+        mut map_path: impl FnMut(&[T::PathSegmentOwned]) -> Option<Vec<T::PathSegmentOwned>>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<usize, InsertOrUpdateNodeValueError<T>> {
+        self.with_rollback(|tree| {
+            let other_root_node = other.root_node();
+            let mut cloned_count = 0;
+            for node in std::iter::once(other_root_node).chain(
+                other
+                    .descendant_nodes(other_root_node)
+                    .map(|child| other.get_node(child.node_id)),
+            ) {
+                let mut path = other
+                    .ancestor_nodes(node)
+                    .map(|ancestor| T::path_segment_to_owned(ancestor.path_segment))
+                    .collect::<Vec<_>>();
+                path.reverse();
+                let Some(new_path) = map_path(&path) else {
+                    continue;
+                };
+                tree.insert_or_update_node_value_by_path_segments(
+                    tree.root_node_id(),
+                    &new_path,
+                    node.node.to_value(),
+                    new_inner_value,
+                )?;
+                cloned_count += 1;
+            }
+            log::debug!("Cloned {cloned_count} value(s)");
+            Ok(cloned_count)
+        })
+    }
+
+    /// Insert or update a node at a path relative to an existing node.
+    ///
+    /// An empty path denotes the given node.
+    pub(crate) fn insert_or_update_node_value_by_path_segments(
+        &mut self,
+        node_id: T::NodeId,
+        path: &[T::PathSegmentOwned],
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<(), InsertOrUpdateNodeValueError<T>> {
+        let Some((child_path_segment, parent_path)) = path.split_last() else {
+            let node = Arc::clone(self.get_node(node_id));
+            self.update_node_value(&node, new_value)?;
+            return Ok(());
+        };
+        let mut parent_node = Arc::clone(self.get_node(node_id));
+        if matches!(parent_node.node, Node::Leaf(_)) {
+            return Err(InsertOrUpdateNodeValueError::PathConflict {
+                conflict: TreeNodeParentChildPathConflict {
+                    parent_node,
+                    child_path_segment: path[0].clone(),
+                },
+                value: new_value,
+            });
+        }
+        for path_segment in parent_path {
+            if let Some(child_node_id) = parent_node.node.find_child(path_segment.borrow()) {
+                let child_node = self.get_node(child_node_id);
+                if matches!(child_node.node, Node::Leaf(_)) {
+                    return Err(InsertOrUpdateNodeValueError::PathConflict {
+                        conflict: TreeNodeParentChildPathConflict {
+                            parent_node,
+                            child_path_segment: path_segment.clone(),
+                        },
+                        value: new_value,
+                    });
+                }
+                parent_node = Arc::clone(child_node);
+                continue;
+            }
+            match self.insert_or_update_child_node_value(
+                &parent_node,
+                path_segment.borrow(),
+                None,
+                NodeValue::Inner(new_inner_value()),
+            ) {
+                Ok(inserted) => {
+                    parent_node = inserted.node;
+                }
+                Err(err) => {
+                    // Return the new value instead of the created inner value.
+                    let InsertOrUpdateNodeValueError::PathConflict { conflict, .. } = err else {
+                        unreachable!("inner values could always be inserted");
+                    };
+                    return Err(InsertOrUpdateNodeValueError::PathConflict {
+                        conflict,
+                        value: new_value,
+                    });
+                }
+            }
+        }
+        self.insert_or_update_child_node_value(
+            &parent_node,
+            child_path_segment.borrow(),
+            None,
+            new_value,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{NodeValue, PathTreeTypes};
+
+/// Conflicting values for the same node.
+#[derive(Debug)]
+pub struct ValueConflict<'a, T>
+where
+    T: PathTreeTypes,
+{
+    /// Path segments of the conflicting node, starting at the root node.
+    pub path: &'a [T::PathSegmentOwned],
+
+    /// The current value in the target tree.
+    pub ours: &'a NodeValue<T>,
+
+    /// The incoming value.
+    pub theirs: &'a NodeValue<T>,
+}
+
+/// Outcome of a [`ConflictPolicy`].
+#[derive(Debug, Clone)]
+pub enum ConflictResolution<T>
+where
+    T: PathTreeTypes,
+{
+    /// Keep the current value.
+    Ours,
+
+    /// Replace the current value with the incoming value.
+    Theirs,
+
+    /// Replace the current value with a custom value, e.g. a merged value.
+    Value(NodeValue<T>),
+
+    /// Leave the conflict unresolved.
+    Reject,
+}
+
+/// Resolves conflicting values.
+///
+/// Shared by all operations that need to combine values from different sources,
+/// e.g. [`crate::PathTree::apply_patch_with_policy()`] or
+/// [`crate::PathTree::insert_or_replace_subtree_with_policy()`].
+///
+/// Closures with a matching signature are policies.
+pub trait ConflictPolicy<T>
+where
+    T: PathTreeTypes,
+{
+    fn resolve(&mut self, conflict: ValueConflict<'_, T>) -> ConflictResolution<T>;
+}
+
+impl<T, F> ConflictPolicy<T> for F
+where
+    T: PathTreeTypes,
+    F: FnMut(ValueConflict<'_, T>) -> ConflictResolution<T>,
+{
+    fn resolve(&mut self, conflict: ValueConflict<'_, T>) -> ConflictResolution<T> {
+        self(conflict)
+    }
+}
+
+/// Leaves all conflicts unresolved.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RejectConflicts;
+
+impl<T> ConflictPolicy<T> for RejectConflicts
+where
+    T: PathTreeTypes,
+{
+    fn resolve(&mut self, _conflict: ValueConflict<'_, T>) -> ConflictResolution<T> {
+        ConflictResolution::Reject
+    }
+}
+
+/// Always keeps the current value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferOurs;
+
+impl<T> ConflictPolicy<T> for PreferOurs
+where
+    T: PathTreeTypes,
+{
+    fn resolve(&mut self, _conflict: ValueConflict<'_, T>) -> ConflictResolution<T> {
+        ConflictResolution::Ours
+    }
+}
+
+/// Always takes the incoming value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferTheirs;
+
+impl<T> ConflictPolicy<T> for PreferTheirs
+where
+    T: PathTreeTypes,
+{
+    fn resolve(&mut self, _conflict: ValueConflict<'_, T>) -> ConflictResolution<T> {
+        ConflictResolution::Theirs
+    }
+}
+
+/// Takes the newest value according to a version or timestamp.
+///
+/// The incoming value wins if both values are equally new.
+#[derive(Debug, Clone, Copy)]
+pub struct PreferNewest<F>(pub F);
+
+impl<T, F, V> ConflictPolicy<T> for PreferNewest<F>
+where
+    T: PathTreeTypes,
+    F: FnMut(&NodeValue<T>) -> V,
+    V: Ord,
+{
+    fn resolve(&mut self, conflict: ValueConflict<'_, T>) -> ConflictResolution<T> {
+        let Self(version_of) = self;
+        if version_of(conflict.ours) > version_of(conflict.theirs) {
+            ConflictResolution::Ours
+        } else {
+            ConflictResolution::Theirs
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Cow, fmt, hash::Hash};
+
+use derive_more::{Display, Error};
+
+use crate::{EventSourcedTree, PathTree, PathTreeTypes};
+
+/// Origin of a mutation.
+///
+/// Attached to log messages, errors, and recorded events for tracing
+/// changes back to the originating request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationContext {
+    /// The name of the operation, e.g. `"publish"`.
+    pub operation: Cow<'static, str>,
+
+    /// The path or location from which the operation originates,
+    /// e.g. a request URL.
+    pub source_path: Option<String>,
+
+    /// An opaque id for correlating related operations across systems.
+    pub correlation_id: Option<String>,
+}
+
+impl OperationContext {
+    #[must_use]
+    pub fn new(operation: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            operation: operation.into(),
+            source_path: None,
+            correlation_id: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_source_path(mut self, source_path: impl Into<String>) -> Self {
+        self.source_path = Some(source_path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+}
+
+impl fmt::Display for OperationContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            operation,
+            source_path,
+            correlation_id,
+        } = self;
+        f.write_str(operation)?;
+        if let Some(source_path) = source_path {
+            write!(f, " from {source_path}")?;
+        }
+        if let Some(correlation_id) = correlation_id {
+            write!(f, " [{correlation_id}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// An error annotated with the [`OperationContext`] of the failed operation.
+#[derive(Debug, Display, Error)]
+#[display("{context}: {source}")]
+pub struct ContextError<E> {
+    #[error(not(source))]
+    pub context: OperationContext,
+
+    pub source: E,
+}
+
+fn annotate_outcome<R, E>(
+    context: &OperationContext,
+    result: Result<R, E>,
+) -> Result<R, ContextError<E>>
+where
+    E: fmt::Display,
+{
+    match result {
+        Ok(result) => {
+            log::debug!("Finished {context}");
+            Ok(result)
+        }
+        Err(source) => {
+            log::warn!("Failed {context}: {source}");
+            Err(ContextError {
+                context: context.clone(),
+                source,
+            })
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Execute mutations within an operation context.
+    ///
+    /// The begin and the outcome of the operation are logged together
+    /// with the context. Errors are annotated with the context.
+    ///
+    /// The tree is not restored on errors. Combine with [`Self::with_rollback()`]
+    /// if needed.
+    pub fn with_context<R, E>(
+        &mut self,
+        context: &OperationContext,
+        modify: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, ContextError<E>>
+    where
+        E: fmt::Display,
+    {
+        log::debug!("Begin {context}");
+        annotate_outcome(context, modify(self))
+    }
+}
+
+impl<T, K> EventSourcedTree<T, K>
+where
+    T: PathTreeTypes,
+    K: Eq + Hash,
+{
+    /// Execute mutations within an operation context.
+    ///
+    /// Like [`PathTree::with_context()`]. Additionally, all events that are
+    /// recorded by the closure are associated with the context, see
+    /// [`Self::event_context()`]. Nested contexts replace the outer context
+    /// temporarily.
+    pub fn with_context<R, E>(
+        &mut self,
+        context: &OperationContext,
+        modify: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, ContextError<E>>
+    where
+        E: fmt::Display,
+    {
+        log::debug!("Begin {context}");
+        let outer_context = self.context.replace(context.clone());
+        let result = modify(self);
+        self.context = outer_context;
+        annotate_outcome(context, result)
+    }
+
+    /// The operation context of a recorded event.
+    ///
+    /// `None` if the event has been recorded outside of [`Self::with_context()`]
+    /// or if the index is out of bounds.
+    #[must_use]
+    pub fn event_context(&self, index: usize) -> Option<&OperationContext> {
+        self.event_contexts.get(&index)
+    }
+}
@@ -0,0 +1,231 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use derive_more::{Display, Error};
+
+use crate::{HalfEdgeOwned, InnerNode, Node, NodeValue, PathTree, PathTreeTypes, TreeNode};
+
+/// Error of [`TreeAppendCursor`].
+///
+/// The value that could not be appended is returned back to the caller.
+#[derive(Debug, Display, Error)]
+#[display("child node {node_id} already exists")]
+pub struct AppendChildNodeError<T>
+where
+    T: PathTreeTypes,
+{
+    /// The id of the existing child node.
+    #[error(not(source))]
+    pub node_id: T::NodeId,
+
+    #[error(not(source))]
+    pub value: NodeValue<T>,
+}
+
+#[derive(Debug)]
+struct PendingParentNode<T>
+where
+    T: PathTreeTypes,
+{
+    id: T::NodeId,
+    parent: Option<HalfEdgeOwned<T>>,
+    node: InnerNode<T>,
+}
+
+/// Appends nodes in depth-first order without resolving paths.
+///
+/// The cursor is positioned at a parent node. New leaf nodes are appended
+/// as children of this parent node. Appending a new inner node moves the
+/// cursor down to this node, i.e. subsequent nodes become its children.
+/// The cursor is moved up again by [`Self::ascend()`].
+///
+/// Parent nodes are written into the tree lazily when the cursor leaves
+/// them or when the cursor is finished. Each node is thereby written only
+/// once, independent of the number of its children. Importing entries in
+/// depth-first order needs linear time.
+///
+/// The cursor owns the tree, which is inconsistent while parent nodes
+/// are pending. The tree is only returned by [`Self::finish()`] after
+/// all pending parent nodes have been written. Dropping the cursor
+/// drops the tree. Cloning a tree is cheap, i.e. the original tree
+/// could be retained by passing a clone to [`PathTree::into_append_cursor()`].
+///
+/// Created by [`PathTree::into_append_cursor()`].
+#[derive(Debug)]
+pub struct TreeAppendCursor<T>
+where
+    T: PathTreeTypes,
+{
+    tree: PathTree<T>,
+    pending_parent_nodes: Vec<PendingParentNode<T>>,
+}
+
+impl<T> TreeAppendCursor<T>
+where
+    T: PathTreeTypes,
+{
+    fn parent_node(&self) -> &PendingParentNode<T> {
+        self.pending_parent_nodes
+            .last()
+            .expect("at least one pending parent node")
+    }
+
+    fn parent_node_mut(&mut self) -> &mut PendingParentNode<T> {
+        self.pending_parent_nodes
+            .last_mut()
+            .expect("at least one pending parent node")
+    }
+
+    /// The id of the current parent node.
+    #[must_use]
+    pub fn parent_node_id(&self) -> T::NodeId {
+        self.parent_node().id.clone()
+    }
+
+    /// The depth of the current parent node relative to the initial node.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.pending_parent_nodes.len() - 1
+    }
+
+    fn append_child_node(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+        value: NodeValue<T>,
+    ) -> Result<T::NodeId, AppendChildNodeError<T>> {
+        let parent_node = self.parent_node();
+        if let Some(node_id) = parent_node.node.find_child(child_path_segment) {
+            return Err(AppendChildNodeError { node_id, value });
+        }
+        let parent_node_id = parent_node.id.clone();
+        let child_node_id = self.tree.new_node_id();
+        self.parent_node_mut()
+            .node
+            .insert_child(child_path_segment, child_node_id.clone());
+        let parent = Some(HalfEdgeOwned {
+            path_segment: T::path_segment_to_owned(child_path_segment),
+            node_id: parent_node_id,
+        });
+        match value {
+            NodeValue::Inner(value) => {
+                // Written when leaving the node.
+                self.pending_parent_nodes.push(PendingParentNode {
+                    id: child_node_id.clone(),
+                    parent,
+                    node: InnerNode::new(value),
+                });
+            }
+            value @ NodeValue::Leaf(_) => {
+                self.tree.put_node(TreeNode {
+                    id: child_node_id.clone(),
+                    parent,
+                    node: Node::from_value_without_children(value),
+                });
+            }
+        }
+        Ok(child_node_id)
+    }
+
+    /// Append a leaf node to the current parent node.
+    ///
+    /// Returns the id of the new node.
+    ///
+    /// Fails if the parent node already has a child with the same path segment.
+    pub fn append_leaf(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+        value: T::LeafValue,
+    ) -> Result<T::NodeId, AppendChildNodeError<T>> {
+        self.append_child_node(child_path_segment, NodeValue::Leaf(value))
+    }
+
+    /// Append an inner node to the current parent node and descend into it.
+    ///
+    /// The new node becomes the current parent node.
+    ///
+    /// Returns the id of the new node.
+    ///
+    /// Fails if the parent node already has a child with the same path segment.
+    pub fn append_inner(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+        value: T::InnerValue,
+    ) -> Result<T::NodeId, AppendChildNodeError<T>> {
+        self.append_child_node(child_path_segment, NodeValue::Inner(value))
+    }
+
+    fn flush_parent_node(&mut self) -> Option<Arc<TreeNode<T>>> {
+        let PendingParentNode { id, parent, node } = self.pending_parent_nodes.pop()?;
+        Some(self.tree.put_node(TreeNode {
+            id,
+            parent,
+            node: Node::Inner(node),
+        }))
+    }
+
+    /// Leave the current parent node and move up to its parent node.
+    ///
+    /// Writes the current parent node into the tree.
+    ///
+    /// Returns `false` if the cursor is already positioned at the initial
+    /// node, which could not be left.
+    pub fn ascend(&mut self) -> bool {
+        if self.pending_parent_nodes.len() <= 1 {
+            return false;
+        }
+        self.flush_parent_node();
+        true
+    }
+
+    /// Write all pending parent nodes into the tree.
+    ///
+    /// Returns the tree and the updated initial node.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn finish(mut self) -> (PathTree<T>, Arc<TreeNode<T>>) {
+        while self.ascend() {}
+        let initial_node = self
+            .flush_parent_node()
+            .expect("at least one pending parent node");
+        let Self {
+            tree,
+            pending_parent_nodes: _,
+        } = self;
+        (tree, initial_node)
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Create a cursor for appending nodes below an inner node.
+    ///
+    /// The cursor takes ownership of the tree until it is finished.
+    ///
+    /// Fails if the node is a leaf node, part of a frozen subtree, or does
+    /// not belong to the tree, e.g. if it has been obtained from an older
+    /// snapshot. In case of an error, the tree is returned back to the caller.
+    pub fn into_append_cursor(self, node: &Arc<TreeNode<T>>) -> Result<TreeAppendCursor<T>, Self> {
+        if !self.contains_node(node) {
+            return Err(self);
+        }
+        if self.find_frozen_ancestor_or_self(node).is_some() {
+            return Err(self);
+        }
+        let Node::Inner(inner_node) = &node.node else {
+            return Err(self);
+        };
+        let pending_parent_node = PendingParentNode {
+            id: node.id.clone(),
+            parent: node.parent.clone(),
+            node: inner_node.clone(),
+        };
+        Ok(TreeAppendCursor {
+            tree: self,
+            pending_parent_nodes: vec![pending_parent_node],
+        })
+    }
+}
@@ -0,0 +1,78 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Debug output of hash maps independent of their iteration order.
+
+use std::fmt;
+
+/// Formats a string without quotes.
+struct Verbatim<'a>(&'a str);
+
+impl fmt::Debug for Verbatim<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+fn sorted_debug_keys<'a, K, V>(entries: impl IntoIterator<Item = (&'a K, V)>) -> Vec<(String, V)>
+where
+    K: fmt::Debug + ?Sized + 'a,
+{
+    let mut entries = entries
+        .into_iter()
+        .map(|(key, value)| (format!("{key:?}"), value))
+        .collect::<Vec<_>>();
+    entries.sort_unstable_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+    entries
+}
+
+/// Map entries sorted by the debug representation of their keys.
+pub(crate) struct SortedDebugMap<'a, V: ?Sized>(Vec<(String, &'a V)>);
+
+impl<'a, V> SortedDebugMap<'a, V>
+where
+    V: ?Sized,
+{
+    pub(crate) fn new<K>(entries: impl IntoIterator<Item = (&'a K, &'a V)>) -> Self
+    where
+        K: fmt::Debug + ?Sized + 'a,
+    {
+        Self(sorted_debug_keys(entries))
+    }
+}
+
+impl<V> fmt::Debug for SortedDebugMap<'_, V>
+where
+    V: fmt::Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(key, value)| (Verbatim(key), value)))
+            .finish()
+    }
+}
+
+/// Set entries sorted by their debug representation.
+pub(crate) struct SortedDebugSet(Vec<String>);
+
+impl SortedDebugSet {
+    pub(crate) fn new<'a, K>(entries: impl IntoIterator<Item = &'a K>) -> Self
+    where
+        K: fmt::Debug + ?Sized + 'a,
+    {
+        Self(
+            sorted_debug_keys(entries.into_iter().map(|key| (key, ())))
+                .into_iter()
+                .map(|(key, ())| key)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Debug for SortedDebugSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.0.iter().map(|key| Verbatim(key)))
+            .finish()
+    }
+}
@@ -0,0 +1,179 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use derive_more::{Display, Error};
+
+use crate::{
+    new_hash_set, HashSet, InsertOrUpdateNodeValueError, Node, NodeInsertedOrUpdated, NodeValue,
+    PathTree, PathTreeTypes, SubtreeRemoved, TreeNode,
+};
+
+/// A [`PathTree`] with deferred values of implicitly created ancestor nodes.
+///
+/// Missing ancestor nodes are inserted with a placeholder value. Their
+/// actual values are provided later by [`Self::fill_inner_value()`].
+/// This decouples creating the structure of the tree from computing
+/// the values of inner nodes.
+///
+/// Placeholders that are replaced by new values or removed from the tree
+/// are no longer considered as unfilled.
+#[derive(Debug, Clone)]
+pub struct DeferredValuesTree<T>
+where
+    T: PathTreeTypes,
+{
+    tree: PathTree<T>,
+    placeholder_value: T::InnerValue,
+    unfilled_node_ids: HashSet<T::NodeId>,
+}
+
+/// The node is not an unfilled placeholder.
+///
+/// The value is returned back to the caller.
+#[derive(Debug, Display, Error)]
+#[display("node {node_id} is not an unfilled placeholder")]
+pub struct FillInnerValueError<T>
+where
+    T: PathTreeTypes,
+{
+    pub node_id: T::NodeId,
+
+    #[error(not(source))]
+    pub value: T::InnerValue,
+}
+
+impl<T> DeferredValuesTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Defer the values of ancestor nodes that are created in the given tree.
+    ///
+    /// The placeholder value is cloned for each created ancestor node.
+    #[must_use]
+    pub fn new(tree: PathTree<T>, placeholder_value: T::InnerValue) -> Self {
+        Self {
+            tree,
+            placeholder_value,
+            unfilled_node_ids: new_hash_set(),
+        }
+    }
+
+    #[must_use]
+    pub const fn tree(&self) -> &PathTree<T> {
+        &self.tree
+    }
+
+    /// Release the tree, including all unfilled placeholders.
+    #[must_use]
+    pub fn into_tree(self) -> PathTree<T> {
+        let Self {
+            tree,
+            placeholder_value: _,
+            unfilled_node_ids: _,
+        } = self;
+        tree
+    }
+
+    /// Whether the values of all placeholders have been provided.
+    #[must_use]
+    pub fn is_filled(&self) -> bool {
+        self.unfilled_node_ids.is_empty()
+    }
+
+    #[must_use]
+    pub fn unfilled_nodes_count(&self) -> usize {
+        self.unfilled_node_ids.size()
+    }
+
+    /// Check if the given node is an unfilled placeholder.
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn is_unfilled(&self, node_id: T::NodeId) -> bool {
+        self.unfilled_node_ids.contains(&node_id)
+    }
+
+    /// All unfilled placeholder nodes.
+    ///
+    /// The order is undefined.
+    pub fn unfilled_nodes(&self) -> impl Iterator<Item = &Arc<TreeNode<T>>> {
+        self.unfilled_node_ids
+            .iter()
+            .map(|node_id| self.tree.get_node(node_id.clone()))
+    }
+
+    /// Insert or update a node in the tree.
+    ///
+    /// Same as [`PathTree::insert_or_update_node_value()`], but all missing
+    /// ancestor nodes are created with the placeholder value.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn insert_or_update_node_value(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
+        let mut placeholders_count = 0;
+        let inserted = self.tree.insert_or_update_node_value(
+            path,
+            new_value,
+            &mut || {
+                placeholders_count += 1;
+                self.placeholder_value.clone()
+            },
+            try_clone_leaf_into_inner_value,
+        )?;
+        // The node has received its final value. No other nodes are
+        // removed or replaced by inserting or updating a node.
+        self.unfilled_node_ids.remove_mut(&inserted.node.id);
+        // Missing ancestor nodes are created in a contiguous chain above the node.
+        for ancestor_node in self
+            .tree
+            .ancestor_nodes(&inserted.node)
+            .take(placeholders_count)
+        {
+            debug_assert!(matches!(ancestor_node.node.node, Node::Inner(_)));
+            self.unfilled_node_ids
+                .insert_mut(ancestor_node.node.id.clone());
+        }
+        Ok(inserted)
+    }
+
+    /// Provide the value of a placeholder node.
+    ///
+    /// Returns the updated node.
+    ///
+    /// Fails if the node does not exist or is not an unfilled placeholder.
+    /// In case of an error, the value is returned back to the caller.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn fill_inner_value(
+        &mut self,
+        node_id: T::NodeId,
+        value: T::InnerValue,
+    ) -> Result<Arc<TreeNode<T>>, FillInnerValueError<T>> {
+        if !self.unfilled_node_ids.remove_mut(&node_id) {
+            return Err(FillInnerValueError { node_id, value });
+        }
+        let node = Arc::clone(self.tree.get_node(node_id));
+        let updated_node = self
+            .tree
+            .update_node_value(&node, NodeValue::Inner(value))
+            .expect("placeholders are inner nodes");
+        Ok(updated_node)
+    }
+
+    /// Remove a node and its children from the tree.
+    ///
+    /// See [`PathTree::remove_subtree_by_id()`].
+    pub fn remove_subtree_by_id(&mut self, node_id: T::NodeId) -> Option<SubtreeRemoved<T>> {
+        let removed = self.tree.remove_subtree_by_id(node_id)?;
+        // Forget about placeholders that are no longer part of the tree.
+        if !self.unfilled_node_ids.is_empty() {
+            for removed_node in removed.removed_subtree.nodes() {
+                self.unfilled_node_ids.remove_mut(&removed_node.id);
+            }
+        }
+        Some(removed)
+    }
+}
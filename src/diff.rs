@@ -0,0 +1,181 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{HalfEdge, Node, PathTree, PathTreeTypes, TreeNode};
+
+/// Differences between two trees.
+///
+/// Nodes are matched by their id. This is only meaningful for
+/// trees that share a common history, i.e. snapshots of the same tree.
+#[derive(Debug, Clone)]
+pub struct TreeDiff<T>
+where
+    T: PathTreeTypes,
+{
+    /// Nodes that only exist in the new tree.
+    pub inserted: Vec<T::NodeId>,
+
+    /// Nodes that only exist in the old tree.
+    pub removed: Vec<T::NodeId>,
+
+    /// Nodes that exist in both trees but differ.
+    ///
+    /// Nodes are compared by identity and not by value. A node also differs
+    /// if its children have changed.
+    pub updated: Vec<T::NodeId>,
+}
+
+impl<T> TreeDiff<T>
+where
+    T: PathTreeTypes,
+{
+    /// Check if both trees are identical.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            inserted,
+            removed,
+            updated,
+        } = self;
+        inserted.is_empty() && removed.is_empty() && updated.is_empty()
+    }
+
+    /// Total number of inserted, removed, and updated nodes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let Self {
+            inserted,
+            removed,
+            updated,
+        } = self;
+        inserted.len() + removed.len() + updated.len()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Compare this (old) tree with a new tree.
+    ///
+    /// All node lists are in no particular order.
+    ///
+    /// Executed in linear time, i.e. O(n) with n the total number
+    /// of nodes in both trees.
+    #[must_use]
+    pub fn diff(&self, new_tree: &Self) -> TreeDiff<T> {
+        self.diff_with(new_tree, |old_node, new_node| {
+            !Arc::ptr_eq(old_node, new_node)
+        })
+    }
+
+    /// Compare the structure of this (old) tree with a new tree.
+    ///
+    /// Like [`Self::diff()`], but ignores value changes. A node is only
+    /// considered as updated if it has been moved or renamed or if it has
+    /// been changed from an inner into a leaf node or vice versa.
+    #[must_use]
+    pub fn diff_structure(&self, new_tree: &Self) -> TreeDiff<T> {
+        self.diff_with(new_tree, |old_node, new_node| {
+            !Arc::ptr_eq(old_node, new_node)
+                && (old_node.parent != new_node.parent
+                    || !is_same_node_kind(&old_node.node, &new_node.node))
+        })
+    }
+
+    /// Compare the values of this (old) tree with a new tree.
+    ///
+    /// Like [`Self::diff()`], but ignores structural changes. A node is only
+    /// considered as updated if its value has changed.
+    #[must_use]
+    pub fn diff_values(&self, new_tree: &Self) -> TreeDiff<T>
+    where
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        self.diff_with(new_tree, |old_node, new_node| {
+            !Arc::ptr_eq(old_node, new_node) && !is_same_node_value(&old_node.node, &new_node.node)
+        })
+    }
+
+    /// Check if both trees have the same structure.
+    ///
+    /// Compares the paths and kinds of all nodes, ignoring both values and node ids.
+    /// In contrast to [`Self::diff_structure()`] this also works for unrelated trees.
+    #[must_use]
+    pub fn has_same_structure(&self, other: &Self) -> bool {
+        let mut pending = vec![(self.root_node(), other.root_node())];
+        while let Some((node, other_node)) = pending.pop() {
+            if !is_same_node_kind(&node.node, &other_node.node)
+                || node.node.children_count() != other_node.node.children_count()
+            {
+                return false;
+            }
+            for HalfEdge {
+                path_segment,
+                child_key: _,
+                node_id,
+            } in node.node.children()
+            {
+                let Some(other_node_id) = other_node.node.find_child(path_segment) else {
+                    return false;
+                };
+                pending.push((self.get_node(node_id), other.get_node(other_node_id)));
+            }
+        }
+        true
+    }
+
+    fn diff_with(
+        &self,
+        new_tree: &Self,
+        mut is_updated: impl FnMut(&Arc<TreeNode<T>>, &Arc<TreeNode<T>>) -> bool,
+    ) -> TreeDiff<T> {
+        let mut removed = Vec::new();
+        let mut updated = Vec::new();
+        for old_node in self.nodes() {
+            match new_tree.lookup_node(old_node.id.clone()) {
+                Some(new_node) => {
+                    if is_updated(old_node, new_node) {
+                        updated.push(old_node.id.clone());
+                    }
+                }
+                None => {
+                    removed.push(old_node.id.clone());
+                }
+            }
+        }
+        let inserted = new_tree
+            .nodes()
+            .filter(|new_node| self.lookup_node(new_node.id.clone()).is_none())
+            .map(|new_node| new_node.id.clone())
+            .collect();
+        TreeDiff {
+            inserted,
+            removed,
+            updated,
+        }
+    }
+}
+
+const fn is_same_node_kind<T: PathTreeTypes>(lhs: &Node<T>, rhs: &Node<T>) -> bool {
+    matches!(
+        (lhs, rhs),
+        (Node::Inner(_), Node::Inner(_)) | (Node::Leaf(_), Node::Leaf(_))
+    )
+}
+
+fn is_same_node_value<T>(lhs: &Node<T>, rhs: &Node<T>) -> bool
+where
+    T: PathTreeTypes,
+    T::InnerValue: PartialEq,
+    T::LeafValue: PartialEq,
+{
+    match (lhs, rhs) {
+        (Node::Inner(lhs), Node::Inner(rhs)) => lhs.value == rhs.value,
+        (Node::Leaf(lhs), Node::Leaf(rhs)) => lhs.value == rhs.value,
+        _ => false,
+    }
+}
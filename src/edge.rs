@@ -46,25 +46,31 @@ pub struct HalfEdge<'a, T: PathTreeTypes> {
     /// Path segment from the (implicit) source to the target node.
     pub path_segment: &'a T::PathSegment,
 
+    /// Lookup key of the target node, derived from the path segment.
+    pub child_key: &'a T::ChildKey,
+
     /// The id of the target node.
     pub node_id: T::NodeId,
 }
 
-impl<'a, T: PathTreeTypes> PartialEq for HalfEdge<'a, T> {
+impl<T: PathTreeTypes> PartialEq for HalfEdge<'_, T> {
     fn eq(&self, other: &Self) -> bool {
+        // The child key is derived from the path segment.
         let Self {
             path_segment,
+            child_key: _,
             node_id,
         } = self;
         let Self {
             node_id: other_node_id,
+            child_key: _,
             path_segment: other_path_segment,
         } = other;
         node_id.eq(other_node_id) && path_segment.eq(other_path_segment)
     }
 }
 
-impl<'a, T: PathTreeTypes> Eq for HalfEdge<'a, T>
+impl<T: PathTreeTypes> Eq for HalfEdge<'_, T>
 where
     T::NodeId: Eq,
     T::PathSegment: Eq,
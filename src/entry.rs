@@ -0,0 +1,166 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{
+    InsertOrUpdateNodeValueError, NodeInsertedOrUpdated, NodeValue, PathTree, PathTreeTypes,
+    SubtreeRemoved, TreeNode, UpdateNodeValueError,
+};
+
+/// A node in a [`PathTree`] that is addressed by its path.
+///
+/// Created by [`PathTree::entry()`].
+#[derive(Debug)]
+pub enum Entry<'a, T>
+where
+    T: PathTreeTypes,
+{
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+/// An existing node, see [`Entry`].
+#[derive(Debug)]
+pub struct OccupiedEntry<'a, T>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a mut PathTree<T>,
+    node: Arc<TreeNode<T>>,
+}
+
+/// A missing node, see [`Entry`].
+#[derive(Debug)]
+pub struct VacantEntry<'a, T>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a mut PathTree<T>,
+    path: T::RootPath,
+}
+
+impl<T> Entry<'_, T>
+where
+    T: PathTreeTypes,
+{
+    /// Insert a new node if the entry is vacant.
+    ///
+    /// Returns the existing or the inserted node. See
+    /// [`VacantEntry::insert()`] for how missing ancestor nodes are created.
+    pub fn or_insert_with(
+        self,
+        new_value: impl FnOnce() -> NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<Arc<TreeNode<T>>, InsertOrUpdateNodeValueError<T>> {
+        match self {
+            Self::Occupied(entry) => Ok(entry.node),
+            Self::Vacant(entry) => entry.insert(new_value(), new_inner_value),
+        }
+    }
+
+    /// Modify the value of an existing node.
+    ///
+    /// The closure receives a clone of the current value. Vacant entries
+    /// are returned unchanged.
+    pub fn and_modify(
+        self,
+        modify_value: impl FnOnce(&mut NodeValue<T>),
+    ) -> Result<Self, UpdateNodeValueError<T>> {
+        match self {
+            Self::Occupied(mut entry) => {
+                let mut value = entry.value();
+                modify_value(&mut value);
+                entry.insert(value)?;
+                Ok(Self::Occupied(entry))
+            }
+            Self::Vacant(entry) => Ok(Self::Vacant(entry)),
+        }
+    }
+}
+
+impl<T> OccupiedEntry<'_, T>
+where
+    T: PathTreeTypes,
+{
+    /// The existing node.
+    #[must_use]
+    pub const fn node(&self) -> &Arc<TreeNode<T>> {
+        &self.node
+    }
+
+    /// Clone the value of the node.
+    #[must_use]
+    pub fn value(&self) -> NodeValue<T> {
+        self.node.node.to_value()
+    }
+
+    /// Replace the value of the node.
+    ///
+    /// See [`PathTree::update_node_value()`].
+    pub fn insert(
+        &mut self,
+        new_value: NodeValue<T>,
+    ) -> Result<Arc<TreeNode<T>>, UpdateNodeValueError<T>> {
+        self.node = self.tree.update_node_value(&self.node, new_value)?;
+        Ok(Arc::clone(&self.node))
+    }
+
+    /// Remove the node and its subtree.
+    ///
+    /// See [`PathTree::remove_subtree_by_id()`].
+    #[must_use]
+    pub fn remove(self) -> Option<SubtreeRemoved<T>> {
+        let Self { tree, node } = self;
+        tree.remove_subtree_by_id(node.id.clone())
+    }
+}
+
+impl<T> VacantEntry<'_, T>
+where
+    T: PathTreeTypes,
+{
+    /// The path of the missing node.
+    #[must_use]
+    pub const fn path(&self) -> &T::RootPath {
+        &self.path
+    }
+
+    /// Insert a new node.
+    ///
+    /// Missing ancestor nodes are created with values provided by
+    /// `new_inner_value`. Existing leaf nodes on the path are not replaced,
+    /// i.e. the insertion fails with a path conflict. Use
+    /// [`PathTree::insert_or_update_node_value()`] for replacing them.
+    ///
+    /// Returns the inserted node.
+    pub fn insert(
+        self,
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<Arc<TreeNode<T>>, InsertOrUpdateNodeValueError<T>> {
+        let Self { tree, path } = self;
+        tree.insert_or_update_node_value(&path, new_value, new_inner_value, |_| None)
+            .map(|NodeInsertedOrUpdated { node, .. }| node)
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Get the entry of a node for in-place manipulation.
+    ///
+    /// Like [`std::collections::HashMap::entry()`] for the common pattern
+    /// of inserting a missing node or otherwise modifying an existing node.
+    pub fn entry(&mut self, path: &T::RootPath) -> Entry<'_, T> {
+        if let Some(node) = self.find_node(path).map(Arc::clone) {
+            Entry::Occupied(OccupiedEntry { tree: self, node })
+        } else {
+            Entry::Vacant(VacantEntry {
+                tree: self,
+                path: path.clone(),
+            })
+        }
+    }
+}
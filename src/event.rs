@@ -0,0 +1,440 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow, collections::BTreeMap, hash::Hash, sync::Arc};
+
+use derive_more::{Display, Error};
+
+use crate::{
+    new_hash_set, HashSet, InsertOrUpdateNodeValueError, MatchNodePath, NodeInsertedOrUpdated,
+    NodePathResolved, NodeValue, OperationContext, PathTree, PathTreeTypes, SegmentedPath as _,
+    SubtreeRemoved,
+};
+
+/// A mutation of a [`PathTree`].
+///
+/// Events address nodes by their path and contain all values that
+/// are needed for applying them deterministically, including the values
+/// of implicitly created ancestor nodes.
+///
+/// Events are serializable with the `"serde"` feature if all
+/// contained types are serializable. Serialized events are unversioned,
+/// see also [`PathTreeTypes::schema_tag()`].
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T::RootPath: serde::Serialize, T::PathSegmentOwned: serde::Serialize, \
+                     T::InnerValue: serde::Serialize, T::LeafValue: serde::Serialize",
+        deserialize = "T::RootPath: serde::Deserialize<'de>, \
+                       T::PathSegmentOwned: serde::Deserialize<'de>, \
+                       T::InnerValue: serde::Deserialize<'de>, T::LeafValue: serde::Deserialize<'de>"
+    ))
+)]
+pub enum PathTreeEvent<T>
+where
+    T: PathTreeTypes,
+{
+    /// See [`PathTree::insert_or_update_node_value()`].
+    InsertOrUpdateNodeValue {
+        path: T::RootPath,
+        value: NodeValue<T>,
+
+        /// Values of the missing ancestor nodes that have been created.
+        ///
+        /// In top-down order.
+        new_inner_values: Vec<T::InnerValue>,
+
+        /// Value of the inner node that replaced a leaf node.
+        ///
+        /// `None` if no leaf node has been replaced.
+        leaf_into_inner_value: Option<T::InnerValue>,
+    },
+
+    /// See [`PathTree::insert_or_update_child_node_value()`].
+    ///
+    /// Renames the child node if `old_child_path_segment` is provided.
+    InsertOrUpdateChildNodeValue {
+        parent_path: T::RootPath,
+        child_path_segment: T::PathSegmentOwned,
+        old_child_path_segment: Option<T::PathSegmentOwned>,
+        value: NodeValue<T>,
+    },
+
+    /// See [`PathTree::remove_subtree_by_id()`].
+    RemoveSubtree { path: T::RootPath },
+}
+
+#[derive(Debug, Display, Error)]
+pub enum ApplyEventError<T>
+where
+    T: PathTreeTypes,
+{
+    /// The addressed node does not exist or could not be removed.
+    #[display("path not found")]
+    PathNotFound {
+        #[error(not(source))]
+        event: PathTreeEvent<T>,
+    },
+    /// The recorded values do not match the current state of the tree.
+    #[display("inconsistent event")]
+    Inconsistent {
+        #[error(not(source))]
+        event: PathTreeEvent<T>,
+    },
+    #[display("{source}")]
+    InsertOrUpdateNodeValue {
+        source: InsertOrUpdateNodeValueError<T>,
+    },
+}
+
+/// Failed to replay a sequence of events.
+#[derive(Debug, Display, Error)]
+#[display("failed to replay event #{index}: {source}")]
+pub struct ReplayEventsError<T>
+where
+    T: PathTreeTypes,
+{
+    /// The index of the failed event.
+    pub index: usize,
+
+    pub source: ApplyEventError<T>,
+}
+
+/// A [`PathTree`] that records all mutations as events.
+///
+/// The current tree could be reconstructed deterministically by
+/// replaying the recorded events, starting with the initial tree.
+/// This includes the ids of all nodes.
+///
+/// Failed mutations are not recorded and leave the tree unchanged.
+///
+/// Events could optionally be applied with an idempotency key of type `K`,
+/// see [`Self::apply_idempotent()`].
+#[derive(Debug, Clone)]
+pub struct EventSourcedTree<T, K = ()>
+where
+    T: PathTreeTypes,
+    K: Eq + Hash,
+{
+    initial_tree: PathTree<T>,
+    tree: PathTree<T>,
+    events: Vec<PathTreeEvent<T>>,
+    applied_keys: HashSet<K>,
+    pub(crate) context: Option<OperationContext>,
+    pub(crate) event_contexts: BTreeMap<usize, OperationContext>,
+}
+
+impl<T, K> EventSourcedTree<T, K>
+where
+    T: PathTreeTypes,
+    K: Eq + Hash,
+{
+    /// Start recording events for the given tree.
+    #[must_use]
+    pub fn new(initial_tree: PathTree<T>) -> Self {
+        let tree = initial_tree.clone();
+        Self {
+            initial_tree,
+            tree,
+            events: Vec::new(),
+            applied_keys: new_hash_set(),
+            context: None,
+            event_contexts: BTreeMap::new(),
+        }
+    }
+
+    /// Restore the idempotency keys of applied events.
+    ///
+    /// The keys are not part of the recorded events and must be restored
+    /// after replaying them.
+    #[must_use]
+    pub fn with_applied_keys(mut self, applied_keys: impl IntoIterator<Item = K>) -> Self {
+        for key in applied_keys {
+            self.applied_keys.insert_mut(key);
+        }
+        self
+    }
+
+    /// Reconstruct a tree by replaying events.
+    ///
+    /// The given initial tree must match the initial tree that has been
+    /// used for recording the events, including the state of its node id
+    /// generator.
+    pub fn replay(
+        initial_tree: PathTree<T>,
+        events: impl IntoIterator<Item = PathTreeEvent<T>>,
+    ) -> Result<Self, ReplayEventsError<T>> {
+        let mut event_sourced_tree = Self::new(initial_tree);
+        for (index, event) in events.into_iter().enumerate() {
+            event_sourced_tree
+                .apply(event)
+                .map_err(|source| ReplayEventsError { index, source })?;
+        }
+        Ok(event_sourced_tree)
+    }
+
+    /// The initial tree before applying any events.
+    #[must_use]
+    pub const fn initial_tree(&self) -> &PathTree<T> {
+        &self.initial_tree
+    }
+
+    /// The current tree after applying all events.
+    #[must_use]
+    pub const fn tree(&self) -> &PathTree<T> {
+        &self.tree
+    }
+
+    /// All recorded events in chronological order.
+    #[must_use]
+    pub fn events(&self) -> &[PathTreeEvent<T>] {
+        &self.events
+    }
+
+    /// Split into the current tree and the recorded events.
+    #[must_use]
+    pub fn into_tree_and_events(self) -> (PathTree<T>, Vec<PathTreeEvent<T>>) {
+        let Self {
+            initial_tree: _,
+            tree,
+            events,
+            applied_keys: _,
+            context: _,
+            event_contexts: _,
+        } = self;
+        (tree, events)
+    }
+
+    /// Apply and record an event.
+    ///
+    /// The tree remains unchanged if the event could not be applied.
+    pub fn apply(&mut self, event: PathTreeEvent<T>) -> Result<(), ApplyEventError<T>> {
+        let mut tree = self.tree.clone();
+        apply_event(&mut tree, &event)?;
+        self.tree = tree;
+        self.record_event(event);
+        Ok(())
+    }
+
+    fn record_event(&mut self, event: PathTreeEvent<T>) {
+        if let Some(context) = &self.context {
+            self.event_contexts
+                .insert(self.events.len(), context.clone());
+        }
+        self.events.push(event);
+    }
+
+    /// Apply and record an event at most once.
+    ///
+    /// Skips the event if an event with the same idempotency key has
+    /// already been applied. The key is only remembered if the event
+    /// has been applied successfully.
+    ///
+    /// Returns `Ok(false)` if the event has been skipped.
+    pub fn apply_idempotent(
+        &mut self,
+        key: K,
+        event: PathTreeEvent<T>,
+    ) -> Result<bool, ApplyEventError<T>> {
+        if self.is_applied(&key) {
+            return Ok(false);
+        }
+        self.apply(event)?;
+        self.applied_keys.insert_mut(key);
+        Ok(true)
+    }
+
+    /// Check if an event with the given idempotency key has already been applied.
+    #[must_use]
+    pub fn is_applied(&self, key: &K) -> bool {
+        self.applied_keys.contains(key)
+    }
+
+    /// Idempotency keys of all applied events in no particular order.
+    pub fn applied_keys(&self) -> impl Iterator<Item = &K> {
+        self.applied_keys.iter()
+    }
+
+    /// See [`PathTree::insert_or_update_node_value()`].
+    ///
+    /// The values returned by `new_inner_value` and `try_clone_leaf_into_inner_value`
+    /// are recorded in the event.
+    pub fn insert_or_update_node_value(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
+        let mut tree = self.tree.clone();
+        let (result, event) = capture_insert_or_update_node_value(
+            &mut tree,
+            path,
+            new_value,
+            new_inner_value,
+            try_clone_leaf_into_inner_value,
+        );
+        let inserted_or_updated = result?;
+        self.tree = tree;
+        self.record_event(event);
+        Ok(inserted_or_updated)
+    }
+
+    /// See [`PathTree::insert_or_update_child_node_value()`].
+    ///
+    /// The parent node is addressed by its path.
+    ///
+    /// Returns `Ok(None)` if the parent node does not exist.
+    pub fn insert_or_update_child_node_value(
+        &mut self,
+        parent_path: &T::RootPath,
+        child_path_segment: &T::PathSegment,
+        old_child_path_segment: Option<&T::PathSegment>,
+        new_value: NodeValue<T>,
+    ) -> Result<Option<NodeInsertedOrUpdated<T>>, InsertOrUpdateNodeValueError<T>> {
+        let Some(parent_node) = self.tree.find_node(parent_path).map(Arc::clone) else {
+            return Ok(None);
+        };
+        let mut tree = self.tree.clone();
+        let value = new_value.clone();
+        let inserted_or_updated = tree.insert_or_update_child_node_value(
+            &parent_node,
+            child_path_segment,
+            old_child_path_segment,
+            new_value,
+        )?;
+        self.tree = tree;
+        self.record_event(PathTreeEvent::InsertOrUpdateChildNodeValue {
+            parent_path: parent_path.clone(),
+            child_path_segment: T::path_segment_to_owned(child_path_segment),
+            old_child_path_segment: old_child_path_segment.map(T::path_segment_to_owned),
+            value,
+        });
+        Ok(Some(inserted_or_updated))
+    }
+
+    /// Remove a subtree by its path.
+    ///
+    /// See [`PathTree::remove_subtree_by_id()`].
+    pub fn remove_subtree(&mut self, path: &T::RootPath) -> Option<SubtreeRemoved<T>> {
+        let node_id = self.tree.find_node(path)?.id.clone();
+        let removed = self.tree.remove_subtree_by_id(node_id)?;
+        self.record_event(PathTreeEvent::RemoveSubtree { path: path.clone() });
+        Some(removed)
+    }
+}
+
+/// Invoke [`PathTree::insert_or_update_node_value()`] and capture the event.
+///
+/// The event is returned independent of the outcome and contains all values
+/// that have been returned by the closures.
+pub(crate) fn capture_insert_or_update_node_value<T: PathTreeTypes>(
+    tree: &mut PathTree<T>,
+    path: &T::RootPath,
+    new_value: NodeValue<T>,
+    new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+) -> (
+    Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>>,
+    PathTreeEvent<T>,
+) {
+    let mut new_inner_values = Vec::new();
+    let mut leaf_into_inner_value = None;
+    let value = new_value.clone();
+    let result = tree.insert_or_update_node_value(
+        path,
+        new_value,
+        &mut || {
+            let inner_value = new_inner_value();
+            new_inner_values.push(inner_value.clone());
+            inner_value
+        },
+        |leaf_value| {
+            let inner_value = try_clone_leaf_into_inner_value(leaf_value);
+            leaf_into_inner_value.clone_from(&inner_value);
+            inner_value
+        },
+    );
+    let event = PathTreeEvent::InsertOrUpdateNodeValue {
+        path: path.clone(),
+        value,
+        new_inner_values,
+        leaf_into_inner_value,
+    };
+    (result, event)
+}
+
+pub(crate) fn apply_event<T: PathTreeTypes>(
+    tree: &mut PathTree<T>,
+    event: &PathTreeEvent<T>,
+) -> Result<(), ApplyEventError<T>> {
+    match event {
+        PathTreeEvent::InsertOrUpdateNodeValue {
+            path,
+            value,
+            new_inner_values,
+            leaf_into_inner_value,
+        } => {
+            // The number of missing ancestor nodes must match the number of recorded values.
+            let parent_segments_count = path.segments().count().saturating_sub(1);
+            let matched_segments_count = tree
+                .resolve_node_path(path, MatchNodePath::PartialOrFull)
+                .map_or(0, |NodePathResolved { matched_path, .. }| {
+                    matched_path.number_of_matched_segments()
+                });
+            let missing_ancestors_count =
+                parent_segments_count.saturating_sub(matched_segments_count);
+            if missing_ancestors_count != new_inner_values.len() {
+                return Err(ApplyEventError::Inconsistent {
+                    event: event.clone(),
+                });
+            }
+            let mut new_inner_values = new_inner_values.iter().cloned();
+            let mut leaf_into_inner_value = leaf_into_inner_value.clone();
+            tree.insert_or_update_node_value(
+                path,
+                value.clone(),
+                &mut || {
+                    new_inner_values
+                        .next()
+                        .expect("number of values has been checked")
+                },
+                |_| leaf_into_inner_value.take(),
+            )
+            .map_err(|source| ApplyEventError::InsertOrUpdateNodeValue { source })?;
+        }
+        PathTreeEvent::InsertOrUpdateChildNodeValue {
+            parent_path,
+            child_path_segment,
+            old_child_path_segment,
+            value,
+        } => {
+            let Some(parent_node) = tree.find_node(parent_path).map(Arc::clone) else {
+                return Err(ApplyEventError::PathNotFound {
+                    event: event.clone(),
+                });
+            };
+            tree.insert_or_update_child_node_value(
+                &parent_node,
+                child_path_segment.borrow(),
+                old_child_path_segment.as_ref().map(Borrow::borrow),
+                value.clone(),
+            )
+            .map_err(|source| ApplyEventError::InsertOrUpdateNodeValue { source })?;
+        }
+        PathTreeEvent::RemoveSubtree { path } => {
+            let node_id = tree.find_node(path).map(|node| node.id.clone());
+            if node_id
+                .and_then(|node_id| tree.remove_subtree_by_id(node_id))
+                .is_none()
+            {
+                return Err(ApplyEventError::PathNotFound {
+                    event: event.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{HalfEdge, HalfEdgeTreeNode, PathTree, PathTreeTypes, SegmentedPath as _, TreeNode};
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Mark a subtree as read-only.
+    ///
+    /// All mutations of the node or its descendants fail with a
+    /// `SubtreeFrozen` error until the subtree is unfrozen again. Removing
+    /// the subtree or one of its ancestors is also rejected. Mutations of
+    /// other nodes are unaffected, even if they need to update the ancestors
+    /// of the frozen node.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn freeze_subtree(&mut self, node_id: T::NodeId) -> bool {
+        if self.lookup_node(node_id.clone()).is_none() {
+            return false;
+        }
+        log::debug!("Freezing subtree of node {node_id}");
+        self.frozen_node_ids.insert_mut(node_id);
+        true
+    }
+
+    /// Revert [`Self::freeze_subtree()`].
+    ///
+    /// Subtrees of descendants that have been frozen separately remain frozen.
+    ///
+    /// Returns `false` if the subtree of the node has not been frozen.
+    pub fn unfreeze_subtree(&mut self, node_id: &T::NodeId) -> bool {
+        let unfrozen = self.frozen_node_ids.remove_mut(node_id);
+        if unfrozen {
+            log::debug!("Unfroze subtree of node {node_id}");
+        }
+        unfrozen
+    }
+
+    /// The ids of all nodes that have been passed to [`Self::freeze_subtree()`].
+    ///
+    /// In no particular order.
+    pub fn frozen_node_ids(&self) -> impl Iterator<Item = &T::NodeId> {
+        self.frozen_node_ids.iter()
+    }
+
+    /// Check if the node is part of a frozen subtree.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    #[must_use]
+    pub fn is_frozen(&self, node: &Arc<TreeNode<T>>) -> bool {
+        debug_assert!(self.contains_node(node));
+        self.find_frozen_ancestor_or_self(node).is_some()
+    }
+
+    /// Find the frozen subtree that contains the given node.
+    ///
+    /// Returns the id of the root node of the frozen subtree.
+    pub(crate) fn find_frozen_ancestor_or_self(
+        &self,
+        node: &Arc<TreeNode<T>>,
+    ) -> Option<T::NodeId> {
+        if self.frozen_node_ids.is_empty() {
+            return None;
+        }
+        if self.frozen_node_ids.contains(&node.id) {
+            return Some(node.id.clone());
+        }
+        self.ancestor_nodes(node)
+            .map(|HalfEdgeTreeNode { node, .. }| &node.id)
+            .find(|node_id| self.frozen_node_ids.contains(*node_id))
+            .cloned()
+    }
+
+    /// Find a frozen subtree that would be affected by removing the given node.
+    ///
+    /// The node is either contained in or contains the frozen subtree.
+    pub(crate) fn find_frozen_node_affected_by_removal(
+        &self,
+        node: &Arc<TreeNode<T>>,
+    ) -> Option<T::NodeId> {
+        self.find_frozen_ancestor_or_self(node).or_else(|| {
+            self.frozen_node_ids
+                .iter()
+                .find(|frozen_node_id| {
+                    let frozen_node = self.get_node((*frozen_node_id).clone());
+                    self.ancestor_nodes(frozen_node)
+                        .any(|HalfEdgeTreeNode { node: ancestor, .. }| ancestor.id == node.id)
+                })
+                .cloned()
+        })
+    }
+
+    /// Find a frozen subtree that would be affected by inserting or updating
+    /// a node at the given path.
+    ///
+    /// All modifications happen at or below the last existing node on the path.
+    pub(crate) fn find_frozen_node_on_path(&self, path: &T::RootPath) -> Option<T::NodeId> {
+        if self.frozen_node_ids.is_empty() {
+            return None;
+        }
+        let mut node = self.root_node();
+        let mut path_segments = path.segments();
+        loop {
+            if self.frozen_node_ids.contains(&node.id) {
+                return Some(node.id.clone());
+            }
+            let child_node_id = path_segments
+                .next()
+                .and_then(|path_segment| node.node.find_child(path_segment))?;
+            node = self.get_node(child_node_id);
+        }
+    }
+
+    /// Find a frozen subtree that would be affected by
+    /// [`Self::insert_or_update_child_node_value()`].
+    pub(crate) fn find_frozen_node_for_child_update(
+        &self,
+        parent_node: &Arc<TreeNode<T>>,
+        child_path_segment: &T::PathSegment,
+        old_child_path_segment: &T::PathSegment,
+    ) -> Option<T::NodeId> {
+        if self.frozen_node_ids.is_empty() {
+            return None;
+        }
+        if let Some(node_id) = self.find_frozen_ancestor_or_self(parent_node) {
+            return Some(node_id);
+        }
+        // The existing child node is either updated or renamed.
+        if let Some(node_id) = parent_node
+            .node
+            .find_child(old_child_path_segment)
+            .filter(|node_id| self.frozen_node_ids.contains(node_id))
+        {
+            return Some(node_id);
+        }
+        if T::child_key(old_child_path_segment) == T::child_key(child_path_segment) {
+            return None;
+        }
+        // Renaming replaces the subtree at the new path segment.
+        let replaced_node = self.get_node(parent_node.node.find_child(child_path_segment)?);
+        if self.frozen_node_ids.contains(&replaced_node.id) {
+            return Some(replaced_node.id.clone());
+        }
+        replaced_node
+            .node
+            .descendants(self)
+            .map(|HalfEdge { node_id, .. }| node_id)
+            .find(|node_id| self.frozen_node_ids.contains(node_id))
+    }
+}
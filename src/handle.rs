@@ -0,0 +1,237 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, sync::Arc};
+
+use derive_more::{Display, Error};
+
+use crate::{HalfEdgeOwned, NodeValue, PathTree, PathTreeTypes, TreeNode};
+
+/// Owned handle to a node.
+///
+/// Bundles the node with a snapshot of the tree it belongs to. Cloning the
+/// tree is cheap, see [`PathTree`].
+///
+/// In contrast to the borrowing accessors of [`PathTree`] handles do not
+/// carry any lifetimes, e.g. for wrapping them in foreign language bindings.
+#[derive(Debug, Clone)]
+pub struct NodeHandle<T>
+where
+    T: PathTreeTypes,
+{
+    tree: PathTree<T>,
+    node: Arc<TreeNode<T>>,
+}
+
+impl<T> NodeHandle<T>
+where
+    T: PathTreeTypes,
+{
+    /// The tree snapshot that contains the node.
+    #[must_use]
+    pub const fn tree(&self) -> &PathTree<T> {
+        &self.tree
+    }
+
+    #[must_use]
+    pub const fn node(&self) -> &Arc<TreeNode<T>> {
+        &self.node
+    }
+
+    #[must_use]
+    pub fn id(&self) -> T::NodeId {
+        self.node.id.clone()
+    }
+
+    /// Clone the value of the node.
+    #[must_use]
+    pub fn value(&self) -> NodeValue<T> {
+        self.node.node.to_value()
+    }
+
+    /// The path segment from the parent node.
+    ///
+    /// `None` for the root node.
+    #[must_use]
+    pub fn path_segment(&self) -> Option<&T::PathSegment> {
+        self.node
+            .parent
+            .as_ref()
+            .map(|parent| parent.path_segment.borrow())
+    }
+
+    /// The parent node.
+    ///
+    /// `None` for the root node.
+    #[must_use]
+    pub fn parent(&self) -> Option<Self> {
+        let parent = self.node.parent.as_ref()?;
+        self.tree.lookup_node_handle(parent.node_id.clone())
+    }
+
+    /// All child nodes.
+    ///
+    /// The order of children is undefined.
+    #[must_use]
+    pub fn children(&self) -> Vec<Self> {
+        self.node
+            .node
+            .children()
+            .filter_map(|child| self.tree.lookup_node_handle(child.node_id))
+            .collect()
+    }
+
+    /// Release the tree snapshot.
+    #[must_use]
+    pub fn into_node(self) -> Arc<TreeNode<T>> {
+        let Self { tree: _, node } = self;
+        node
+    }
+}
+
+/// Stable reference to a child node.
+///
+/// Addresses a child node by the id of its parent node and its path segment.
+/// The handle remains valid when the value of the child node is updated or
+/// when the child node is replaced by a different node, e.g. by
+/// [`PathTree::insert_or_replace_subtree()`]. In contrast to a full path
+/// renaming any ancestor node of the parent node does not affect the handle.
+#[derive(Debug, Clone)]
+pub struct ChildHandle<T>
+where
+    T: PathTreeTypes,
+{
+    parent_node_id: T::NodeId,
+    path_segment: T::PathSegmentOwned,
+
+    /// The id of the child node when the handle has been created.
+    ///
+    /// Only used for detecting why a handle has been invalidated.
+    node_id: T::NodeId,
+}
+
+/// The child node of a [`ChildHandle`] could not be resolved.
+#[derive(Debug, Display, Error)]
+pub enum ChildHandleInvalidated<T>
+where
+    T: PathTreeTypes,
+{
+    /// The parent node has been removed or replaced by a leaf node.
+    #[display("parent node removed")]
+    ParentRemoved,
+
+    /// The child node has been renamed.
+    ///
+    /// Contains the new path segment.
+    #[display("child node renamed")]
+    Renamed {
+        #[error(not(source))]
+        path_segment: T::PathSegmentOwned,
+    },
+
+    /// The child node has been removed or moved to a different parent node.
+    #[display("child node removed")]
+    Removed,
+}
+
+impl<T> ChildHandle<T>
+where
+    T: PathTreeTypes,
+{
+    #[must_use]
+    pub fn parent_node_id(&self) -> T::NodeId {
+        self.parent_node_id.clone()
+    }
+
+    #[must_use]
+    pub const fn path_segment(&self) -> &T::PathSegmentOwned {
+        &self.path_segment
+    }
+
+    /// Resolve the current child node.
+    ///
+    /// Requires a single lookup of the parent and the child node respectively.
+    pub fn resolve<'a>(
+        &self,
+        tree: &'a PathTree<T>,
+    ) -> Result<&'a Arc<TreeNode<T>>, ChildHandleInvalidated<T>> {
+        let Some(parent_node) = tree.lookup_node(self.parent_node_id.clone()) else {
+            return Err(ChildHandleInvalidated::ParentRemoved);
+        };
+        if parent_node.node.inner_value().is_none() {
+            return Err(ChildHandleInvalidated::ParentRemoved);
+        }
+        if let Some(child_node_id) = parent_node.node.find_child(self.path_segment.borrow()) {
+            return Ok(tree.get_node(child_node_id));
+        }
+        let renamed_path_segment = tree.lookup_node(self.node_id.clone()).and_then(|node| {
+            let HalfEdgeOwned {
+                path_segment,
+                node_id,
+            } = node.parent.as_ref()?;
+            (*node_id == self.parent_node_id).then(|| path_segment.clone())
+        });
+        Err(
+            renamed_path_segment.map_or(ChildHandleInvalidated::Removed, |path_segment| {
+                ChildHandleInvalidated::Renamed { path_segment }
+            }),
+        )
+    }
+
+    /// Check if the child node could still be resolved.
+    #[must_use]
+    pub fn is_valid(&self, tree: &PathTree<T>) -> bool {
+        self.resolve(tree).is_ok()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Create a stable handle for the given node.
+    ///
+    /// Returns `None` for the root node.
+    #[must_use]
+    pub fn child_handle(&self, node: &Arc<TreeNode<T>>) -> Option<ChildHandle<T>> {
+        debug_assert!(self.contains_node(node));
+        let HalfEdgeOwned {
+            path_segment,
+            node_id: parent_node_id,
+        } = node.parent.as_ref()?;
+        Some(ChildHandle {
+            parent_node_id: parent_node_id.clone(),
+            path_segment: path_segment.clone(),
+            node_id: node.id.clone(),
+        })
+    }
+
+    /// Owned variant of [`Self::root_node()`].
+    #[must_use]
+    pub fn root_node_handle(&self) -> NodeHandle<T> {
+        NodeHandle {
+            tree: self.clone(),
+            node: Arc::clone(self.root_node()),
+        }
+    }
+
+    /// Owned variant of [`Self::lookup_node()`].
+    #[must_use]
+    pub fn lookup_node_handle(&self, id: T::NodeId) -> Option<NodeHandle<T>> {
+        let node = Arc::clone(self.lookup_node(id)?);
+        Some(NodeHandle {
+            tree: self.clone(),
+            node,
+        })
+    }
+
+    /// Owned variant of [`Self::find_node()`].
+    #[must_use]
+    pub fn find_node_handle(&self, path: &T::RootPath) -> Option<NodeHandle<T>> {
+        let node = Arc::clone(self.find_node(path)?);
+        Some(NodeHandle {
+            tree: self.clone(),
+            node,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{
+    event::apply_event, ApplyEventError, PathTree, PathTreeEvent, PathTreeTypes, TreeDiff, TreeNode,
+};
+
+/// Versioned history of a [`PathTree`].
+///
+/// Each applied event creates a new version. The initial tree
+/// has version 0.
+///
+/// Snapshots of intermediate versions could be dropped by [`Self::compact()`].
+/// Those versions are then reconstructed on demand by replaying the recorded
+/// events, starting with the nearest preceding snapshot.
+#[derive(Debug, Clone)]
+pub struct PathTreeHistory<T>
+where
+    T: PathTreeTypes,
+{
+    /// Snapshots of all versions, indexed by version.
+    ///
+    /// Snapshots of the initial and the current version are always present.
+    snapshots: Vec<Option<PathTree<T>>>,
+
+    /// The events that transform each version into the next version.
+    events: Vec<PathTreeEvent<T>>,
+}
+
+impl<T> PathTreeHistory<T>
+where
+    T: PathTreeTypes,
+{
+    /// Start a new history with the given initial tree.
+    #[must_use]
+    pub fn new(initial_tree: PathTree<T>) -> Self {
+        Self {
+            snapshots: vec![Some(initial_tree)],
+            events: Vec::new(),
+        }
+    }
+
+    /// The current version.
+    #[must_use]
+    pub fn current_version(&self) -> usize {
+        self.events.len()
+    }
+
+    /// The tree of the current version.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn current_tree(&self) -> &PathTree<T> {
+        self.snapshots
+            .last()
+            .and_then(Option::as_ref)
+            .expect("snapshot of the current version")
+    }
+
+    /// All events in chronological order.
+    ///
+    /// The event at index `i` transforms version `i` into version `i + 1`.
+    #[must_use]
+    pub fn events(&self) -> &[PathTreeEvent<T>] {
+        &self.events
+    }
+
+    /// Apply an event and create a new version.
+    ///
+    /// Returns the new version.
+    pub fn apply(&mut self, event: PathTreeEvent<T>) -> Result<usize, ApplyEventError<T>> {
+        let mut tree = self.current_tree().clone();
+        apply_event(&mut tree, &event)?;
+        self.snapshots.push(Some(tree));
+        self.events.push(event);
+        Ok(self.current_version())
+    }
+
+    /// The tree at the given version.
+    ///
+    /// Returns `None` if the version does not exist.
+    ///
+    /// Reconstructs the tree if its snapshot has been dropped.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn tree_at(&self, version: usize) -> Option<PathTree<T>> {
+        if version > self.current_version() {
+            return None;
+        }
+        let (snapshot_version, snapshot) = self.snapshots[..=version]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(version, snapshot)| snapshot.as_ref().map(|tree| (version, tree)))
+            .expect("snapshot of the initial version");
+        let mut tree = snapshot.clone();
+        for event in &self.events[snapshot_version..version] {
+            apply_event(&mut tree, event).expect("recorded events are consistent");
+        }
+        Some(tree)
+    }
+
+    /// Number of versions with a snapshot.
+    #[must_use]
+    pub fn snapshots_count(&self) -> usize {
+        self.snapshots.iter().filter(|tree| tree.is_some()).count()
+    }
+
+    /// Drop snapshots of intermediate versions.
+    ///
+    /// Retains the snapshots of the last `keep_last_n` versions and of every
+    /// `keep_every_kth` version as checkpoints. Passing 0 for `keep_every_kth`
+    /// retains no checkpoints. The snapshots of the initial and the current
+    /// version are always retained.
+    ///
+    /// All versions could still be reconstructed from the recorded events.
+    pub fn compact(&mut self, keep_last_n: usize, keep_every_kth: usize) {
+        let current_version = self.current_version();
+        for (version, snapshot) in self.snapshots.iter_mut().enumerate() {
+            if version == 0
+                || version == current_version
+                || version.saturating_add(keep_last_n) > current_version
+                || (keep_every_kth > 0 && version % keep_every_kth == 0)
+            {
+                continue;
+            }
+            *snapshot = None;
+        }
+    }
+
+    /// Find a node by its path at the given version.
+    ///
+    /// Returns `None` if either the version or the node does not exist.
+    #[must_use]
+    pub fn find_node_at(&self, version: usize, path: &T::RootPath) -> Option<Arc<TreeNode<T>>> {
+        self.tree_at(version)?.find_node(path).map(Arc::clone)
+    }
+
+    /// Compare the trees of two versions.
+    ///
+    /// Returns `None` if one of the versions does not exist.
+    #[must_use]
+    pub fn diff(&self, old_version: usize, new_version: usize) -> Option<TreeDiff<T>> {
+        let old_tree = self.tree_at(old_version)?;
+        let new_tree = self.tree_at(new_version)?;
+        Some(old_tree.diff(&new_tree))
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    hash::Hash,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Statistics of a [`ValueInterner`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InternStats {
+    /// The number of distinct values that are currently interned.
+    pub distinct_count: usize,
+
+    /// The number of values that have been interned.
+    pub interned_count: usize,
+
+    /// The number of interned values that have been replaced by an
+    /// equal, shared value.
+    pub shared_count: usize,
+}
+
+impl InternStats {
+    /// The number of bytes that have been saved by sharing values.
+    ///
+    /// Only accounts for the size of the value itself and not for
+    /// any heap allocations that it might own, i.e. a lower bound.
+    #[must_use]
+    pub const fn saved_bytes<V>(&self) -> usize {
+        self.shared_count * std::mem::size_of::<V>()
+    }
+}
+
+#[derive(Debug)]
+struct Interned<V> {
+    values: Option<HashSet<Arc<V>>>,
+    stats: InternStats,
+}
+
+/// Deduplicates equal values by sharing them.
+///
+/// Intended for implementing [`PathTreeTypes::intern_leaf_value()`](crate::PathTreeTypes::intern_leaf_value)
+/// if many leaf values are equal, e.g. empty markers or default metadata.
+/// Could be used in a `static` variable.
+///
+/// Interned values are kept alive until they are purged.
+#[derive(Debug)]
+pub struct ValueInterner<V> {
+    interned: Mutex<Interned<V>>,
+}
+
+impl<V> ValueInterner<V> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            interned: Mutex::new(Interned {
+                values: None,
+                stats: InternStats {
+                    distinct_count: 0,
+                    interned_count: 0,
+                    shared_count: 0,
+                },
+            }),
+        }
+    }
+
+    /// Statistics about the interned values.
+    #[must_use]
+    pub fn stats(&self) -> InternStats {
+        self.interned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stats
+    }
+}
+
+impl<V> Default for ValueInterner<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> ValueInterner<V>
+where
+    V: Eq + Hash,
+{
+    /// Intern a value.
+    ///
+    /// Returns the shared value if an equal value has already been interned.
+    #[must_use]
+    pub fn intern(&self, value: V) -> Arc<V> {
+        self.intern_with(value, Arc::new)
+    }
+
+    /// Intern a shared value.
+    ///
+    /// Returns the shared value if an equal value has already been interned.
+    #[must_use]
+    pub fn intern_arc(&self, value: Arc<V>) -> Arc<V> {
+        self.intern_with(value, std::convert::identity)
+    }
+
+    fn intern_with<B>(&self, value: B, into_shared: impl FnOnce(B) -> Arc<V>) -> Arc<V>
+    where
+        B: Borrow<V>,
+    {
+        let mut interned = self.interned.lock().unwrap_or_else(PoisonError::into_inner);
+        let Interned { values, stats } = &mut *interned;
+        let values = values.get_or_insert_with(HashSet::new);
+        stats.interned_count += 1;
+        if let Some(shared_value) = values.get(value.borrow()) {
+            stats.shared_count += 1;
+            return Arc::clone(shared_value);
+        }
+        let new_value = into_shared(value);
+        values.insert(Arc::clone(&new_value));
+        stats.distinct_count = values.len();
+        new_value
+    }
+
+    /// Purge all interned values that are no longer used.
+    ///
+    /// Returns the number of purged values.
+    pub fn purge_unused(&self) -> usize {
+        let mut interned = self.interned.lock().unwrap_or_else(PoisonError::into_inner);
+        let Interned { values, stats } = &mut *interned;
+        let Some(values) = values else {
+            return 0;
+        };
+        let count_before = values.len();
+        values.retain(|value| Arc::strong_count(value) > 1);
+        stats.distinct_count = values.len();
+        count_before - values.len()
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use derive_more::{Display, Error};
+
+use crate::{
+    InsertOrUpdateNodeValueError, NodeInsertedOrUpdated, NodeValue, PathSegment, PathTree,
+    PathTreeTypes, SegmentedPath as _,
+};
+
+/// Path segment with a different type per level of the tree.
+///
+/// Typically implemented by an enum with one variant per level, e.g.
+/// tenant / project / resource.
+pub trait LeveledPathSegment: PathSegment {
+    /// Check if the segment may occur at the given depth.
+    ///
+    /// The depth is the number of ancestor nodes of the node that is
+    /// addressed by the segment, i.e. 1 for the children of the root node.
+    #[must_use]
+    fn is_valid_at_depth(&self, depth: usize) -> bool;
+}
+
+/// Error of [`PathTree::insert_or_update_leveled_node_value()`].
+#[derive(Debug, Display, Error)]
+pub enum InsertOrUpdateLeveledNodeValueError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("invalid path segment at depth {depth}")]
+    InvalidPathSegment { depth: usize, value: NodeValue<T> },
+    #[display("{_0}")]
+    InsertOrUpdate(InsertOrUpdateNodeValueError<T>),
+}
+
+impl<T> InsertOrUpdateLeveledNodeValueError<T>
+where
+    T: PathTreeTypes,
+{
+    pub fn into_value(self) -> NodeValue<T> {
+        match self {
+            Self::InvalidPathSegment { value, .. } => value,
+            Self::InsertOrUpdate(err) => err.into_value(),
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+    T::PathSegment: LeveledPathSegment,
+{
+    /// Find the depth of the first path segment that is invalid at its level.
+    ///
+    /// Returns `None` if all path segments are valid.
+    #[must_use]
+    pub fn find_invalid_leveled_path_segment(path: &T::RootPath) -> Option<usize> {
+        path.segments().zip(1..).find_map(|(path_segment, depth)| {
+            (!path_segment.is_valid_at_depth(depth)).then_some(depth)
+        })
+    }
+
+    /// Insert or update a node after validating the levels of all path segments.
+    ///
+    /// Rejects paths with misplaced segments before modifying the tree.
+    /// Otherwise, behaves like [`Self::insert_or_update_node_value()`].
+    pub fn insert_or_update_leveled_node_value(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateLeveledNodeValueError<T>> {
+        if let Some(depth) = Self::find_invalid_leveled_path_segment(path) {
+            return Err(InsertOrUpdateLeveledNodeValueError::InvalidPathSegment {
+                depth,
+                value: new_value,
+            });
+        }
+        self.insert_or_update_node_value(
+            path,
+            new_value,
+            new_inner_value,
+            try_clone_leaf_into_inner_value,
+        )
+        .map_err(InsertOrUpdateLeveledNodeValueError::InsertOrUpdate)
+    }
+}
@@ -3,37 +3,242 @@
 
 //! Immutable, path-addressable tree data structure.
 
+/// Log target of per-node messages that are emitted while mutating a tree.
+///
+/// Operation-level messages are logged with the default target of the module.
+pub const MUTATIONS_LOG_TARGET: &str = "rpds_pathtree::mutations";
+
+/// Log a per-node message while mutating a tree.
+///
+/// Compiled out unless the feature "log-mutations" is enabled.
+macro_rules! log_mutation {
+    ($($arg:tt)+) => {
+        if cfg!(feature = "log-mutations") {
+            log::debug!(target: $crate::MUTATIONS_LOG_TARGET, $($arg)+);
+        }
+    };
+}
+
+/// Check a cheap invariant that guards against silent corruption of a tree.
+///
+/// Only checked by debug assertions unless the feature "strict-invariants"
+/// is enabled.
+macro_rules! strict_assert {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "strict-invariants")]
+        assert!($($arg)+);
+        #[cfg(not(feature = "strict-invariants"))]
+        debug_assert!($($arg)+);
+    };
+}
+
+/// Like `strict_assert!`, but checks that two expressions are equal.
+macro_rules! strict_assert_eq {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "strict-invariants")]
+        assert_eq!($($arg)+);
+        #[cfg(not(feature = "strict-invariants"))]
+        debug_assert_eq!($($arg)+);
+    };
+}
+
+mod any;
+pub use self::any::{
+    AnyNewNodeId, AnyPath, AnyPathTree, AnyPathTreeTypes, AnyValue, AnyValueTypeMismatch,
+};
+
+#[cfg(feature = "async")]
+mod asynchronous;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+mod bulk;
+
+mod children;
+
+mod clone_values;
+
+mod conflict;
+pub use self::conflict::{
+    ConflictPolicy, ConflictResolution, PreferNewest, PreferOurs, PreferTheirs, RejectConflicts,
+    ValueConflict,
+};
+
+mod context;
+pub use self::context::{ContextError, OperationContext};
+
+mod cursor;
+pub use self::cursor::{AppendChildNodeError, TreeAppendCursor};
+
+mod deferred;
+pub use self::deferred::{DeferredValuesTree, FillInnerValueError};
+
+mod debug;
+
+mod diff;
+pub use self::diff::TreeDiff;
+
 mod edge;
 pub use self::edge::{HalfEdge, HalfEdgeOwned, HalfEdgeTreeNode};
 
+mod entry;
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+
+mod event;
+pub use self::event::{ApplyEventError, EventSourcedTree, PathTreeEvent, ReplayEventsError};
+
+mod handle;
+pub use self::handle::{ChildHandle, ChildHandleInvalidated, NodeHandle};
+
+mod freeze;
+
+mod history;
+pub use self::history::PathTreeHistory;
+
+mod intern;
+pub use self::intern::{InternStats, ValueInterner};
+
+mod leveled;
+pub use self::leveled::{InsertOrUpdateLeveledNodeValueError, LeveledPathSegment};
+
+#[cfg(feature = "mmap")]
+mod mapped;
+#[cfg(feature = "mmap")]
+pub use self::mapped::{
+    MappedSnapshot, MappedTree, SnapshotCodec, SnapshotError, SnapshotNode, SnapshotView,
+};
+
+mod materialized;
+pub use self::materialized::FromMaterializedPathsError;
+
+mod merge;
+pub use self::merge::MergeError;
+
+mod metrics;
+
+mod natural;
+pub use self::natural::natural_cmp;
+
 mod node;
-pub use self::node::{DepthFirstDescendantsIter, InnerNode, LeafNode, Node, NodeValue};
+pub use self::node::{
+    CountedDescendantsIter, DepthFirstDescendantsIter, InnerNode, LeafNode, Node, NodeValue,
+    OrderedDescendantsIter, PathSegmentCmp, ReverseDescendantsIter,
+};
+
+mod patch;
+pub use self::patch::{
+    ParseTreePatchError, ParseVersionedTreePatchError, PatchConflict, PatchConflictKind,
+    PatchOperation, PatchReport, TreePatch, PATCH_FORMAT_VERSION,
+};
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+mod path_hash;
+pub use self::path_hash::{PathHash, PathHashIndex};
 
 mod path;
 pub use self::path::{PathSegment, RootPath, SegmentedPath};
 
+#[cfg(feature = "python")]
+pub mod python;
+
+mod provenance;
+pub use self::provenance::{TreeId, TreeProvenance, UnrelatedTreesError};
+
+mod reconcile;
+pub use self::reconcile::{ReconcileOptions, ReconcileSubtreeError};
+
+mod recorder;
+pub use self::recorder::{
+    OperationRecorder, OperationScript, RecordedOperation, ReplayScriptError,
+};
+
+mod repair;
+pub use self::repair::{RepairAction, RepairPolicy, RepairReport, RepairedRow};
+
+mod rows;
+pub use self::rows::{ClosureTableRow, FromRowsError, TreeRow};
+
+mod scratch;
+pub use self::scratch::TraversalScratch;
+
+mod search;
+pub use self::search::{SearchLimits, SearchResults, SearchTruncated, ValueMatch};
+
+#[cfg(feature = "serde")]
+mod simple;
+#[cfg(feature = "serde")]
+pub use self::simple::{SimpleJsonTree, SimpleJsonTreeTypes, SimpleNewNodeId, SimplePath};
+
+mod stats;
+pub use self::stats::{ChildrenStats, SubtreeKindCounts, TreeChildrenStats, ValueSharingReport};
+
+mod token;
+pub use self::token::SnapshotToken;
+
+mod traversal;
+pub use self::traversal::{
+    DepthFirstTraversalIter, NodesWithPathsIter, PostOrderNodesIter, TraversalEvent,
+};
+
 mod tree;
 pub use self::tree::{
-    AncestorTreeNodeIter, InsertOrUpdateNodeValueError, MatchNodePath, NewNodeId,
-    NodeInsertedOrUpdated, NodePathMatched, NodePathResolved, ParentNodeUpdated, PathTree,
-    PathTreeTypes, SubtreeInsertedOrReplaced, SubtreeRemoved, TreeNode,
-    TreeNodeParentChildPathConflict, UpdateNodeValueError,
+    AncestorTreeNodeIter, EmptyInnerNodes, ExportOrder, IdMap, InsertOrUpdateNodeValueError,
+    MatchNodePath, NewNodeId, NodeInsertedOrUpdated, NodePathMatched, NodePathResolved,
+    ParentNodeUpdated, PartitionNewNodeId, PathRemoved, PathTree, PathTreeTypes,
+    RewritePrefixError, RootPathPolicy, SpliceNodeError, SubtreeInsertedOrReplaced, SubtreeRemoved,
+    TreeNode, TreeNodeParentChildPathConflict, TryInsertOrUpdateNodeValueError,
+    UpdateNodeValueError,
 };
 
-#[cfg(feature = "sync")]
-type HashMap<K, V> = rpds::HashTrieMapSync<K, V>;
+mod truncate;
+pub use self::truncate::{TruncatedNodePath, TruncationStrategy};
+
+mod update;
+pub use self::update::{BreakPolicy, LeavesUpdated};
+
+mod validate;
+pub use self::validate::{IdAudit, TreeValidationError};
+
+mod visitor;
+pub use self::visitor::{NodeValueVisitor, Visit, VisitControl, VisitedNode, VisitedValue};
+
+mod weight;
+pub use self::weight::SubtreeWeightIndex;
+
+mod zipper;
+pub use self::zipper::{TreeCursor, TreeZipper};
+
+// Targets without atomic pointers, e.g. some embedded platforms, could
+// not support thread-safety.
+#[cfg(all(feature = "sync", not(target_has_atomic = "ptr")))]
+compile_error!("The \"sync\" feature requires a target with atomic pointers");
 
 #[cfg(feature = "sync")]
-fn new_hash_map<K: std::hash::Hash + Eq, V>() -> rpds::HashTrieMapSync<K, V> {
-    rpds::HashTrieMapSync::new_sync()
-}
+type SharedPointerKind = archery::ArcTK;
 
 #[cfg(not(feature = "sync"))]
-type HashMap<K, V> = rpds::HashTrieMap<K, V>;
+type SharedPointerKind = archery::RcK;
 
-#[cfg(not(feature = "sync"))]
-fn new_hash_map<K: std::hash::Hash + Eq, V>() -> rpds::HashTrieMap<K, V> {
-    rpds::HashTrieMap::new()
+/// Fixed hasher that results in a reproducible iteration order.
+#[cfg(feature = "deterministic")]
+type BuildHasher = std::hash::BuildHasherDefault<self::path_hash::FixedHasher>;
+
+#[cfg(not(feature = "deterministic"))]
+type BuildHasher = std::collections::hash_map::RandomState;
+
+type HashMap<K, V> = rpds::HashTrieMap<K, V, SharedPointerKind, BuildHasher>;
+
+fn new_hash_map<K: std::hash::Hash + Eq, V>() -> HashMap<K, V> {
+    HashMap::new_with_hasher_and_ptr_kind(BuildHasher::default())
+}
+
+type HashSet<T> = rpds::HashTrieSet<T, SharedPointerKind, BuildHasher>;
+
+fn new_hash_set<T: std::hash::Hash + Eq>() -> HashSet<T> {
+    HashSet::new_with_hasher_with_ptr_kind(BuildHasher::default())
 }
 
 #[cfg(test)]
@@ -0,0 +1,614 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Read-only trees backed by memory-mapped snapshots.
+//!
+//! A snapshot consists of a header with the [schema tag](PathTreeTypes::schema_tag)
+//! of the types, a table of fixed-size node records in breadth-first order, and a data section with the encoded
+//! path segments and values. The children of each node are stored
+//! contiguously and sorted by their encoded path segments, i.e. they
+//! could be found by a binary search without decoding.
+//!
+//! All integers are encoded as little-endian `u32`. Path segments and
+//! values are only decoded on access.
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    marker::PhantomData,
+    path::Path,
+    sync::Arc,
+};
+
+use derive_more::{Display, Error};
+
+use crate::{HalfEdge, Node, NodeValue, PathTree, PathTreeTypes, SegmentedPath as _};
+
+/// Identifies the format and its version.
+const MAGIC: &[u8; 8] = b"RPDSPTS2";
+
+/// Length of the header without the schema tag.
+const HEADER_LEN: usize = 20;
+
+const RECORD_LEN: usize = 32;
+
+/// Parent index of the root node.
+const NO_PARENT: u32 = u32::MAX;
+
+const FLAG_LEAF: u32 = 1;
+
+/// Binary encoding of path segments and values in snapshots.
+pub trait SnapshotCodec: PathTreeTypes {
+    fn encode_path_segment(path_segment: &Self::PathSegment, buf: &mut Vec<u8>);
+
+    /// Returns `None` if the bytes are invalid.
+    fn decode_path_segment(bytes: &[u8]) -> Option<Self::PathSegmentOwned>;
+
+    fn encode_inner_value(value: &Self::InnerValue, buf: &mut Vec<u8>);
+
+    /// Returns `None` if the bytes are invalid.
+    fn decode_inner_value(bytes: &[u8]) -> Option<Self::InnerValue>;
+
+    fn encode_leaf_value(value: &Self::LeafValue, buf: &mut Vec<u8>);
+
+    /// Returns `None` if the bytes are invalid.
+    fn decode_leaf_value(bytes: &[u8]) -> Option<Self::LeafValue>;
+}
+
+/// Error when reading a snapshot.
+#[derive(Debug, Display, Error)]
+pub enum SnapshotError {
+    #[display("invalid snapshot header")]
+    InvalidHeader,
+    #[display("schema mismatch: expected {expected:?}, found {found:?}")]
+    SchemaMismatch { expected: String, found: String },
+    #[display("corrupted snapshot")]
+    Corrupted,
+    #[display("{_0}")]
+    Io(io::Error),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(from: io::Error) -> Self {
+        Self::Io(from)
+    }
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "tree too large for snapshot")
+}
+
+fn to_u32(value: usize) -> io::Result<u32> {
+    value.try_into().map_err(|_| too_large())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut le_bytes = [0; 4];
+    le_bytes.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(le_bytes)
+}
+
+/// Decoded fixed-size part of a node.
+#[derive(Debug, Clone, Copy)]
+struct NodeRecord {
+    parent_index: u32,
+    flags: u32,
+    children_start: u32,
+    children_count: u32,
+    segment_offset: u32,
+    segment_len: u32,
+    value_offset: u32,
+    value_len: u32,
+}
+
+impl NodeRecord {
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        let Self {
+            parent_index,
+            flags,
+            children_start,
+            children_count,
+            segment_offset,
+            segment_len,
+            value_offset,
+            value_len,
+        } = self;
+        for field in [
+            parent_index,
+            flags,
+            children_start,
+            children_count,
+            segment_offset,
+            segment_len,
+            value_offset,
+            value_len,
+        ] {
+            buf.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    fn read_from(bytes: &[u8]) -> Self {
+        debug_assert_eq!(RECORD_LEN, bytes.len());
+        Self {
+            parent_index: read_u32(bytes, 0),
+            flags: read_u32(bytes, 4),
+            children_start: read_u32(bytes, 8),
+            children_count: read_u32(bytes, 12),
+            segment_offset: read_u32(bytes, 16),
+            segment_len: read_u32(bytes, 20),
+            value_offset: read_u32(bytes, 24),
+            value_len: read_u32(bytes, 28),
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: SnapshotCodec,
+{
+    /// Write a snapshot that could be opened by [`MappedSnapshot::open()`].
+    ///
+    /// Node ids are not included in the snapshot.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn write_snapshot(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut pending_nodes = vec![(self.root_node(), NO_PARENT, Vec::new())];
+        let mut records = Vec::with_capacity(self.nodes_count().get());
+        let mut data = Vec::new();
+        let mut index = 0;
+        while index < pending_nodes.len() {
+            let node = pending_nodes[index].0;
+            let parent_index = pending_nodes[index].1;
+            let segment = std::mem::take(&mut pending_nodes[index].2);
+            let mut children = node
+                .node
+                .children()
+                .map(
+                    |HalfEdge {
+                         path_segment,
+                         child_key: _,
+                         node_id,
+                     }| {
+                        let mut segment = Vec::new();
+                        T::encode_path_segment(path_segment, &mut segment);
+                        (segment, self.get_node(node_id))
+                    },
+                )
+                .collect::<Vec<_>>();
+            children.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            let segment_offset = to_u32(data.len())?;
+            data.extend_from_slice(&segment);
+            let value_offset = to_u32(data.len())?;
+            let flags = match &node.node {
+                Node::Inner(inner) => {
+                    T::encode_inner_value(&inner.value, &mut data);
+                    0
+                }
+                Node::Leaf(leaf) => {
+                    T::encode_leaf_value(&leaf.value, &mut data);
+                    FLAG_LEAF
+                }
+            };
+            records.push(NodeRecord {
+                parent_index,
+                flags,
+                children_start: to_u32(pending_nodes.len())?,
+                children_count: to_u32(children.len())?,
+                segment_offset,
+                segment_len: to_u32(segment.len())?,
+                value_offset,
+                value_len: to_u32(data.len())? - value_offset,
+            });
+            let parent_index = to_u32(index)?;
+            pending_nodes.extend(
+                children
+                    .into_iter()
+                    .map(|(segment, child_node)| (child_node, parent_index, segment)),
+            );
+            index += 1;
+        }
+        let schema_tag = T::schema_tag();
+        let mut header =
+            Vec::with_capacity(HEADER_LEN + schema_tag.len() + records.len() * RECORD_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&to_u32(records.len())?.to_le_bytes());
+        header.extend_from_slice(&to_u32(data.len())?.to_le_bytes());
+        header.extend_from_slice(&to_u32(schema_tag.len())?.to_le_bytes());
+        header.extend_from_slice(schema_tag.as_bytes());
+        for record in &records {
+            record.write_to(&mut header);
+        }
+        writer.write_all(&header)?;
+        writer.write_all(&data)?;
+        writer.flush()
+    }
+}
+
+/// Read-only view of an encoded snapshot.
+///
+/// Only the header is validated upfront. All nodes are decoded lazily.
+pub struct SnapshotView<'a, T> {
+    nodes_count: u32,
+    records: &'a [u8],
+    data: &'a [u8],
+    _types: PhantomData<T>,
+}
+
+impl<T> fmt::Debug for SnapshotView<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotView")
+            .field("nodes_count", &self.nodes_count)
+            .field("data_len", &self.data.len())
+            .finish()
+    }
+}
+
+impl<T> Clone for SnapshotView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SnapshotView<'_, T> {}
+
+impl<'a, T> SnapshotView<'a, T>
+where
+    T: SnapshotCodec,
+{
+    /// Access an encoded snapshot, e.g. from a memory-mapped file.
+    ///
+    /// Fails if the schema tag in the header differs from that of the types.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::InvalidHeader);
+        }
+        let nodes_count = read_u32(bytes, 8);
+        let data_len = read_u32(bytes, 12) as usize;
+        let schema_tag_len = read_u32(bytes, 16) as usize;
+        let records_start = HEADER_LEN
+            .checked_add(schema_tag_len)
+            .ok_or(SnapshotError::InvalidHeader)?;
+        let records_end = (nodes_count as usize)
+            .checked_mul(RECORD_LEN)
+            .and_then(|records_len| records_len.checked_add(records_start))
+            .ok_or(SnapshotError::InvalidHeader)?;
+        if nodes_count == 0 || records_end.checked_add(data_len) != Some(bytes.len()) {
+            return Err(SnapshotError::InvalidHeader);
+        }
+        let expected = T::schema_tag();
+        let found = &bytes[HEADER_LEN..records_start];
+        if found != expected.as_bytes() {
+            return Err(SnapshotError::SchemaMismatch {
+                expected,
+                found: String::from_utf8_lossy(found).into_owned(),
+            });
+        }
+        Ok(Self {
+            nodes_count,
+            records: &bytes[records_start..records_end],
+            data: &bytes[records_end..],
+            _types: PhantomData,
+        })
+    }
+
+    #[must_use]
+    pub const fn nodes_count(&self) -> usize {
+        self.nodes_count as usize
+    }
+
+    pub fn root_node(&self) -> Result<SnapshotNode<'a, T>, SnapshotError> {
+        self.node(0)
+    }
+
+    fn node(&self, index: u32) -> Result<SnapshotNode<'a, T>, SnapshotError> {
+        let offset = index as usize * RECORD_LEN;
+        let record = self
+            .records
+            .get(offset..offset + RECORD_LEN)
+            .map(NodeRecord::read_from)
+            .ok_or(SnapshotError::Corrupted)?;
+        let NodeRecord {
+            children_start,
+            children_count,
+            segment_offset,
+            segment_len,
+            value_offset,
+            value_len,
+            ..
+        } = record;
+        let data_len = self.data.len() as u64;
+        let nodes_count = u64::from(self.nodes_count);
+        if u64::from(segment_offset) + u64::from(segment_len) > data_len
+            || u64::from(value_offset) + u64::from(value_len) > data_len
+            || u64::from(children_start) + u64::from(children_count) > nodes_count
+        {
+            return Err(SnapshotError::Corrupted);
+        }
+        Ok(SnapshotNode {
+            view: *self,
+            index,
+            record,
+        })
+    }
+
+    /// Find a node by its path.
+    ///
+    /// Executed in logarithmic time per path segment.
+    pub fn find_node(
+        &self,
+        path: &T::RootPath,
+    ) -> Result<Option<SnapshotNode<'a, T>>, SnapshotError> {
+        let mut node = self.root_node()?;
+        for path_segment in path.segments() {
+            let Some(child_node) = node.find_child(path_segment)? else {
+                return Ok(None);
+            };
+            node = child_node;
+        }
+        Ok(Some(node))
+    }
+
+    /// Decode all nodes into a new tree.
+    ///
+    /// All nodes are assigned new ids by `new_node_id`.
+    ///
+    /// Fails if the snapshot is corrupted, e.g. if siblings share the same
+    /// path segment or [child key](PathTreeTypes::child_key).
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn to_path_tree(&self, new_node_id: T::NewNodeId) -> Result<PathTree<T>, SnapshotError> {
+        let root_node = self.root_node()?;
+        let mut tree = PathTree::new(new_node_id, root_node.value()?);
+        // All nodes are stored in breadth-first order.
+        let mut node_ids = Vec::with_capacity(self.nodes_count());
+        node_ids.push(tree.root_node_id());
+        for index in 1..self.nodes_count {
+            let node = self.node(index)?;
+            let parent_index = node.record.parent_index as usize;
+            let parent_node_id = node_ids
+                .get(parent_index)
+                .cloned()
+                .ok_or(SnapshotError::Corrupted)?;
+            let parent_node = Arc::clone(tree.get_node(parent_node_id));
+            let path_segment = node.path_segment()?.ok_or(SnapshotError::Corrupted)?;
+            let path_segment = std::borrow::Borrow::borrow(&path_segment);
+            if parent_node.node.find_child(path_segment).is_some() {
+                // Existing nodes must not be updated silently.
+                return Err(SnapshotError::Corrupted);
+            }
+            let inserted = tree
+                .insert_or_update_child_node_value(&parent_node, path_segment, None, node.value()?)
+                .map_err(|_| SnapshotError::Corrupted)?;
+            node_ids.push(inserted.node.id.clone());
+        }
+        Ok(tree)
+    }
+}
+
+/// A lazily decoded node of a [`SnapshotView`].
+pub struct SnapshotNode<'a, T> {
+    view: SnapshotView<'a, T>,
+    index: u32,
+    record: NodeRecord,
+}
+
+impl<T> fmt::Debug for SnapshotNode<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotNode")
+            .field("index", &self.index)
+            .field("record", &self.record)
+            .finish()
+    }
+}
+
+impl<T> Clone for SnapshotNode<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SnapshotNode<'_, T> {}
+
+impl<'a, T> SnapshotNode<'a, T>
+where
+    T: SnapshotCodec,
+{
+    /// The position in breadth-first order, starting with 0 for the root node.
+    #[must_use]
+    pub const fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[must_use]
+    pub const fn is_leaf(&self) -> bool {
+        self.record.flags & FLAG_LEAF != 0
+    }
+
+    #[must_use]
+    pub const fn children_count(&self) -> usize {
+        self.record.children_count as usize
+    }
+
+    fn segment_bytes(&self) -> &'a [u8] {
+        let NodeRecord {
+            segment_offset,
+            segment_len,
+            ..
+        } = self.record;
+        let start = segment_offset as usize;
+        &self.view.data[start..start + segment_len as usize]
+    }
+
+    /// The path segment from the parent node.
+    ///
+    /// `None` for the root node.
+    pub fn path_segment(&self) -> Result<Option<T::PathSegmentOwned>, SnapshotError> {
+        if self.record.parent_index == NO_PARENT {
+            return Ok(None);
+        }
+        T::decode_path_segment(self.segment_bytes())
+            .map(Some)
+            .ok_or(SnapshotError::Corrupted)
+    }
+
+    /// Decode the value.
+    pub fn value(&self) -> Result<NodeValue<T>, SnapshotError> {
+        let NodeRecord {
+            value_offset,
+            value_len,
+            ..
+        } = self.record;
+        let start = value_offset as usize;
+        let bytes = &self.view.data[start..start + value_len as usize];
+        let value = if self.is_leaf() {
+            T::decode_leaf_value(bytes).map(NodeValue::Leaf)
+        } else {
+            T::decode_inner_value(bytes).map(NodeValue::Inner)
+        };
+        value.ok_or(SnapshotError::Corrupted)
+    }
+
+    /// The parent node.
+    ///
+    /// `None` for the root node.
+    pub fn parent(&self) -> Result<Option<Self>, SnapshotError> {
+        if self.record.parent_index == NO_PARENT {
+            return Ok(None);
+        }
+        self.view.node(self.record.parent_index).map(Some)
+    }
+
+    /// All child nodes, ordered by their encoded path segments.
+    pub fn children(&self) -> impl Iterator<Item = Result<Self, SnapshotError>> + 'a
+    where
+        T: 'a,
+    {
+        let view = self.view;
+        let NodeRecord {
+            children_start,
+            children_count,
+            ..
+        } = self.record;
+        (children_start..children_start + children_count).map(move |index| view.node(index))
+    }
+
+    /// Find a child node by its path segment.
+    ///
+    /// Executed in logarithmic time.
+    pub fn find_child(&self, path_segment: &T::PathSegment) -> Result<Option<Self>, SnapshotError> {
+        let mut segment = Vec::new();
+        T::encode_path_segment(path_segment, &mut segment);
+        let NodeRecord {
+            children_start,
+            children_count,
+            ..
+        } = self.record;
+        let (mut low, mut high) = (children_start, children_start + children_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let child_node = self.view.node(mid)?;
+            match child_node.segment_bytes().cmp(&segment) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(Some(child_node)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A snapshot file that is mapped into memory.
+pub struct MappedSnapshot<T> {
+    mmap: memmap2::Mmap,
+    _types: PhantomData<T>,
+}
+
+impl<T> fmt::Debug for MappedSnapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedSnapshot")
+            .field("len", &self.mmap.len())
+            .finish()
+    }
+}
+
+impl<T> MappedSnapshot<T>
+where
+    T: SnapshotCodec,
+{
+    /// Open a snapshot file.
+    ///
+    /// Only the header is read and validated.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, neither by this nor by
+    /// any other process, while the snapshot is mapped into memory. See
+    /// also [`memmap2::Mmap::map()`].
+    #[allow(unsafe_code)]
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let file = fs::File::open(path)?;
+        // SAFETY: The file is not modified while it is mapped, which is
+        // guaranteed by the caller.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        SnapshotView::<T>::new(&mmap)?;
+        Ok(Self {
+            mmap,
+            _types: PhantomData,
+        })
+    }
+
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn view(&self) -> SnapshotView<'_, T> {
+        SnapshotView::new(&self.mmap).expect("validated when opened")
+    }
+}
+
+/// A tree that is read from a snapshot until it is modified.
+///
+/// The snapshot is promoted into an in-memory [`PathTree`] on first
+/// mutable access.
+#[derive(Debug)]
+pub enum MappedTree<T>
+where
+    T: SnapshotCodec,
+{
+    Mapped(MappedSnapshot<T>),
+    Promoted(PathTree<T>),
+}
+
+impl<T> MappedTree<T>
+where
+    T: SnapshotCodec,
+{
+    /// Access the tree for mutation.
+    ///
+    /// Decodes all nodes of a mapped snapshot into a new tree with node ids
+    /// generated by `new_node_id`.
+    pub fn make_mut(
+        &mut self,
+        new_node_id: impl FnOnce() -> T::NewNodeId,
+    ) -> Result<&mut PathTree<T>, SnapshotError> {
+        if let Self::Mapped(snapshot) = self {
+            let tree = snapshot.view().to_path_tree(new_node_id())?;
+            log::debug!(
+                "Promoted mapped snapshot into a tree with {nodes_count} node(s)",
+                nodes_count = tree.nodes_count()
+            );
+            *self = Self::Promoted(tree);
+        }
+        let Self::Promoted(tree) = self else {
+            unreachable!("promoted");
+        };
+        Ok(tree)
+    }
+
+    /// Release the promoted tree.
+    ///
+    /// `None` if the snapshot has not been promoted yet.
+    #[must_use]
+    pub fn into_path_tree(self) -> Option<PathTree<T>> {
+        match self {
+            Self::Mapped(_) => None,
+            Self::Promoted(tree) => Some(tree),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use derive_more::{Display, Error};
+
+use crate::{
+    ExportOrder, HalfEdge, InsertOrUpdateNodeValueError, NodeValue, PathTree, PathTreeTypes,
+};
+
+/// Escapes occurrences of the separator and itself within path segments.
+const ESCAPE_CHAR: char = '\\';
+
+/// Error of [`PathTree::from_materialized_paths()`].
+#[derive(Debug, Display, Error)]
+pub enum FromMaterializedPathsError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("invalid materialized path: {path}")]
+    InvalidPath { path: String, value: NodeValue<T> },
+    #[display("failed to import materialized path: {path}")]
+    InsertOrUpdate {
+        path: String,
+        source: InsertOrUpdateNodeValueError<T>,
+    },
+}
+
+fn escape_path_segment(path_segment: &str, separator: char, path: &mut String) {
+    for c in path_segment.chars() {
+        if c == separator || c == ESCAPE_CHAR {
+            path.push(ESCAPE_CHAR);
+        }
+        path.push(c);
+    }
+}
+
+/// Split a materialized path into unescaped path segments.
+///
+/// Returns `None` if the path is invalid.
+fn parse_materialized_path(path: &str, separator: char) -> Option<Vec<String>> {
+    let mut chars = path.chars();
+    let mut path_segments = Vec::new();
+    match chars.next() {
+        None => {
+            // Root path
+            return Some(path_segments);
+        }
+        Some(c) if c == separator => (),
+        Some(_) => {
+            return None;
+        }
+    }
+    let mut path_segment = String::new();
+    while let Some(c) = chars.next() {
+        if c == ESCAPE_CHAR {
+            let escaped = chars
+                .next()
+                .filter(|&c| c == separator || c == ESCAPE_CHAR)?;
+            path_segment.push(escaped);
+            continue;
+        }
+        if c == separator {
+            if path_segment.is_empty() {
+                return None;
+            }
+            path_segments.push(std::mem::take(&mut path_segment));
+            continue;
+        }
+        path_segment.push(c);
+    }
+    if path_segment.is_empty() {
+        return None;
+    }
+    path_segments.push(path_segment);
+    Some(path_segments)
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Export all nodes with their materialized paths.
+    ///
+    /// Each path segment is prefixed by `separator`, i.e. the root node
+    /// has an empty path and its children have paths like `/child`.
+    /// Occurrences of `separator` and backslashes within path segments
+    /// are escaped by a backslash.
+    ///
+    /// Parent nodes are exported before their children. Siblings are
+    /// exported in the given order, e.g. [`ExportOrder::sorted()`] for
+    /// reproducible exports.
+    ///
+    /// Materialized paths are unversioned. Store the
+    /// [schema tag](PathTreeTypes::schema_tag) alongside persisted entries
+    /// for detecting incompatible types when importing them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `separator` is a backslash.
+    pub fn to_materialized_paths(
+        &self,
+        separator: char,
+        export_order: ExportOrder<T>,
+    ) -> impl Iterator<Item = (String, NodeValue<T>)> + '_
+    where
+        T::PathSegment: AsRef<str>,
+    {
+        assert_ne!(separator, ESCAPE_CHAR);
+        let root_node = self.root_node();
+        std::iter::once((String::new(), root_node.node.to_value())).chain(
+            self.descendant_nodes_for_export(root_node, export_order)
+                .map(move |HalfEdge { node_id, .. }| {
+                    let node = self.get_node(node_id);
+                    let mut path_segments = self
+                        .ancestor_nodes(node)
+                        .map(|ancestor| ancestor.path_segment.as_ref())
+                        .collect::<Vec<_>>();
+                    path_segments.reverse();
+                    let mut path = String::new();
+                    for path_segment in path_segments {
+                        path.push(separator);
+                        escape_path_segment(path_segment, separator, &mut path);
+                    }
+                    (path, node.node.to_value())
+                }),
+        )
+    }
+
+    /// Import nodes from materialized paths.
+    ///
+    /// Reciprocal of [`Self::to_materialized_paths()`].
+    ///
+    /// The entries could be imported in any order. Missing ancestor nodes,
+    /// including the root node, are created with values provided by
+    /// `new_inner_value`. Their values are updated when the corresponding
+    /// entries are imported subsequently.
+    ///
+    /// The path and the value of the entry that could not be imported are
+    /// returned back to the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `separator` is a backslash.
+    pub fn from_materialized_paths(
+        new_node_id: T::NewNodeId,
+        separator: char,
+        entries: impl IntoIterator<Item = (String, NodeValue<T>)>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<Self, FromMaterializedPathsError<T>>
+    where
+        T::PathSegmentOwned: From<String>,
+    {
+        assert_ne!(separator, ESCAPE_CHAR);
+        let mut tree = Self::new(new_node_id, NodeValue::Inner(new_inner_value()));
+        for (path, value) in entries {
+            let Some(path_segments) = parse_materialized_path(&path, separator) else {
+                return Err(FromMaterializedPathsError::InvalidPath { path, value });
+            };
+            let path_segments = path_segments
+                .into_iter()
+                .map(Into::into)
+                .collect::<Vec<_>>();
+            if let Err(source) = tree.insert_or_update_node_value_by_path_segments(
+                tree.root_node_id(),
+                &path_segments,
+                value,
+                new_inner_value,
+            ) {
+                return Err(FromMaterializedPathsError::InsertOrUpdate { path, source });
+            }
+        }
+        Ok(tree)
+    }
+}
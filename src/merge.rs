@@ -0,0 +1,237 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use derive_more::{Display, Error};
+
+use crate::{
+    ConflictPolicy, ConflictResolution, HalfEdge, InsertOrUpdateNodeValueError, Node, NodeValue,
+    PathTree, PathTreeTypes, TreeNode, UnrelatedTreesError, UpdateNodeValueError, ValueConflict,
+};
+
+/// Error of [`PathTree::merge()`].
+#[derive(Debug, Display, Error)]
+pub enum MergeError<T>
+where
+    T: PathTreeTypes,
+{
+    /// Conflicts that have been rejected by the policy.
+    #[display("{} unresolved merge conflict(s)", paths.len())]
+    Unresolved {
+        /// Path segments of the conflicting nodes, starting at the root node.
+        #[error(not(source))]
+        paths: Vec<Vec<T::PathSegmentOwned>>,
+    },
+    #[display("{_0}")]
+    InsertOrUpdate(InsertOrUpdateNodeValueError<T>),
+    /// Both trees belong to different lineages, see [`PathTree::check_same_lineage()`].
+    #[display("{_0}")]
+    UnrelatedTrees(UnrelatedTreesError),
+}
+
+impl<T> From<InsertOrUpdateNodeValueError<T>> for MergeError<T>
+where
+    T: PathTreeTypes,
+{
+    fn from(from: InsertOrUpdateNodeValueError<T>) -> Self {
+        Self::InsertOrUpdate(from)
+    }
+}
+
+impl<T> From<UpdateNodeValueError<T>> for MergeError<T>
+where
+    T: PathTreeTypes,
+{
+    fn from(from: UpdateNodeValueError<T>) -> Self {
+        Self::InsertOrUpdate(from.into())
+    }
+}
+
+fn node_values_equal<T>(ours: &Node<T>, theirs: &Node<T>) -> bool
+where
+    T: PathTreeTypes,
+    T::InnerValue: PartialEq,
+    T::LeafValue: PartialEq,
+{
+    match (ours, theirs) {
+        (Node::Inner(ours), Node::Inner(theirs)) => ours.value == theirs.value,
+        (Node::Leaf(ours), Node::Leaf(theirs)) => ours.value == theirs.value,
+        _ => false,
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Merge another tree into this tree.
+    ///
+    /// Nodes are matched by their path. Missing nodes are inserted together
+    /// with their descendants and are assigned new ids. Existing nodes retain
+    /// their id.
+    ///
+    /// Nodes that exist in both trees with different values are resolved
+    /// by the `policy`. This includes collisions between inner and leaf
+    /// nodes. Nodes that are shared by both trees, e.g. after cloning, or
+    /// that have equal values are not in conflict. Replacing an inner node
+    /// with a leaf value removes all its descendants. The descendants of the
+    /// other node are only merged if both nodes are inner nodes after
+    /// resolving the conflict.
+    ///
+    /// Either all or none of the changes are applied. The tree remains unchanged
+    /// if any conflict is rejected. The error contains all rejected conflicts
+    /// and not only the first one.
+    ///
+    /// Fails without any changes if both trees belong to unrelated lineages.
+    pub fn merge(
+        &mut self,
+        other: &Self,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> Result<(), MergeError<T>>
+    where
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        self.check_same_lineage(other)
+            .map_err(MergeError::UnrelatedTrees)?;
+        log::debug!(
+            "Merging tree with {nodes_count} node(s)",
+            nodes_count = other.nodes_count(),
+        );
+        self.with_rollback(|tree| tree.merge_nodes(other, policy))
+    }
+
+    fn merge_nodes(
+        &mut self,
+        other: &Self,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> Result<(), MergeError<T>>
+    where
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        let mut unresolved_paths = Vec::new();
+        // Pairs of matching inner nodes, whose children need to be merged.
+        let mut pending = Vec::new();
+        let root_node = Arc::clone(self.root_node());
+        if let Some(node_id) = self.merge_node_value(
+            &root_node,
+            other.root_node(),
+            &[],
+            policy,
+            &mut unresolved_paths,
+        )? {
+            pending.push((node_id, other.root_node(), Vec::new()));
+        }
+        while let Some((node_id, other_node, path)) = pending.pop() {
+            for HalfEdge {
+                path_segment,
+                child_key: _,
+                node_id: other_child_node_id,
+            } in other_node.node.children()
+            {
+                let other_child_node = other.get_node(other_child_node_id);
+                let parent_node = Arc::clone(self.get_node(node_id.clone()));
+                let Some(child_node_id) = parent_node.node.find_child(path_segment) else {
+                    let subtree = other.clone_subtree(other_child_node);
+                    self.insert_or_replace_subtree(&parent_node, path_segment, None, subtree)?;
+                    continue;
+                };
+                let child_node = Arc::clone(self.get_node(child_node_id));
+                let mut child_path = path.clone();
+                child_path.push(T::path_segment_to_owned(path_segment));
+                if let Some(child_node_id) = self.merge_node_value(
+                    &child_node,
+                    other_child_node,
+                    &child_path,
+                    policy,
+                    &mut unresolved_paths,
+                )? {
+                    pending.push((child_node_id, other_child_node, child_path));
+                }
+            }
+        }
+        if !unresolved_paths.is_empty() {
+            return Err(MergeError::Unresolved {
+                paths: unresolved_paths,
+            });
+        }
+        Ok(())
+    }
+
+    /// Resolve the value of a node that exists in both trees.
+    ///
+    /// Returns the id of the node if the children need to be merged.
+    fn merge_node_value(
+        &mut self,
+        node: &Arc<TreeNode<T>>,
+        other_node: &Arc<TreeNode<T>>,
+        path: &[T::PathSegmentOwned],
+        policy: &mut impl ConflictPolicy<T>,
+        unresolved_paths: &mut Vec<Vec<T::PathSegmentOwned>>,
+    ) -> Result<Option<T::NodeId>, MergeError<T>>
+    where
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        let node =
+            if Arc::ptr_eq(node, other_node) || node_values_equal(&node.node, &other_node.node) {
+                Arc::clone(node)
+            } else {
+                let ours = node.node.to_value();
+                let theirs = other_node.node.to_value();
+                let resolution = policy.resolve(ValueConflict {
+                    path,
+                    ours: &ours,
+                    theirs: &theirs,
+                });
+                match resolution {
+                    ConflictResolution::Ours => Arc::clone(node),
+                    ConflictResolution::Theirs => self.replace_node_value(node, theirs)?,
+                    ConflictResolution::Value(value) => self.replace_node_value(node, value)?,
+                    ConflictResolution::Reject => {
+                        log::debug!("Unresolved merge conflict at {path:?}");
+                        unresolved_paths.push(path.to_vec());
+                        Arc::clone(node)
+                    }
+                }
+            };
+        let merge_children =
+            matches!(node.node, Node::Inner(_)) && matches!(other_node.node, Node::Inner(_));
+        Ok(merge_children.then(|| node.id.clone()))
+    }
+
+    /// Update the value of a node and remove its children if needed.
+    fn replace_node_value(
+        &mut self,
+        node: &Arc<TreeNode<T>>,
+        value: NodeValue<T>,
+    ) -> Result<Arc<TreeNode<T>>, MergeError<T>> {
+        let value = match self.update_node_value(node, value) {
+            Ok(node) => return Ok(node),
+            Err(UpdateNodeValueError::ValueTypeMismatch { value }) => value,
+            Err(err) => return Err(err.into()),
+        };
+        // An inner node with children could only be replaced by a leaf node
+        // after removing all its descendants.
+        let child_node_ids = node
+            .node
+            .children()
+            .map(|HalfEdge { node_id, .. }| node_id)
+            .collect::<Vec<_>>();
+        for child_node_id in &child_node_ids {
+            if let Some(node_id) =
+                self.find_frozen_node_affected_by_removal(self.get_node(child_node_id.clone()))
+            {
+                return Err(UpdateNodeValueError::SubtreeFrozen { node_id, value }.into());
+            }
+        }
+        for child_node_id in child_node_ids {
+            let removed = self.remove_subtree_by_id(child_node_id);
+            debug_assert!(removed.is_some());
+        }
+        let node = Arc::clone(self.get_node(node.id.clone()));
+        Ok(self.update_node_value(&node, value)?)
+    }
+}
@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Optional instrumentation through the `metrics` facade.
+//!
+//! All functions are no-ops unless the feature "metrics" is enabled.
+
+#[cfg(feature = "metrics")]
+const LOOKUPS_TOTAL: &str = "rpds_pathtree_lookups_total";
+
+#[cfg(feature = "metrics")]
+const FAILED_LOOKUPS_TOTAL: &str = "rpds_pathtree_failed_lookups_total";
+
+#[cfg(feature = "metrics")]
+const RESOLVED_DEPTH: &str = "rpds_pathtree_resolved_depth";
+
+#[cfg(feature = "metrics")]
+const INSERTS_TOTAL: &str = "rpds_pathtree_inserts_total";
+
+#[cfg(feature = "metrics")]
+const REMOVALS_TOTAL: &str = "rpds_pathtree_removals_total";
+
+#[cfg(feature = "metrics")]
+const NODES_COUNT: &str = "rpds_pathtree_nodes_count";
+
+#[cfg(feature = "metrics")]
+#[allow(clippy::cast_precision_loss)] // Only used for statistics
+const fn usize_to_f64(value: usize) -> f64 {
+    value as f64
+}
+
+/// Record a lookup of a node by its path.
+///
+/// `None` if no node has been found.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_lookup(resolved_depth: Option<usize>) {
+    ::metrics::counter!(LOOKUPS_TOTAL).increment(1);
+    let Some(resolved_depth) = resolved_depth else {
+        // Failed lookups would otherwise skew the histogram towards 0.
+        ::metrics::counter!(FAILED_LOOKUPS_TOTAL).increment(1);
+        return;
+    };
+    ::metrics::histogram!(RESOLVED_DEPTH).record(usize_to_f64(resolved_depth));
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) const fn record_lookup(_resolved_depth: Option<usize>) {}
+
+/// Record the insertion or update of a single node.
+///
+/// Invoked implicitly for every new node when modifying the tree, but
+/// not for nodes that are replaced with an updated value.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_insert(nodes_count: usize) {
+    ::metrics::counter!(INSERTS_TOTAL).increment(1);
+    ::metrics::histogram!(NODES_COUNT).record(usize_to_f64(nodes_count));
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) const fn record_insert(_nodes_count: usize) {}
+
+/// Record the removal of a single node.
+///
+/// Invoked implicitly for every removed node when modifying the tree.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_removal(nodes_count: usize) {
+    ::metrics::counter!(REMOVALS_TOTAL).increment(1);
+    ::metrics::histogram!(NODES_COUNT).record(usize_to_f64(nodes_count));
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) const fn record_removal(_nodes_count: usize) {}
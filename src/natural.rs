@@ -0,0 +1,65 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+/// Take the next run of ASCII digits.
+fn take_digits(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Compare two runs of ASCII digits by their numeric value.
+///
+/// Equal values with more leading zeros are ordered last.
+fn cmp_digits(lhs: &str, rhs: &str) -> Ordering {
+    let lhs_value = lhs.trim_start_matches('0');
+    let rhs_value = rhs.trim_start_matches('0');
+    lhs_value
+        .len()
+        .cmp(&rhs_value.len())
+        .then_with(|| lhs_value.cmp(rhs_value))
+        .then_with(|| lhs.len().cmp(&rhs.len()))
+}
+
+/// Compare strings in natural order.
+///
+/// Embedded runs of ASCII digits are compared by their numeric value,
+/// e.g. `"file2"` is ordered before `"file10"`. All other characters are
+/// compared by their code points. Numbers of arbitrary length are supported.
+///
+/// Could be used for sorting path segments, e.g. by
+/// [`PathTree::child_nodes_sorted_by()`](crate::PathTree::child_nodes_sorted_by).
+#[must_use]
+pub fn natural_cmp(lhs: &str, rhs: &str) -> Ordering {
+    let mut lhs_chars = lhs.chars().peekable();
+    let mut rhs_chars = rhs.chars().peekable();
+    loop {
+        match (lhs_chars.peek().copied(), rhs_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(lhs_char), Some(rhs_char))
+                if lhs_char.is_ascii_digit() && rhs_char.is_ascii_digit() =>
+            {
+                let lhs_digits = take_digits(&mut lhs_chars);
+                let rhs_digits = take_digits(&mut rhs_chars);
+                let ordering = cmp_digits(&lhs_digits, &rhs_digits);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(lhs_char), Some(rhs_char)) => {
+                let ordering = lhs_char.cmp(&rhs_char);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                lhs_chars.next();
+                rhs_chars.next();
+            }
+        }
+    }
+}
@@ -1,13 +1,25 @@
 // SPDX-FileCopyrightText: The rpds-pathtree authors
 // SPDX-License-Identifier: MPL-2.0
 
-use std::borrow::Borrow as _;
+use std::{borrow::Borrow as _, cmp::Ordering, fmt};
 
-use crate::{new_hash_map, HalfEdge, HashMap, PathTree, PathTreeTypes};
+use crate::{
+    children::{new_children_map, ChildrenMap},
+    debug::SortedDebugMap,
+    HalfEdge, HalfEdgeOwned, PathTree, PathTreeTypes,
+};
 
 const DESCENDANTS_ITER_STACK_CAPACITY: usize = 1024;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T::InnerValue: serde::Serialize, T::LeafValue: serde::Serialize",
+        deserialize = "T::InnerValue: serde::Deserialize<'de>, T::LeafValue: serde::Deserialize<'de>"
+    ))
+)]
 pub enum NodeValue<T: PathTreeTypes> {
     Inner(T::InnerValue),
     Leaf(T::LeafValue),
@@ -29,12 +41,38 @@ where
     pub(crate) fn from_value_without_children(value: NodeValue<T>) -> Self {
         let node = match value {
             NodeValue::Inner(value) => Self::Inner(InnerNode::new(value)),
-            NodeValue::Leaf(value) => Self::Leaf(LeafNode::new(value)),
+            NodeValue::Leaf(value) => Self::Leaf(LeafNode::new(T::intern_leaf_value(value))),
         };
         debug_assert_eq!(node.children_count(), 0);
         node
     }
 
+    /// Clone the value of the node.
+    #[must_use]
+    pub fn to_value(&self) -> NodeValue<T> {
+        match self {
+            Self::Inner(InnerNode { value, .. }) => NodeValue::Inner(value.clone()),
+            Self::Leaf(LeafNode { value }) => NodeValue::Leaf(value.clone()),
+        }
+    }
+
+    /// Check if the node has a value that is equal to the given value.
+    ///
+    /// Values are compared by [`PathTreeTypes::inner_values_equal()`]
+    /// and [`PathTreeTypes::leaf_values_equal()`].
+    #[must_use]
+    pub fn has_equal_value(&self, value: &NodeValue<T>) -> bool {
+        match (self, value) {
+            (Self::Inner(InnerNode { value, .. }), NodeValue::Inner(other_value)) => {
+                T::inner_values_equal(value, other_value)
+            }
+            (Self::Leaf(LeafNode { value }), NodeValue::Leaf(other_value)) => {
+                T::leaf_values_equal(value, other_value)
+            }
+            _ => false,
+        }
+    }
+
     pub const fn inner_value(&self) -> Option<&T::InnerValue> {
         match self {
             Self::Inner(InnerNode { value, .. }) => Some(value),
@@ -48,6 +86,18 @@ where
             Self::Inner(InnerNode { .. }) => None,
         }
     }
+
+    /// The weight of the value.
+    ///
+    /// See [`PathTreeTypes::inner_value_weight()`] and
+    /// [`PathTreeTypes::leaf_value_weight()`].
+    #[must_use]
+    pub fn value_weight(&self) -> usize {
+        match self {
+            Self::Inner(InnerNode { value, .. }) => T::inner_value_weight(value),
+            Self::Leaf(LeafNode { value }) => T::leaf_value_weight(value),
+        }
+    }
 }
 
 impl<T> From<InnerNode<T>> for Node<T>
@@ -123,15 +173,29 @@ where
 }
 
 /// Intrinsic data of an inner node.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct InnerNode<T>
 where
     T: PathTreeTypes,
 {
-    pub(crate) children: HashMap<T::PathSegmentOwned, T::NodeId>,
+    pub(crate) children: ChildrenMap<T::ChildKeyOwned, HalfEdgeOwned<T>>,
     pub value: T::InnerValue,
 }
 
+// Children are sorted for a reproducible output.
+impl<T> fmt::Debug for InnerNode<T>
+where
+    T: PathTreeTypes,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { children, value } = self;
+        f.debug_struct("InnerNode")
+            .field("children", &SortedDebugMap::new(children.iter()))
+            .field("value", value)
+            .finish()
+    }
+}
+
 impl<T> InnerNode<T>
 where
     T: PathTreeTypes,
@@ -139,21 +203,29 @@ where
     /// Create an empty inner node with no children
     pub(crate) fn new(value: T::InnerValue) -> Self {
         Self {
-            children: new_hash_map(),
+            children: new_children_map(),
             value,
         }
     }
 
     /// Edges to children of this node
     ///
-    /// In arbitrary but stable ordering.
+    /// In arbitrary but stable ordering. In insertion order if the
+    /// feature "insertion-order" is enabled.
     pub fn children(&self) -> impl ExactSizeIterator<Item = HalfEdge<'_, T>> + '_ {
-        self.children
-            .iter()
-            .map(|(path_segment, node_id)| HalfEdge {
+        self.children.iter().map(
+            |(
+                child_key,
+                HalfEdgeOwned {
+                    path_segment,
+                    node_id,
+                },
+            )| HalfEdge {
                 path_segment: path_segment.borrow(),
-                node_id: *node_id,
-            })
+                child_key: child_key.borrow(),
+                node_id: node_id.clone(),
+            },
+        )
     }
 
     /// Returns the number of children.
@@ -169,7 +241,44 @@ where
     ///
     /// Returns the id of the child node or `None` if not found.
     pub fn find_child(&self, child_path_segment: &T::PathSegment) -> Option<T::NodeId> {
-        self.children.get(child_path_segment).copied()
+        self.children
+            .get(&*T::child_key(child_path_segment))
+            .map(|HalfEdgeOwned { node_id, .. }| node_id.clone())
+    }
+
+    /// Add a new child node.
+    ///
+    /// The child key is derived from the given path segment, which is
+    /// stored along with the node id. The child key must not be occupied.
+    pub(crate) fn insert_child(&mut self, child_path_segment: &T::PathSegment, node_id: T::NodeId) {
+        let child_key = T::child_key(child_path_segment);
+        strict_assert!(!self.children.contains_key(&*child_key));
+        self.children.insert_mut(
+            child_key.into_owned(),
+            HalfEdgeOwned {
+                path_segment: T::path_segment_to_owned(child_path_segment),
+                node_id,
+            },
+        );
+    }
+
+    /// Replace the node id of an existing child node.
+    ///
+    /// The child key and its position among the siblings are retained.
+    pub(crate) fn replace_child(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+        node_id: T::NodeId,
+    ) {
+        let child_key = T::child_key(child_path_segment);
+        strict_assert!(self.children.contains_key(&*child_key));
+        self.children.insert_mut(
+            child_key.into_owned(),
+            HalfEdgeOwned {
+                path_segment: T::path_segment_to_owned(child_path_segment),
+                node_id,
+            },
+        );
     }
 
     fn descendants<'a>(&'a self, tree: &'a PathTree<T>) -> DepthFirstDescendantsIter<'a, T> {
@@ -191,6 +300,7 @@ where
             |count,
              HalfEdge {
                  path_segment: _,
+                 child_key: _,
                  node_id,
              }| {
                 count
@@ -248,7 +358,7 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         let child = self.children_stack.pop()?;
-        let Some(node) = self.tree.lookup_node(child.node_id) else {
+        let Some(node) = self.tree.lookup_node(child.node_id.clone()) else {
             unreachable!("child node not found: {node_id}", node_id = child.node_id);
         };
         match &node.node {
@@ -259,6 +369,224 @@ where
         }
         Some(child)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // All pending children will be yielded, but their descendants are unknown.
+        descendants_size_hint(self.children_stack.len())
+    }
+}
+
+const fn descendants_size_hint(pending_count: usize) -> (usize, Option<usize>) {
+    let upper_bound = if pending_count == 0 { Some(0) } else { None };
+    (pending_count, upper_bound)
+}
+
+/// Iterator over descendants of a node with a known number of items
+///
+/// Yields the same items in the same order as [`DepthFirstDescendantsIter`].
+///
+/// Returned by [`PathTree::descendant_nodes_counted()`].
+#[derive(Debug)]
+pub struct CountedDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    iter: DepthFirstDescendantsIter<'a, T>,
+    remaining_count: usize,
+}
+
+impl<'a, T> CountedDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    pub(crate) fn new(node: &'a Node<T>, tree: &'a PathTree<T>) -> Self {
+        Self {
+            iter: node.descendants(tree),
+            remaining_count: node.descendants_count(tree),
+        }
+    }
+}
+
+impl<'a, T> Iterator for CountedDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    type Item = HalfEdge<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.iter.next()?;
+        debug_assert!(self.remaining_count > 0);
+        self.remaining_count -= 1;
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining_count, Some(self.remaining_count))
+    }
+}
+
+impl<T> ExactSizeIterator for CountedDescendantsIter<'_, T> where T: PathTreeTypes {}
+
+#[derive(Debug)]
+struct PendingReverseDescendant<'a, T>
+where
+    T: PathTreeTypes,
+{
+    child: HalfEdge<'a, T>,
+    children_pushed: bool,
+}
+
+/// Iterator over descendants of a node in reverse order
+///
+/// Yields the same items as [`DepthFirstDescendantsIter`], but in reverse
+/// order, i.e. all descendants of a node are yielded before the node itself.
+/// Taking the last items of a depth-first traversal doesn't require to
+/// visit the preceding items.
+///
+/// Returned by [`PathTree::descendant_nodes_rev()`].
+#[derive(Debug)]
+pub struct ReverseDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a PathTree<T>,
+    pending_stack: Vec<PendingReverseDescendant<'a, T>>,
+}
+
+impl<'a, T> ReverseDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    pub(crate) fn new(node: &'a Node<T>, tree: &'a PathTree<T>) -> Self {
+        let mut iter = Self {
+            tree,
+            pending_stack: Vec::with_capacity(DESCENDANTS_ITER_STACK_CAPACITY),
+        };
+        iter.push_children(node);
+        iter
+    }
+
+    fn push_children(&mut self, node: &'a Node<T>) {
+        // The last child ends up at the top of the stack.
+        self.pending_stack
+            .extend(node.children().map(|child| PendingReverseDescendant {
+                child,
+                children_pushed: false,
+            }));
+    }
+}
+
+impl<'a, T> Iterator for ReverseDescendantsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    type Item = HalfEdge<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let PendingReverseDescendant {
+                child,
+                children_pushed,
+            } = self.pending_stack.pop()?;
+            if children_pushed {
+                return Some(child);
+            }
+            let Some(node) = self.tree.lookup_node(child.node_id.clone()) else {
+                unreachable!("child node not found: {node_id}", node_id = child.node_id);
+            };
+            if node.node.children_count() == 0 {
+                return Some(child);
+            }
+            // Revisited after all its descendants have been yielded.
+            self.pending_stack.push(PendingReverseDescendant {
+                child,
+                children_pushed: true,
+            });
+            self.push_children(&node.node);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        descendants_size_hint(self.pending_stack.len())
+    }
+}
+
+/// Compares path segments.
+pub type PathSegmentCmp<T> =
+    fn(&<T as PathTreeTypes>::PathSegment, &<T as PathTreeTypes>::PathSegment) -> Ordering;
+
+/// Iterator over descendants of a node in a deterministic order
+///
+/// Returned by [`PathTree::descendant_nodes_ordered()`] and
+/// [`PathTree::descendant_nodes_sorted_by()`].
+pub struct OrderedDescendantsIter<'a, T, C = PathSegmentCmp<T>>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a PathTree<T>,
+    children_stack: Vec<HalfEdge<'a, T>>,
+    compare: C,
+}
+
+impl<T, C> fmt::Debug for OrderedDescendantsIter<'_, T, C>
+where
+    T: PathTreeTypes,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrderedDescendantsIter")
+            .field("children_stack", &self.children_stack)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, T, C> OrderedDescendantsIter<'a, T, C>
+where
+    T: PathTreeTypes,
+    C: FnMut(&T::PathSegment, &T::PathSegment) -> Ordering,
+{
+    pub(crate) fn new(tree: &'a PathTree<T>, node: &'a Node<T>, compare: C) -> Self {
+        let mut iter = Self {
+            tree,
+            children_stack: Vec::with_capacity(DESCENDANTS_ITER_STACK_CAPACITY),
+            compare,
+        };
+        if let Node::Inner(inner) = node {
+            iter.push_parent(inner);
+        }
+        iter
+    }
+
+    fn push_parent(&mut self, parent: &'a InnerNode<T>) {
+        let len_before = self.children_stack.len();
+        self.children_stack.extend(parent.children());
+        // Sort in reverse order so that the first child ends up at the top of the stack.
+        let compare = &mut self.compare;
+        self.children_stack[len_before..]
+            .sort_unstable_by(|lhs, rhs| compare(rhs.path_segment, lhs.path_segment));
+    }
+}
+
+impl<'a, T, C> Iterator for OrderedDescendantsIter<'a, T, C>
+where
+    T: PathTreeTypes,
+    C: FnMut(&T::PathSegment, &T::PathSegment) -> Ordering,
+{
+    type Item = HalfEdge<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = self.children_stack.pop()?;
+        let Some(node) = self.tree.lookup_node(child.node_id.clone()) else {
+            unreachable!("child node not found: {node_id}", node_id = child.node_id);
+        };
+        if let Node::Inner(inner) = &node.node {
+            self.push_parent(inner);
+        }
+        Some(child)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        descendants_size_hint(self.children_stack.len())
+    }
 }
 
 /// Intrinsic data of a leaf node.
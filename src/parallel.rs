@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+use crate::{InsertOrUpdateNodeValueError, NodeValue, PartitionNewNodeId, PathTree, PathTreeTypes};
+
+type PathEntry<T> = (Vec<<T as PathTreeTypes>::PathSegmentOwned>, NodeValue<T>);
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+    T::NewNodeId: PartitionNewNodeId<T::NodeId> + Send,
+    T::PathSegmentOwned: Send,
+    T::InnerValue: Send,
+    T::LeafValue: Send,
+    Self: Send,
+    InsertOrUpdateNodeValueError<T>: Send,
+{
+    /// Build a tree from paths and values in parallel.
+    ///
+    /// The entries are partitioned by their first path segment. The subtrees
+    /// below the root node are built independently on the thread pool of
+    /// `rayon` and then mounted under the root node. Each subtree generates
+    /// its node ids with a separate generator obtained by
+    /// [`PartitionNewNodeId::partition()`].
+    ///
+    /// An empty path denotes the root node. Missing ancestor nodes,
+    /// including the root node, are created with values provided by
+    /// `new_inner_value`.
+    ///
+    /// The resulting tree is the same as if all entries had been inserted
+    /// sequentially, except for the node ids.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn build_parallel(
+        mut new_node_id: T::NewNodeId,
+        entries: impl IntoIterator<Item = PathEntry<T>>,
+        new_inner_value: &(impl Fn() -> T::InnerValue + Sync),
+    ) -> Result<Self, InsertOrUpdateNodeValueError<T>> {
+        let mut root_value = None;
+        let mut partitions = Vec::<(T::PathSegmentOwned, Vec<PathEntry<T>>)>::new();
+        let mut partition_indexes = HashMap::new();
+        for (mut path, value) in entries {
+            if path.is_empty() {
+                root_value = Some(value);
+                continue;
+            }
+            let path_segment = path.remove(0);
+            let index = match partition_indexes.entry(path_segment) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let index = partitions.len();
+                    partitions.push((entry.key().clone(), Vec::new()));
+                    *entry.insert(index)
+                }
+            };
+            partitions[index].1.push((path, value));
+        }
+        let subtree_new_node_ids = new_node_id.partition(partitions.len());
+        debug_assert_eq!(partitions.len(), subtree_new_node_ids.len());
+        let subtrees = partitions
+            .into_iter()
+            .zip(subtree_new_node_ids)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|((path_segment, entries), new_node_id)| {
+                Self::build_sequential(new_node_id, entries, new_inner_value)
+                    .map(|subtree| (path_segment, subtree))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut tree = Self::new(new_node_id, NodeValue::Inner(new_inner_value()));
+        let root_node_id = tree.root_node_id();
+        for (path_segment, subtree) in subtrees {
+            tree.mount_subtree(root_node_id.clone(), path_segment, subtree);
+        }
+        if let Some(root_value) = root_value {
+            let root_node = std::sync::Arc::clone(tree.root_node());
+            tree.update_node_value(&root_node, root_value)?;
+        }
+        log::debug!(
+            "Built tree with {nodes_count} node(s) in parallel",
+            nodes_count = tree.nodes_count()
+        );
+        Ok(tree)
+    }
+
+    fn build_sequential(
+        new_node_id: T::NewNodeId,
+        entries: Vec<PathEntry<T>>,
+        new_inner_value: &(impl Fn() -> T::InnerValue + Sync),
+    ) -> Result<Self, InsertOrUpdateNodeValueError<T>> {
+        let mut new_inner_value = || new_inner_value();
+        let mut tree = Self::new(new_node_id, NodeValue::Inner(new_inner_value()));
+        for (path, value) in entries {
+            tree.insert_or_update_node_value_by_path_segments(
+                tree.root_node_id(),
+                &path,
+                value,
+                &mut new_inner_value,
+            )?;
+        }
+        Ok(tree)
+    }
+}
@@ -0,0 +1,727 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow, collections::HashMap, fmt, str::FromStr, sync::Arc};
+
+use derive_more::{Display, Error};
+
+use crate::{
+    ConflictPolicy, ConflictResolution, InsertOrUpdateNodeValueError, Node, NodeValue, PathTree,
+    PathTreeTypes, RejectConflicts, TreeId, TreeNode, UpdateNodeValueError, ValueConflict,
+};
+
+/// A single operation of a [`TreePatch`].
+///
+/// Nodes are addressed by the segments of their path, starting at the root node.
+#[derive(Debug, Clone)]
+pub enum PatchOperation<T>
+where
+    T: PathTreeTypes,
+{
+    /// Remove a node with all its descendants.
+    Remove { path: Vec<T::PathSegmentOwned> },
+
+    /// Replace the value of an existing node.
+    Update {
+        path: Vec<T::PathSegmentOwned>,
+        value: NodeValue<T>,
+    },
+
+    /// Insert a new node below an existing parent node.
+    Insert {
+        path: Vec<T::PathSegmentOwned>,
+        value: NodeValue<T>,
+    },
+}
+
+impl<T> PatchOperation<T>
+where
+    T: PathTreeTypes,
+{
+    /// The path segments of the affected node.
+    #[must_use]
+    pub fn path(&self) -> &[T::PathSegmentOwned] {
+        match self {
+            Self::Remove { path } | Self::Update { path, .. } | Self::Insert { path, .. } => path,
+        }
+    }
+}
+
+/// Path-based changes between two trees.
+///
+/// All removals precede all updates that precede all insertions.
+/// Parent nodes are inserted before their children.
+///
+/// Patches created by [`PathTree::diff_patch()`] are ordered by the kind
+/// of the operations and then by their paths. Their textual representation
+/// is reproducible and does not depend on the iteration order of nodes.
+///
+/// In contrast to [`crate::TreeDiff`] the patch does not depend on node ids.
+/// Moved or renamed nodes are removed and inserted again.
+///
+/// # Textual representation
+///
+/// Patches could be formatted and parsed in a line-based, textual representation:
+///
+/// ```text
+/// - /path/of/removed/node
+/// ~ /path/of/updated/node inner <value>
+/// + /path/of/inserted/node leaf <value>
+/// ```
+///
+/// Path segments and values are escaped with `%XX` sequences.
+///
+/// The plain representation is unversioned. Persisted patches should be
+/// formatted with a self-describing header by [`Self::to_string_with_header()`].
+#[derive(Debug, Clone)]
+pub struct TreePatch<T>
+where
+    T: PathTreeTypes,
+{
+    pub operations: Vec<PatchOperation<T>>,
+
+    source_tree_id: Option<TreeId>,
+}
+
+impl<T> TreePatch<T>
+where
+    T: PathTreeTypes,
+{
+    /// Create a patch from operations.
+    ///
+    /// The patch is not associated with any source tree.
+    #[must_use]
+    pub const fn new(operations: Vec<PatchOperation<T>>) -> Self {
+        Self {
+            operations,
+            source_tree_id: None,
+        }
+    }
+
+    /// The id of the (new) tree from which the patch has been created.
+    ///
+    /// Recorded in the provenance of the patched tree. Not included
+    /// in the textual format.
+    #[must_use]
+    pub const fn source_tree_id(&self) -> Option<TreeId> {
+        self.source_tree_id
+    }
+
+    /// Check if the patch contains no operations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Check if the patch could be applied to a tree without conflicts.
+    ///
+    /// Performs a dry run of [`PathTree::apply_patch()`] on a copy of the tree.
+    /// All operations are checked, i.e. the report includes all conflicts and
+    /// not only the first one.
+    #[must_use]
+    pub fn check(&self, tree: &PathTree<T>) -> PatchReport {
+        self.check_with_policy(tree, &mut RejectConflicts)
+    }
+
+    /// Check if the patch could be applied to a tree without unresolved conflicts.
+    ///
+    /// See also [`PathTree::apply_patch_with_policy()`].
+    #[must_use]
+    pub fn check_with_policy(
+        &self,
+        tree: &PathTree<T>,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> PatchReport {
+        let mut tree = tree.clone();
+        self.apply_to(&mut tree, policy)
+    }
+
+    fn apply_to(&self, tree: &mut PathTree<T>, policy: &mut impl ConflictPolicy<T>) -> PatchReport {
+        let conflicts = self
+            .operations
+            .iter()
+            .enumerate()
+            .filter_map(|(operation_index, operation)| {
+                let kind = tree.apply_patch_operation(operation, policy).err()?;
+                log::debug!("Conflicting patch operation #{operation_index}: {kind:?}");
+                Some(PatchConflict {
+                    operation_index,
+                    kind,
+                })
+            })
+            .collect();
+        PatchReport { conflicts }
+    }
+}
+
+/// The reason why a [`PatchOperation`] could not be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchConflictKind {
+    /// The removed or updated node does not exist.
+    NodeNotFound,
+
+    /// The parent node of an inserted node does not exist or is a leaf node.
+    ParentNodeNotFound,
+
+    /// The inserted node already exists, e.g. after a concurrent rename,
+    /// and the conflicting values have not been resolved.
+    NodeAlreadyExists,
+
+    /// An inner node with children could not be updated with a leaf value.
+    NodeKindMismatch,
+
+    /// The root node could not be removed.
+    RootNode,
+
+    /// The affected node is part of or contains a frozen subtree,
+    /// see [`PathTree::freeze_subtree()`].
+    SubtreeFrozen,
+}
+
+/// A conflicting [`PatchOperation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatchConflict {
+    /// The index in [`TreePatch::operations`].
+    pub operation_index: usize,
+
+    pub kind: PatchConflictKind,
+}
+
+/// Conflicts that prevent a [`TreePatch`] from being applied.
+#[derive(Debug, Clone, Default, Display, Error)]
+#[display("{} conflicting patch operation(s)", conflicts.len())]
+pub struct PatchReport {
+    /// Conflicting operations in order.
+    pub conflicts: Vec<PatchConflict>,
+}
+
+impl PatchReport {
+    /// Check if the patch could be applied without conflicts.
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Create a patch that transforms this (old) tree into a new tree.
+    ///
+    /// Nodes are matched by their path, independent of their id. Values are
+    /// compared by equality. The trees do not need to share node ids, e.g.
+    /// if one of them has been built independently, imported, or has been
+    /// assigned new ids by [`Self::reassign_ids()`]. Moved nodes that retain
+    /// their id are removed and inserted again at their new path.
+    ///
+    /// Operations of the same kind are sorted by their paths.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn diff_patch(&self, new_tree: &Self) -> TreePatch<T>
+    where
+        T::PathSegment: Ord,
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        let old_nodes = self.nodes_with_paths(self.root_node()).collect::<Vec<_>>();
+        let new_nodes = new_tree
+            .nodes_with_paths(new_tree.root_node())
+            .collect::<Vec<_>>();
+        let old_index = old_nodes
+            .iter()
+            .map(|(path, node)| (path.as_slice(), *node))
+            .collect::<HashMap<_, _>>();
+        let new_index = new_nodes
+            .iter()
+            .map(|(path, node)| (path.as_slice(), *node))
+            .collect::<HashMap<_, _>>();
+        let mut removed = Vec::new();
+        let mut updated = Vec::new();
+        for (path, old_node) in &old_nodes {
+            if let Some(new_node) = new_index.get(path.as_slice()) {
+                if !is_same_value(old_node, new_node) {
+                    updated.push(PatchOperation::Update {
+                        path: to_owned_path::<T>(path),
+                        value: new_node.node.to_value(),
+                    });
+                }
+                continue;
+            }
+            let (_, parent_path) = path.split_last().expect("not the root node");
+            if new_index.contains_key(parent_path) {
+                // Only the root node of the removed subtree needs to be removed.
+                removed.push(PatchOperation::Remove {
+                    path: to_owned_path::<T>(path),
+                });
+            }
+        }
+        let mut inserted = new_nodes
+            .iter()
+            .filter(|(path, _)| !old_index.contains_key(path.as_slice()))
+            .map(|(path, new_node)| PatchOperation::Insert {
+                path: to_owned_path::<T>(path),
+                value: new_node.node.to_value(),
+            })
+            .collect::<Vec<_>>();
+        // Sorting by path results in a total order, because the paths of
+        // all operations are distinct. Parent nodes are inserted before
+        // their children, because a path is ordered before its extensions.
+        let cmp_paths = |lhs: &PatchOperation<T>, rhs: &PatchOperation<T>| {
+            let lhs = lhs.path().iter().map(Borrow::<T::PathSegment>::borrow);
+            let rhs = rhs.path().iter().map(Borrow::<T::PathSegment>::borrow);
+            lhs.cmp(rhs)
+        };
+        removed.sort_by(cmp_paths);
+        updated.sort_by(cmp_paths);
+        inserted.sort_by(cmp_paths);
+        let operations = removed.into_iter().chain(updated).chain(inserted).collect();
+        TreePatch {
+            operations,
+            source_tree_id: new_tree.tree_id(),
+        }
+    }
+
+    /// Apply a patch.
+    ///
+    /// Either all or none of the operations are applied. The tree remains
+    /// unchanged if any operation conflicts. See also [`TreePatch::check()`].
+    pub fn apply_patch(&mut self, patch: &TreePatch<T>) -> Result<(), PatchReport> {
+        self.apply_patch_with_policy(patch, &mut RejectConflicts)
+    }
+
+    /// Apply a patch and resolve conflicting values.
+    ///
+    /// Inserting an existing node is resolved by the `policy`.
+    ///
+    /// See also [`Self::apply_patch()`].
+    pub fn apply_patch_with_policy(
+        &mut self,
+        patch: &TreePatch<T>,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> Result<(), PatchReport> {
+        let mut tree = self.clone();
+        let report = patch.apply_to(&mut tree, policy);
+        if !report.is_ok() {
+            return Err(report);
+        }
+        tree.record_applied_patch(patch.source_tree_id);
+        *self = tree;
+        Ok(())
+    }
+
+    fn apply_patch_operation(
+        &mut self,
+        operation: &PatchOperation<T>,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> Result<(), PatchConflictKind> {
+        match operation {
+            PatchOperation::Remove { path } => {
+                if path.is_empty() {
+                    return Err(PatchConflictKind::RootNode);
+                }
+                let node_id = self
+                    .find_node_by_path_segments(path)
+                    .ok_or(PatchConflictKind::NodeNotFound)?
+                    .id
+                    .clone();
+                self.remove_subtree_by_id(node_id)
+                    .ok_or(PatchConflictKind::SubtreeFrozen)?;
+            }
+            PatchOperation::Update { path, value } => {
+                let node = self
+                    .find_node_by_path_segments(path)
+                    .map(Arc::clone)
+                    .ok_or(PatchConflictKind::NodeNotFound)?;
+                self.update_node_value(&node, value.clone())
+                    .map_err(|err| update_conflict_kind(&err))?;
+            }
+            PatchOperation::Insert { path, value } => {
+                let (child_path_segment, parent_path) = path
+                    .split_last()
+                    .ok_or(PatchConflictKind::NodeAlreadyExists)?;
+                let parent_node = self
+                    .find_node_by_path_segments(parent_path)
+                    .filter(|node| matches!(node.node, Node::Inner(_)))
+                    .map(Arc::clone)
+                    .ok_or(PatchConflictKind::ParentNodeNotFound)?;
+                if let Some(child_node_id) =
+                    parent_node.node.find_child(child_path_segment.borrow())
+                {
+                    let child_node = Arc::clone(self.get_node(child_node_id));
+                    let conflict = ValueConflict {
+                        path,
+                        ours: &child_node.node.to_value(),
+                        theirs: value,
+                    };
+                    let new_value = match policy.resolve(conflict) {
+                        ConflictResolution::Ours => return Ok(()),
+                        ConflictResolution::Theirs => value.clone(),
+                        ConflictResolution::Value(value) => value,
+                        ConflictResolution::Reject => {
+                            return Err(PatchConflictKind::NodeAlreadyExists);
+                        }
+                    };
+                    self.update_node_value(&child_node, new_value)
+                        .map_err(|err| update_conflict_kind(&err))?;
+                    return Ok(());
+                }
+                self.insert_or_update_child_node_value(
+                    &parent_node,
+                    child_path_segment.borrow(),
+                    None,
+                    value.clone(),
+                )
+                .map_err(|err| match err {
+                    InsertOrUpdateNodeValueError::SubtreeFrozen { .. } => {
+                        PatchConflictKind::SubtreeFrozen
+                    }
+                    _ => PatchConflictKind::ParentNodeNotFound,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_node_by_path_segments(
+        &self,
+        path: &[T::PathSegmentOwned],
+    ) -> Option<&Arc<TreeNode<T>>> {
+        path.iter()
+            .try_fold(self.root_node(), |node, path_segment| {
+                let child_node_id = node.node.find_child(path_segment.borrow())?;
+                self.lookup_node(child_node_id)
+            })
+    }
+}
+
+const fn update_conflict_kind<T>(err: &UpdateNodeValueError<T>) -> PatchConflictKind
+where
+    T: PathTreeTypes,
+{
+    match err {
+        UpdateNodeValueError::SubtreeFrozen { .. } => PatchConflictKind::SubtreeFrozen,
+        UpdateNodeValueError::ValueTypeMismatch { .. }
+        | UpdateNodeValueError::StaleNodeReference { .. } => PatchConflictKind::NodeKindMismatch,
+    }
+}
+
+fn to_owned_path<T>(path: &[&T::PathSegment]) -> Vec<T::PathSegmentOwned>
+where
+    T: PathTreeTypes,
+{
+    path.iter()
+        .map(|path_segment| T::path_segment_to_owned(path_segment))
+        .collect()
+}
+
+fn is_same_value<T>(old_node: &Arc<TreeNode<T>>, new_node: &Arc<TreeNode<T>>) -> bool
+where
+    T: PathTreeTypes,
+    T::InnerValue: PartialEq,
+    T::LeafValue: PartialEq,
+{
+    if Arc::ptr_eq(old_node, new_node) {
+        return true;
+    }
+    match (&old_node.node, &new_node.node) {
+        (Node::Inner(old), Node::Inner(new)) => old.value == new.value,
+        (Node::Leaf(old), Node::Leaf(new)) => old.value == new.value,
+        _ => false,
+    }
+}
+
+const PATH_SEPARATOR: char = '/';
+
+const INNER_VALUE_TAG: &str = "inner";
+
+const LEAF_VALUE_TAG: &str = "leaf";
+
+fn write_escaped(
+    f: &mut fmt::Formatter<'_>,
+    text: &str,
+    must_escape: impl Fn(char) -> bool,
+) -> fmt::Result {
+    for c in text.chars() {
+        if c == '%' || must_escape(c) {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                write!(f, "%{byte:02X}")?;
+            }
+        } else {
+            write!(f, "{c}")?;
+        }
+    }
+    Ok(())
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [input.next()?, input.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn write_path<S: fmt::Display>(f: &mut fmt::Formatter<'_>, path: &[S]) -> fmt::Result {
+    if path.is_empty() {
+        return write!(f, "{PATH_SEPARATOR}");
+    }
+    for segment in path {
+        write!(f, "{PATH_SEPARATOR}")?;
+        write_escaped(f, &segment.to_string(), |c| {
+            c == PATH_SEPARATOR || c.is_whitespace()
+        })?;
+    }
+    Ok(())
+}
+
+fn write_value<T>(f: &mut fmt::Formatter<'_>, value: &NodeValue<T>) -> fmt::Result
+where
+    T: PathTreeTypes,
+    T::InnerValue: fmt::Display,
+    T::LeafValue: fmt::Display,
+{
+    let (tag, value) = match value {
+        NodeValue::Inner(value) => (INNER_VALUE_TAG, value.to_string()),
+        NodeValue::Leaf(value) => (LEAF_VALUE_TAG, value.to_string()),
+    };
+    write!(f, "{tag} ")?;
+    write_escaped(f, &value, |c| c == '\n' || c == '\r')
+}
+
+impl<T> fmt::Display for TreePatch<T>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: fmt::Display,
+    T::InnerValue: fmt::Display,
+    T::LeafValue: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for operation in &self.operations {
+            match operation {
+                PatchOperation::Remove { path } => {
+                    write!(f, "- ")?;
+                    write_path(f, path)?;
+                }
+                PatchOperation::Update { path, value } => {
+                    write!(f, "~ ")?;
+                    write_path(f, path)?;
+                    write!(f, " ")?;
+                    write_value(f, value)?;
+                }
+                PatchOperation::Insert { path, value } => {
+                    write!(f, "+ ")?;
+                    write_path(f, path)?;
+                    write!(f, " ")?;
+                    write_value(f, value)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tag at the start of the header line of a versioned [`TreePatch`].
+const HEADER_TAG: &str = "#!rpds-pathtree-patch";
+
+/// The current version of the textual representation of a [`TreePatch`].
+///
+/// Written into the header line by [`TreePatch::to_string_with_header()`].
+pub const PATCH_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+struct DisplayWithHeader<'a, T>(&'a TreePatch<T>)
+where
+    T: PathTreeTypes;
+
+impl<T> fmt::Display for DisplayWithHeader<'_, T>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: fmt::Display,
+    T::InnerValue: fmt::Display,
+    T::LeafValue: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(patch) = self;
+        write!(f, "{HEADER_TAG} {PATCH_FORMAT_VERSION} ")?;
+        write_escaped(f, &T::schema_tag(), char::is_whitespace)?;
+        writeln!(f)?;
+        write!(f, "{patch}")
+    }
+}
+
+impl<T> TreePatch<T>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: fmt::Display,
+    T::InnerValue: fmt::Display,
+    T::LeafValue: fmt::Display,
+{
+    /// Format the patch with a self-describing header line.
+    ///
+    /// The header line precedes the operations and contains the format
+    /// version and the [schema tag](PathTreeTypes::schema_tag) of the types:
+    ///
+    /// ```text
+    /// #!rpds-pathtree-patch <version> <schema tag>
+    /// ```
+    ///
+    /// Both are verified by [`Self::parse_with_header()`].
+    #[must_use]
+    pub fn to_string_with_header(&self) -> String {
+        DisplayWithHeader(self).to_string()
+    }
+}
+
+/// Invalid or incompatible versioned textual representation of a [`TreePatch`].
+#[derive(Debug, Display, Error)]
+pub enum ParseVersionedTreePatchError {
+    #[display("missing or invalid header")]
+    InvalidHeader,
+    #[display("unsupported format version {version}")]
+    UnsupportedVersion {
+        #[error(not(source))]
+        version: u32,
+    },
+    #[display("schema mismatch: expected {expected:?}, found {found:?}")]
+    SchemaMismatch { expected: String, found: String },
+    #[display("{_0}")]
+    Patch(ParseTreePatchError),
+}
+
+impl<T> TreePatch<T>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: From<String>,
+    T::InnerValue: FromStr,
+    T::LeafValue: FromStr,
+{
+    /// Parse a patch with a self-describing header line.
+    ///
+    /// Reciprocal of [`Self::to_string_with_header()`]. Fails if the format version
+    /// is not supported or if the schema tag differs from that of the types.
+    ///
+    /// Line numbers of errors account for the header line.
+    pub fn parse_with_header(text: &str) -> Result<Self, ParseVersionedTreePatchError> {
+        let (header, operations) = text.split_once('\n').unwrap_or((text, ""));
+        let header = header.strip_suffix('\r').unwrap_or(header);
+        let mut fields = header.split(' ');
+        if fields.next() != Some(HEADER_TAG) {
+            return Err(ParseVersionedTreePatchError::InvalidHeader);
+        }
+        let version = fields
+            .next()
+            .and_then(|version| version.parse::<u32>().ok())
+            .ok_or(ParseVersionedTreePatchError::InvalidHeader)?;
+        if version != PATCH_FORMAT_VERSION {
+            return Err(ParseVersionedTreePatchError::UnsupportedVersion { version });
+        }
+        let found = fields
+            .next()
+            .and_then(unescape)
+            .ok_or(ParseVersionedTreePatchError::InvalidHeader)?;
+        if fields.next().is_some() {
+            return Err(ParseVersionedTreePatchError::InvalidHeader);
+        }
+        let expected = T::schema_tag();
+        if found != expected {
+            return Err(ParseVersionedTreePatchError::SchemaMismatch { expected, found });
+        }
+        operations.parse().map_err(|ParseTreePatchError { line }| {
+            ParseVersionedTreePatchError::Patch(ParseTreePatchError { line: line + 1 })
+        })
+    }
+}
+
+/// Invalid textual representation of a [`TreePatch`].
+#[derive(Debug, Display, Error)]
+#[display("invalid patch operation in line {line}")]
+pub struct ParseTreePatchError {
+    /// The line number, starting at 1.
+    pub line: usize,
+}
+
+fn parse_path<S: From<String>>(text: &str) -> Option<Vec<S>> {
+    let segments = text.strip_prefix(PATH_SEPARATOR)?;
+    if segments.is_empty() {
+        return Some(Vec::new());
+    }
+    segments
+        .split(PATH_SEPARATOR)
+        .map(|segment| {
+            if segment.is_empty() {
+                return None;
+            }
+            unescape(segment).map(S::from)
+        })
+        .collect()
+}
+
+fn parse_value<T>(text: &str) -> Option<NodeValue<T>>
+where
+    T: PathTreeTypes,
+    T::InnerValue: FromStr,
+    T::LeafValue: FromStr,
+{
+    let (tag, value) = text.split_once(' ')?;
+    let value = unescape(value)?;
+    match tag {
+        INNER_VALUE_TAG => value.parse().ok().map(NodeValue::Inner),
+        LEAF_VALUE_TAG => value.parse().ok().map(NodeValue::Leaf),
+        _ => None,
+    }
+}
+
+fn parse_operation<T>(line: &str) -> Option<PatchOperation<T>>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: From<String>,
+    T::InnerValue: FromStr,
+    T::LeafValue: FromStr,
+{
+    let (tag, rest) = line.split_once(' ')?;
+    if tag == "-" {
+        let path = parse_path(rest)?;
+        return Some(PatchOperation::Remove { path });
+    }
+    let (path, value) = rest.split_once(' ')?;
+    let path = parse_path(path)?;
+    let value = parse_value(value)?;
+    match tag {
+        "~" => Some(PatchOperation::Update { path, value }),
+        // The root node could not be inserted.
+        "+" if !path.is_empty() => Some(PatchOperation::Insert { path, value }),
+        _ => None,
+    }
+}
+
+impl<T> FromStr for TreePatch<T>
+where
+    T: PathTreeTypes,
+    T::PathSegmentOwned: From<String>,
+    T::InnerValue: FromStr,
+    T::LeafValue: FromStr,
+{
+    type Err = ParseTreePatchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let operations = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                parse_operation(line).ok_or(ParseTreePatchError { line: index + 1 })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(operations))
+    }
+}
@@ -0,0 +1,270 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, collections::HashMap, sync::Arc};
+
+use crate::{HalfEdge, PathTree, PathTreeTypes, TreeNode};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Stable 64-bit hash of a path.
+///
+/// The hash of a path is derived from the hash of its parent path and
+/// the last path segment. It is independent of the platform and the
+/// version of this crate and could be used for referring to nodes
+/// without sending the full path, e.g. over a network.
+///
+/// Uses the FNV-1a algorithm with length-prefixed path segments.
+/// Not resistant against malicious collisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathHash(pub u64);
+
+impl PathHash {
+    /// The hash of the root path without any path segments.
+    pub const ROOT: Self = Self(FNV_OFFSET_BASIS);
+
+    /// The hash of a child path.
+    #[must_use]
+    pub fn child(self, path_segment: &[u8]) -> Self {
+        let Self(mut hash) = self;
+        let len = path_segment.len() as u64;
+        for byte in len.to_le_bytes().iter().chain(path_segment) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+        Self(hash)
+    }
+
+    /// The hash of a path.
+    #[must_use]
+    pub fn of_path_segments<'a>(path_segments: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        path_segments.into_iter().fold(Self::ROOT, Self::child)
+    }
+}
+
+/// Hasher with a fixed algorithm for the internal hash maps.
+///
+/// Uses the FNV-1a algorithm. Integers are hashed as little-endian bytes
+/// and `usize`/`isize` are widened to 64 bits. The resulting hashes and
+/// the iteration order of the internal maps are thus independent of the
+/// platform and the Rust release, provided that the `Hash` impls of the
+/// keys are stable.
+#[cfg(feature = "deterministic")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FixedHasher(u64);
+
+#[cfg(feature = "deterministic")]
+impl Default for FixedHasher {
+    fn default() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+}
+
+#[cfg(feature = "deterministic")]
+impl std::hash::Hasher for FixedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let Self(hash) = self;
+        for byte in bytes {
+            *hash ^= u64::from(*byte);
+            *hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+fn child_path_hash<T>(parent_path_hash: PathHash, path_segment: &T::PathSegmentOwned) -> PathHash
+where
+    T: PathTreeTypes,
+    T::PathSegment: AsRef<[u8]>,
+{
+    let path_segment: &T::PathSegment = path_segment.borrow();
+    parent_path_hash.child(path_segment.as_ref())
+}
+
+/// An index of all nodes in a tree by their [`PathHash`].
+///
+/// The index is created for a tree and must be synchronized explicitly after
+/// the tree has been modified. Synchronization accounts for inserted, removed,
+/// moved and renamed nodes, including all descendants of moved or renamed
+/// nodes.
+///
+/// Collisions of path hashes are not resolved. Only one of the colliding
+/// nodes could be found by its path hash.
+#[derive(Debug, Clone)]
+pub struct PathHashIndex<T>
+where
+    T: PathTreeTypes,
+{
+    snapshot: PathTree<T>,
+    node_ids: HashMap<PathHash, T::NodeId>,
+    path_hashes: HashMap<T::NodeId, PathHash>,
+}
+
+impl<T> PathHashIndex<T>
+where
+    T: PathTreeTypes,
+    T::PathSegment: AsRef<[u8]>,
+{
+    /// Create an index for a tree.
+    ///
+    /// Executed in linear time, i.e. O(n) with n the number of nodes.
+    #[must_use]
+    pub fn new(tree: &PathTree<T>) -> Self {
+        let mut index = Self {
+            snapshot: tree.clone(),
+            node_ids: HashMap::new(),
+            path_hashes: HashMap::new(),
+        };
+        index.insert_subtree(tree, tree.root_node(), PathHash::ROOT);
+        index
+    }
+
+    fn insert_node(&mut self, node_id: T::NodeId, path_hash: PathHash) {
+        if let Some(old_path_hash) = self.path_hashes.insert(node_id.clone(), path_hash) {
+            if self.node_ids.get(&old_path_hash) == Some(&node_id) {
+                self.node_ids.remove(&old_path_hash);
+            }
+        }
+        self.node_ids.insert(path_hash, node_id);
+    }
+
+    fn remove_node(&mut self, node_id: &T::NodeId) {
+        let Some(path_hash) = self.path_hashes.remove(node_id) else {
+            return;
+        };
+        if self.node_ids.get(&path_hash) == Some(node_id) {
+            self.node_ids.remove(&path_hash);
+        }
+    }
+
+    fn insert_subtree(&mut self, tree: &PathTree<T>, node: &Arc<TreeNode<T>>, path_hash: PathHash) {
+        self.insert_node(node.id.clone(), path_hash);
+        for HalfEdge { node_id, .. } in tree.descendant_nodes(node) {
+            let node = tree.get_node(node_id.clone());
+            let parent = node.parent.as_ref().expect("has parent");
+            let parent_path_hash = self.path_hashes[&parent.node_id];
+            let path_hash = child_path_hash::<T>(parent_path_hash, &parent.path_segment);
+            self.insert_node(node_id, path_hash);
+        }
+    }
+
+    /// Synchronize the index with a modified tree.
+    ///
+    /// The tree must be a descendant of the tree for which the index has been
+    /// created or synchronized, i.e. it must share the same history.
+    ///
+    /// Executed in linear time, i.e. O(n) with n the number of nodes, plus
+    /// the number of descendants of all inserted, moved or renamed nodes.
+    pub fn sync(&mut self, tree: &PathTree<T>) {
+        let diff = self.snapshot.diff_structure(tree);
+        for node_id in diff.removed {
+            self.remove_node(&node_id);
+        }
+        for node_id in diff.inserted.into_iter().chain(diff.updated) {
+            let node = tree.get_node(node_id.clone());
+            let path_hash = match &node.parent {
+                Some(parent) => {
+                    let Some(parent_path_hash) = self.path_hashes.get(&parent.node_id) else {
+                        // The parent node is synchronized later, including all its descendants.
+                        continue;
+                    };
+                    child_path_hash::<T>(*parent_path_hash, &parent.path_segment)
+                }
+                None => PathHash::ROOT,
+            };
+            if self.path_hashes.get(&node_id) == Some(&path_hash) {
+                // Unchanged path, e.g. only the kind of node has changed.
+                continue;
+            }
+            self.insert_subtree(tree, node, path_hash);
+        }
+        self.snapshot = tree.clone();
+    }
+
+    /// Find a node id by its path hash.
+    #[must_use]
+    pub fn find_node_id(&self, path_hash: PathHash) -> Option<T::NodeId> {
+        self.node_ids.get(&path_hash).cloned()
+    }
+
+    /// The path hash of a node.
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn path_hash(&self, node_id: T::NodeId) -> Option<PathHash> {
+        self.path_hashes.get(&node_id).copied()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+    T::PathSegment: AsRef<[u8]>,
+{
+    /// Find a node by its path hash.
+    ///
+    /// The index must be synchronized with this tree. Otherwise the
+    /// result is unspecified.
+    #[must_use]
+    pub fn find_node_by_path_hash(
+        &self,
+        index: &PathHashIndex<T>,
+        path_hash: PathHash,
+    ) -> Option<&Arc<TreeNode<T>>> {
+        index
+            .find_node_id(path_hash)
+            .and_then(|node_id| self.lookup_node(node_id))
+    }
+}
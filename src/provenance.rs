@@ -0,0 +1,184 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher as _, Hasher as _},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use derive_more::{Display, Error};
+
+use crate::{PathTree, PathTreeTypes, TreeDiff, TreePatch};
+
+/// Identifies a tree.
+///
+/// Generated ids are unique within a process with a very high probability.
+/// They are not suitable for security purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TreeId(pub u64);
+
+impl TreeId {
+    /// Generate a new, random id.
+    #[must_use]
+    pub fn generate() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        Self(hasher.finish())
+    }
+}
+
+impl fmt::Display for TreeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(id) = self;
+        write!(f, "{id:016x}")
+    }
+}
+
+/// Origin and history of a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeProvenance {
+    /// The id of this tree.
+    pub tree_id: TreeId,
+
+    /// The id of the tree that started the lineage.
+    ///
+    /// Shared by all trees that have been forked from the same tree.
+    pub lineage_id: TreeId,
+
+    /// The id of the tree from which this tree has been forked.
+    pub created_from: Option<TreeId>,
+
+    /// The ids of the trees from which all applied patches have been created,
+    /// in order.
+    pub patch_lineage: Vec<TreeId>,
+}
+
+impl TreeProvenance {
+    /// Start a new lineage.
+    #[must_use]
+    pub const fn new(tree_id: TreeId) -> Self {
+        Self {
+            tree_id,
+            lineage_id: tree_id,
+            created_from: None,
+            patch_lineage: Vec::new(),
+        }
+    }
+
+    /// Continue the lineage with a new tree.
+    #[must_use]
+    pub fn fork(&self, tree_id: TreeId) -> Self {
+        Self {
+            tree_id,
+            lineage_id: self.lineage_id,
+            created_from: Some(self.tree_id),
+            patch_lineage: self.patch_lineage.clone(),
+        }
+    }
+}
+
+/// Trees from unrelated lineages have been combined.
+#[derive(Debug, Display, Error)]
+#[display("unrelated trees: {tree_id} (lineage {lineage_id}) and {other_tree_id} (lineage {other_lineage_id})")]
+pub struct UnrelatedTreesError {
+    pub tree_id: TreeId,
+    pub lineage_id: TreeId,
+    pub other_tree_id: TreeId,
+    pub other_lineage_id: TreeId,
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// The provenance of this tree.
+    ///
+    /// `None` if no tree id has been assigned.
+    #[must_use]
+    pub fn provenance(&self) -> Option<&TreeProvenance> {
+        self.provenance.as_deref()
+    }
+
+    /// The id of this tree.
+    #[must_use]
+    pub fn tree_id(&self) -> Option<TreeId> {
+        self.provenance().map(|provenance| provenance.tree_id)
+    }
+
+    /// Assign a new tree id that starts a new lineage.
+    ///
+    /// Replaces any existing provenance.
+    pub fn assign_tree_id(&mut self, tree_id: TreeId) {
+        self.provenance = Some(Arc::new(TreeProvenance::new(tree_id)));
+    }
+
+    /// Create a copy of this tree with a new tree id in the same lineage.
+    ///
+    /// Starts a new lineage if this tree has no provenance. The copy shares
+    /// all nodes with this tree like [`Clone::clone()`].
+    #[must_use]
+    pub fn fork(&self, tree_id: TreeId) -> Self {
+        let provenance = match self.provenance() {
+            Some(provenance) => provenance.fork(tree_id),
+            None => TreeProvenance::new(tree_id),
+        };
+        let mut tree = self.clone();
+        tree.provenance = Some(Arc::new(provenance));
+        tree
+    }
+
+    /// Check that both trees belong to the same lineage.
+    ///
+    /// Trees without provenance are considered as related to any tree.
+    pub fn check_same_lineage(&self, other: &Self) -> Result<(), UnrelatedTreesError> {
+        let (Some(provenance), Some(other_provenance)) = (self.provenance(), other.provenance())
+        else {
+            return Ok(());
+        };
+        if provenance.lineage_id == other_provenance.lineage_id {
+            return Ok(());
+        }
+        Err(UnrelatedTreesError {
+            tree_id: provenance.tree_id,
+            lineage_id: provenance.lineage_id,
+            other_tree_id: other_provenance.tree_id,
+            other_lineage_id: other_provenance.lineage_id,
+        })
+    }
+
+    /// Compare this (old) tree with a new tree of the same lineage.
+    ///
+    /// See also [`Self::diff()`] and [`Self::check_same_lineage()`].
+    pub fn try_diff(&self, new_tree: &Self) -> Result<TreeDiff<T>, UnrelatedTreesError> {
+        self.check_same_lineage(new_tree)?;
+        Ok(self.diff(new_tree))
+    }
+
+    /// Create a patch from a new tree of the same lineage.
+    ///
+    /// See also [`Self::diff_patch()`] and [`Self::check_same_lineage()`].
+    pub fn try_diff_patch(&self, new_tree: &Self) -> Result<TreePatch<T>, UnrelatedTreesError>
+    where
+        T::PathSegment: Ord,
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        self.check_same_lineage(new_tree)?;
+        Ok(self.diff_patch(new_tree))
+    }
+
+    /// Record that a patch created from the given tree has been applied.
+    pub(crate) fn record_applied_patch(&mut self, source_tree_id: Option<TreeId>) {
+        let (Some(provenance), Some(source_tree_id)) = (&mut self.provenance, source_tree_id)
+        else {
+            return;
+        };
+        Arc::make_mut(provenance).patch_lineage.push(source_tree_id);
+    }
+}
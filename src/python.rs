@@ -0,0 +1,237 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Python bindings
+//!
+//! A concrete instantiation of [`PathTree`] with string segments and string values.
+//! Paths are strings with segments separated by `/`. Empty segments are ignored.
+//!
+//! Objects must only be accessed by the thread that created them.
+
+use std::{borrow::Cow, sync::Arc};
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{AnyNewNodeId, AnyPath, ExportOrder, NodeHandle, NodeValue, PathTree, PathTreeTypes};
+
+/// Type system of the Python bindings.
+#[derive(Debug, Clone, Default)]
+pub struct PyPathTreeTypes;
+
+impl PathTreeTypes for PyPathTreeTypes {
+    type NodeId = usize;
+    type NewNodeId = AnyNewNodeId;
+    type InnerValue = String;
+    type LeafValue = String;
+    type PathSegmentOwned = String;
+    type PathSegment = str;
+    type RootPath = AnyPath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.to_owned()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+fn parse_path(path: &str) -> AnyPath {
+    AnyPath(
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(Arc::from)
+            .collect(),
+    )
+}
+
+/// A tree with string segments and string values.
+#[pyclass(name = "PathTree", unsendable)]
+#[derive(Debug, Clone)]
+pub struct PyPathTree {
+    tree: PathTree<PyPathTreeTypes>,
+}
+
+#[pymethods]
+impl PyPathTree {
+    #[new]
+    fn new(root_value: String) -> Self {
+        let tree = PathTree::new(AnyNewNodeId::default(), NodeValue::Inner(root_value));
+        Self { tree }
+    }
+
+    /// The root node.
+    #[getter]
+    fn root(&self) -> PyTreeNode {
+        PyTreeNode {
+            handle: self.tree.root_node_handle(),
+        }
+    }
+
+    /// Insert or update the value of a node.
+    ///
+    /// Missing ancestor nodes are created as inner nodes with an empty value.
+    #[pyo3(signature = (path, value, leaf = true))]
+    fn insert(&mut self, path: &str, value: String, leaf: bool) -> PyResult<PyTreeNode> {
+        let value = if leaf {
+            NodeValue::Leaf(value)
+        } else {
+            NodeValue::Inner(value)
+        };
+        let inserted = self
+            .tree
+            .insert_or_update_node_value(&parse_path(path), value, &mut String::new, |_| None)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(self.node(inserted.node.id))
+    }
+
+    /// Find a node by path.
+    fn find(&self, path: &str) -> Option<PyTreeNode> {
+        self.tree
+            .find_node_handle(&parse_path(path))
+            .map(|handle| PyTreeNode { handle })
+    }
+
+    /// Remove a node with all its descendants.
+    ///
+    /// Returns the number of removed nodes.
+    fn remove(&mut self, path: &str) -> usize {
+        let Some(node_id) = self.tree.find_node(&parse_path(path)).map(|node| node.id) else {
+            return 0;
+        };
+        self.tree
+            .remove_subtree_by_id(node_id)
+            .map_or(0, |removed| removed.removed_subtree.nodes_count().get())
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.nodes_count().get()
+    }
+
+    /// Iterate over all nodes, parent nodes before their children.
+    ///
+    /// Siblings are sorted by their path segments.
+    fn __iter__(&self) -> PyTreeNodeIter {
+        let root_node = self.tree.root_node();
+        let nodes = std::iter::once(root_node.id)
+            .chain(
+                self.tree
+                    .descendant_nodes_for_export(root_node, ExportOrder::sorted())
+                    .map(|child| child.node_id),
+            )
+            .map(|node_id| self.node(node_id))
+            .collect::<Vec<_>>();
+        PyTreeNodeIter {
+            nodes: nodes.into_iter(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PathTree(nodes_count={})", self.tree.nodes_count())
+    }
+}
+
+impl PyPathTree {
+    fn node(&self, node_id: usize) -> PyTreeNode {
+        let handle = self.tree.lookup_node_handle(node_id).expect("node exists");
+        PyTreeNode { handle }
+    }
+}
+
+/// A node of a tree snapshot.
+#[pyclass(name = "TreeNode", unsendable)]
+#[derive(Debug, Clone)]
+pub struct PyTreeNode {
+    handle: NodeHandle<PyPathTreeTypes>,
+}
+
+#[pymethods]
+impl PyTreeNode {
+    #[getter]
+    fn id(&self) -> usize {
+        self.handle.id()
+    }
+
+    #[getter]
+    fn value(&self) -> String {
+        match self.handle.value() {
+            NodeValue::Inner(value) | NodeValue::Leaf(value) => value,
+        }
+    }
+
+    #[getter]
+    fn is_leaf(&self) -> bool {
+        matches!(self.handle.value(), NodeValue::Leaf(_))
+    }
+
+    /// The path segment from the parent node or `None` for the root node.
+    #[getter]
+    fn path_segment(&self) -> Option<String> {
+        self.handle.path_segment().map(str::to_owned)
+    }
+
+    #[getter]
+    fn parent(&self) -> Option<Self> {
+        self.handle.parent().map(|handle| Self { handle })
+    }
+
+    #[getter]
+    fn children(&self) -> Vec<Self> {
+        self.handle
+            .children()
+            .into_iter()
+            .map(|handle| Self { handle })
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.handle.node().node.children_count()
+    }
+
+    /// Iterate over all children.
+    fn __iter__(&self) -> PyTreeNodeIter {
+        PyTreeNodeIter {
+            nodes: self.children().into_iter(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TreeNode(id={id}, path_segment={path_segment:?}, value={value:?}, is_leaf={is_leaf})",
+            id = self.id(),
+            path_segment = self.path_segment(),
+            value = self.value(),
+            is_leaf = self.is_leaf(),
+        )
+    }
+}
+
+/// Iterator over nodes.
+#[pyclass(name = "TreeNodeIter", unsendable)]
+#[derive(Debug)]
+pub struct PyTreeNodeIter {
+    nodes: std::vec::IntoIter<PyTreeNode>,
+}
+
+#[pymethods]
+impl PyTreeNodeIter {
+    #[allow(clippy::missing_const_for_fn)] // Python methods could not be const
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<PyTreeNode> {
+        self.nodes.next()
+    }
+}
+
+/// Python module `rpds_pathtree`.
+#[pymodule]
+pub fn rpds_pathtree(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPathTree>()?;
+    module.add_class::<PyTreeNode>()?;
+    module.add_class::<PyTreeNodeIter>()?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::{HashMap, HashSet};
+
+use derive_more::{Display, Error};
+
+use crate::{
+    HalfEdge, InsertOrUpdateNodeValueError, Node, NodeValue, PathTree, PathTreeTypes, TreeDiff,
+};
+
+/// Options of [`PathTree::reconcile_subtree()`].
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileOptions {
+    /// Keep existing nodes that are not listed.
+    ///
+    /// By default, all nodes that are neither listed nor an ancestor
+    /// of a listed node are removed.
+    pub keep_missing: bool,
+}
+
+/// Error of [`PathTree::reconcile_subtree()`].
+#[derive(Debug, Display, Error)]
+pub enum ReconcileSubtreeError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("node not found")]
+    NotFound,
+    /// A removed node is part of or contains a frozen subtree,
+    /// see [`PathTree::freeze_subtree()`].
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("{_0}")]
+    InsertOrUpdate(InsertOrUpdateNodeValueError<T>),
+}
+
+impl<T> From<InsertOrUpdateNodeValueError<T>> for ReconcileSubtreeError<T>
+where
+    T: PathTreeTypes,
+{
+    fn from(from: InsertOrUpdateNodeValueError<T>) -> Self {
+        Self::InsertOrUpdate(from)
+    }
+}
+
+fn is_same_value<T>(node: &Node<T>, value: &NodeValue<T>) -> bool
+where
+    T: PathTreeTypes,
+    T::InnerValue: PartialEq,
+    T::LeafValue: PartialEq,
+{
+    match (node, value) {
+        (Node::Inner(inner), NodeValue::Inner(value)) => inner.value == *value,
+        (Node::Leaf(leaf), NodeValue::Leaf(value)) => leaf.value == *value,
+        _ => false,
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Paths of all descendants of a node, relative to this node.
+    fn relative_descendant_paths(
+        &self,
+        node_id: T::NodeId,
+    ) -> HashMap<T::NodeId, Vec<T::PathSegmentOwned>> {
+        let mut paths = HashMap::new();
+        paths.insert(node_id.clone(), Vec::new());
+        for HalfEdge {
+            path_segment,
+            child_key: _,
+            node_id: child_node_id,
+        } in self.descendant_nodes(self.get_node(node_id))
+        {
+            let parent = self
+                .get_node(child_node_id.clone())
+                .parent
+                .as_ref()
+                .expect("has parent");
+            let mut path = paths[&parent.node_id].clone();
+            path.push(T::path_segment_to_owned(path_segment));
+            paths.insert(child_node_id, path);
+        }
+        paths
+    }
+
+    /// Make a subtree match a desired listing of nodes.
+    ///
+    /// The listing contains the paths of nodes relative to the given node and
+    /// their desired values. An empty path denotes the given node. Existing
+    /// nodes are matched by their path and retain their id. Only nodes with
+    /// different values are updated. Nodes that are neither listed nor an
+    /// ancestor of a listed node are removed, unless configured otherwise.
+    /// Ancestor nodes that are not listed are created with values provided
+    /// by `new_inner_value`.
+    ///
+    /// Returns the inserted, updated, and removed nodes, e.g. for
+    /// notifying observers.
+    ///
+    /// The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn reconcile_subtree(
+        &mut self,
+        node_id: T::NodeId,
+        desired: impl IntoIterator<Item = (Vec<T::PathSegmentOwned>, NodeValue<T>)>,
+        options: &ReconcileOptions,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<TreeDiff<T>, ReconcileSubtreeError<T>>
+    where
+        T::InnerValue: PartialEq,
+        T::LeafValue: PartialEq,
+    {
+        if self.lookup_node(node_id.clone()).is_none() {
+            return Err(ReconcileSubtreeError::NotFound);
+        }
+        let ReconcileOptions { keep_missing } = options;
+        let mut desired = desired.into_iter().collect::<Vec<_>>();
+        // Parent nodes before their children.
+        desired.sort_by_key(|(path, _)| path.len());
+        self.with_rollback(|tree| {
+            let existing_paths = tree.relative_descendant_paths(node_id.clone());
+            let mut diff = TreeDiff {
+                inserted: Vec::new(),
+                removed: Vec::new(),
+                updated: Vec::new(),
+            };
+            if !keep_missing {
+                let retained_paths = std::iter::once([].as_slice())
+                    .chain(
+                        desired
+                            .iter()
+                            .flat_map(|(path, _)| (1..=path.len()).map(|len| &path[..len])),
+                    )
+                    .collect::<HashSet<_>>();
+                // Only the topmost nodes of removed subtrees.
+                let removed_node_ids = existing_paths
+                    .iter()
+                    .filter(|(_, path)| {
+                        let Some((_, parent_path)) = path.split_last() else {
+                            return false;
+                        };
+                        !retained_paths.contains(path.as_slice())
+                            && retained_paths.contains(parent_path)
+                    })
+                    .map(|(node_id, _)| node_id.clone())
+                    .collect::<Vec<_>>();
+                for removed_node_id in removed_node_ids {
+                    if let Some(node_id) = tree.find_frozen_node_affected_by_removal(
+                        tree.get_node(removed_node_id.clone()),
+                    ) {
+                        return Err(ReconcileSubtreeError::SubtreeFrozen { node_id });
+                    }
+                    let removed = tree
+                        .remove_subtree_by_id(removed_node_id)
+                        .expect("subtree exists");
+                    diff.removed
+                        .extend(removed.removed_subtree.nodes().map(|node| node.id.clone()));
+                }
+            }
+            let existing_node_ids = existing_paths
+                .into_iter()
+                .map(|(node_id, path)| (path, node_id))
+                .collect::<HashMap<_, _>>();
+            for (path, value) in desired {
+                if let Some(existing_node_id) = existing_node_ids.get(&path).cloned() {
+                    if is_same_value(&tree.get_node(existing_node_id.clone()).node, &value) {
+                        continue;
+                    }
+                    diff.updated.push(existing_node_id);
+                }
+                tree.insert_or_update_node_value_by_path_segments(
+                    node_id.clone(),
+                    &path,
+                    value,
+                    new_inner_value,
+                )?;
+            }
+            let existing_node_ids = existing_node_ids.into_values().collect::<HashSet<_>>();
+            diff.inserted.extend(
+                tree.descendant_nodes(tree.get_node(node_id))
+                    .map(|HalfEdge { node_id, .. }| node_id)
+                    .filter(|node_id| !existing_node_ids.contains(node_id)),
+            );
+            Ok(diff)
+        })
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use derive_more::{Display, Error};
+
+use crate::{
+    event::{apply_event, capture_insert_or_update_node_value},
+    InsertOrUpdateNodeValueError, NodeInsertedOrUpdated, NodeValue, PathTree, PathTreeEvent,
+    PathTreeTypes, SubtreeRemoved,
+};
+
+/// A mutating call that has been captured by an [`OperationRecorder`].
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "PathTreeEvent<T>: serde::Serialize",
+        deserialize = "PathTreeEvent<T>: serde::Deserialize<'de>"
+    ))
+)]
+pub struct RecordedOperation<T>
+where
+    T: PathTreeTypes,
+{
+    /// The operation and its arguments.
+    pub event: PathTreeEvent<T>,
+
+    /// Whether the operation has succeeded or failed.
+    pub succeeded: bool,
+}
+
+/// A replayable sequence of mutating calls.
+///
+/// Created by [`OperationRecorder::into_script()`]. The script does not
+/// contain the initial tree, which must be provided for replaying it.
+///
+/// Scripts are serializable with the `"serde"` feature if the contained
+/// [`PathTreeEvent`]s are serializable, e.g. for attaching them to a bug report.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "PathTreeEvent<T>: serde::Serialize",
+        deserialize = "PathTreeEvent<T>: serde::Deserialize<'de>"
+    ))
+)]
+pub struct OperationScript<T>
+where
+    T: PathTreeTypes,
+{
+    /// All recorded operations in chronological order.
+    pub operations: Vec<RecordedOperation<T>>,
+}
+
+/// The outcome of a replayed operation differs from the recorded outcome.
+#[derive(Debug, Display, Error)]
+#[display("replayed operation #{index} diverged from the recording")]
+pub struct ReplayScriptError<T>
+where
+    T: PathTreeTypes,
+{
+    /// The index in [`OperationScript::operations`].
+    pub index: usize,
+
+    /// The tree before the diverging operation.
+    #[error(not(source))]
+    pub tree: PathTree<T>,
+}
+
+impl<T> OperationScript<T>
+where
+    T: PathTreeTypes,
+{
+    /// Replay all operations, starting with the initial tree.
+    ///
+    /// The given initial tree must match the initial tree that has been
+    /// used for recording, including the state of its node id generator.
+    ///
+    /// Failed operations are expected to fail again. Returns the resulting
+    /// tree or the first operation whose outcome differs from the recording,
+    /// together with the tree before this operation.
+    pub fn replay(&self, initial_tree: PathTree<T>) -> Result<PathTree<T>, ReplayScriptError<T>> {
+        let mut tree = initial_tree;
+        for (index, RecordedOperation { event, succeeded }) in self.operations.iter().enumerate() {
+            let tree_before = tree.clone();
+            if apply_event(&mut tree, event).is_ok() != *succeeded {
+                log::warn!("Replayed operation #{index} diverged: {event:?}");
+                return Err(ReplayScriptError {
+                    index,
+                    tree: tree_before,
+                });
+            }
+        }
+        Ok(tree)
+    }
+}
+
+/// Captures mutating calls of a [`PathTree`] for reproducing bugs.
+///
+/// Intended for debugging. In contrast to [`crate::EventSourcedTree`]
+/// failed calls are also recorded and operations are applied to the tree
+/// directly. The recording is converted into a script that could be
+/// replayed, e.g. in a test.
+///
+/// Only the mutations that could be expressed as a [`PathTreeEvent`] are
+/// supported, i.e. inserting, updating, renaming, and removing nodes by
+/// their path. The recorder owns the tree and only provides shared access
+/// to it. Other mutating operations of [`PathTree`] are therefore not
+/// available while recording and could not be captured.
+#[derive(Debug, Clone)]
+pub struct OperationRecorder<T>
+where
+    T: PathTreeTypes,
+{
+    initial_tree: PathTree<T>,
+    tree: PathTree<T>,
+    script: OperationScript<T>,
+}
+
+impl<T> OperationRecorder<T>
+where
+    T: PathTreeTypes,
+{
+    /// Start recording operations of the given tree.
+    #[must_use]
+    pub fn new(initial_tree: PathTree<T>) -> Self {
+        let tree = initial_tree.clone();
+        Self {
+            initial_tree,
+            tree,
+            script: OperationScript {
+                operations: Vec::new(),
+            },
+        }
+    }
+
+    /// The tree before the first operation.
+    #[must_use]
+    pub const fn initial_tree(&self) -> &PathTree<T> {
+        &self.initial_tree
+    }
+
+    /// The current tree after applying all recorded operations.
+    #[must_use]
+    pub const fn tree(&self) -> &PathTree<T> {
+        &self.tree
+    }
+
+    /// The recording so far.
+    #[must_use]
+    pub const fn script(&self) -> &OperationScript<T> {
+        &self.script
+    }
+
+    /// Stop recording.
+    #[must_use]
+    pub fn into_script(self) -> OperationScript<T> {
+        let Self {
+            initial_tree: _,
+            tree: _,
+            script,
+        } = self;
+        script
+    }
+
+    fn record(&mut self, event: PathTreeEvent<T>, succeeded: bool) {
+        log::debug!("Recorded operation: {event:?}");
+        self.script
+            .operations
+            .push(RecordedOperation { event, succeeded });
+    }
+
+    /// See [`PathTree::insert_or_update_node_value()`].
+    ///
+    /// The values returned by `new_inner_value` and `try_clone_leaf_into_inner_value`
+    /// are recorded.
+    pub fn insert_or_update_node_value(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
+        let (result, event) = capture_insert_or_update_node_value(
+            &mut self.tree,
+            path,
+            new_value,
+            new_inner_value,
+            try_clone_leaf_into_inner_value,
+        );
+        self.record(event, result.is_ok());
+        result
+    }
+
+    /// See [`PathTree::insert_or_update_child_node_value()`].
+    ///
+    /// The parent node is addressed by its path.
+    ///
+    /// Returns `Ok(None)` if the parent node does not exist.
+    pub fn insert_or_update_child_node_value(
+        &mut self,
+        parent_path: &T::RootPath,
+        child_path_segment: &T::PathSegment,
+        old_child_path_segment: Option<&T::PathSegment>,
+        new_value: NodeValue<T>,
+    ) -> Result<Option<NodeInsertedOrUpdated<T>>, InsertOrUpdateNodeValueError<T>> {
+        let event = PathTreeEvent::InsertOrUpdateChildNodeValue {
+            parent_path: parent_path.clone(),
+            child_path_segment: T::path_segment_to_owned(child_path_segment),
+            old_child_path_segment: old_child_path_segment.map(T::path_segment_to_owned),
+            value: new_value.clone(),
+        };
+        let Some(parent_node) = self.tree.find_node(parent_path).map(Arc::clone) else {
+            self.record(event, false);
+            return Ok(None);
+        };
+        let result = self.tree.insert_or_update_child_node_value(
+            &parent_node,
+            child_path_segment,
+            old_child_path_segment,
+            new_value,
+        );
+        self.record(event, result.is_ok());
+        result.map(Some)
+    }
+
+    /// Remove a subtree by its path.
+    ///
+    /// See [`PathTree::remove_subtree_by_id()`].
+    pub fn remove_subtree(&mut self, path: &T::RootPath) -> Option<SubtreeRemoved<T>> {
+        let removed = self
+            .tree
+            .find_node(path)
+            .map(|node| node.id.clone())
+            .and_then(|node_id| self.tree.remove_subtree_by_id(node_id));
+        self.record(
+            PathTreeEvent::RemoveSubtree { path: path.clone() },
+            removed.is_some(),
+        );
+        removed
+    }
+}
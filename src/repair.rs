@@ -0,0 +1,268 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, collections::HashMap, sync::Arc};
+
+use crate::{FromRowsError, HalfEdgeOwned, Node, PathTree, PathTreeTypes, TreeRow};
+
+/// Treatment of orphaned rows by [`PathTree::repair()`].
+///
+/// A row is orphaned if it could not be reached from the root row, i.e.
+/// if its parent row is missing, a leaf, part of a cycle, or already has a
+/// child with the same path segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepairPolicy {
+    /// Drop orphaned rows.
+    #[default]
+    DropOrphans,
+
+    /// Reattach orphaned rows as children of the root node.
+    ///
+    /// Orphaned rows are dropped if the root node is a leaf or if their
+    /// path segment is already occupied.
+    ReattachOrphans,
+}
+
+/// Applied fix of a single row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairAction {
+    /// The id of the row has already been used by a preceding row.
+    DroppedDuplicateRow,
+
+    /// The row has no parent, but the root row has already been found.
+    DroppedDuplicateRoot,
+
+    /// The row is orphaned and has been dropped.
+    DroppedOrphan,
+
+    /// The row is orphaned and has been reattached to the root node.
+    ReattachedOrphan,
+}
+
+/// A fixed row.
+#[derive(Debug, Clone)]
+pub struct RepairedRow<T>
+where
+    T: PathTreeTypes,
+{
+    /// The id of the row, not the id of a node in the repaired tree.
+    pub row_id: T::NodeId,
+
+    pub action: RepairAction,
+}
+
+/// Outcome of [`PathTree::repair()`].
+#[derive(Debug, Clone)]
+pub struct RepairReport<T>
+where
+    T: PathTreeTypes,
+{
+    /// All fixed rows in the order in which they have been fixed.
+    pub repaired_rows: Vec<RepairedRow<T>>,
+}
+
+impl<T> RepairReport<T>
+where
+    T: PathTreeTypes,
+{
+    /// Check if all rows have been imported without fixes.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.repaired_rows.is_empty()
+    }
+}
+
+struct Repair<T>
+where
+    T: PathTreeTypes,
+{
+    rows: HashMap<T::NodeId, TreeRow<T>>,
+    child_row_ids: HashMap<T::NodeId, Vec<T::NodeId>>,
+    node_ids: HashMap<T::NodeId, T::NodeId>,
+}
+
+impl<T> Repair<T>
+where
+    T: PathTreeTypes,
+{
+    /// Attach a row as a child of an imported node.
+    ///
+    /// Returns `false` if the parent node is a leaf or if the path
+    /// segment is already occupied.
+    fn attach_row(
+        &mut self,
+        tree: &mut PathTree<T>,
+        parent_node_id: T::NodeId,
+        row_id: T::NodeId,
+        path_segment: &T::PathSegment,
+    ) -> bool {
+        let parent_node = Arc::clone(tree.get_node(parent_node_id));
+        let Node::Inner(inner_node) = &parent_node.node else {
+            return false;
+        };
+        if inner_node.find_child(path_segment).is_some() {
+            return false;
+        }
+        let TreeRow { value, .. } = self.rows.remove(&row_id).expect("row exists");
+        let inserted = tree
+            .insert_or_update_child_node_value(&parent_node, path_segment, None, value)
+            .expect("vacant child of an inner node");
+        self.node_ids.insert(row_id, inserted.node.id.clone());
+        true
+    }
+
+    /// Attach all reachable descendants of an imported row.
+    fn attach_descendant_rows(&mut self, tree: &mut PathTree<T>, row_id: T::NodeId) {
+        let mut pending_row_ids = vec![row_id];
+        while let Some(parent_row_id) = pending_row_ids.pop() {
+            let parent_node_id = self.node_ids[&parent_row_id].clone();
+            let Some(child_row_ids) = self.child_row_ids.remove(&parent_row_id) else {
+                continue;
+            };
+            for child_row_id in child_row_ids {
+                let Some(path_segment) = self
+                    .rows
+                    .get(&child_row_id)
+                    .and_then(|row| row.parent.as_ref())
+                    .map(|parent| parent.path_segment.clone())
+                else {
+                    // Already imported or dropped.
+                    continue;
+                };
+                if self.attach_row(
+                    tree,
+                    parent_node_id.clone(),
+                    child_row_id.clone(),
+                    path_segment.borrow(),
+                ) {
+                    pending_row_ids.push(child_row_id);
+                }
+            }
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Import flat rows with broken invariants.
+    ///
+    /// In contrast to [`Self::from_rows()`] the rows could be passed in any
+    /// order and violations are fixed instead of rejected. Duplicate rows
+    /// and duplicate root rows are dropped. Orphaned rows are treated
+    /// according to the `policy`. Intended for loading snapshots that have
+    /// been written by faulty applications.
+    ///
+    /// All nodes are assigned new ids by `new_node_id`. The returned report
+    /// refers to the ids of the rows.
+    ///
+    /// Only fails if no root row exists.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn repair(
+        new_node_id: T::NewNodeId,
+        rows: impl IntoIterator<Item = TreeRow<T>>,
+        policy: RepairPolicy,
+    ) -> Result<(Self, RepairReport<T>), FromRowsError<T>> {
+        let mut repaired_rows = Vec::new();
+        let mut row_ids = Vec::new();
+        let mut repair = Repair {
+            rows: HashMap::new(),
+            child_row_ids: HashMap::new(),
+            node_ids: HashMap::new(),
+        };
+        let mut root_row_id = None;
+        for row in rows {
+            let row_id = row.node_id.clone();
+            if repair.rows.contains_key(&row_id) {
+                repaired_rows.push(RepairedRow {
+                    row_id,
+                    action: RepairAction::DroppedDuplicateRow,
+                });
+                continue;
+            }
+            if let Some(HalfEdgeOwned {
+                node_id: parent_row_id,
+                ..
+            }) = &row.parent
+            {
+                repair
+                    .child_row_ids
+                    .entry(parent_row_id.clone())
+                    .or_default()
+                    .push(row_id.clone());
+            } else if root_row_id.is_none() {
+                root_row_id = Some(row_id.clone());
+            } else {
+                repaired_rows.push(RepairedRow {
+                    row_id,
+                    action: RepairAction::DroppedDuplicateRoot,
+                });
+                continue;
+            }
+            row_ids.push(row_id.clone());
+            repair.rows.insert(row_id, row);
+        }
+        let root_row_id = root_row_id.ok_or(FromRowsError::RootRowMissing)?;
+        let TreeRow { value, .. } = repair.rows.remove(&root_row_id).expect("root row exists");
+        let mut tree = Self::new(new_node_id, value);
+        let root_node_id = tree.root_node_id();
+        repair
+            .node_ids
+            .insert(root_row_id.clone(), root_node_id.clone());
+        repair.attach_descendant_rows(&mut tree, root_row_id);
+        // Orphans with a missing parent row first. They are the topmost rows
+        // of detached subtrees.
+        let mut orphan_row_ids = row_ids
+            .into_iter()
+            .filter(|row_id| repair.rows.contains_key(row_id))
+            .collect::<Vec<_>>();
+        orphan_row_ids.sort_by_key(|row_id| {
+            repair.rows[row_id]
+                .parent
+                .as_ref()
+                .is_some_and(|parent| repair.rows.contains_key(&parent.node_id))
+        });
+        for row_id in orphan_row_ids {
+            let Some(path_segment) = repair
+                .rows
+                .get(&row_id)
+                .and_then(|row| row.parent.as_ref())
+                .map(|parent| parent.path_segment.clone())
+            else {
+                // Already reattached or dropped.
+                continue;
+            };
+            let reattached = match policy {
+                RepairPolicy::DropOrphans => false,
+                RepairPolicy::ReattachOrphans => repair.attach_row(
+                    &mut tree,
+                    root_node_id.clone(),
+                    row_id.clone(),
+                    path_segment.borrow(),
+                ),
+            };
+            if reattached {
+                repaired_rows.push(RepairedRow {
+                    row_id: row_id.clone(),
+                    action: RepairAction::ReattachedOrphan,
+                });
+                repair.attach_descendant_rows(&mut tree, row_id);
+            } else {
+                repair.rows.remove(&row_id);
+                repaired_rows.push(RepairedRow {
+                    row_id,
+                    action: RepairAction::DroppedOrphan,
+                });
+            }
+        }
+        debug_assert!(repair.rows.is_empty());
+        if !repaired_rows.is_empty() {
+            log::debug!(
+                "Repaired {repaired_rows_count} row(s)",
+                repaired_rows_count = repaired_rows.len()
+            );
+        }
+        Ok((tree, RepairReport { repaired_rows }))
+    }
+}
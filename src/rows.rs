@@ -0,0 +1,206 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, collections::HashMap, sync::Arc};
+
+use derive_more::{Display, Error};
+
+use crate::{ExportOrder, HalfEdge, HalfEdgeOwned, Node, NodeValue, PathTree, PathTreeTypes};
+
+/// A flat representation of a node with a reference to its parent node.
+///
+/// Maps directly to an adjacency list, e.g. a table in an SQL database
+/// with a foreign key that references the parent row.
+#[derive(Debug, Clone)]
+pub struct TreeRow<T>
+where
+    T: PathTreeTypes,
+{
+    pub node_id: T::NodeId,
+
+    /// The parent node and the path segment of this node.
+    ///
+    /// `None` for the root node.
+    pub parent: Option<HalfEdgeOwned<T>>,
+
+    /// The value, which also determines the kind of node.
+    pub value: NodeValue<T>,
+}
+
+/// A pair of an ancestor and a descendant node.
+///
+/// Each node is paired with itself at depth 0.
+///
+/// Maps directly to a closure table in an SQL database.
+#[derive(Debug, Clone)]
+pub struct ClosureTableRow<T>
+where
+    T: PathTreeTypes,
+{
+    pub ancestor_node_id: T::NodeId,
+
+    pub descendant_node_id: T::NodeId,
+
+    /// The distance between both nodes.
+    pub depth: usize,
+}
+
+/// Error of [`PathTree::from_rows()`].
+#[derive(Debug, Display, Error)]
+pub enum FromRowsError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("missing root row")]
+    RootRowMissing,
+    #[display("multiple root rows: {node_id}")]
+    RootRowDuplicate {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("duplicate row: {node_id}")]
+    NodeIdDuplicate {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("parent row of {node_id} not found")]
+    ParentRowNotFound {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("parent row of {node_id} is a leaf")]
+    ParentRowLeaf {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("path segment of {node_id} is not unique")]
+    PathSegmentDuplicate {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Export all nodes as flat rows.
+    ///
+    /// Starts with the root node. Parent nodes are exported before their
+    /// children. Siblings are exported in the given order, e.g.
+    /// [`ExportOrder::sorted()`] for reproducible exports.
+    ///
+    /// Rows are unversioned. Store the [schema tag](PathTreeTypes::schema_tag)
+    /// alongside persisted rows for detecting incompatible types when importing them.
+    pub fn to_rows(&self, export_order: ExportOrder<T>) -> impl Iterator<Item = TreeRow<T>> + '_ {
+        let root_node = self.root_node();
+        std::iter::once(root_node)
+            .chain(
+                self.descendant_nodes_for_export(root_node, export_order)
+                    .map(|HalfEdge { node_id, .. }| self.get_node(node_id)),
+            )
+            .map(|node| TreeRow {
+                node_id: node.id.clone(),
+                parent: node.parent.clone(),
+                value: node.node.to_value(),
+            })
+    }
+
+    /// Export all ancestor-descendant pairs of nodes.
+    ///
+    /// The pairs are generated lazily, descendants in the same order as by
+    /// [`Self::to_rows()`] with the same `export_order`. The pairs of each
+    /// descendant start with the node itself at depth 0, followed by its
+    /// ancestors up to the root node. Unversioned like the rows.
+    ///
+    /// The number of pairs is the sum of the depths of all nodes plus
+    /// the number of nodes.
+    pub fn to_closure_table(
+        &self,
+        export_order: ExportOrder<T>,
+    ) -> impl Iterator<Item = ClosureTableRow<T>> + '_ {
+        let root_node = self.root_node();
+        std::iter::once(root_node.id.clone())
+            .chain(
+                self.descendant_nodes_for_export(root_node, export_order)
+                    .map(|HalfEdge { node_id, .. }| node_id),
+            )
+            .flat_map(move |descendant_node_id| {
+                std::iter::successors(Some(descendant_node_id.clone()), |node_id| {
+                    self.get_node(node_id.clone())
+                        .parent
+                        .as_ref()
+                        .map(|parent| parent.node_id.clone())
+                })
+                .enumerate()
+                .map(move |(depth, ancestor_node_id)| ClosureTableRow {
+                    ancestor_node_id,
+                    descendant_node_id: descendant_node_id.clone(),
+                    depth,
+                })
+            })
+    }
+
+    /// Import nodes from flat rows.
+    ///
+    /// The first row must be the root row without a parent. All other rows must
+    /// reference a parent row that appeared before, e.g. as exported by
+    /// [`Self::to_rows()`].
+    ///
+    /// The rows are validated while rebuilding the tree. The ids of the rows are
+    /// only used for resolving the parent references. All nodes are assigned
+    /// new ids by `new_node_id`.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn from_rows(
+        new_node_id: T::NewNodeId,
+        rows: impl IntoIterator<Item = TreeRow<T>>,
+    ) -> Result<Self, FromRowsError<T>> {
+        let mut rows = rows.into_iter();
+        let TreeRow {
+            node_id: root_row_id,
+            parent,
+            value,
+        } = rows.next().ok_or(FromRowsError::RootRowMissing)?;
+        if parent.is_some() {
+            return Err(FromRowsError::ParentRowNotFound {
+                node_id: root_row_id,
+            });
+        }
+        let mut tree = Self::new(new_node_id, value);
+        let mut node_ids = HashMap::new();
+        node_ids.insert(root_row_id, tree.root_node_id());
+        for TreeRow {
+            node_id: row_id,
+            parent,
+            value,
+        } in rows
+        {
+            let HalfEdgeOwned {
+                path_segment,
+                node_id: parent_row_id,
+            } = parent.ok_or_else(|| FromRowsError::RootRowDuplicate {
+                node_id: row_id.clone(),
+            })?;
+            if node_ids.contains_key(&row_id) {
+                return Err(FromRowsError::NodeIdDuplicate { node_id: row_id });
+            }
+            let parent_node_id = node_ids.get(&parent_row_id).cloned().ok_or_else(|| {
+                FromRowsError::ParentRowNotFound {
+                    node_id: row_id.clone(),
+                }
+            })?;
+            let parent_node = Arc::clone(tree.get_node(parent_node_id));
+            let Node::Inner(inner_node) = &parent_node.node else {
+                return Err(FromRowsError::ParentRowLeaf { node_id: row_id });
+            };
+            if inner_node.find_child(path_segment.borrow()).is_some() {
+                return Err(FromRowsError::PathSegmentDuplicate { node_id: row_id });
+            }
+            let inserted = tree
+                .insert_or_update_child_node_value(&parent_node, path_segment.borrow(), None, value)
+                .expect("vacant child of an inner node");
+            node_ids.insert(row_id, inserted.node.id.clone());
+        }
+        Ok(tree)
+    }
+}
@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{HalfEdge, Node, PathTree, PathTreeTypes};
+
+/// Reusable buffers for transient allocations while mutating a tree.
+///
+/// Operations like [`PathTree::remove_subtree_by_id()`] and
+/// [`PathTree::retain_nodes()`] need to collect node ids temporarily.
+/// Passing the same scratch to their `_with_scratch` variants amortizes
+/// these allocations, e.g. in loops with many mutations.
+///
+/// The buffers are empty between operations. Only their capacity is retained.
+#[derive(Debug)]
+pub struct TraversalScratch<T>
+where
+    T: PathTreeTypes,
+{
+    pub(crate) descendants: Vec<T::NodeId>,
+    pending: Vec<T::NodeId>,
+    pub(crate) candidates: Vec<T::NodeId>,
+}
+
+impl<T> TraversalScratch<T>
+where
+    T: PathTreeTypes,
+{
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            descendants: Vec::new(),
+            pending: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Release the memory of all buffers.
+    pub fn shrink_to_fit(&mut self) {
+        let Self {
+            descendants,
+            pending,
+            candidates,
+        } = self;
+        descendants.shrink_to_fit();
+        pending.shrink_to_fit();
+        candidates.shrink_to_fit();
+    }
+
+    /// Collect the ids of all descendants of a node into [`Self::descendants`].
+    ///
+    /// In no particular order.
+    pub(crate) fn collect_descendants(&mut self, tree: &PathTree<T>, node: &Node<T>) {
+        debug_assert!(self.descendants.is_empty());
+        debug_assert!(self.pending.is_empty());
+        let mut node = node;
+        loop {
+            if let Node::Inner(inner_node) = node {
+                self.pending.extend(
+                    inner_node
+                        .children()
+                        .map(|HalfEdge { node_id, .. }| node_id),
+                );
+            }
+            let Some(node_id) = self.pending.pop() else {
+                break;
+            };
+            node = &tree.get_node(node_id.clone()).node;
+            self.descendants.push(node_id);
+        }
+    }
+}
+
+impl<T> Default for TraversalScratch<T>
+where
+    T: PathTreeTypes,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
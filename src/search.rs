@@ -0,0 +1,203 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+use crate::{HalfEdge, Node, PathTree, PathTreeTypes, TreeNode};
+
+/// Limits of a single search query.
+///
+/// Keeps interactive searches on huge trees responsive.
+/// All limits are disabled by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// The maximum number of results.
+    pub max_results: Option<usize>,
+
+    /// The maximum number of visited nodes.
+    pub max_visited_nodes: Option<usize>,
+
+    /// The maximum duration of the search.
+    ///
+    /// Durations that are too large for computing a deadline, e.g. `Duration::MAX`,
+    /// are treated like `None`.
+    ///
+    /// Not supported on platforms without a system clock, e.g. `wasm32-unknown-unknown`.
+    pub time_budget: Option<Duration>,
+}
+
+/// The limit that caused a search to stop early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchTruncated {
+    MaxResults,
+    MaxVisitedNodes,
+    TimeBudget,
+}
+
+/// Results of a search query.
+#[derive(Debug, Clone)]
+pub struct SearchResults<'a, T>
+where
+    T: PathTreeTypes,
+{
+    /// The matching nodes in depth-first order.
+    pub nodes: Vec<&'a Arc<TreeNode<T>>>,
+
+    /// The number of visited nodes.
+    pub visited_nodes: usize,
+
+    /// `Some` if the search has been stopped before visiting all nodes.
+    ///
+    /// The results are incomplete in this case.
+    pub truncated: Option<SearchTruncated>,
+}
+
+impl<T> SearchResults<'_, T>
+where
+    T: PathTreeTypes,
+{
+    /// Check if all matching nodes have been found.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.truncated.is_none()
+    }
+}
+
+/// A leaf value that has been matched by [`PathTree::grep_values()`].
+#[derive(Debug, Clone)]
+pub struct ValueMatch<'a, T, M>
+where
+    T: PathTreeTypes,
+{
+    /// The full path of the leaf node.
+    pub path: Vec<&'a T::PathSegment>,
+
+    /// The id of the leaf node.
+    pub node_id: T::NodeId,
+
+    /// Metadata of the match as returned by the matcher.
+    pub matched: M,
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Search a subtree for matching nodes within the given limits.
+    ///
+    /// Visits the given node and all its descendants in the same order as
+    /// by [`Self::descendant_nodes()`] and collects all nodes that match the
+    /// predicate. The search is stopped early if any of the limits is exceeded.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn search_nodes<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        limits: &SearchLimits,
+        mut predicate: impl FnMut(&TreeNode<T>) -> bool,
+    ) -> SearchResults<'a, T> {
+        debug_assert!(self.contains_node(node));
+        let SearchLimits {
+            max_results,
+            max_visited_nodes,
+            time_budget,
+        } = limits;
+        // Budgets that exceed the range of `Instant` impose no deadline.
+        let deadline = time_budget.and_then(|time_budget| Instant::now().checked_add(time_budget));
+        let mut results = SearchResults {
+            nodes: Vec::new(),
+            visited_nodes: 0,
+            truncated: None,
+        };
+        for node in std::iter::once(node).chain(
+            self.descendant_nodes(node)
+                .map(|HalfEdge { node_id, .. }| self.get_node(node_id)),
+        ) {
+            if max_results.is_some_and(|max_results| results.nodes.len() >= max_results) {
+                results.truncated = Some(SearchTruncated::MaxResults);
+                break;
+            }
+            if max_visited_nodes
+                .is_some_and(|max_visited_nodes| results.visited_nodes >= max_visited_nodes)
+            {
+                results.truncated = Some(SearchTruncated::MaxVisitedNodes);
+                break;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                results.truncated = Some(SearchTruncated::TimeBudget);
+                break;
+            }
+            results.visited_nodes += 1;
+            if predicate(node) {
+                results.nodes.push(node);
+            }
+        }
+        results
+    }
+
+    /// Scan all leaf values and collect the matches with their full path.
+    ///
+    /// The `matcher` returns `Some` with arbitrary metadata for matching
+    /// values, e.g. the positions of a matched substring. The matches are
+    /// returned in the same order as by [`Self::nodes_with_paths()`].
+    pub fn grep_values<M>(
+        &self,
+        mut matcher: impl FnMut(&T::LeafValue) -> Option<M>,
+    ) -> Vec<ValueMatch<'_, T, M>> {
+        self.leaf_values_with_paths()
+            .filter_map(|(path, node_id, value)| {
+                matcher(value).map(|matched| ValueMatch {
+                    path,
+                    node_id,
+                    matched,
+                })
+            })
+            .collect()
+    }
+
+    /// Scan all leaf values in parallel and collect the matches with their full path.
+    ///
+    /// The tree is traversed sequentially while the `matcher` is invoked on
+    /// the thread pool of `rayon`. Returns the same matches in the same order
+    /// as [`Self::grep_values()`].
+    #[cfg(feature = "rayon")]
+    pub fn par_grep_values<M>(
+        &self,
+        matcher: impl Fn(&T::LeafValue) -> Option<M> + Sync,
+    ) -> Vec<ValueMatch<'_, T, M>>
+    where
+        T::NodeId: Send,
+        T::PathSegment: Sync,
+        T::LeafValue: Sync,
+        M: Send,
+    {
+        self.leaf_values_with_paths()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .filter_map(|(path, node_id, value)| {
+                matcher(value).map(|matched| ValueMatch {
+                    path,
+                    node_id,
+                    matched,
+                })
+            })
+            .collect()
+    }
+
+    fn leaf_values_with_paths(
+        &self,
+    ) -> impl Iterator<Item = (Vec<&T::PathSegment>, T::NodeId, &T::LeafValue)> + '_ {
+        self.nodes_with_paths(self.root_node())
+            .filter_map(|(path, node)| match &node.node {
+                Node::Leaf(leaf) => Some((path, node.id.clone(), &leaf.value)),
+                Node::Inner(_) => None,
+            })
+    }
+}
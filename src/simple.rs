@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+//! Ready-made tree types with integer ids, string segments, and JSON values.
+//!
+//! Intended as a starting point, e.g. for prototyping or for applications
+//! that do not need custom types.
+//!
+//! ```
+//! use rpds_pathtree::{NodeValue, SimpleJsonTree, SimplePath};
+//! use serde_json::json;
+//!
+//! let mut tree = SimpleJsonTree::new(Default::default(), NodeValue::Inner(json!({})));
+//! tree.insert_or_update_node_value(
+//!     &SimplePath::new(["config", "name"]),
+//!     NodeValue::Leaf(json!("example")),
+//!     &mut || json!({}),
+//!     |_| None,
+//! )
+//! .unwrap();
+//! let node = tree.find_node(&SimplePath::new(["config", "name"])).unwrap();
+//! assert_eq!(Some(&json!("example")), node.node.leaf_value());
+//! ```
+
+use std::borrow::Cow;
+
+use crate::{PathTree, PathTreeTypes, RootPath, SegmentedPath};
+
+/// Sequential node ids of a [`SimpleJsonTree`].
+#[derive(Debug, Clone, Default)]
+pub struct SimpleNewNodeId {
+    next_node_id: u64,
+}
+
+impl crate::NewNodeId<u64> for SimpleNewNodeId {
+    fn new_node_id(&mut self) -> u64 {
+        let next_node_id = self.next_node_id;
+        self.next_node_id = self.next_node_id.checked_add(1).expect("node id overflow");
+        next_node_id
+    }
+}
+
+/// Path of a [`SimpleJsonTree`].
+///
+/// The root path has no segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SimplePath(pub Vec<String>);
+
+impl SimplePath {
+    /// Create a path from its segments.
+    #[must_use]
+    pub fn new(segments: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self(segments.into_iter().map(Into::into).collect())
+    }
+}
+
+impl SegmentedPath<str> for SimplePath {
+    fn segments(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        let Self(segments) = self;
+        Box::new(segments.iter().map(String::as_str))
+    }
+
+    fn parent_child_segments(&self) -> (Box<dyn Iterator<Item = &str> + '_>, Option<&str>) {
+        let Self(segments) = self;
+        let Some((child_segment, parent_segments)) = segments.split_last() else {
+            return (Box::new(std::iter::empty()), None);
+        };
+        (
+            Box::new(parent_segments.iter().map(String::as_str)),
+            Some(child_segment),
+        )
+    }
+}
+
+impl RootPath<str> for SimplePath {
+    fn is_root(&self) -> bool {
+        let Self(segments) = self;
+        segments.is_empty()
+    }
+}
+
+/// Type system of a [`SimpleJsonTree`].
+///
+/// Values are compared by equality, i.e. updating a node with an equal
+/// value leaves the tree unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct SimpleJsonTreeTypes;
+
+impl PathTreeTypes for SimpleJsonTreeTypes {
+    type NodeId = u64;
+    type NewNodeId = SimpleNewNodeId;
+    type InnerValue = serde_json::Value;
+    type LeafValue = serde_json::Value;
+    type PathSegmentOwned = String;
+    type PathSegment = str;
+    type RootPath = SimplePath;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
+
+    fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+        path_segment.to_owned()
+    }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+
+    fn inner_values_equal(old_value: &Self::InnerValue, new_value: &Self::InnerValue) -> bool {
+        old_value == new_value
+    }
+
+    fn leaf_values_equal(old_value: &Self::LeafValue, new_value: &Self::LeafValue) -> bool {
+        old_value == new_value
+    }
+}
+
+/// Tree with integer ids, string segments, and JSON values.
+pub type SimpleJsonTree = PathTree<SimpleJsonTreeTypes>;
@@ -0,0 +1,258 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::{HalfEdge, InnerNode, Node, PathTree, PathTreeTypes};
+
+/// The branching factor of the hash array mapped tries used for children.
+///
+/// The default of `rpds`.
+const CHILDREN_TRIE_DEGREE: usize = usize::BITS as usize;
+
+/// Statistics about the children of a single inner node.
+///
+/// The internal structure of the persistent hash maps, i.e. the actual depth
+/// and the number of hash collisions, is not exposed by `rpds`. Only a lower
+/// bound of the depth could be derived from the number of children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildrenStats {
+    /// The number of direct children.
+    pub children_count: usize,
+
+    /// The minimum number of trie levels needed for storing all children.
+    ///
+    /// Lookups need to traverse at least this number of levels. The
+    /// actual number is higher if hashes collide.
+    pub min_trie_depth: usize,
+}
+
+impl ChildrenStats {
+    const fn new(children_count: usize) -> Self {
+        let mut min_trie_depth = 0;
+        let mut capacity = 1;
+        while capacity < children_count {
+            capacity = capacity.saturating_mul(CHILDREN_TRIE_DEGREE);
+            min_trie_depth += 1;
+        }
+        if children_count > 0 && min_trie_depth == 0 {
+            min_trie_depth = 1;
+        }
+        Self {
+            children_count,
+            min_trie_depth,
+        }
+    }
+}
+
+/// Aggregated statistics about the children of all inner nodes in a tree.
+///
+/// Intended for choosing a suitable backend for storing children,
+/// e.g. hash maps, ordered maps, or small inline vectors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeChildrenStats {
+    /// The number of inner nodes.
+    pub inner_nodes_count: usize,
+
+    /// The number of inner nodes without children.
+    pub empty_inner_nodes_count: usize,
+
+    /// The total number of children of all inner nodes.
+    pub children_count: usize,
+
+    /// The maximum number of children of a single inner node.
+    pub max_children_count: usize,
+
+    /// The maximum of [`ChildrenStats::min_trie_depth`] of all inner nodes.
+    pub max_min_trie_depth: usize,
+
+    /// Histogram of the number of children per inner node.
+    ///
+    /// The first bucket counts inner nodes without children. The bucket
+    /// with index `i > 0` counts inner nodes with `2^(i-1)..2^i` children.
+    pub children_count_histogram: Vec<usize>,
+}
+
+impl TreeChildrenStats {
+    fn add(&mut self, stats: ChildrenStats) {
+        let ChildrenStats {
+            children_count,
+            min_trie_depth,
+        } = stats;
+        self.inner_nodes_count += 1;
+        if children_count == 0 {
+            self.empty_inner_nodes_count += 1;
+        }
+        self.children_count += children_count;
+        self.max_children_count = self.max_children_count.max(children_count);
+        self.max_min_trie_depth = self.max_min_trie_depth.max(min_trie_depth);
+        let bucket = (usize::BITS - children_count.leading_zeros()) as usize;
+        if self.children_count_histogram.len() <= bucket {
+            self.children_count_histogram.resize(bucket + 1, 0);
+        }
+        self.children_count_histogram[bucket] += 1;
+    }
+
+    /// The average number of children of inner nodes.
+    ///
+    /// `None` if the tree has no inner nodes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // Only used for statistics
+    pub fn mean_children_count(&self) -> Option<f64> {
+        (self.inner_nodes_count > 0)
+            .then(|| self.children_count as f64 / self.inner_nodes_count as f64)
+    }
+}
+
+impl<T> InnerNode<T>
+where
+    T: PathTreeTypes,
+{
+    /// Statistics about the children of this node.
+    ///
+    /// In constant time, i.e. O(1).
+    pub fn children_stats(&self) -> ChildrenStats {
+        ChildrenStats::new(self.children_count())
+    }
+}
+
+/// The number of nodes per kind in a subtree.
+///
+/// Returned by [`PathTree::subtree_kind_counts()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubtreeKindCounts {
+    /// The number of inner nodes, including the root node of the subtree
+    /// if it is an inner node.
+    pub inner_nodes_count: usize,
+
+    /// The number of leaf nodes, including the root node of the subtree
+    /// if it is a leaf node.
+    pub leaf_nodes_count: usize,
+}
+
+impl SubtreeKindCounts {
+    pub(crate) fn add<T: PathTreeTypes>(&mut self, node: &Node<T>) {
+        match node {
+            Node::Inner(_) => self.inner_nodes_count += 1,
+            Node::Leaf(_) => self.leaf_nodes_count += 1,
+        }
+    }
+
+    pub(crate) fn add_counts(&mut self, other: &Self) {
+        let Self {
+            inner_nodes_count,
+            leaf_nodes_count,
+        } = other;
+        self.inner_nodes_count += inner_nodes_count;
+        self.leaf_nodes_count += leaf_nodes_count;
+    }
+}
+
+/// The number of nodes per kind that are shared with another tree.
+///
+/// Values are stored within the nodes. Shared nodes and their values are
+/// thus only allocated once for both trees.
+///
+/// Returned by [`PathTree::value_sharing_report()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueSharingReport {
+    /// The number of inner nodes that are shared with the other tree.
+    pub shared_inner_nodes_count: usize,
+
+    /// The number of inner nodes that are owned exclusively by this tree.
+    pub unique_inner_nodes_count: usize,
+
+    /// The number of leaf nodes that are shared with the other tree.
+    pub shared_leaf_nodes_count: usize,
+
+    /// The number of leaf nodes that are owned exclusively by this tree.
+    pub unique_leaf_nodes_count: usize,
+}
+
+impl ValueSharingReport {
+    /// The total number of shared nodes.
+    #[must_use]
+    pub const fn shared_nodes_count(&self) -> usize {
+        self.shared_inner_nodes_count + self.shared_leaf_nodes_count
+    }
+
+    /// The total number of nodes that are owned exclusively by this tree.
+    #[must_use]
+    pub const fn unique_nodes_count(&self) -> usize {
+        self.unique_inner_nodes_count + self.unique_leaf_nodes_count
+    }
+
+    fn add<T: PathTreeTypes>(&mut self, node: &Node<T>, shared: bool) {
+        let count = match (node, shared) {
+            (Node::Inner(_), true) => &mut self.shared_inner_nodes_count,
+            (Node::Inner(_), false) => &mut self.unique_inner_nodes_count,
+            (Node::Leaf(_), true) => &mut self.shared_leaf_nodes_count,
+            (Node::Leaf(_), false) => &mut self.unique_leaf_nodes_count,
+        };
+        *count += 1;
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Aggregated statistics about the children of all inner nodes.
+    ///
+    /// Executed in linear time, i.e. O(n).
+    #[must_use]
+    pub fn children_stats(&self) -> TreeChildrenStats {
+        let mut stats = TreeChildrenStats::default();
+        for node in self.nodes() {
+            let Node::Inner(inner_node) = &node.node else {
+                continue;
+            };
+            stats.add(inner_node.children_stats());
+        }
+        stats
+    }
+
+    /// The number of inner and leaf nodes in a subtree.
+    ///
+    /// Includes the given node, e.g. for displaying badges like
+    /// "42 files, 7 folders" for a folder.
+    ///
+    /// Returns `None` if the node does not exist.
+    ///
+    /// Computed on demand in linear time with respect to the size of the
+    /// subtree. Use a [`SubtreeWeightIndex`](crate::SubtreeWeightIndex) for
+    /// repeated queries while the tree is modified.
+    #[must_use]
+    pub fn subtree_kind_counts(&self, node_id: T::NodeId) -> Option<SubtreeKindCounts> {
+        let node = self.lookup_node(node_id)?;
+        let mut counts = SubtreeKindCounts::default();
+        counts.add(&node.node);
+        for HalfEdge { node_id, .. } in self.descendant_nodes(node) {
+            counts.add(&self.get_node(node_id).node);
+        }
+        Some(counts)
+    }
+
+    /// Count the nodes of this tree that are shared with another tree.
+    ///
+    /// Nodes are shared between snapshots that have been cloned from each
+    /// other until they are modified. Quantifies the memory savings of the
+    /// structural sharing, e.g. for detecting unintended deep clones that
+    /// do not share any nodes.
+    ///
+    /// Only the nodes of this tree are counted. Nodes that only exist in
+    /// the other tree are ignored.
+    ///
+    /// Executed in linear time, i.e. O(n).
+    #[must_use]
+    pub fn value_sharing_report(&self, other: &Self) -> ValueSharingReport {
+        let mut report = ValueSharingReport::default();
+        for node in self.nodes() {
+            let shared = other
+                .lookup_node(node.id.clone())
+                .is_some_and(|other_node| Arc::ptr_eq(node, other_node));
+            report.add(&node.node, shared);
+        }
+        report
+    }
+}
@@ -1,12 +1,55 @@
 // SPDX-FileCopyrightText: The rpds-pathtree authors
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{borrow::Cow, sync::Arc};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    cell::Cell,
+    sync::Arc,
+};
 
 use crate::{
-    MatchNodePath, NodeInsertedOrUpdated, NodePathMatched, RootPath, SegmentedPath, SubtreeRemoved,
+    AnyPath, AnyValue, ApplyEventError, ChildHandleInvalidated, ConflictResolution, FromRowsError,
+    HalfEdge, HalfEdgeTreeNode, InnerNode, LeafNode, MatchNodePath, Node, NodeInsertedOrUpdated,
+    NodePathMatched, NodeValueVisitor, ParseVersionedTreePatchError, PatchConflict,
+    PatchConflictKind, PathRemoved, PreferNewest, PreferOurs, PreferTheirs, RejectConflicts,
+    RepairAction, RepairPolicy, RewritePrefixError, RootPath, RootPathPolicy, SegmentedPath,
+    SubtreeInsertedOrReplaced, SubtreeRemoved, TraversalEvent, TreeId, TreeValidationError,
+    TryInsertOrUpdateNodeValueError, UpdateNodeValueError, ValueConflict, VisitControl,
+    VisitedNode, VisitedValue,
 };
 
+/// Counts the allocations of each thread for detecting regressions.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+#[allow(unsafe_code)] // Delegates to the system allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Fails silently while the thread is being destroyed.
+        let _ = ALLOCATIONS_COUNT.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Count the allocations of the current thread while executing `f`.
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let count_before = ALLOCATIONS_COUNT.with(Cell::get);
+    let result = f();
+    let count_after = ALLOCATIONS_COUNT.with(Cell::get);
+    (result, count_after - count_before)
+}
+
 /// A lazy path implementation for testing.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct SlashPath<'a>(Cow<'a, str>);
@@ -111,6 +154,20 @@ impl crate::NewNodeId<usize> for NewNodeId {
     }
 }
 
+impl crate::PartitionNewNodeId<usize> for NewNodeId {
+    fn partition(&mut self, count: usize) -> Vec<Self> {
+        // Disjoint blocks of ids that are sufficiently large for testing.
+        const BLOCK_SIZE: usize = 1 << 20;
+        let partitions = (0..count)
+            .map(|index| Self {
+                next_node_id: self.next_node_id + index * BLOCK_SIZE,
+            })
+            .collect();
+        self.next_node_id += count * BLOCK_SIZE;
+        partitions
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct PathTreeTypes;
 
@@ -120,16 +177,75 @@ impl crate::PathTreeTypes for PathTreeTypes {
     type PathSegmentOwned = Cow<'static, str>;
     type PathSegment = str;
     type RootPath = SlashPath<'static>;
+    type ChildKey = str;
+    type ChildKeyOwned = String;
     type InnerValue = isize;
     type LeafValue = usize;
 
     fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
         Cow::Owned(path_segment.to_owned())
     }
+
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+        Cow::Borrowed(path_segment)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl crate::SnapshotCodec for PathTreeTypes {
+    fn encode_path_segment(path_segment: &Self::PathSegment, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(path_segment.as_bytes());
+    }
+
+    fn decode_path_segment(bytes: &[u8]) -> Option<Self::PathSegmentOwned> {
+        std::str::from_utf8(bytes)
+            .ok()
+            .map(|path_segment| Cow::Owned(path_segment.to_owned()))
+    }
+
+    fn encode_inner_value(value: &Self::InnerValue, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn decode_inner_value(bytes: &[u8]) -> Option<Self::InnerValue> {
+        bytes.try_into().ok().map(isize::from_le_bytes)
+    }
+
+    fn encode_leaf_value(value: &Self::LeafValue, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn decode_leaf_value(bytes: &[u8]) -> Option<Self::LeafValue> {
+        bytes.try_into().ok().map(usize::from_le_bytes)
+    }
 }
 
 type PathTree = crate::PathTree<PathTreeTypes>;
 type NodeValue = crate::NodeValue<PathTreeTypes>;
+type EventSourcedTree = crate::EventSourcedTree<PathTreeTypes>;
+type PathTreeEvent = crate::PathTreeEvent<PathTreeTypes>;
+type TreePatch = crate::TreePatch<PathTreeTypes>;
+type DeferredValuesTree = crate::DeferredValuesTree<PathTreeTypes>;
+type TreeRow = crate::TreeRow<PathTreeTypes>;
+type TreeNode = crate::TreeNode<PathTreeTypes>;
+
+/// Check that both trees contain the same nodes with the same ids and values.
+fn assert_same_nodes(expected: &PathTree, actual: &PathTree) {
+    assert_eq!(expected.root_node_id(), actual.root_node_id());
+    assert_eq!(expected.nodes_count(), actual.nodes_count());
+    for expected_node in expected.nodes() {
+        let actual_node = actual.lookup_node(expected_node.id).unwrap();
+        assert_eq!(expected_node.parent, actual_node.parent);
+        assert_eq!(
+            expected_node.node.inner_value(),
+            actual_node.node.inner_value()
+        );
+        assert_eq!(
+            expected_node.node.leaf_value(),
+            actual_node.node.leaf_value()
+        );
+    }
+}
 
 // <https://github.com/rust-lang/api-guidelines/issues/223#issuecomment-683346783>
 #[cfg(feature = "sync")]
@@ -366,12 +482,15 @@ fn multiple_nodes() {
             parent_node: _,
             child_path_segment,
             removed_subtree,
+            affected_nodes,
         } = path_tree.remove_subtree_by_id(node.id).unwrap();
         debug_assert_eq!(child_path_segment.as_ref(), "baz");
         debug_assert_eq!(
             1 + descendant_nodes_count,
             removed_subtree.nodes_count().get()
         );
+        // The removed nodes and the updated parent node.
+        assert_eq!(2 + descendant_nodes_count, affected_nodes);
         assert_eq!(3, path_tree.nodes_count().get());
     }
 
@@ -388,12 +507,15 @@ fn multiple_nodes() {
             parent_node: _,
             child_path_segment,
             removed_subtree,
+            affected_nodes,
         } = path_tree.remove_subtree_by_id(node.id).unwrap();
         debug_assert_eq!(child_path_segment.as_ref(), "bar");
         debug_assert_eq!(
             1 + descendant_nodes_count,
             removed_subtree.nodes_count().get()
         );
+        // The removed nodes and the updated parent node.
+        assert_eq!(2 + descendant_nodes_count, affected_nodes);
         assert_eq!(2, path_tree.nodes_count().get());
     }
 
@@ -410,12 +532,15 @@ fn multiple_nodes() {
             parent_node: _,
             child_path_segment,
             removed_subtree,
+            affected_nodes,
         } = path_tree.remove_subtree_by_id(node.id).unwrap();
         debug_assert_eq!(child_path_segment.as_ref(), "foo");
         debug_assert_eq!(
             1 + descendant_nodes_count,
             removed_subtree.nodes_count().get()
         );
+        // The removed nodes and the updated parent node.
+        assert_eq!(2 + descendant_nodes_count, affected_nodes);
         // Only the root node remains.
         assert_eq!(1, path_tree.nodes_count().get());
     }
@@ -822,7 +947,11 @@ fn insert_or_update_child_node_value_leaf() {
             .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
             .unwrap(),
     );
-    let NodeInsertedOrUpdated { node: _, parent } = path_tree
+    let NodeInsertedOrUpdated {
+        node: _,
+        parent,
+        affected_nodes: _,
+    } = path_tree
         .insert_or_update_child_node_value(&parent_node, "bar2", Some("bar"), NodeValue::Leaf(4))
         .unwrap();
     assert_eq!(4, path_tree.nodes_count().get());
@@ -845,7 +974,11 @@ fn insert_or_update_child_node_value_leaf() {
             .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
             .unwrap(),
     );
-    let NodeInsertedOrUpdated { node: _, parent } = path_tree
+    let NodeInsertedOrUpdated {
+        node: _,
+        parent,
+        affected_nodes: _,
+    } = path_tree
         .insert_or_update_child_node_value(&parent_node, "baz", Some("bar2"), NodeValue::Leaf(5))
         .unwrap();
     assert_eq!(3, path_tree.nodes_count().get());
@@ -946,7 +1079,11 @@ fn insert_or_update_child_node_value_inner() {
             .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
             .unwrap(),
     );
-    let NodeInsertedOrUpdated { node: _, parent } = path_tree
+    let NodeInsertedOrUpdated {
+        node: _,
+        parent,
+        affected_nodes: _,
+    } = path_tree
         .insert_or_update_child_node_value(
             &parent_node,
             "inner2",
@@ -974,7 +1111,11 @@ fn insert_or_update_child_node_value_inner() {
             .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
             .unwrap(),
     );
-    let NodeInsertedOrUpdated { node: _, parent } = path_tree
+    let NodeInsertedOrUpdated {
+        node: _,
+        parent,
+        affected_nodes: _,
+    } = path_tree
         .insert_or_update_child_node_value(
             &parent_node,
             "bar",
@@ -1019,3 +1160,6817 @@ fn insert_or_update_child_node_value_inner() {
             .root_node_id()
     );
 }
+
+#[test]
+fn affected_nodes() {
+    let mut path_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+
+    // Inserts "/foo" and "/foo/bar" and updates the root node.
+    let NodeInsertedOrUpdated { affected_nodes, .. } = path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(3, affected_nodes);
+
+    // Inserts "/foo/baz" and updates "/foo".
+    let NodeInsertedOrUpdated { affected_nodes, .. } = path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/baz")),
+            NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(2, affected_nodes);
+
+    // Only updates "/foo/bar".
+    let NodeInsertedOrUpdated { affected_nodes, .. } = path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(3),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(1, affected_nodes);
+
+    // Only updates the root node.
+    let NodeInsertedOrUpdated { affected_nodes, .. } = path_tree
+        .insert_or_update_node_value(
+            &SlashPath::ROOT,
+            NodeValue::Inner(-2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(1, affected_nodes);
+
+    // Replaces the leaf node "/foo/bar" with an inner node and inserts "/foo/bar/baz".
+    let NodeInsertedOrUpdated { affected_nodes, .. } = path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar/baz")),
+            NodeValue::Leaf(4),
+            &mut || unreachable!(),
+            |_| Some(-3),
+        )
+        .unwrap();
+    assert_eq!(2, affected_nodes);
+
+    // Removes "/foo" with all 3 descendants and updates the root node.
+    let node_id = path_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+        .unwrap()
+        .id;
+    let SubtreeRemoved {
+        removed_subtree,
+        affected_nodes,
+        ..
+    } = path_tree.remove_subtree_by_id(node_id).unwrap();
+    assert_eq!(5, affected_nodes);
+
+    // Inserts all 4 nodes of the subtree and updates the root node.
+    let root_node = Arc::clone(path_tree.root_node());
+    let SubtreeInsertedOrReplaced { affected_nodes, .. } = path_tree
+        .insert_or_replace_subtree(&root_node, "foo", None, removed_subtree)
+        .unwrap();
+    assert_eq!(5, affected_nodes);
+    assert_eq!(5, path_tree.nodes_count().get());
+}
+
+#[test]
+fn deep_clone() {
+    let mut path_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+
+    let shallow_clone = path_tree.clone();
+    let deep_clone = path_tree.deep_clone();
+    assert_eq!(path_tree.nodes_count(), deep_clone.nodes_count());
+    assert_eq!(path_tree.root_node_id(), deep_clone.root_node_id());
+    for node in path_tree.nodes() {
+        assert!(shallow_clone.contains_node(node));
+        assert!(!deep_clone.contains_node(node));
+        let cloned_node = deep_clone.lookup_node(node.id).unwrap();
+        assert_eq!(node.parent, cloned_node.parent);
+        assert_eq!(node.node.inner_value(), cloned_node.node.inner_value());
+        assert_eq!(node.node.leaf_value(), cloned_node.node.leaf_value());
+    }
+    assert_eq!(
+        Some(&1),
+        deep_clone
+            .find_node(&SlashPath::new(Cow::Borrowed("/foo/bar")))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+
+    assert!(path_tree.shallow_size() < path_tree.deep_size());
+    assert_eq!(path_tree.deep_size(), deep_clone.deep_size());
+
+    // Each additional node is accounted for together with its child entry in the parent node.
+    let mut grown_tree = path_tree.clone();
+    grown_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/baz")),
+            NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    let node_size = std::mem::size_of::<usize>()
+        + std::mem::size_of::<Arc<crate::TreeNode<PathTreeTypes>>>()
+        + 2 * std::mem::size_of::<usize>()
+        + std::mem::size_of::<crate::TreeNode<PathTreeTypes>>();
+    let child_size =
+        std::mem::size_of::<String>() + std::mem::size_of::<crate::HalfEdgeOwned<PathTreeTypes>>();
+    assert_eq!(
+        path_tree.deep_size() + node_size + child_size,
+        grown_tree.deep_size()
+    );
+}
+
+#[test]
+fn node_refcounts() {
+    let mut path_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    path_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo")),
+            NodeValue::Leaf(1),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(2, path_tree.node_refcounts().len());
+    assert!(path_tree
+        .node_refcounts()
+        .all(|(_, strong_count)| strong_count == 1));
+
+    // Retain the node outside of the tree.
+    let node = Arc::clone(
+        path_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+            .unwrap(),
+    );
+    assert!(path_tree
+        .node_refcounts()
+        .all(|(node_id, strong_count)| strong_count == if node_id == node.id { 2 } else { 1 }));
+
+    // The retained node is still referenced after it has been removed from the tree.
+    path_tree.remove_subtree_by_id(node.id).unwrap();
+    assert_eq!(1, path_tree.node_refcounts().len());
+    assert_eq!(1, Arc::strong_count(&node));
+}
+
+#[test]
+fn event_sourced_tree_replay() {
+    let initial_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let mut event_sourced_tree = EventSourcedTree::new(initial_tree.clone());
+    let mut new_inner_value = {
+        let mut inner_value = 0;
+        move || {
+            inner_value -= 1;
+            inner_value
+        }
+    };
+
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar/baz")),
+            NodeValue::Leaf(1),
+            &mut new_inner_value,
+            |_| None,
+        )
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar/baz/leaf")),
+            NodeValue::Leaf(2),
+            &mut new_inner_value,
+            |&leaf_value| Some(-10 * isize::try_from(leaf_value).unwrap()),
+        )
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_child_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo")),
+            "renamed",
+            Some("bar"),
+            NodeValue::Inner(-20),
+        )
+        .unwrap()
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/other/leaf")),
+            NodeValue::Leaf(3),
+            &mut new_inner_value,
+            |_| None,
+        )
+        .unwrap();
+    assert!(event_sourced_tree
+        .remove_subtree(&SlashPath::new(Cow::Borrowed("/other")))
+        .is_some());
+
+    // Failed mutations are not recorded.
+    assert!(event_sourced_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/renamed/baz/leaf/invalid")),
+            NodeValue::Leaf(4),
+            &mut new_inner_value,
+            |_| None,
+        )
+        .is_err());
+    assert!(event_sourced_tree
+        .remove_subtree(&SlashPath::new(Cow::Borrowed("/other")))
+        .is_none());
+    assert_eq!(5, event_sourced_tree.events().len());
+
+    let replayed_tree =
+        EventSourcedTree::replay(initial_tree, event_sourced_tree.events().iter().cloned())
+            .unwrap();
+    assert_same_nodes(event_sourced_tree.tree(), replayed_tree.tree());
+    assert_eq!(
+        Some(&-10),
+        replayed_tree
+            .tree()
+            .find_node(&SlashPath::new(Cow::Borrowed("/foo/renamed/baz")))
+            .unwrap()
+            .node
+            .inner_value()
+    );
+}
+
+#[test]
+fn event_sourced_tree_apply_inconsistent_event() {
+    let mut event_sourced_tree =
+        EventSourcedTree::new(PathTree::new(Default::default(), NodeValue::Inner(0)));
+
+    // The value of the missing ancestor node "/foo" has not been recorded.
+    assert!(matches!(
+        event_sourced_tree.apply(PathTreeEvent::InsertOrUpdateNodeValue {
+            path: SlashPath::new(Cow::Borrowed("/foo/bar")),
+            value: NodeValue::Leaf(1),
+            new_inner_values: vec![],
+            leaf_into_inner_value: None,
+        }),
+        Err(ApplyEventError::Inconsistent { .. })
+    ));
+    assert!(matches!(
+        event_sourced_tree.apply(PathTreeEvent::RemoveSubtree {
+            path: SlashPath::new(Cow::Borrowed("/foo")),
+        }),
+        Err(ApplyEventError::PathNotFound { .. })
+    ));
+    assert!(event_sourced_tree.events().is_empty());
+    assert_eq!(1, event_sourced_tree.tree().nodes_count().get());
+
+    event_sourced_tree
+        .apply(PathTreeEvent::InsertOrUpdateNodeValue {
+            path: SlashPath::new(Cow::Borrowed("/foo/bar")),
+            value: NodeValue::Leaf(1),
+            new_inner_values: vec![-1],
+            leaf_into_inner_value: None,
+        })
+        .unwrap();
+    assert_eq!(1, event_sourced_tree.events().len());
+    assert_eq!(3, event_sourced_tree.tree().nodes_count().get());
+}
+
+#[test]
+fn event_sourced_tree_apply_idempotent() {
+    let mut event_sourced_tree = crate::EventSourcedTree::<PathTreeTypes, u64>::new(PathTree::new(
+        Default::default(),
+        NodeValue::Inner(0),
+    ));
+    let event = PathTreeEvent::InsertOrUpdateNodeValue {
+        path: SlashPath::new(Cow::Borrowed("/foo")),
+        value: NodeValue::Leaf(1),
+        new_inner_values: vec![],
+        leaf_into_inner_value: None,
+    };
+
+    assert!(event_sourced_tree
+        .apply_idempotent(1, event.clone())
+        .unwrap());
+    assert!(event_sourced_tree.is_applied(&1));
+    // Delivered twice.
+    assert!(!event_sourced_tree
+        .apply_idempotent(1, event.clone())
+        .unwrap());
+    assert_eq!(1, event_sourced_tree.events().len());
+
+    // Failed events are not remembered.
+    let failing_event = PathTreeEvent::RemoveSubtree {
+        path: SlashPath::new(Cow::Borrowed("/bar")),
+    };
+    assert!(event_sourced_tree
+        .apply_idempotent(2, failing_event)
+        .is_err());
+    assert!(!event_sourced_tree.is_applied(&2));
+
+    // The keys must be restored after replaying the events.
+    let replayed_tree = crate::EventSourcedTree::<PathTreeTypes, u64>::replay(
+        event_sourced_tree.initial_tree().clone(),
+        event_sourced_tree.events().iter().cloned(),
+    )
+    .unwrap()
+    .with_applied_keys(event_sourced_tree.applied_keys().copied());
+    assert!(replayed_tree.is_applied(&1));
+    assert_same_nodes(event_sourced_tree.tree(), replayed_tree.tree());
+}
+
+#[test]
+fn path_tree_history() {
+    let mut history =
+        crate::PathTreeHistory::new(PathTree::new(Default::default(), NodeValue::Inner(0)));
+    let foo_path = SlashPath::new(Cow::Borrowed("/foo"));
+    let foo_bar_path = SlashPath::new(Cow::Borrowed("/foo/bar"));
+    assert_eq!(0, history.current_version());
+
+    assert_eq!(
+        1,
+        history
+            .apply(PathTreeEvent::InsertOrUpdateNodeValue {
+                path: foo_bar_path.clone(),
+                value: NodeValue::Leaf(1),
+                new_inner_values: vec![-1],
+                leaf_into_inner_value: None,
+            })
+            .unwrap()
+    );
+    assert_eq!(
+        2,
+        history
+            .apply(PathTreeEvent::InsertOrUpdateNodeValue {
+                path: foo_bar_path.clone(),
+                value: NodeValue::Leaf(2),
+                new_inner_values: vec![],
+                leaf_into_inner_value: None,
+            })
+            .unwrap()
+    );
+    assert_eq!(
+        3,
+        history
+            .apply(PathTreeEvent::RemoveSubtree {
+                path: foo_path.clone(),
+            })
+            .unwrap()
+    );
+    // Failed events don't create a new version.
+    assert!(history
+        .apply(PathTreeEvent::RemoveSubtree { path: foo_path })
+        .is_err());
+    assert_eq!(3, history.current_version());
+
+    assert!(history.find_node_at(0, &foo_bar_path).is_none());
+    assert_eq!(
+        Some(&1),
+        history
+            .find_node_at(1, &foo_bar_path)
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+    assert_eq!(
+        Some(&2),
+        history
+            .find_node_at(2, &foo_bar_path)
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+    assert!(history.find_node_at(3, &foo_bar_path).is_none());
+    assert!(history.find_node_at(4, &foo_bar_path).is_none());
+
+    let root_node_id = history.current_tree().root_node_id();
+    let diff = history.diff(0, 1).unwrap();
+    assert_eq!(2, diff.inserted.len());
+    assert!(diff.removed.is_empty());
+    assert_eq!(vec![root_node_id], diff.updated);
+
+    let bar_node_id = history.find_node_at(1, &foo_bar_path).unwrap().id;
+    let diff = history.diff(1, 2).unwrap();
+    assert!(diff.inserted.is_empty());
+    assert!(diff.removed.is_empty());
+    assert_eq!(vec![bar_node_id], diff.updated);
+
+    let diff = history.diff(2, 3).unwrap();
+    assert!(diff.inserted.is_empty());
+    assert_eq!(2, diff.removed.len());
+    assert_eq!(vec![root_node_id], diff.updated);
+
+    assert!(history.diff(3, 3).unwrap().is_empty());
+    assert!(history.diff(0, 4).is_none());
+}
+
+#[test]
+fn path_tree_history_compact() {
+    let mut history =
+        crate::PathTreeHistory::new(PathTree::new(Default::default(), NodeValue::Inner(0)));
+    let path = SlashPath::new(Cow::Borrowed("/foo/bar"));
+    history
+        .apply(PathTreeEvent::InsertOrUpdateNodeValue {
+            path: path.clone(),
+            value: NodeValue::Leaf(1),
+            new_inner_values: vec![-1],
+            leaf_into_inner_value: None,
+        })
+        .unwrap();
+    for value in 2..=10 {
+        history
+            .apply(PathTreeEvent::InsertOrUpdateNodeValue {
+                path: path.clone(),
+                value: NodeValue::Leaf(value),
+                new_inner_values: vec![],
+                leaf_into_inner_value: None,
+            })
+            .unwrap();
+    }
+    assert_eq!(10, history.current_version());
+    assert_eq!(11, history.snapshots_count());
+    let trees_before = (0..=10)
+        .map(|version| history.tree_at(version).unwrap())
+        .collect::<Vec<_>>();
+
+    // Retains all versions without overflowing.
+    history.compact(usize::MAX, 0);
+    assert_eq!(11, history.snapshots_count());
+
+    // Retains versions 0, 4, 8, 9, and 10.
+    history.compact(2, 4);
+    assert_eq!(5, history.snapshots_count());
+    for (version, tree_before) in trees_before.iter().enumerate() {
+        assert_same_nodes(tree_before, &history.tree_at(version).unwrap());
+    }
+    assert_eq!(
+        Some(&7),
+        history.find_node_at(7, &path).unwrap().node.leaf_value()
+    );
+    assert!(history.find_node_at(0, &path).is_none());
+
+    // Only retains the initial and the current version.
+    history.compact(0, 0);
+    assert_eq!(2, history.snapshots_count());
+    for (version, tree_before) in trees_before.iter().enumerate() {
+        assert_same_nodes(tree_before, &history.tree_at(version).unwrap());
+    }
+    assert!(history.tree_at(11).is_none());
+}
+
+#[test]
+fn diff_structure_and_values() {
+    let mut old_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    old_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+
+    // Only update the value of "/foo/bar".
+    let mut new_tree = old_tree.clone();
+    new_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(1, old_tree.diff(&new_tree).updated.len());
+    assert!(old_tree.diff_structure(&new_tree).is_empty());
+    assert_eq!(1, old_tree.diff_values(&new_tree).updated.len());
+    assert!(old_tree.has_same_structure(&new_tree));
+
+    // Rename "/foo/bar" to "/foo/baz" with the same value.
+    let mut new_tree = old_tree.clone();
+    let parent_node = Arc::clone(
+        new_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+            .unwrap(),
+    );
+    new_tree
+        .insert_or_update_child_node_value(&parent_node, "baz", Some("bar"), NodeValue::Leaf(1))
+        .unwrap();
+    // Both "/foo" and "/foo/baz" have been updated.
+    assert_eq!(2, old_tree.diff(&new_tree).updated.len());
+    // Only "/foo/baz" has been moved.
+    assert_eq!(1, old_tree.diff_structure(&new_tree).updated.len());
+    assert!(old_tree.diff_values(&new_tree).is_empty());
+    assert!(!old_tree.has_same_structure(&new_tree));
+
+    // Unrelated trees with the same structure.
+    let mut other_tree = PathTree::new(NewNodeId { next_node_id: 100 }, NodeValue::Inner(0));
+    other_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(3),
+            &mut || -3,
+            |_| None,
+        )
+        .unwrap();
+    assert!(old_tree.has_same_structure(&other_tree));
+    assert!(other_tree.has_same_structure(&old_tree));
+    assert_eq!(3, old_tree.diff_structure(&other_tree).inserted.len());
+}
+
+#[test]
+fn tree_patch_text() {
+    let mut old_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/qux", 5)] {
+        old_tree
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+    }
+
+    let mut new_tree = old_tree.clone();
+    let qux_node_id = new_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/foo/qux")))
+        .unwrap()
+        .id;
+    new_tree.remove_subtree_by_id(qux_node_id).unwrap();
+    new_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo")),
+            NodeValue::Inner(-2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    new_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/baz/x y")),
+            NodeValue::Leaf(7),
+            &mut || -3,
+            |_| None,
+        )
+        .unwrap();
+
+    assert!(old_tree.diff_patch(&old_tree).is_empty());
+    let patch = old_tree.diff_patch(&new_tree);
+    let text = patch.to_string();
+    assert_eq!(
+        "- /foo/qux\n~ /foo inner -2\n+ /foo/baz inner -3\n+ /foo/baz/x%20y leaf 7\n",
+        text
+    );
+    let parsed_patch = text.parse::<TreePatch>().unwrap();
+    assert_eq!(patch.operations.len(), parsed_patch.operations.len());
+    assert_eq!(text, parsed_patch.to_string());
+
+    let err = "~ /foo inner -2\n+ / leaf 1\n"
+        .parse::<TreePatch>()
+        .unwrap_err();
+    assert_eq!(2, err.line);
+    assert!("+ /foo//bar leaf 1".parse::<TreePatch>().is_err());
+    assert!("~ /foo value 1".parse::<TreePatch>().is_err());
+}
+
+#[test]
+fn tree_patch_with_header() {
+    #[derive(Debug, Clone, Default)]
+    struct TaggedPathTreeTypes;
+
+    impl crate::PathTreeTypes for TaggedPathTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = isize;
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+
+        fn schema_tag() -> String {
+            "my schema v2".to_owned()
+        }
+    }
+
+    let patch = "~ /foo inner -2\n+ /foo/bar leaf 1\n"
+        .parse::<TreePatch>()
+        .unwrap();
+    let text = patch.to_string_with_header();
+    assert!(text.starts_with(&format!(
+        "#!rpds-pathtree-patch {} ",
+        crate::PATCH_FORMAT_VERSION
+    )));
+    assert!(text.ends_with(&patch.to_string()));
+    let parsed_patch = TreePatch::parse_with_header(&text).unwrap();
+    assert_eq!(patch.to_string(), parsed_patch.to_string());
+
+    // Same values but different schema tags.
+    assert!(matches!(
+        crate::TreePatch::<TaggedPathTreeTypes>::parse_with_header(&text),
+        Err(ParseVersionedTreePatchError::SchemaMismatch { .. })
+    ));
+    let tagged_text = format!("#!rpds-pathtree-patch 1 my%20schema%20v2\n{patch}");
+    assert!(crate::TreePatch::<TaggedPathTreeTypes>::parse_with_header(&tagged_text).is_ok());
+    assert!(matches!(
+        TreePatch::parse_with_header(&tagged_text),
+        Err(ParseVersionedTreePatchError::SchemaMismatch { .. })
+    ));
+
+    // Headerless patches are rejected.
+    assert!(matches!(
+        TreePatch::parse_with_header(&patch.to_string()),
+        Err(ParseVersionedTreePatchError::InvalidHeader)
+    ));
+    assert!(matches!(
+        TreePatch::parse_with_header(&text.replacen(" 1 ", " 2 ", 1)),
+        Err(ParseVersionedTreePatchError::UnsupportedVersion { version: 2 })
+    ));
+    let err = TreePatch::parse_with_header(&format!("{text}~ /foo value 1\n")).unwrap_err();
+    assert!(matches!(
+        err,
+        ParseVersionedTreePatchError::Patch(crate::ParseTreePatchError { line: 4 })
+    ));
+}
+
+#[test]
+fn tree_patch_check_and_apply() {
+    let mut old_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/qux", 5)] {
+        old_tree
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+    }
+    let patch = "- /foo/qux\n~ /foo inner -2\n+ /foo/baz inner -3\n+ /foo/baz/x leaf 7\n"
+        .parse::<TreePatch>()
+        .unwrap();
+    assert!(patch.check(&old_tree).is_ok());
+
+    let mut new_tree = old_tree.clone();
+    new_tree.apply_patch(&patch).unwrap();
+    assert_eq!(
+        patch.to_string(),
+        old_tree.diff_patch(&new_tree).to_string()
+    );
+
+    // Applying the same patch again conflicts for all but the update operation.
+    let report = patch.check(&new_tree);
+    assert_eq!(
+        vec![
+            PatchConflict {
+                operation_index: 0,
+                kind: PatchConflictKind::NodeNotFound,
+            },
+            PatchConflict {
+                operation_index: 2,
+                kind: PatchConflictKind::NodeAlreadyExists,
+            },
+            PatchConflict {
+                operation_index: 3,
+                kind: PatchConflictKind::NodeAlreadyExists,
+            },
+        ],
+        report.conflicts
+    );
+
+    // Nothing is applied if any operation conflicts.
+    let patch = "~ /foo inner -4\n+ /foo/bar/x leaf 8\n- /\n"
+        .parse::<TreePatch>()
+        .unwrap();
+    let report = new_tree.apply_patch(&patch).unwrap_err();
+    assert_eq!(
+        vec![
+            PatchConflict {
+                operation_index: 1,
+                kind: PatchConflictKind::ParentNodeNotFound,
+            },
+            PatchConflict {
+                operation_index: 2,
+                kind: PatchConflictKind::RootNode,
+            },
+        ],
+        report.conflicts
+    );
+    assert_eq!(3, new_tree.diff_patch(&old_tree).operations.len());
+    assert!(matches!(
+        new_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -2, .. })
+    ));
+
+    // Frozen subtrees are neither removed nor updated.
+    let foo_node_id = new_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+        .unwrap()
+        .id;
+    assert!(new_tree.freeze_subtree(foo_node_id));
+    let patch = "- /foo/bar\n~ /foo/baz inner -4\n+ /foo/y leaf 9\n"
+        .parse::<TreePatch>()
+        .unwrap();
+    let report = new_tree.apply_patch(&patch).unwrap_err();
+    assert_eq!(
+        (0..3)
+            .map(|operation_index| PatchConflict {
+                operation_index,
+                kind: PatchConflictKind::SubtreeFrozen,
+            })
+            .collect::<Vec<_>>(),
+        report.conflicts
+    );
+    assert!(new_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/foo/bar")))
+        .is_some());
+}
+
+#[test]
+fn tree_patch_conflict_policy() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo")),
+        NodeValue::Leaf(5),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    let leaf_value_of = |tree: &PathTree| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+            .unwrap()
+            .node
+            .leaf_value()
+            .copied()
+    };
+    let patch = "+ /foo leaf 3\n".parse::<TreePatch>().unwrap();
+    assert!(!patch.check(&tree).is_ok());
+    assert!(patch.check_with_policy(&tree, &mut PreferOurs).is_ok());
+
+    let mut new_tree = tree.clone();
+    new_tree
+        .apply_patch_with_policy(&patch, &mut PreferOurs)
+        .unwrap();
+    assert_eq!(Some(5), leaf_value_of(&new_tree));
+
+    let mut new_tree = tree.clone();
+    new_tree
+        .apply_patch_with_policy(&patch, &mut PreferTheirs)
+        .unwrap();
+    assert_eq!(Some(3), leaf_value_of(&new_tree));
+
+    let mut new_tree = tree.clone();
+    new_tree
+        .apply_patch_with_policy(
+            &patch,
+            &mut PreferNewest(|value: &NodeValue| match value {
+                NodeValue::Inner(_) => 0,
+                NodeValue::Leaf(value) => *value,
+            }),
+        )
+        .unwrap();
+    assert_eq!(Some(5), leaf_value_of(&new_tree));
+
+    let mut new_tree = tree.clone();
+    new_tree
+        .apply_patch_with_policy(&patch, &mut |conflict: ValueConflict<'_, PathTreeTypes>| {
+            assert_eq!(1, conflict.path.len());
+            match (conflict.ours, conflict.theirs) {
+                (NodeValue::Leaf(ours), NodeValue::Leaf(theirs)) => {
+                    ConflictResolution::Value(NodeValue::Leaf(ours + theirs))
+                }
+                _ => ConflictResolution::Reject,
+            }
+        })
+        .unwrap();
+    assert_eq!(Some(8), leaf_value_of(&new_tree));
+}
+
+#[cfg(feature = "deterministic")]
+#[test]
+fn deterministic_iteration_order() {
+    let new_tree = || {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        for i in 0..100 {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Owned(format!("/foo/{i}"))),
+                NodeValue::Leaf(i),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+        }
+        tree
+    };
+    assert_eq!(format!("{:?}", new_tree()), format!("{:?}", new_tree()));
+    // The order is pinned across platforms and Rust releases.
+    let node_ids = new_tree()
+        .nodes()
+        .map(|node| node.id)
+        .take(8)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![37, 101, 4, 68, 39, 6, 70, 97], node_ids);
+}
+
+#[cfg(feature = "deterministic")]
+#[test]
+fn deterministic_hasher() {
+    use std::hash::Hasher as _;
+
+    use crate::path_hash::FixedHasher;
+
+    let hash = |write: fn(&mut FixedHasher)| {
+        let mut hasher = FixedHasher::default();
+        write(&mut hasher);
+        hasher.finish()
+    };
+    // Test vectors of FNV-1a (64 bits).
+    assert_eq!(0xcbf2_9ce4_8422_2325, hash(|_| {}));
+    assert_eq!(0xaf63_dc4c_8601_ec8c, hash(|hasher| hasher.write(b"a")));
+    assert_eq!(
+        0x8594_4171_f739_67e8,
+        hash(|hasher| hasher.write(b"foobar"))
+    );
+    // Independent of the pointer width and the byte order of the platform.
+    assert_eq!(
+        hash(|hasher| hasher.write(&[1, 0, 0, 0, 0, 0, 0, 0])),
+        hash(|hasher| hasher.write_usize(1))
+    );
+    assert_eq!(
+        hash(|hasher| hasher.write(&[0xff; 8])),
+        hash(|hasher| hasher.write_isize(-1))
+    );
+}
+
+#[test]
+fn accept_node_value_visitor() {
+    #[derive(Default)]
+    struct Visitor {
+        paths: Vec<(usize, Option<String>)>,
+        leaf_sum: usize,
+        inner_count: usize,
+    }
+
+    impl NodeValueVisitor for Visitor {
+        fn visit_node(&mut self, node: VisitedNode<'_>) {
+            self.paths
+                .push((node.depth, node.path_segment_str().map(ToOwned::to_owned)));
+            match node.value {
+                VisitedValue::Inner(_) => {
+                    assert!(node.value.downcast_ref::<isize>().is_some());
+                    self.inner_count += 1;
+                }
+                VisitedValue::Leaf(_) => {
+                    self.leaf_sum += node.value.downcast_ref::<usize>().unwrap();
+                }
+            }
+        }
+    }
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/baz", 2), ("/qux", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let mut visitor = Visitor::default();
+    tree.accept(&mut visitor);
+    assert_eq!(2, visitor.inner_count);
+    assert_eq!(6, visitor.leaf_sum);
+    assert_eq!((0, None), visitor.paths[0]);
+    let mut paths = visitor.paths;
+    paths.sort();
+    assert_eq!(
+        vec![
+            (0, None),
+            (1, Some("foo".to_owned())),
+            (1, Some("qux".to_owned())),
+            (2, Some("bar".to_owned())),
+            (2, Some("baz".to_owned())),
+        ],
+        paths
+    );
+}
+
+#[test]
+fn visit_with_pruning() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/baz", 2), ("/qux", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let mut visited_count = 0;
+    assert!(tree.visit(&mut |_, _: &Arc<TreeNode>| {
+        visited_count += 1;
+        VisitControl::Continue
+    }));
+    assert_eq!(tree.nodes_count().get(), visited_count);
+
+    // Skip the children of all inner nodes below the root node.
+    let mut leaf_sum = 0;
+    assert!(tree.visit(&mut |depth, node: &Arc<TreeNode>| {
+        match &node.node {
+            Node::Inner(_) if depth > 0 => VisitControl::SkipSubtree,
+            Node::Inner(_) => VisitControl::Continue,
+            Node::Leaf(leaf) => {
+                leaf_sum += leaf.value;
+                VisitControl::Continue
+            }
+        }
+    }));
+    assert_eq!(3, leaf_sum);
+
+    // Stop at the first leaf node.
+    let mut visited_count = 0;
+    assert!(!tree.visit(&mut |_, node: &Arc<TreeNode>| {
+        visited_count += 1;
+        if matches!(node.node, Node::Leaf(_)) {
+            VisitControl::Stop
+        } else {
+            VisitControl::Continue
+        }
+    }));
+    assert!(visited_count < tree.nodes_count().get());
+}
+
+#[test]
+fn any_path_tree() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/baz", 2), ("/qux", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let any_tree = tree.to_any();
+    assert_eq!(tree.nodes_count(), any_tree.nodes_count());
+    let any_node = any_tree
+        .find_node(&AnyPath(vec!["foo".into(), "baz".into()]))
+        .unwrap();
+    assert_eq!(
+        Some(&2),
+        any_node
+            .node
+            .leaf_value()
+            .and_then(|value| value.downcast_ref::<usize>())
+    );
+
+    let typed_tree = any_tree
+        .to_typed::<PathTreeTypes>(Default::default())
+        .unwrap();
+    assert!(tree.has_same_structure(&typed_tree));
+    assert!(tree.diff_patch(&typed_tree).is_empty());
+
+    // Values of a different type could not be converted.
+    let mut any_tree = any_tree;
+    let any_node = Arc::clone(any_tree.find_node(&AnyPath(vec!["qux".into()])).unwrap());
+    any_tree
+        .update_node_value(
+            &any_node,
+            crate::NodeValue::Leaf(Arc::new("qux") as AnyValue),
+        )
+        .unwrap();
+    let err = any_tree
+        .to_typed::<PathTreeTypes>(Default::default())
+        .unwrap_err();
+    assert_eq!(any_node.id, err.node_id);
+}
+
+#[test]
+fn node_handle() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/foo/bar", 1), ("/foo/baz", 2)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let handle = tree
+        .find_node_handle(&SlashPath::new(Cow::Borrowed("/foo/bar")))
+        .unwrap();
+    assert_eq!(Some("bar"), handle.path_segment());
+    assert!(matches!(handle.value(), NodeValue::Leaf(1)));
+    let parent = handle.parent().unwrap();
+    assert_eq!(Some("foo"), parent.path_segment());
+    assert_eq!(2, parent.children().len());
+    assert_eq!(tree.root_node().id, parent.parent().unwrap().id());
+    assert!(tree.root_node_handle().parent().is_none());
+
+    // The handle keeps the snapshot alive after the tree has been modified.
+    let node_id = handle.id();
+    tree.remove_subtree_by_id(node_id).unwrap();
+    assert!(tree.lookup_node_handle(node_id).is_none());
+    assert!(handle.tree().lookup_node(node_id).is_some());
+    assert_eq!(node_id, handle.into_node().id);
+}
+
+#[cfg(feature = "capi")]
+#[test]
+#[allow(unsafe_code)] // FFI
+fn capi() {
+    use std::ffi::{c_char, c_void, CStr};
+
+    use crate::capi::{
+        rpds_pathtree_children, rpds_pathtree_find, rpds_pathtree_free, rpds_pathtree_insert,
+        rpds_pathtree_new, rpds_pathtree_node_value, rpds_pathtree_nodes_count,
+        rpds_pathtree_remove, rpds_pathtree_string_free, RpdsPathTreeStatus,
+    };
+
+    extern "C" fn collect_child(
+        user_data: *mut c_void,
+        _node_id: usize,
+        path_segment: *const c_char,
+    ) {
+        let segments = unsafe { &mut *user_data.cast::<Vec<String>>() };
+        segments.push(
+            unsafe { CStr::from_ptr(path_segment) }
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        );
+    }
+
+    unsafe {
+        let tree = rpds_pathtree_new(b"root\0".as_ptr().cast());
+        assert!(!tree.is_null());
+        let mut node_id = usize::MAX;
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_insert(
+                tree,
+                b"/foo/bar\0".as_ptr().cast(),
+                b"1\0".as_ptr().cast(),
+                true,
+                &mut node_id
+            )
+        );
+        assert_eq!(
+            RpdsPathTreeStatus::Conflict,
+            rpds_pathtree_insert(
+                tree,
+                b"/foo/bar/baz\0".as_ptr().cast(),
+                b"2\0".as_ptr().cast(),
+                true,
+                std::ptr::null_mut()
+            )
+        );
+        assert_eq!(3, rpds_pathtree_nodes_count(tree));
+
+        let mut found_node_id = usize::MAX;
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_find(tree, b"foo/bar\0".as_ptr().cast(), &mut found_node_id)
+        );
+        assert_eq!(node_id, found_node_id);
+
+        let mut value = std::ptr::null_mut();
+        let mut leaf = false;
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_node_value(tree, node_id, &mut value, &mut leaf)
+        );
+        assert!(leaf);
+        assert_eq!("1", CStr::from_ptr(value).to_str().unwrap());
+        rpds_pathtree_string_free(value);
+
+        let mut parent_node_id = usize::MAX;
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_find(tree, b"/foo\0".as_ptr().cast(), &mut parent_node_id)
+        );
+        let mut segments = Vec::<String>::new();
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_children(
+                tree,
+                parent_node_id,
+                Some(collect_child),
+                std::ptr::from_mut(&mut segments).cast()
+            )
+        );
+        assert_eq!(vec!["bar".to_owned()], segments);
+        assert_eq!(
+            RpdsPathTreeStatus::NullPointer,
+            rpds_pathtree_children(tree, parent_node_id, None, std::ptr::null_mut())
+        );
+
+        let mut removed_count = 0;
+        assert_eq!(
+            RpdsPathTreeStatus::Ok,
+            rpds_pathtree_remove(tree, parent_node_id, &mut removed_count)
+        );
+        assert_eq!(2, removed_count);
+        assert_eq!(
+            RpdsPathTreeStatus::NotFound,
+            rpds_pathtree_find(tree, b"/foo\0".as_ptr().cast(), std::ptr::null_mut())
+        );
+        rpds_pathtree_free(tree);
+    }
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn python_bindings() {
+    use pyo3::{prelude::*, types::PyDict};
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let module = PyModule::new(py, "rpds_pathtree").unwrap();
+        crate::python::rpds_pathtree(&module).unwrap();
+        let locals = PyDict::new(py);
+        locals.set_item("rpds_pathtree", module).unwrap();
+        let code = std::ffi::CString::new(
+            "
+tree = rpds_pathtree.PathTree('root')
+tree.insert('/foo/bar', '1')
+tree.insert('/foo/baz', '2')
+assert len(tree) == 4
+node = tree.find('foo/bar')
+assert node.value == '1' and node.is_leaf
+assert node.parent.path_segment == 'foo'
+assert sorted(child.path_segment for child in node.parent) == ['bar', 'baz']
+assert [node.path_segment for node in tree][0] is None
+try:
+    tree.insert('/foo/bar/qux', '3')
+    assert False
+except ValueError:
+    pass
+assert tree.remove('/foo') == 3
+assert tree.find('/foo') is None
+assert len(tree) == 1
+",
+        )
+        .unwrap();
+        py.run(&code, None, Some(&locals)).unwrap();
+    });
+}
+
+#[test]
+fn try_insert_or_update_node_value() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo")),
+        NodeValue::Leaf(1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+
+    // Fail after the first missing ancestor node has been created.
+    let mut next_inner_value = -1;
+    let err = tree
+        .try_insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/bar/baz/qux")),
+            NodeValue::Leaf(2),
+            &mut || {
+                if next_inner_value < -1 {
+                    return Err("no more inner values");
+                }
+                next_inner_value -= 1;
+                Ok(next_inner_value)
+            },
+            |_| unreachable!(),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        TryInsertOrUpdateNodeValueError::NewValue {
+            source: "no more inner values",
+            value: NodeValue::Leaf(2),
+        }
+    ));
+    assert_eq!(2, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/bar")))
+        .is_none());
+
+    let err = tree
+        .try_insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(3),
+            &mut || unreachable!(),
+            |_| Err("leaf could not be converted"),
+        )
+        .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Leaf(3)));
+    assert_eq!(2, tree.nodes_count().get());
+
+    let inserted = tree
+        .try_insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar")),
+            NodeValue::Leaf(3),
+            &mut || unreachable!(),
+            |leaf_value| Ok::<_, ()>(Some(-isize::try_from(*leaf_value).unwrap())),
+        )
+        .unwrap();
+    assert_eq!(2, inserted.affected_nodes);
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -1, .. })
+    ));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn insert_or_update_node_value_async() {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let mut next_inner_value = 0;
+    let inserted = block_on(tree.insert_or_update_node_value_async(
+        &SlashPath::new(Cow::Borrowed("/foo/bar/baz")),
+        NodeValue::Leaf(1),
+        || {
+            next_inner_value -= 1;
+            std::future::ready(next_inner_value)
+        },
+        |_| async { unreachable!() },
+    ))
+    .unwrap();
+    assert_eq!(4, tree.nodes_count().get());
+    assert_eq!(4, inserted.affected_nodes);
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/foo/bar")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -2, .. })
+    ));
+
+    // Replace the leaf node with an inner node.
+    block_on(tree.insert_or_update_node_value_async(
+        &SlashPath::new(Cow::Borrowed("/foo/bar/baz/qux")),
+        NodeValue::Leaf(2),
+        || async { unreachable!() },
+        |leaf_value| async move { Some(-isize::try_from(leaf_value).unwrap()) },
+    ))
+    .unwrap();
+    assert_eq!(5, tree.nodes_count().get());
+
+    // Path conflict with a leaf node that is not replaced.
+    let err = block_on(tree.insert_or_update_node_value_async(
+        &SlashPath::new(Cow::Borrowed("/foo/bar/baz/qux/x/y")),
+        NodeValue::Leaf(3),
+        || async { unreachable!() },
+        |_| async { None },
+    ))
+    .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Leaf(3)));
+    assert_eq!(5, tree.nodes_count().get());
+}
+
+#[test]
+fn deferred_inner_values() {
+    let mut tree =
+        DeferredValuesTree::new(PathTree::new(Default::default(), NodeValue::Inner(0)), -1);
+    assert!(tree.is_filled());
+
+    let inserted = tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo/bar/baz")),
+            NodeValue::Leaf(1),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(4, tree.tree().nodes_count().get());
+    assert_eq!(2, tree.unfilled_nodes_count());
+    assert!(!tree.is_unfilled(inserted.node.id));
+    assert!(tree
+        .unfilled_nodes()
+        .all(|node| matches!(node.node, Node::Inner(InnerNode { value: -1, .. }))));
+
+    let bar_node_id = tree
+        .tree()
+        .find_node(&SlashPath::new(Cow::Borrowed("/foo/bar")))
+        .unwrap()
+        .id;
+    assert!(tree.is_unfilled(bar_node_id));
+    let updated_node = tree.fill_inner_value(bar_node_id, 2).unwrap();
+    assert!(matches!(
+        updated_node.node,
+        Node::Inner(InnerNode { value: 2, .. })
+    ));
+    assert_eq!(1, tree.unfilled_nodes_count());
+
+    // Filling the same node again fails and returns the value.
+    let err = tree.fill_inner_value(bar_node_id, 3).unwrap_err();
+    assert_eq!(bar_node_id, err.node_id);
+    assert_eq!(3, err.value);
+
+    // Updating a placeholder with a new value fills it.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo")),
+        NodeValue::Inner(4),
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.is_filled());
+
+    // Removed placeholders are no longer unfilled.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/qux/quux")),
+        NodeValue::Leaf(5),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(1, tree.unfilled_nodes_count());
+    let qux_node_id = tree.unfilled_nodes().next().unwrap().id;
+    tree.remove_subtree_by_id(qux_node_id).unwrap();
+    assert!(tree.is_filled());
+
+    // Placeholders are also removed together with their ancestors.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo/bar/qux/quux")),
+        NodeValue::Leaf(6),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(1, tree.unfilled_nodes_count());
+    tree.remove_subtree_by_id(bar_node_id).unwrap();
+    assert!(tree.is_filled());
+    assert_eq!(2, tree.into_tree().nodes_count().get());
+}
+
+#[test]
+fn root_value() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(matches!(tree.root_value(), NodeValue::Inner(0)));
+
+    tree.set_root_value(NodeValue::Inner(1)).unwrap();
+    assert!(matches!(tree.root_value(), NodeValue::Inner(1)));
+
+    let updated_root_node = tree
+        .update_root_value_with(|value| match value {
+            NodeValue::Inner(value) => NodeValue::Inner(value + 1),
+            NodeValue::Leaf(_) => unreachable!(),
+        })
+        .unwrap();
+    assert_eq!(tree.root_node().id, updated_root_node.id);
+    assert!(matches!(tree.root_value(), NodeValue::Inner(2)));
+
+    // Inner root nodes with children cannot be replaced by leaf values.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo")),
+        NodeValue::Leaf(3),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    let err = tree.set_root_value(NodeValue::Leaf(4)).unwrap_err();
+    assert!(matches!(
+        err,
+        UpdateNodeValueError::ValueTypeMismatch {
+            value: NodeValue::Leaf(4)
+        }
+    ));
+    assert!(matches!(tree.root_value(), NodeValue::Inner(2)));
+}
+
+#[test]
+fn write_amplification() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b/c/d/e")),
+        NodeValue::Leaf(1),
+        &mut || 0,
+        |_| None,
+    )
+    .unwrap();
+    let snapshot = tree.clone();
+    assert_eq!(0, tree.rewritten_nodes_count(&snapshot));
+
+    // Updating a deeply nested node only rewrites the node itself.
+    let inserted = tree.assert_write_amplification(1, |tree| {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c/d/e")),
+            NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap()
+    });
+    assert_eq!(
+        inserted.affected_nodes,
+        tree.rewritten_nodes_count(&snapshot)
+    );
+
+    // Inserting a sibling rewrites the new node and its parent node.
+    tree.assert_write_amplification(2, |tree| {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c/d/f")),
+            NodeValue::Leaf(3),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    });
+
+    // Removing a subtree rewrites all removed nodes and the parent node.
+    let removed = tree.assert_write_amplification(4, |tree| {
+        let node_id = tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/b/c/d")))
+            .unwrap()
+            .id;
+        tree.remove_subtree_by_id(node_id).unwrap()
+    });
+    assert_eq!(4, removed.affected_nodes);
+
+    // Restructuring only rewrites the affected nodes and their parent nodes,
+    // but neither their descendants nor their ancestors.
+    for path in ["/a/b/c/d", "/f/g"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(4),
+            &mut || 0,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let c_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b/c")))
+        .unwrap()
+        .id;
+    let f_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/f")))
+        .unwrap()
+        .id;
+    let g_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/f/g")))
+        .unwrap()
+        .id;
+    tree.assert_write_amplification(3, |tree| {
+        tree.move_subtree(c_node_id, f_node_id, "c").unwrap();
+    });
+    tree.assert_write_amplification(2, |tree| {
+        tree.rename_child_segment(f_node_id, "c", "h").unwrap();
+    });
+    tree.assert_write_amplification(3, |tree| {
+        tree.swap_subtrees(c_node_id, g_node_id).unwrap();
+    });
+    tree.assert_write_amplification(4, |tree| {
+        tree.splice_node(f_node_id).unwrap();
+    });
+}
+
+#[test]
+#[should_panic(expected = "rewritten 2 nodes instead of at most 1")]
+fn write_amplification_exceeded() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.assert_write_amplification(1, |tree| {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a")),
+            NodeValue::Leaf(1),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    });
+}
+
+#[test]
+fn clone_values_from() {
+    let mut other = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/src/old/a", 1), ("/src/old/b/c", 2), ("/doc/d", 3)] {
+        other
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+    }
+
+    // Re-root the subtree at /src/old below /new/root.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(10));
+    let cloned_count = tree
+        .clone_values_from(
+            &other,
+            |path| {
+                let relative_path =
+                    path.strip_prefix(&[Cow::Borrowed("src"), Cow::Borrowed("old")])?;
+                let mut new_path = vec![Cow::Borrowed("new"), Cow::Borrowed("root")];
+                new_path.extend(relative_path.iter().cloned());
+                Some(new_path)
+            },
+            &mut || 20,
+        )
+        .unwrap();
+    assert_eq!(4, cloned_count);
+    assert_eq!(6, tree.nodes_count().get());
+    assert!(matches!(tree.root_value(), NodeValue::Inner(10)));
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/new")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: 20, .. })
+    ));
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/new/root")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -1, .. })
+    ));
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/new/root/b/c")))
+            .unwrap()
+            .node,
+        Node::Leaf(LeafNode { value: 2 })
+    ));
+
+    // Leaf nodes could not become parent nodes. Conflicting paths leave
+    // the tree unchanged.
+    let tree_before = tree.clone();
+    let err = tree
+        .clone_values_from(
+            &other,
+            |path| {
+                let mut new_path = vec![
+                    Cow::Borrowed("new"),
+                    Cow::Borrowed("root"),
+                    Cow::Borrowed("a"),
+                    Cow::Borrowed("x"),
+                ];
+                new_path.extend(path.iter().cloned());
+                Some(new_path)
+            },
+            &mut || 30,
+        )
+        .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Inner(0)));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn rewrite_prefix() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/src/old/a", 1), ("/src/old/b/c", 2), ("/src/x", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let nodes_count = tree.nodes_count().get();
+    let old_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/old")))
+        .unwrap()
+        .id;
+    let c_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/old/b/c")))
+        .unwrap()
+        .id;
+
+    let moved_node_ids = tree
+        .rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src/old")),
+            &SlashPath::new(Cow::Borrowed("/src/new/dir")),
+            &mut || -2,
+        )
+        .unwrap();
+    assert_eq!(4, moved_node_ids.len());
+    assert_eq!(old_node_id, moved_node_ids[0]);
+    assert!(moved_node_ids.contains(&c_node_id));
+    // One ancestor node has been created.
+    assert_eq!(nodes_count + 1, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/old")))
+        .is_none());
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/src/new")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -2, .. })
+    ));
+    assert_eq!(
+        old_node_id,
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/src/new/dir")))
+            .unwrap()
+            .id
+    );
+    let c_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/new/dir/b/c")))
+        .unwrap();
+    assert_eq!(c_node_id, c_node.id);
+    assert!(matches!(c_node.node, Node::Leaf(LeafNode { value: 2 })));
+
+    // Renaming within the same parent node.
+    let moved_node_ids = tree
+        .rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src/x")),
+            &SlashPath::new(Cow::Borrowed("/src/y")),
+            &mut || unreachable!(),
+        )
+        .unwrap();
+    assert_eq!(1, moved_node_ids.len());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/y")))
+        .is_some());
+
+    let tree_before = tree.clone();
+    assert!(matches!(
+        tree.rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src/old")),
+            &SlashPath::new(Cow::Borrowed("/old")),
+            &mut || unreachable!(),
+        ),
+        Err(RewritePrefixError::NotFound)
+    ));
+    assert!(matches!(
+        tree.rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src")),
+            &SlashPath::new(Cow::Borrowed("/src/new/src")),
+            &mut || unreachable!(),
+        ),
+        Err(RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src/y")),
+            &SlashPath::new(Cow::Borrowed("/src/new")),
+            &mut || unreachable!(),
+        ),
+        Err(RewritePrefixError::Occupied { node_id }) if node_id != old_node_id
+    ));
+    // Leaf nodes could not become parent nodes.
+    assert!(matches!(
+        tree.rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/src/new")),
+            &SlashPath::new(Cow::Borrowed("/src/y/z")),
+            &mut || -3,
+        ),
+        Err(RewritePrefixError::PathConflict { .. })
+    ));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+}
+
+#[test]
+fn move_subtree() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/src/a/b", "/src/a/c", "/dst/d", "/frozen/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let find_node_id = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .map(|node| node.id)
+    };
+    let nodes_count = tree.nodes_count().get();
+    let a_node_id = find_node_id(&tree, "/src/a").unwrap();
+    let b_node_id = find_node_id(&tree, "/src/a/b").unwrap();
+    let dst_node_id = find_node_id(&tree, "/dst").unwrap();
+    let d_node_id = find_node_id(&tree, "/dst/d").unwrap();
+    let frozen_node_id = find_node_id(&tree, "/frozen").unwrap();
+
+    let moved_node_ids = tree
+        .move_subtree(a_node_id, dst_node_id, &Cow::Borrowed("moved"))
+        .unwrap();
+    assert_eq!(3, moved_node_ids.len());
+    assert_eq!(a_node_id, moved_node_ids[0]);
+    assert_eq!(nodes_count, tree.nodes_count().get());
+    assert!(find_node_id(&tree, "/src/a").is_none());
+    assert_eq!(Some(a_node_id), find_node_id(&tree, "/dst/moved"));
+    assert_eq!(Some(b_node_id), find_node_id(&tree, "/dst/moved/b"));
+
+    let tree_before = tree.clone();
+    assert!(matches!(
+        tree.move_subtree(tree.root_node_id(), dst_node_id, &Cow::Borrowed("root")),
+        Err(RewritePrefixError::RootNode)
+    ));
+    assert!(matches!(
+        tree.move_subtree(usize::MAX, dst_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::NotFound)
+    ));
+    assert!(matches!(
+        tree.move_subtree(dst_node_id, b_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.move_subtree(dst_node_id, dst_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.move_subtree(a_node_id, d_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::PathConflict { .. })
+    ));
+    assert!(matches!(
+        tree.move_subtree(a_node_id, dst_node_id, &Cow::Borrowed("d")),
+        Err(RewritePrefixError::Occupied { node_id }) if node_id == d_node_id
+    ));
+    tree.freeze_subtree(frozen_node_id);
+    assert!(matches!(
+        tree.move_subtree(a_node_id, frozen_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::SubtreeFrozen { node_id }) if node_id == frozen_node_id
+    ));
+    assert!(matches!(
+        tree.move_subtree(frozen_node_id, dst_node_id, &Cow::Borrowed("x")),
+        Err(RewritePrefixError::SubtreeFrozen { node_id }) if node_id == frozen_node_id
+    ));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+}
+
+#[test]
+fn with_rollback() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let tree_before = tree.clone();
+    let err = tree
+        .with_rollback(|tree| {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo")),
+                NodeValue::Leaf(1),
+                &mut || unreachable!(),
+                |_| None,
+            )?;
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo/bar")),
+                NodeValue::Leaf(2),
+                &mut || unreachable!(),
+                |_| None,
+            )
+        })
+        .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Leaf(2)));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    let affected_nodes = tree
+        .with_rollback(|tree| {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo")),
+                NodeValue::Leaf(1),
+                &mut || unreachable!(),
+                |_| None,
+            )
+            .map(|inserted| inserted.affected_nodes)
+        })
+        .unwrap();
+    assert_eq!(2, affected_nodes);
+    assert_eq!(2, tree.nodes_count().get());
+}
+
+#[test]
+fn insert_or_replace_subtree_atomically() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/foo/bar/baz")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let tree_before = tree.clone();
+
+    // The inner node "/foo/bar" with children could not be replaced by a leaf node.
+    let mut subtree = PathTree::new(Default::default(), NodeValue::Inner(10));
+    subtree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/bar")),
+            NodeValue::Leaf(11),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    let root_node = Arc::clone(tree.root_node());
+    let err = tree
+        .insert_or_replace_subtree(&root_node, "foo", None, subtree)
+        .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Leaf(11)));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    // Updating the value of "/foo" in place does not modify the root node.
+    let mut subtree = PathTree::new(Default::default(), NodeValue::Inner(10));
+    subtree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/qux")),
+            NodeValue::Leaf(12),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    let SubtreeInsertedOrReplaced {
+        child_node_id,
+        parent,
+        ..
+    } = tree
+        .insert_or_replace_subtree(&root_node, "foo", None, subtree)
+        .unwrap();
+    assert!(Arc::ptr_eq(&root_node, &parent.node));
+    assert!(parent.removed_subtree.is_none());
+    assert!(matches!(
+        tree.lookup_node(child_node_id).unwrap().node,
+        Node::Inner(InnerNode { value: 10, .. })
+    ));
+    assert_eq!(5, tree.nodes_count().get());
+}
+
+#[test]
+fn subtree_weight_bytes() {
+    #[derive(Debug, Clone, Default)]
+    struct WeightedPathTreeTypes;
+
+    impl crate::PathTreeTypes for WeightedPathTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = String;
+        type LeafValue = Vec<u8>;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+
+        fn inner_value_weight(value: &Self::InnerValue) -> usize {
+            value.len()
+        }
+
+        fn leaf_value_weight(value: &Self::LeafValue) -> usize {
+            value.len()
+        }
+    }
+
+    let mut tree = crate::PathTree::<WeightedPathTreeTypes>::new(
+        Default::default(),
+        crate::NodeValue::Inner("root".to_owned()),
+    );
+    for (path, size) in [("/a/b", 10), ("/a/c", 20), ("/d", 30)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            crate::NodeValue::Leaf(vec![0; size]),
+            &mut || "dir".to_owned(),
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert_eq!(Some(67), tree.subtree_weight_bytes(tree.root_node_id()));
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    assert_eq!(Some(33), tree.subtree_weight_bytes(a_node_id));
+
+    let assert_index_synced =
+        |index: &crate::SubtreeWeightIndex<WeightedPathTreeTypes>,
+         tree: &crate::PathTree<WeightedPathTreeTypes>| {
+            for node in tree.nodes() {
+                assert_eq!(
+                    tree.subtree_weight_bytes(node.id),
+                    index.subtree_weight_bytes(node.id)
+                );
+            }
+        };
+    let mut index = crate::SubtreeWeightIndex::new(&tree);
+    assert_index_synced(&index, &tree);
+    assert_eq!(Some(67), index.subtree_weight_bytes(tree.root_node_id()));
+
+    // Insert new nodes and update existing values.
+    for (path, size) in [("/a/b", 5), ("/a/e/f", 40)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            crate::NodeValue::Leaf(vec![0; size]),
+            &mut || "dir".to_owned(),
+            |_| None,
+        )
+        .unwrap();
+    }
+    index.sync(&tree);
+    assert_index_synced(&index, &tree);
+    assert_eq!(Some(105), index.subtree_weight_bytes(tree.root_node_id()));
+
+    // Move a subtree.
+    let e_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/e")))
+        .unwrap()
+        .id;
+    tree.move_subtree(e_node_id, tree.root_node_id(), "g")
+        .unwrap();
+    index.sync(&tree);
+    assert_index_synced(&index, &tree);
+    assert_eq!(Some(28), index.subtree_weight_bytes(a_node_id));
+    assert_eq!(Some(43), index.subtree_weight_bytes(e_node_id));
+
+    // Remove a subtree.
+    tree.remove_subtree_by_id(a_node_id).unwrap();
+    index.sync(&tree);
+    assert_index_synced(&index, &tree);
+    assert_eq!(None, index.subtree_weight_bytes(a_node_id));
+    assert_eq!(Some(77), index.subtree_weight_bytes(tree.root_node_id()));
+
+    // Values are ignored by default.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a")),
+        NodeValue::Leaf(1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(Some(0), tree.subtree_weight_bytes(tree.root_node_id()));
+    assert_eq!(None, tree.subtree_weight_bytes(usize::MAX));
+}
+
+#[test]
+fn intern_leaf_values() {
+    static INTERNER: crate::ValueInterner<String> = crate::ValueInterner::new();
+
+    #[derive(Debug, Clone, Default)]
+    struct InterningPathTreeTypes;
+
+    impl crate::PathTreeTypes for InterningPathTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = ();
+        type LeafValue = Arc<String>;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+
+        fn intern_leaf_value(value: Self::LeafValue) -> Self::LeafValue {
+            INTERNER.intern_arc(value)
+        }
+    }
+
+    let mut tree = crate::PathTree::<InterningPathTreeTypes>::new(
+        Default::default(),
+        crate::NodeValue::Inner(()),
+    );
+    for (path, value) in [("/a", "x"), ("/b", "y"), ("/c/d", "x")] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            crate::NodeValue::Leaf(Arc::new(value.to_owned())),
+            &mut || (),
+            |_| None,
+        )
+        .unwrap();
+    }
+    let leaf_value = |path| {
+        Arc::clone(
+            tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+                .unwrap()
+                .node
+                .leaf_value()
+                .unwrap(),
+        )
+    };
+    assert!(Arc::ptr_eq(&leaf_value("/a"), &leaf_value("/c/d")));
+    assert!(!Arc::ptr_eq(&leaf_value("/a"), &leaf_value("/b")));
+    let stats = INTERNER.stats();
+    assert_eq!(2, stats.distinct_count);
+    assert_eq!(3, stats.interned_count);
+    assert_eq!(1, stats.shared_count);
+    assert_eq!(std::mem::size_of::<String>(), stats.saved_bytes::<String>());
+
+    // Values that are no longer used by any tree could be purged.
+    assert_eq!(0, INTERNER.purge_unused());
+    drop(tree);
+    assert_eq!(2, INTERNER.purge_unused());
+    assert_eq!(0, INTERNER.stats().distinct_count);
+}
+
+#[test]
+fn append_cursor() {
+    let tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let root_node = Arc::clone(tree.root_node());
+    let snapshot = tree.clone();
+    let mut cursor = tree.into_append_cursor(&root_node).unwrap();
+    assert_eq!(root_node.id, cursor.parent_node_id());
+    assert!(!cursor.ascend());
+    let a_node_id = cursor.append_inner("a", 1).unwrap();
+    assert_eq!(a_node_id, cursor.parent_node_id());
+    assert_eq!(1, cursor.depth());
+    cursor.append_leaf("b", 2).unwrap();
+    cursor.append_inner("c", 3).unwrap();
+    cursor.append_leaf("d", 4).unwrap();
+    let err = cursor.append_leaf("d", 5).unwrap_err();
+    assert!(matches!(err.value, NodeValue::Leaf(5)));
+    assert!(cursor.ascend());
+    assert!(cursor.ascend());
+    assert_eq!(0, cursor.depth());
+    let err = cursor.append_inner("a", 6).unwrap_err();
+    assert_eq!(a_node_id, err.node_id);
+    cursor.append_leaf("e", 7).unwrap();
+    let (tree, root_node) = cursor.finish();
+    assert_eq!(2, root_node.node.children_count());
+    tree.validate().unwrap();
+    assert_eq!(6, tree.nodes_count().get());
+    // Each node has been written only once.
+    assert_eq!(6, tree.rewritten_nodes_count(&snapshot));
+    for (path, value) in [("/a/b", 2), ("/a/c/d", 4), ("/e", 7)] {
+        let node = tree
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap();
+        assert_eq!(Some(&value), node.node.leaf_value());
+    }
+
+    // Appending below existing nodes of a clone.
+    let a_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+            .unwrap(),
+    );
+    let mut cursor = tree.clone().into_append_cursor(&a_node).unwrap();
+    assert!(cursor.append_inner("c", 8).is_err());
+    cursor.append_inner("f", 9).unwrap();
+    cursor.append_leaf("g", 10).unwrap();
+    let (appended_tree, _) = cursor.finish();
+    appended_tree.validate().unwrap();
+    assert!(appended_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/f/g")))
+        .is_some());
+    // The original tree remains unchanged.
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/f")))
+        .is_none());
+
+    // The tree is returned back on error.
+    let leaf_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/e")))
+            .unwrap(),
+    );
+    let tree = tree.into_append_cursor(&leaf_node).unwrap_err();
+    assert_eq!(6, tree.nodes_count().get());
+}
+
+#[test]
+fn to_rows_from_rows() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/a/c/d", 2), ("/e", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let rows = tree
+        .to_rows(crate::ExportOrder::sorted())
+        .collect::<Vec<_>>();
+    assert_eq!(tree.nodes_count().get(), rows.len());
+    assert_eq!(tree.root_node_id(), rows[0].node_id);
+    assert!(rows[0].parent.is_none());
+    // Parent rows precede their children.
+    for (index, row) in rows.iter().enumerate().skip(1) {
+        let parent = row.parent.as_ref().unwrap();
+        assert!(rows[..index]
+            .iter()
+            .any(|parent_row| parent_row.node_id == parent.node_id));
+    }
+
+    let imported = PathTree::from_rows(Default::default(), rows.clone()).unwrap();
+    imported.validate().unwrap();
+    assert_eq!(tree.nodes_count(), imported.nodes_count());
+    for (path, value) in [("/a/b", 1), ("/a/c/d", 2), ("/e", 3)] {
+        let node = imported
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap();
+        assert_eq!(Some(&value), node.node.leaf_value());
+    }
+    assert_eq!(
+        Some(&-1),
+        imported
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/c")))
+            .unwrap()
+            .node
+            .inner_value()
+    );
+
+    assert!(matches!(
+        PathTree::from_rows(Default::default(), std::iter::empty()),
+        Err(FromRowsError::RootRowMissing)
+    ));
+    // Children before parents
+    assert!(matches!(
+        PathTree::from_rows(Default::default(), rows.iter().rev().cloned()),
+        Err(FromRowsError::ParentRowNotFound { .. })
+    ));
+    assert!(matches!(
+        PathTree::from_rows(
+            Default::default(),
+            rows.iter().cloned().chain(std::iter::once(rows[0].clone()))
+        ),
+        Err(FromRowsError::RootRowDuplicate { .. })
+    ));
+    let last_row = rows.last().unwrap().clone();
+    assert!(matches!(
+        PathTree::from_rows(
+            Default::default(),
+            rows.iter()
+                .cloned()
+                .chain(std::iter::once(last_row.clone()))
+        ),
+        Err(FromRowsError::NodeIdDuplicate { .. })
+    ));
+    let mut sibling_row = last_row.clone();
+    sibling_row.node_id = usize::MAX;
+    assert!(matches!(
+        PathTree::from_rows(
+            Default::default(),
+            rows.iter().cloned().chain(std::iter::once(sibling_row))
+        ),
+        Err(FromRowsError::PathSegmentDuplicate { .. })
+    ));
+    let leaf_row = rows
+        .iter()
+        .find(|row| matches!(row.value, NodeValue::Leaf(_)))
+        .unwrap();
+    let child_row = TreeRow {
+        node_id: usize::MAX,
+        parent: Some(crate::HalfEdgeOwned {
+            path_segment: Cow::Borrowed("x"),
+            node_id: leaf_row.node_id,
+        }),
+        value: NodeValue::Leaf(4),
+    };
+    assert!(matches!(
+        PathTree::from_rows(
+            Default::default(),
+            rows.iter().cloned().chain(std::iter::once(child_row))
+        ),
+        Err(FromRowsError::ParentRowLeaf { .. })
+    ));
+}
+
+#[test]
+fn to_closure_table() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let node_id = |path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap()
+            .id
+    };
+    let rows = tree
+        .to_closure_table(crate::ExportOrder::sorted())
+        .collect::<Vec<_>>();
+    // 6 nodes at depths 0 + 1 + 2 + 2 + 3 + 1
+    assert_eq!(6 + 9, rows.len());
+    assert_eq!(
+        tree.nodes_count().get(),
+        rows.iter().filter(|row| row.depth == 0).count()
+    );
+    let d_rows = rows
+        .iter()
+        .filter(|row| row.descendant_node_id == node_id("/a/c/d"))
+        .map(|row| (row.ancestor_node_id, row.depth))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (node_id("/a/c/d"), 0),
+            (node_id("/a/c"), 1),
+            (node_id("/a"), 2),
+            (tree.root_node_id(), 3),
+        ],
+        d_rows
+    );
+}
+
+#[test]
+fn materialized_paths() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/a/c/d", 2), ("/e", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    // Path segments that contain the separator or the escape character.
+    let root_node = Arc::clone(tree.root_node());
+    tree.insert_or_update_child_node_value(&root_node, "f.g\\h", None, NodeValue::Leaf(4))
+        .unwrap();
+
+    let mut entries = tree
+        .to_materialized_paths('.', crate::ExportOrder::sorted())
+        .collect::<Vec<_>>();
+    assert_eq!(tree.nodes_count().get(), entries.len());
+    assert_eq!("", entries[0].0);
+    assert!(entries.iter().any(|(path, _)| path == ".a.c.d"));
+    assert!(entries.iter().any(|(path, _)| path == ".f\\.g\\\\h"));
+
+    // The order of entries does not matter.
+    entries.reverse();
+    let imported =
+        PathTree::from_materialized_paths(Default::default(), '.', entries, &mut || -2).unwrap();
+    imported.validate().unwrap();
+    assert_eq!(tree.nodes_count(), imported.nodes_count());
+    assert_eq!(Some(&0), imported.root_node().node.inner_value());
+    for (path, value) in [("/a/b", 1), ("/a/c/d", 2), ("/e", 3)] {
+        let node = imported
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap();
+        assert_eq!(Some(&value), node.node.leaf_value());
+    }
+    assert_eq!(
+        Some(&-1),
+        imported
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/c")))
+            .unwrap()
+            .node
+            .inner_value()
+    );
+    let child_node_id = imported.root_node().node.find_child("f.g\\h").unwrap();
+    assert_eq!(
+        Some(&4),
+        imported
+            .lookup_node(child_node_id)
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+
+    for invalid_path in ["a", ".", ".a.", ".a..b", ".a\\", ".a\\b"] {
+        assert!(matches!(
+            PathTree::from_materialized_paths(
+                Default::default(),
+                '.',
+                [(invalid_path.to_owned(), NodeValue::Leaf(5))],
+                &mut || 0,
+            ),
+            Err(crate::FromMaterializedPathsError::InvalidPath {
+                value: NodeValue::Leaf(5),
+                ..
+            })
+        ));
+    }
+    assert!(matches!(
+        PathTree::from_materialized_paths(
+            Default::default(),
+            '.',
+            [
+                (".a".to_owned(), NodeValue::Leaf(5)),
+                (".a.b".to_owned(), NodeValue::Leaf(6))
+            ],
+            &mut || 0,
+        ),
+        Err(crate::FromMaterializedPathsError::InsertOrUpdate { .. })
+    ));
+}
+
+#[test]
+fn path_hash_index() {
+    fn path_hash(path: &str) -> crate::PathHash {
+        crate::PathHash::of_path_segments(
+            SlashPath::new(Cow::Borrowed(path))
+                .segments()
+                .map(str::as_bytes),
+        )
+    }
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let mut index = crate::PathHashIndex::new(&tree);
+    assert_eq!(crate::PathHash::ROOT, path_hash("/"));
+    // Stable across platforms and versions.
+    assert_eq!(crate::PathHash(0x529a_4ddc_8ff5_6bbf), path_hash("/a"));
+    assert_eq!(
+        tree.root_node_id(),
+        tree.find_node_by_path_hash(&index, path_hash("/"))
+            .unwrap()
+            .id
+    );
+    let c_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b/c")))
+        .unwrap()
+        .id;
+    assert_eq!(Some(path_hash("/a/b/c")), index.path_hash(c_node_id));
+    assert_eq!(Some(c_node_id), index.find_node_id(path_hash("/a/b/c")));
+    assert_ne!(path_hash("/a/bc"), path_hash("/a/b/c"));
+
+    // Rename an inner node, remove and insert nodes.
+    let root_node = Arc::clone(tree.root_node());
+    tree.insert_or_update_child_node_value(&root_node, "x", Some("a"), NodeValue::Inner(-2))
+        .unwrap();
+    let d_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/d")))
+        .unwrap()
+        .id;
+    tree.remove_subtree_by_id(d_node_id);
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/e/f")),
+        NodeValue::Leaf(2),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    index.sync(&tree);
+    assert_eq!(None, index.find_node_id(path_hash("/a/b/c")));
+    assert_eq!(None, index.find_node_id(path_hash("/d")));
+    assert_eq!(None, index.path_hash(d_node_id));
+    assert_eq!(Some(c_node_id), index.find_node_id(path_hash("/x/b/c")));
+    for path in ["/x", "/x/b", "/x/b/c", "/e", "/e/f"] {
+        let node = tree
+            .find_node_by_path_hash(&index, path_hash(path))
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            node,
+            tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+                .unwrap()
+        ));
+    }
+}
+
+#[test]
+fn search_nodes_with_limits() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/a/c", 2), ("/d", 3), ("/e", 4)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let is_leaf = |node: &crate::TreeNode<_>| matches!(node.node, Node::Leaf(_));
+
+    let results = tree.search_nodes(tree.root_node(), &Default::default(), is_leaf);
+    assert!(results.is_complete());
+    assert_eq!(4, results.nodes.len());
+    assert_eq!(6, results.visited_nodes);
+
+    let results = tree.search_nodes(
+        tree.root_node(),
+        &crate::SearchLimits {
+            max_results: Some(2),
+            ..Default::default()
+        },
+        is_leaf,
+    );
+    assert_eq!(Some(crate::SearchTruncated::MaxResults), results.truncated);
+    assert_eq!(2, results.nodes.len());
+
+    let results = tree.search_nodes(
+        tree.root_node(),
+        &crate::SearchLimits {
+            max_visited_nodes: Some(3),
+            ..Default::default()
+        },
+        is_leaf,
+    );
+    assert_eq!(
+        Some(crate::SearchTruncated::MaxVisitedNodes),
+        results.truncated
+    );
+    assert_eq!(3, results.visited_nodes);
+
+    let results = tree.search_nodes(
+        tree.root_node(),
+        &crate::SearchLimits {
+            time_budget: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        },
+        is_leaf,
+    );
+    assert_eq!(Some(crate::SearchTruncated::TimeBudget), results.truncated);
+    assert!(results.nodes.is_empty());
+
+    // Limits that are not exceeded
+    let results = tree.search_nodes(
+        tree.root_node(),
+        &crate::SearchLimits {
+            max_results: Some(4),
+            max_visited_nodes: Some(6),
+            time_budget: Some(std::time::Duration::from_secs(3600)),
+        },
+        is_leaf,
+    );
+    assert!(results.is_complete());
+    assert_eq!(4, results.nodes.len());
+
+    let results = tree.search_nodes(
+        tree.root_node(),
+        &crate::SearchLimits {
+            time_budget: Some(std::time::Duration::MAX),
+            ..Default::default()
+        },
+        is_leaf,
+    );
+    assert!(results.is_complete());
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn reconcile_subtree() {
+    fn relative_path(path: &str) -> Vec<Cow<'static, str>> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| Cow::Owned(segment.to_owned()))
+            .collect()
+    }
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [
+        ("/dir/a", 1),
+        ("/dir/b", 2),
+        ("/dir/sub/c", 3),
+        ("/dir/old/d", 4),
+        ("/other", 5),
+    ] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let node_id = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .map(|node| node.id)
+    };
+    let dir_node_id = node_id(&tree, "/dir").unwrap();
+    let a_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/dir/a")))
+            .unwrap(),
+    );
+    let b_node_id = node_id(&tree, "/dir/b").unwrap();
+    let old_node_id = node_id(&tree, "/dir/old").unwrap();
+
+    let desired = [
+        ("a", NodeValue::Leaf(1)),
+        ("b", NodeValue::Leaf(20)),
+        ("sub/c", NodeValue::Leaf(3)),
+        ("new/e", NodeValue::Leaf(6)),
+    ]
+    .into_iter()
+    .map(|(path, value)| (relative_path(path), value));
+    let diff = tree
+        .reconcile_subtree(dir_node_id, desired, &Default::default(), &mut || -2)
+        .unwrap();
+    tree.validate().unwrap();
+    // Unchanged nodes are not rewritten, independent of the value
+    // equality hooks of the types.
+    assert!(Arc::ptr_eq(
+        &a_node,
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/dir/a")))
+            .unwrap()
+    ));
+    assert_eq!(vec![b_node_id], diff.updated);
+    assert_eq!(Some(b_node_id), node_id(&tree, "/dir/b"));
+    assert_eq!(2, diff.removed.len());
+    assert!(diff.removed.contains(&old_node_id));
+    assert_eq!(None, node_id(&tree, "/dir/old"));
+    let mut inserted = diff.inserted.clone();
+    inserted.sort_unstable();
+    let mut expected_inserted = vec![
+        node_id(&tree, "/dir/new").unwrap(),
+        node_id(&tree, "/dir/new/e").unwrap(),
+    ];
+    expected_inserted.sort_unstable();
+    assert_eq!(expected_inserted, inserted);
+    assert!(node_id(&tree, "/other").is_some());
+
+    // Reconciling again is a no-op.
+    let snapshot = tree.clone();
+    let desired = [
+        ("", NodeValue::Inner(-1)),
+        ("a", NodeValue::Leaf(1)),
+        ("b", NodeValue::Leaf(20)),
+        ("sub/c", NodeValue::Leaf(3)),
+        ("new/e", NodeValue::Leaf(6)),
+    ]
+    .into_iter()
+    .map(|(path, value)| (relative_path(path), value));
+    assert!(tree
+        .reconcile_subtree(dir_node_id, desired, &Default::default(), &mut || -2)
+        .unwrap()
+        .is_empty());
+    assert_eq!(0, tree.rewritten_nodes_count(&snapshot));
+
+    // Keep missing nodes.
+    let diff = tree
+        .reconcile_subtree(
+            dir_node_id,
+            [(relative_path("f"), NodeValue::Leaf(7))],
+            &crate::ReconcileOptions { keep_missing: true },
+            &mut || -2,
+        )
+        .unwrap();
+    assert_eq!(1, diff.inserted.len());
+    assert!(diff.removed.is_empty());
+    assert!(node_id(&tree, "/dir/a").is_some());
+
+    // Conflicts are rolled back.
+    let snapshot = tree.clone();
+    assert!(matches!(
+        tree.reconcile_subtree(
+            dir_node_id,
+            [
+                (relative_path("x"), NodeValue::Leaf(8)),
+                (relative_path("a/y"), NodeValue::Leaf(9)),
+            ],
+            &Default::default(),
+            &mut || -2,
+        ),
+        Err(crate::ReconcileSubtreeError::InsertOrUpdate(
+            crate::InsertOrUpdateNodeValueError::PathConflict { .. }
+        ))
+    ));
+    assert_eq!(0, tree.rewritten_nodes_count(&snapshot));
+    assert!(matches!(
+        tree.reconcile_subtree(usize::MAX, [], &Default::default(), &mut || -2),
+        Err(crate::ReconcileSubtreeError::NotFound)
+    ));
+
+    // An empty listing removes all children.
+    tree.reconcile_subtree(dir_node_id, [], &Default::default(), &mut || -2)
+        .unwrap();
+    assert_eq!(
+        0,
+        tree.lookup_node(dir_node_id).unwrap().node.children_count()
+    );
+}
+
+#[test]
+fn update_node_value_with_equal_value() {
+    #[derive(Debug, Clone, Default)]
+    struct EqualValuesPathTreeTypes;
+
+    impl crate::PathTreeTypes for EqualValuesPathTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = isize;
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+
+        fn inner_values_equal(old_value: &Self::InnerValue, new_value: &Self::InnerValue) -> bool {
+            old_value == new_value
+        }
+
+        fn leaf_values_equal(old_value: &Self::LeafValue, new_value: &Self::LeafValue) -> bool {
+            old_value == new_value
+        }
+    }
+
+    let mut tree = crate::PathTree::<EqualValuesPathTreeTypes>::new(
+        Default::default(),
+        crate::NodeValue::Inner(0),
+    );
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        crate::NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let snapshot = tree.clone();
+    for (path, value) in [
+        ("/a", crate::NodeValue::Inner(-1)),
+        ("/a/b", crate::NodeValue::Leaf(1)),
+    ] {
+        let node = Arc::clone(
+            tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+                .unwrap(),
+        );
+        let updated_node = tree.update_node_value(&node, value).unwrap();
+        assert!(Arc::ptr_eq(&node, &updated_node));
+    }
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        crate::NodeValue::Leaf(1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(snapshot.diff(&tree).is_empty());
+
+    // Values are always updated by default.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let root_node = Arc::clone(tree.root_node());
+    let updated_root_node = tree
+        .update_node_value(&root_node, NodeValue::Inner(0))
+        .unwrap();
+    assert!(!Arc::ptr_eq(&root_node, &updated_root_node));
+}
+
+#[test]
+fn depth_first_traversal() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    // Serialize the tree into a nested format.
+    let mut depth = 0;
+    let mut max_depth = 0;
+    let mut lines = Vec::new();
+    for event in tree.depth_first_traversal(tree.root_node()) {
+        match event {
+            TraversalEvent::Enter(node) => {
+                let name = node
+                    .parent
+                    .as_ref()
+                    .map_or("root", |parent| parent.path_segment.as_ref());
+                lines.push(format!("{indent}<{name}>", indent = "  ".repeat(depth)));
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            TraversalEvent::Leave(node) => {
+                depth -= 1;
+                let name = node
+                    .parent
+                    .as_ref()
+                    .map_or("root", |parent| parent.path_segment.as_ref());
+                lines.push(format!("{indent}</{name}>", indent = "  ".repeat(depth)));
+            }
+        }
+    }
+    assert_eq!(0, depth);
+    assert_eq!(4, max_depth);
+    assert_eq!(2 * tree.nodes_count().get(), lines.len());
+    assert_eq!("</root>", lines.last().unwrap());
+
+    // Events of the same node enclose the events of all its descendants.
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let events = tree.depth_first_traversal(a_node).collect::<Vec<_>>();
+    assert_eq!(8, events.len());
+    assert!(matches!(events.first(), Some(TraversalEvent::Enter(node)) if node.id == a_node.id));
+    assert!(matches!(events.last(), Some(TraversalEvent::Leave(node)) if node.id == a_node.id));
+    let leaf_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/c/d")))
+        .unwrap();
+    let enter_index = events
+        .iter()
+        .position(|event| {
+            matches!(event, TraversalEvent::Enter(_)) && event.node().id == leaf_node.id
+        })
+        .unwrap();
+    assert!(
+        matches!(&events[enter_index + 1], TraversalEvent::Leave(node) if node.id == leaf_node.id)
+    );
+}
+
+#[test]
+fn child_handle() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let inserted = tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert!(tree.child_handle(tree.root_node()).is_none());
+    let handle = tree.child_handle(&inserted.node).unwrap();
+    assert_eq!("b", handle.path_segment());
+    assert_eq!(inserted.node.id, handle.resolve(&tree).unwrap().id);
+
+    // Updating the value of the child node.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        NodeValue::Leaf(2),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(matches!(
+        handle.resolve(&tree).unwrap().node,
+        Node::Leaf(LeafNode { value: 2 })
+    ));
+
+    // Replacing the child node with a new node.
+    let parent_node = Arc::clone(tree.lookup_node(handle.parent_node_id()).unwrap());
+    let removed = tree.remove_subtree_by_id(inserted.node.id).unwrap();
+    assert!(matches!(
+        handle.resolve(&tree),
+        Err(ChildHandleInvalidated::Removed)
+    ));
+    let parent_node = Arc::clone(tree.lookup_node(parent_node.id).unwrap());
+    let replaced = tree
+        .insert_or_replace_subtree(&parent_node, "b", None, removed.removed_subtree)
+        .unwrap();
+    assert_ne!(inserted.node.id, replaced.child_node_id);
+    assert_eq!(replaced.child_node_id, handle.resolve(&tree).unwrap().id);
+
+    // Renaming the replaced child node.
+    let handle = tree
+        .child_handle(tree.lookup_node(replaced.child_node_id).unwrap())
+        .unwrap();
+    let parent_node = Arc::clone(tree.lookup_node(handle.parent_node_id()).unwrap());
+    tree.insert_or_update_child_node_value(&parent_node, "c", Some("b"), NodeValue::Leaf(3))
+        .unwrap();
+    assert!(matches!(
+        handle.resolve(&tree),
+        Err(ChildHandleInvalidated::Renamed { path_segment }) if path_segment == "c"
+    ));
+
+    // Removing the parent node.
+    let handle = tree
+        .child_handle(tree.lookup_node(replaced.child_node_id).unwrap())
+        .unwrap();
+    assert!(handle.is_valid(&tree));
+    tree.remove_subtree_by_id(handle.parent_node_id()).unwrap();
+    assert!(!handle.is_valid(&tree));
+    assert!(matches!(
+        handle.resolve(&tree),
+        Err(ChildHandleInvalidated::ParentRemoved)
+    ));
+}
+
+#[test]
+fn remove_paths() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/d", "/e/f", "/g"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert_eq!(8, tree.nodes_count().get());
+
+    let paths = ["/a/b/c", "/a", "/x", "/", "/e/f", "/a/d", "/e/f"]
+        .into_iter()
+        .map(|path| SlashPath::new(Cow::Borrowed(path)))
+        .collect::<Vec<_>>();
+    let outcomes = tree.remove_paths(&paths);
+    assert_eq!(paths.len(), outcomes.len());
+    assert!(matches!(outcomes[0], PathRemoved::Included));
+    assert!(
+        matches!(&outcomes[1], PathRemoved::Removed(removed) if removed.removed_subtree.nodes_count().get() == 4)
+    );
+    assert!(matches!(outcomes[2], PathRemoved::NotFound));
+    assert!(matches!(outcomes[3], PathRemoved::RootNode));
+    assert!(matches!(outcomes[4], PathRemoved::Removed(_)));
+    assert!(matches!(outcomes[5], PathRemoved::Included));
+    assert!(matches!(outcomes[6], PathRemoved::Included));
+    assert_eq!(3, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/e")))
+        .is_some());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/g")))
+        .is_some());
+
+    // Frozen subtrees are neither removed nor do they include nested paths.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let b_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .unwrap()
+        .id;
+    assert!(tree.freeze_subtree(b_node_id));
+    let paths = ["/a/b", "/a", "/a/c"]
+        .into_iter()
+        .map(|path| SlashPath::new(Cow::Borrowed(path)))
+        .collect::<Vec<_>>();
+    let outcomes = tree.remove_paths(&paths);
+    assert!(matches!(outcomes[0], PathRemoved::Frozen { node_id } if node_id == b_node_id));
+    assert!(matches!(outcomes[1], PathRemoved::Frozen { node_id } if node_id == b_node_id));
+    assert!(matches!(outcomes[2], PathRemoved::Removed(_)));
+    assert_eq!(3, tree.nodes_count().get());
+}
+
+#[test]
+fn validate() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(tree.validate().is_ok());
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/c")),
+        NodeValue::Leaf(2),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.validate().is_ok());
+
+    // Renaming a non-existent child node updates the existing child node
+    // instead of orphaning it. Found by fuzzing.
+    let root_node = Arc::clone(tree.root_node());
+    let c_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/c")))
+        .unwrap()
+        .id;
+    let updated = tree
+        .insert_or_update_child_node_value(&root_node, "c", Some("x"), NodeValue::Inner(3))
+        .unwrap();
+    assert_eq!(c_node_id, updated.node.id);
+    assert_eq!(4, tree.nodes_count().get());
+    let result: Result<(), TreeValidationError<PathTreeTypes>> = tree.validate();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn tree_provenance() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(tree.provenance().is_none());
+    tree.assign_tree_id(TreeId(1));
+    let mut forked_tree = tree.fork(TreeId(2));
+    let forked_provenance = forked_tree.provenance().unwrap();
+    assert_eq!(TreeId(2), forked_provenance.tree_id);
+    assert_eq!(TreeId(1), forked_provenance.lineage_id);
+    assert_eq!(Some(TreeId(1)), forked_provenance.created_from);
+    forked_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a")),
+            NodeValue::Leaf(1),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+
+    let patch = tree.try_diff_patch(&forked_tree).unwrap();
+    assert_eq!(Some(TreeId(2)), patch.source_tree_id());
+    tree.apply_patch(&patch).unwrap();
+    assert_eq!(
+        vec![TreeId(2)],
+        tree.provenance().unwrap().patch_lineage.as_slice()
+    );
+    assert!(tree.try_diff(&forked_tree).is_ok());
+
+    let mut unrelated_tree = tree.deep_clone();
+    unrelated_tree.assign_tree_id(TreeId(3));
+    let err = tree.try_diff(&unrelated_tree).unwrap_err();
+    assert_eq!(TreeId(1), err.lineage_id);
+    assert_eq!(TreeId(3), err.other_lineage_id);
+    assert!(tree.try_diff_patch(&unrelated_tree).is_err());
+    let err = tree.merge(&unrelated_tree, &mut PreferTheirs).unwrap_err();
+    assert!(matches!(
+        err,
+        crate::MergeError::UnrelatedTrees(crate::UnrelatedTreesError {
+            lineage_id: TreeId(1),
+            other_lineage_id: TreeId(3),
+            ..
+        })
+    ));
+    tree.merge(&forked_tree, &mut PreferTheirs).unwrap();
+
+    // Trees without provenance are related to any tree.
+    let anonymous_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(tree.check_same_lineage(&anonymous_tree).is_ok());
+}
+
+#[test]
+fn read_path_allocations() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b/c")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let path = SlashPath::new(Cow::Borrowed("/a/b/c"));
+
+    // Only the boxed iterators over the path segments are allocated,
+    // independent of the number of path segments.
+    let short_path = SlashPath::new(Cow::Borrowed("/a"));
+    let (node, short_path_allocations_count) = count_allocations(|| tree.find_node(&short_path));
+    assert!(node.is_some());
+    let (node, allocations_count) = count_allocations(|| tree.find_node(&path));
+    assert!(node.is_some());
+    assert_eq!(short_path_allocations_count, allocations_count);
+
+    let (ancestors_count, allocations_count) =
+        count_allocations(|| tree.ancestor_nodes(node.unwrap()).count());
+    assert_eq!(3, ancestors_count);
+    assert_eq!(0, allocations_count);
+
+    let handle = tree.find_node_handle(&path).unwrap();
+    let (path_segment, allocations_count) = count_allocations(|| handle.path_segment());
+    assert_eq!(Some("c"), path_segment);
+    assert_eq!(0, allocations_count);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn build_parallel() {
+    let entries = ["/a/b", "/a/c", "/d", "/e/f/g"]
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let path = SlashPath::new(Cow::Borrowed(path))
+                .segments()
+                .map(|path_segment| Cow::Owned(path_segment.to_owned()))
+                .collect::<Vec<_>>();
+            (path, NodeValue::Leaf(index))
+        })
+        .chain(std::iter::once((Vec::new(), NodeValue::Inner(1))))
+        .collect::<Vec<_>>();
+    let tree = PathTree::build_parallel(Default::default(), entries.clone(), &|| -1).unwrap();
+    assert!(tree.validate().is_ok());
+    assert_eq!(8, tree.nodes_count().get());
+    assert!(matches!(
+        tree.root_node().node,
+        Node::Inner(InnerNode { value: 1, .. })
+    ));
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/e/f/g")))
+            .unwrap()
+            .node,
+        Node::Leaf(LeafNode { value: 3 })
+    ));
+    assert!(matches!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/e/f")))
+            .unwrap()
+            .node,
+        Node::Inner(InnerNode { value: -1, .. })
+    ));
+
+    // Conflicting paths
+    let conflicting_entries = entries.into_iter().chain(std::iter::once((
+        vec![Cow::Borrowed("d"), Cow::Borrowed("x")],
+        NodeValue::Leaf(4),
+    )));
+    assert!(PathTree::build_parallel(Default::default(), conflicting_entries, &|| -1).is_err());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mapped_snapshot() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/c", 1), ("/a/b", 2), ("/d", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let mut bytes = Vec::new();
+    tree.write_snapshot(&mut bytes).unwrap();
+
+    let view = crate::SnapshotView::<PathTreeTypes>::new(&bytes).unwrap();
+    assert_eq!(5, view.nodes_count());
+    let root_node = view.root_node().unwrap();
+    assert!(root_node.path_segment().unwrap().is_none());
+    assert!(matches!(root_node.value().unwrap(), NodeValue::Inner(0)));
+    let node = view
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .unwrap()
+        .unwrap();
+    assert!(node.is_leaf());
+    assert_eq!(Some(Cow::Borrowed("b")), node.path_segment().unwrap());
+    assert!(matches!(node.value().unwrap(), NodeValue::Leaf(2)));
+    let parent_node = node.parent().unwrap().unwrap();
+    assert!(matches!(parent_node.value().unwrap(), NodeValue::Inner(-1)));
+    // Children are ordered by their encoded path segments.
+    assert_eq!(
+        vec![Some(Cow::Borrowed("b")), Some(Cow::Borrowed("c"))],
+        parent_node
+            .children()
+            .map(|child_node| child_node.unwrap().path_segment().unwrap())
+            .collect::<Vec<_>>()
+    );
+    assert!(view
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/x")))
+        .unwrap()
+        .is_none());
+    assert!(view
+        .find_node(&SlashPath::new(Cow::Borrowed("/d/x")))
+        .unwrap()
+        .is_none());
+
+    let promoted_tree = view.to_path_tree(Default::default()).unwrap();
+    assert!(promoted_tree.validate().is_ok());
+    assert!(tree.diff_patch(&promoted_tree).operations.is_empty());
+
+    assert!(matches!(
+        crate::SnapshotView::<PathTreeTypes>::new(&bytes[..bytes.len() - 1]),
+        Err(crate::SnapshotError::InvalidHeader)
+    ));
+
+    // The schema tag is verified.
+    let mut other_schema_bytes = bytes.clone();
+    other_schema_bytes[20] ^= 0x20;
+    assert!(matches!(
+        crate::SnapshotView::<PathTreeTypes>::new(&other_schema_bytes),
+        Err(crate::SnapshotError::SchemaMismatch { .. })
+    ));
+
+    // Duplicate path segments of siblings are rejected.
+    let mut duplicate_bytes = bytes.clone();
+    let c_index = duplicate_bytes
+        .iter()
+        .rposition(|byte| *byte == b'c')
+        .unwrap();
+    duplicate_bytes[c_index] = b'b';
+    let duplicate_view = crate::SnapshotView::<PathTreeTypes>::new(&duplicate_bytes).unwrap();
+    assert!(matches!(
+        duplicate_view.to_path_tree(Default::default()),
+        Err(crate::SnapshotError::Corrupted)
+    ));
+
+    let file_path = std::env::temp_dir().join(format!(
+        "rpds-pathtree-mapped-snapshot-{pid}.bin",
+        pid = std::process::id()
+    ));
+    std::fs::write(&file_path, &bytes).unwrap();
+    // SAFETY: The temporary file is not modified while it is mapped.
+    #[allow(unsafe_code)]
+    let snapshot = unsafe { crate::MappedSnapshot::<PathTreeTypes>::open(&file_path) }.unwrap();
+    assert_eq!(5, snapshot.view().nodes_count());
+    let mut mapped_tree = crate::MappedTree::Mapped(snapshot);
+    let promoted_tree = mapped_tree.make_mut(Default::default).unwrap();
+    promoted_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/e")),
+            NodeValue::Leaf(4),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(6, mapped_tree.into_path_tree().unwrap().nodes_count().get());
+    std::fs::remove_file(&file_path).unwrap();
+}
+
+#[test]
+fn descendant_nodes_ordered() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/c", "/a/z", "/b", "/a/y/x", "/a/b"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        vec!["a", "b", "y", "x", "z", "b", "c"],
+        tree.descendant_nodes_ordered(tree.root_node())
+            .map(|HalfEdge { path_segment, .. }| path_segment)
+            .collect::<Vec<_>>()
+    );
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    assert_eq!(
+        vec!["b", "y", "x", "z"],
+        tree.descendant_nodes_ordered(a_node)
+            .map(|HalfEdge { path_segment, .. }| path_segment)
+            .collect::<Vec<_>>()
+    );
+    let c_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/c")))
+        .unwrap();
+    assert_eq!(0, tree.descendant_nodes_ordered(c_node).count());
+}
+
+#[test]
+fn repair() {
+    let row = |node_id, parent: Option<(&'static str, usize)>, value| TreeRow {
+        node_id,
+        parent: parent.map(|(path_segment, node_id)| crate::HalfEdgeOwned {
+            path_segment: Cow::Borrowed(path_segment),
+            node_id,
+        }),
+        value,
+    };
+    let rows = vec![
+        // Children before their parents
+        row(2, Some(("b", 1)), NodeValue::Leaf(2)),
+        row(1, Some(("a", 0)), NodeValue::Inner(1)),
+        row(0, None, NodeValue::Inner(0)),
+        row(2, Some(("c", 1)), NodeValue::Leaf(3)),
+        // Missing parent row
+        row(10, Some(("x", 99)), NodeValue::Inner(10)),
+        row(11, Some(("y", 10)), NodeValue::Leaf(11)),
+        // Occupied path segment
+        row(12, Some(("a", 0)), NodeValue::Leaf(12)),
+        // Cycle
+        row(20, Some(("p", 21)), NodeValue::Inner(20)),
+        row(21, Some(("q", 20)), NodeValue::Inner(21)),
+        row(30, None, NodeValue::Inner(30)),
+    ];
+
+    let (tree, report) =
+        PathTree::repair(Default::default(), rows.clone(), RepairPolicy::DropOrphans).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(3, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .is_some());
+    let actions = |report: &crate::RepairReport<PathTreeTypes>, row_id| {
+        report
+            .repaired_rows
+            .iter()
+            .filter(|repaired_row| repaired_row.row_id == row_id)
+            .map(|repaired_row| repaired_row.action)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![RepairAction::DroppedDuplicateRow], actions(&report, 2));
+    assert_eq!(
+        vec![RepairAction::DroppedDuplicateRoot],
+        actions(&report, 30)
+    );
+    for row_id in [10, 11, 12, 20, 21] {
+        assert_eq!(vec![RepairAction::DroppedOrphan], actions(&report, row_id));
+    }
+
+    let (tree, report) =
+        PathTree::repair(Default::default(), rows, RepairPolicy::ReattachOrphans).unwrap();
+    tree.validate().unwrap();
+    assert_eq!(7, tree.nodes_count().get());
+    for path in ["/a/b", "/x/y", "/p/q"] {
+        assert!(tree
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .is_some());
+    }
+    assert_eq!(vec![RepairAction::ReattachedOrphan], actions(&report, 10));
+    assert!(actions(&report, 11).is_empty());
+    assert_eq!(vec![RepairAction::DroppedOrphan], actions(&report, 12));
+    assert_eq!(5, report.repaired_rows.len());
+
+    let (_, report) = PathTree::repair(
+        Default::default(),
+        tree.to_rows(crate::ExportOrder::sorted()),
+        RepairPolicy::DropOrphans,
+    )
+    .unwrap();
+    assert!(report.is_clean());
+    assert!(matches!(
+        PathTree::repair(
+            Default::default(),
+            std::iter::empty(),
+            RepairPolicy::DropOrphans
+        ),
+        Err(FromRowsError::RootRowMissing)
+    ));
+}
+
+#[test]
+fn post_order_nodes() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let nodes = tree.post_order_nodes(tree.root_node()).collect::<Vec<_>>();
+    assert_eq!(tree.nodes_count().get(), nodes.len());
+    assert_eq!(tree.root_node_id(), nodes.last().unwrap().id);
+    // Children are yielded before their parent.
+    for (index, node) in nodes.iter().enumerate() {
+        for child in node.node.children() {
+            assert!(nodes[..index].iter().any(|node| node.id == child.node_id));
+        }
+    }
+
+    // Aggregate the number of nodes in each subtree bottom-up.
+    let mut subtree_sizes = std::collections::HashMap::new();
+    for node in tree.post_order_nodes(tree.root_node()) {
+        let size = 1 + node
+            .node
+            .children()
+            .map(|child| subtree_sizes[&child.node_id])
+            .sum::<usize>();
+        subtree_sizes.insert(node.id, size);
+    }
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    assert_eq!(4, subtree_sizes[&a_node.id]);
+    assert_eq!(
+        tree.nodes_count().get(),
+        subtree_sizes[&tree.root_node_id()]
+    );
+}
+
+#[test]
+fn natural_ordering() {
+    use std::cmp::Ordering;
+
+    use crate::natural_cmp;
+
+    assert_eq!(Ordering::Less, natural_cmp("file2", "file10"));
+    assert_eq!(Ordering::Greater, natural_cmp("file10", "file2"));
+    assert_eq!(Ordering::Equal, natural_cmp("file10", "file10"));
+    assert_eq!(Ordering::Less, natural_cmp("file", "file1"));
+    assert_eq!(Ordering::Less, natural_cmp("a10b2", "a10b10"));
+    assert_eq!(Ordering::Less, natural_cmp("7", "007"));
+    assert_eq!(
+        Ordering::Less,
+        natural_cmp("99999999999999999999", "100000000000000000000")
+    );
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/file10", "/file2", "/file1/b10", "/file1/b9"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        vec!["file1", "file2", "file10"],
+        tree.child_nodes_sorted_naturally(tree.root_node())
+            .into_iter()
+            .map(|HalfEdge { path_segment, .. }| path_segment)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["file1", "b9", "b10", "file2", "file10"],
+        tree.descendant_nodes_sorted_by(tree.root_node(), natural_cmp)
+            .map(|HalfEdge { path_segment, .. }| path_segment)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn nodes_with_paths() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let mut paths = tree
+        .nodes_with_paths(tree.root_node())
+        .map(|(path, node)| {
+            // The paths match the ancestors of each node.
+            let mut ancestor_path = tree
+                .ancestor_nodes(node)
+                .map(|ancestor| ancestor.path_segment)
+                .collect::<Vec<_>>();
+            ancestor_path.reverse();
+            assert_eq!(ancestor_path, path);
+            path.join("/")
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(tree.nodes_count().get(), paths.len());
+    assert_eq!("", paths[0]);
+    paths.sort();
+    assert_eq!(vec!["", "a", "a/b", "a/c", "a/c/d", "e"], paths);
+
+    // Paths are relative to the given node.
+    let c_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/c")))
+        .unwrap();
+    let paths = tree
+        .nodes_with_paths(c_node)
+        .map(|(path, _)| path.join("/"))
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["", "d"], paths);
+}
+
+#[test]
+fn grep_values() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 12), ("/a/c/d", 3), ("/e", 21), ("/f", 4)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let find_digit = |value: &usize| value.to_string().find('2');
+    let mut matches = tree
+        .grep_values(find_digit)
+        .into_iter()
+        .map(|value_match| {
+            assert!(tree.lookup_node(value_match.node_id).is_some());
+            (value_match.path.join("/"), value_match.matched)
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    assert_eq!(vec![("a/b".to_owned(), 1), ("e".to_owned(), 0)], matches);
+    assert!(tree.grep_values(|_| None::<()>).is_empty());
+
+    #[cfg(feature = "rayon")]
+    {
+        let par_matches = tree.par_grep_values(find_digit);
+        let matches = tree.grep_values(find_digit);
+        assert_eq!(matches.len(), par_matches.len());
+        for (value_match, par_value_match) in matches.iter().zip(&par_matches) {
+            assert_eq!(value_match.path, par_value_match.path);
+            assert_eq!(value_match.node_id, par_value_match.node_id);
+            assert_eq!(value_match.matched, par_value_match.matched);
+        }
+    }
+}
+
+#[test]
+fn leaf_nodes() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert_eq!(3, tree.leaf_nodes().count());
+    assert!(tree
+        .leaf_nodes()
+        .all(|node| matches!(node.node, Node::Leaf(_))));
+
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let mut path_segments = tree
+        .leaf_nodes_below(a_node)
+        .map(|HalfEdgeTreeNode { path_segment, node }| {
+            assert!(matches!(node.node, Node::Leaf(_)));
+            path_segment
+        })
+        .collect::<Vec<_>>();
+    path_segments.sort_unstable();
+    assert_eq!(vec!["b", "d"], path_segments);
+
+    // Leaf nodes have no descendants.
+    let e_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/e")))
+        .unwrap();
+    assert_eq!(0, tree.leaf_nodes_below(e_node).count());
+}
+
+#[test]
+fn verify_id_set() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/c"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let node_ids = tree.nodes().map(|node| node.id).collect::<Vec<_>>();
+    assert!(tree.verify_id_set(node_ids.iter().copied()).is_consistent());
+
+    let b_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .unwrap()
+        .id;
+    let unknown_id = node_ids.iter().max().unwrap() + 1;
+    let audit = tree.verify_id_set(
+        node_ids
+            .iter()
+            .copied()
+            .filter(|node_id| *node_id != b_node_id)
+            .chain([unknown_id, unknown_id + 1, unknown_id]),
+    );
+    assert!(!audit.is_consistent());
+    assert_eq!(vec![unknown_id, unknown_id + 1], audit.missing_ids);
+    assert_eq!(vec![b_node_id], audit.uncovered_node_ids);
+
+    let audit = tree.verify_id_set([]);
+    assert!(audit.missing_ids.is_empty());
+    assert_eq!(tree.nodes_count().get(), audit.uncovered_node_ids.len());
+}
+
+#[test]
+fn children_stats() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let stats = tree.children_stats();
+    assert_eq!(1, stats.inner_nodes_count);
+    assert_eq!(1, stats.empty_inner_nodes_count);
+    assert_eq!(vec![1], stats.children_count_histogram);
+    assert_eq!(Some(0.0), stats.mean_children_count());
+
+    for path in ["/a/b", "/a/c", "/a/d", "/e/"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            if path.ends_with('/') {
+                NodeValue::Inner(1)
+            } else {
+                NodeValue::Leaf(1)
+            },
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let Node::Inner(root_node) = &tree.root_node().node else {
+        unreachable!();
+    };
+    let root_stats = root_node.children_stats();
+    assert_eq!(2, root_stats.children_count);
+    assert_eq!(1, root_stats.min_trie_depth);
+
+    let stats = tree.children_stats();
+    assert_eq!(3, stats.inner_nodes_count);
+    assert_eq!(1, stats.empty_inner_nodes_count);
+    assert_eq!(5, stats.children_count);
+    assert_eq!(3, stats.max_children_count);
+    assert_eq!(1, stats.max_min_trie_depth);
+    // Buckets: 0, 1, 2..4
+    assert_eq!(vec![1, 0, 2], stats.children_count_histogram);
+
+    // More children than fit into a single trie level.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let root_node_id = tree.root_node_id();
+    for i in 0..=usize::BITS {
+        tree.insert_or_update_child_node_value(
+            &Arc::clone(tree.get_node(root_node_id)),
+            i.to_string().as_str(),
+            None,
+            NodeValue::Leaf(1),
+        )
+        .unwrap();
+    }
+    assert_eq!(2, tree.children_stats().max_min_trie_depth);
+}
+
+#[test]
+fn child_nodes() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let child_nodes = tree.child_nodes(a_node);
+    assert_eq!(2, child_nodes.len());
+    let mut children = child_nodes
+        .map(|HalfEdgeTreeNode { path_segment, node }| {
+            assert_eq!(
+                Some(a_node.id),
+                node.parent.as_ref().map(|parent| parent.node_id)
+            );
+            (path_segment, matches!(node.node, Node::Leaf(_)))
+        })
+        .collect::<Vec<_>>();
+    children.sort_unstable();
+    assert_eq!(vec![("b", true), ("c", false)], children);
+
+    let b_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .unwrap();
+    assert_eq!(0, tree.child_nodes(b_node).len());
+}
+
+#[test]
+fn non_copy_node_ids() {
+    #[derive(Debug, Clone, Default)]
+    struct NewStringNodeId {
+        next_node_id: usize,
+    }
+
+    impl crate::NewNodeId<Arc<str>> for NewStringNodeId {
+        fn new_node_id(&mut self) -> Arc<str> {
+            let next_node_id = self.next_node_id;
+            self.next_node_id += 1;
+            format!("node-{next_node_id}").into()
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct StringNodeIdTypes;
+
+    impl crate::PathTreeTypes for StringNodeIdTypes {
+        type NodeId = Arc<str>;
+        type NewNodeId = NewStringNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = isize;
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+    }
+
+    let mut tree =
+        crate::PathTree::<StringNodeIdTypes>::new(Default::default(), crate::NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            crate::NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    assert!(tree.validate().is_ok());
+    assert_eq!("node-0", &*tree.root_node_id());
+    let b_node_id = Arc::clone(
+        &tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+            .unwrap()
+            .id,
+    );
+    assert!(tree.lookup_node(Arc::clone(&b_node_id)).is_some());
+
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let removed = tree.remove_subtree_by_id(Arc::clone(&a_node.id)).unwrap();
+    assert!(tree.lookup_node(Arc::clone(&b_node_id)).is_none());
+    assert!(removed.removed_subtree.lookup_node(b_node_id).is_some());
+    assert_eq!(2, tree.nodes_count().get());
+
+    let rows = tree
+        .to_rows(crate::ExportOrder::sorted())
+        .collect::<Vec<_>>();
+    let tree = crate::PathTree::<StringNodeIdTypes>::from_rows(Default::default(), rows).unwrap();
+    assert!(tree.validate().is_ok());
+}
+
+#[test]
+fn cursor_and_zipper() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let mut cursor = tree.cursor();
+    assert!(!cursor.move_up());
+    assert!(!cursor.move_to_sibling("a"));
+    assert!(!cursor.move_down("x"));
+    assert!(cursor.move_down("a"));
+    assert!(cursor.move_down("b"));
+    assert_eq!(2, cursor.depth());
+    assert_eq!(["a", "b"], cursor.path());
+    assert!(cursor.move_to_sibling("c"));
+    assert_eq!(["a", "c"], cursor.path());
+    assert!(!cursor.move_to_sibling("x"));
+    assert_eq!(["a", "c"], cursor.path());
+    assert!(cursor.move_up());
+    assert_eq!(["a"], cursor.path());
+    assert!(cursor.move_to_sibling("d"));
+    assert!(matches!(cursor.node().node, Node::Leaf(_)));
+
+    // Edit a copy of the tree at the current position.
+    let mut zipper = cursor.to_zipper();
+    assert_eq!(["d"], zipper.path());
+    zipper.set_value(NodeValue::Leaf(2)).unwrap();
+    assert!(zipper.move_to_sibling("a"));
+    // Inner nodes with children could not be converted into leaf nodes.
+    assert!(zipper.set_value(NodeValue::Leaf(2)).is_err());
+    zipper
+        .insert_or_update_child("e", NodeValue::Leaf(3))
+        .unwrap();
+    assert!(zipper.remove_child("b").is_some());
+    assert!(zipper.remove_child("b").is_none());
+    assert!(zipper.move_down("e"));
+    assert_eq!(["a", "e"], zipper.path());
+    assert!(zipper.move_up());
+    assert!(zipper.move_up());
+    assert!(!zipper.move_up());
+    assert_eq!(0, zipper.depth());
+    let edited_tree = zipper.commit();
+    assert!(edited_tree.validate().is_ok());
+
+    // The original tree remains unchanged.
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .is_some());
+    assert!(edited_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .is_none());
+    assert!(matches!(
+        edited_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/e")))
+            .map(|node| node.node.to_value()),
+        Some(NodeValue::Leaf(3))
+    ));
+    assert!(matches!(
+        edited_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/d")))
+            .map(|node| node.node.to_value()),
+        Some(NodeValue::Leaf(2))
+    ));
+
+    let mut zipper = tree.clone().into_zipper();
+    assert!(zipper.move_down("d"));
+    zipper.set_value(NodeValue::Leaf(4)).unwrap();
+    let edited_tree = zipper.commit();
+    assert!(matches!(
+        edited_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/d")))
+            .map(|node| node.node.to_value()),
+        Some(NodeValue::Leaf(4))
+    ));
+}
+
+#[test]
+fn try_update_leaves_in_subtree() {
+    use std::ops::ControlFlow;
+
+    use crate::BreakPolicy;
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/a/c", 2), ("/a/d/e", 3), ("/f", 4)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    let leaf_values = |tree: &PathTree| {
+        let mut values = tree
+            .leaf_nodes()
+            .map(|node| match &node.node {
+                Node::Leaf(leaf) => leaf.value,
+                Node::Inner(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        values
+    };
+
+    // Update all leaf nodes in the subtree.
+    let updated = tree
+        .try_update_leaves_in_subtree(a_node_id, BreakPolicy::KeepUpdates, |_, value| {
+            ControlFlow::<(), _>::Continue((*value != 2).then_some(value * 10))
+        })
+        .unwrap();
+    assert!(updated.is_complete());
+    assert_eq!(3, updated.visited_count);
+    assert_eq!(2, updated.updated_count);
+    assert_eq!(vec![2, 4, 10, 30], leaf_values(&tree));
+
+    // Stop early and keep the preceding updates.
+    let updated = tree
+        .try_update_leaves_in_subtree(a_node_id, BreakPolicy::KeepUpdates, |_, value| {
+            if *value == 2 {
+                ControlFlow::Break("invalid")
+            } else {
+                ControlFlow::Continue(Some(value + 1))
+            }
+        })
+        .unwrap();
+    assert!(!updated.is_complete());
+    assert!(!updated.rolled_back);
+    let (stopped_node_id, reason) = updated.stopped.unwrap();
+    assert_eq!("invalid", reason);
+    assert_eq!(
+        "c",
+        tree.get_node(stopped_node_id)
+            .parent
+            .as_ref()
+            .unwrap()
+            .path_segment
+    );
+    assert_eq!(updated.visited_count - 1, updated.updated_count);
+    assert_eq!(
+        updated.updated_count,
+        leaf_values(&tree)
+            .into_iter()
+            .filter(|value| [11, 31].contains(value))
+            .count()
+    );
+
+    // Stop early and roll back.
+    let tree_before = tree.clone();
+    let updated = tree
+        .try_update_leaves_in_subtree(a_node_id, BreakPolicy::RollBack, |_, value| {
+            if *value == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(Some(0))
+            }
+        })
+        .unwrap();
+    assert!(updated.rolled_back);
+    assert_eq!(leaf_values(&tree_before), leaf_values(&tree));
+
+    assert!(tree
+        .try_update_leaves_in_subtree(usize::MAX, BreakPolicy::RollBack, |_, _| {
+            ControlFlow::<(), _>::Continue(None)
+        })
+        .is_none());
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn leveled_path_segments() {
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum ResourceSegment {
+        Tenant(&'static str),
+        Project(&'static str),
+        Resource(&'static str),
+    }
+
+    impl crate::PathSegment for ResourceSegment {
+        fn is_empty(&self) -> bool {
+            let (Self::Tenant(name) | Self::Project(name) | Self::Resource(name)) = self;
+            name.is_empty()
+        }
+    }
+
+    impl crate::LeveledPathSegment for ResourceSegment {
+        fn is_valid_at_depth(&self, depth: usize) -> bool {
+            match self {
+                Self::Tenant(_) => depth == 1,
+                Self::Project(_) => depth == 2,
+                Self::Resource(_) => depth == 3,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct ResourcePath(Vec<ResourceSegment>);
+
+    impl SegmentedPath<ResourceSegment> for ResourcePath {
+        fn segments(&self) -> Box<dyn Iterator<Item = &ResourceSegment> + '_> {
+            Box::new(self.0.iter())
+        }
+
+        fn parent_child_segments(
+            &self,
+        ) -> (
+            Box<dyn Iterator<Item = &ResourceSegment> + '_>,
+            Option<&ResourceSegment>,
+        ) {
+            let Some((child_segment, parent_segments)) = self.0.split_last() else {
+                return (Box::new(std::iter::empty()), None);
+            };
+            (Box::new(parent_segments.iter()), Some(child_segment))
+        }
+    }
+
+    impl RootPath<ResourceSegment> for ResourcePath {
+        fn is_root(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct ResourceTreeTypes;
+
+    impl crate::PathTreeTypes for ResourceTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = ResourceSegment;
+        type PathSegment = ResourceSegment;
+        type RootPath = ResourcePath;
+        type ChildKey = ResourceSegment;
+        type ChildKeyOwned = ResourceSegment;
+        type InnerValue = ();
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            path_segment.clone()
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+    }
+
+    type ResourceTree = crate::PathTree<ResourceTreeTypes>;
+
+    let mut tree = ResourceTree::new(Default::default(), crate::NodeValue::Inner(()));
+    let path = ResourcePath(vec![
+        ResourceSegment::Tenant("acme"),
+        ResourceSegment::Project("rocket"),
+        ResourceSegment::Resource("engine"),
+    ]);
+    assert_eq!(None, ResourceTree::find_invalid_leveled_path_segment(&path));
+    tree.insert_or_update_leveled_node_value(&path, crate::NodeValue::Leaf(1), &mut || (), |_| {
+        None
+    })
+    .unwrap();
+    assert_eq!(4, tree.nodes_count().get());
+
+    // A resource directly below a tenant is rejected without modifying the tree.
+    let path = ResourcePath(vec![
+        ResourceSegment::Tenant("acme"),
+        ResourceSegment::Resource("engine"),
+    ]);
+    assert_eq!(
+        Some(2),
+        ResourceTree::find_invalid_leveled_path_segment(&path)
+    );
+    let err = tree
+        .insert_or_update_leveled_node_value(&path, crate::NodeValue::Leaf(2), &mut || (), |_| None)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        crate::InsertOrUpdateLeveledNodeValueError::InvalidPathSegment { depth: 2, .. }
+    ));
+    assert!(matches!(err.into_value(), crate::NodeValue::Leaf(2)));
+    assert_eq!(4, tree.nodes_count().get());
+
+    // Update the existing node.
+    let path = ResourcePath(vec![
+        ResourceSegment::Tenant("acme"),
+        ResourceSegment::Project("rocket"),
+        ResourceSegment::Resource("engine"),
+    ]);
+    tree.insert_or_update_leveled_node_value(&path, crate::NodeValue::Leaf(3), &mut || (), |_| {
+        None
+    })
+    .unwrap();
+    assert_eq!(4, tree.nodes_count().get());
+}
+
+#[test]
+fn descendant_nodes_within_depth() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c/d", "/a/e", "/f"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let root_node = tree.root_node();
+    assert_eq!(0, tree.descendant_nodes_within_depth(root_node, 0).count());
+    let mut level_one = tree
+        .descendant_nodes_within_depth(root_node, 1)
+        .map(|(depth, child)| (depth, child.path_segment))
+        .collect::<Vec<_>>();
+    level_one.sort_unstable();
+    assert_eq!(vec![(1, "a"), (1, "f")], level_one);
+    let mut level_two = tree
+        .descendant_nodes_within_depth(root_node, 2)
+        .map(|(depth, child)| (depth, child.path_segment))
+        .collect::<Vec<_>>();
+    level_two.sort_unstable();
+    assert_eq!(vec![(1, "a"), (1, "f"), (2, "b"), (2, "e")], level_two);
+    assert_eq!(
+        tree.descendant_nodes(root_node).count(),
+        tree.descendant_nodes_within_depth(root_node, usize::MAX)
+            .count()
+    );
+
+    // Depths are relative to the given node.
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let depths = tree
+        .descendant_nodes_within_depth(a_node, 2)
+        .map(|(depth, child)| (child.path_segment, depth))
+        .collect::<std::collections::HashMap<_, _>>();
+    assert_eq!(3, depths.len());
+    assert_eq!(1, depths["b"]);
+    assert_eq!(2, depths["c"]);
+    assert_eq!(1, depths["e"]);
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn freeze_subtree() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/published/a", "/published/b/c", "/draft/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let published_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/published")))
+        .unwrap()
+        .id;
+    assert!(tree.freeze_subtree(published_node_id));
+    assert!(!tree.freeze_subtree(usize::MAX));
+    assert_eq!(
+        vec![&published_node_id],
+        tree.frozen_node_ids().collect::<Vec<_>>()
+    );
+    let nodes_count = tree.nodes_count().get();
+
+    // Inserting, updating, and removing nodes in the frozen subtree fails.
+    for path in [
+        "/published",
+        "/published/a",
+        "/published/b/new",
+        "/published/e/f",
+    ] {
+        let err = tree
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(2),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::InsertOrUpdateNodeValueError::SubtreeFrozen { node_id, .. } if node_id == published_node_id
+        ));
+    }
+    let c_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/published/b/c")))
+            .unwrap(),
+    );
+    assert!(tree.is_frozen(&c_node));
+    assert!(matches!(
+        tree.update_node_value(&c_node, NodeValue::Leaf(2)),
+        Err(UpdateNodeValueError::SubtreeFrozen { .. })
+    ));
+    assert!(tree.remove_subtree_by_id(c_node.id).is_none());
+    assert!(tree.remove_subtree_by_id(published_node_id).is_none());
+    assert!(tree
+        .clone()
+        .into_append_cursor(
+            tree.find_node(&SlashPath::new(Cow::Borrowed("/published/b")))
+                .unwrap()
+        )
+        .is_err());
+    assert!(matches!(
+        tree.rewrite_prefix(
+            &SlashPath::new(Cow::Borrowed("/published/b")),
+            &SlashPath::new(Cow::Borrowed("/draft/b")),
+            &mut || -1,
+        ),
+        Err(RewritePrefixError::SubtreeFrozen { .. })
+    ));
+    // Only leaf nodes outside of the frozen subtree are removed.
+    tree.retain_nodes(|node| matches!(node.node, Node::Inner(_)));
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/published/b/c")))
+        .is_some());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/draft/d")))
+        .is_none());
+    assert_eq!(nodes_count - 1, tree.nodes_count().get());
+
+    // Other subtrees could still be modified, even if the root node is affected.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/draft/e")),
+        NodeValue::Leaf(2),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/other")),
+        NodeValue::Leaf(3),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.set_root_value(NodeValue::Inner(1)).is_ok());
+    let draft_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/draft")))
+            .unwrap(),
+    );
+    assert!(!tree.is_frozen(&draft_node));
+
+    // Removing an ancestor of a frozen subtree fails.
+    assert!(tree.freeze_subtree(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/draft/e")))
+            .unwrap()
+            .id
+    ));
+    assert!(tree.remove_subtree_by_id(draft_node.id).is_none());
+    let nodes_count = tree.nodes_count().get();
+    assert!(matches!(
+        tree.reconcile_subtree(tree.root_node_id(), [], &Default::default(), &mut || -1),
+        Err(crate::ReconcileSubtreeError::SubtreeFrozen { .. })
+    ));
+    assert_eq!(nodes_count, tree.nodes_count().get());
+
+    // Unfreezing restores mutability.
+    assert!(tree.unfreeze_subtree(&published_node_id));
+    assert!(!tree.unfreeze_subtree(&published_node_id));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/published/b/new")),
+        NodeValue::Leaf(2),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.remove_subtree_by_id(published_node_id).is_some());
+}
+
+#[test]
+fn operation_context() {
+    let context = crate::OperationContext::new("publish")
+        .with_source_path("/api/publish")
+        .with_correlation_id("req-42");
+    assert_eq!("publish from /api/publish [req-42]", context.to_string());
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let err = tree
+        .with_context(&context, |tree| {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo")),
+                NodeValue::Leaf(1),
+                &mut || -1,
+                |_| None,
+            )?;
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo/bar")),
+                NodeValue::Leaf(2),
+                &mut || -1,
+                |_| None,
+            )
+        })
+        .unwrap_err();
+    assert_eq!(context, err.context);
+    assert!(err
+        .to_string()
+        .starts_with("publish from /api/publish [req-42]: "));
+    assert!(matches!(err.source.into_value(), NodeValue::Leaf(2)));
+
+    let mut event_sourced_tree = EventSourcedTree::new(tree);
+    event_sourced_tree
+        .with_context(&context, |tree| {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/bar")),
+                NodeValue::Leaf(3),
+                &mut || -1,
+                |_| None,
+            )
+        })
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/baz")),
+            NodeValue::Leaf(4),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(2, event_sourced_tree.events().len());
+    assert_eq!(Some(&context), event_sourced_tree.event_context(0));
+    assert_eq!(None, event_sourced_tree.event_context(1));
+}
+
+#[test]
+fn nodes_at_depth() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let path_segments_at_depth = |depth| {
+        let mut path_segments = tree
+            .nodes_at_depth(depth)
+            .map(|node| {
+                node.parent
+                    .as_ref()
+                    .map(|parent| parent.path_segment.as_ref())
+            })
+            .collect::<Vec<_>>();
+        path_segments.sort_unstable();
+        path_segments
+    };
+    assert_eq!(vec![None], path_segments_at_depth(0));
+    assert_eq!(vec![Some("a"), Some("e")], path_segments_at_depth(1));
+    assert_eq!(vec![Some("b"), Some("d")], path_segments_at_depth(2));
+    assert_eq!(vec![Some("c")], path_segments_at_depth(3));
+    assert!(path_segments_at_depth(4).is_empty());
+}
+
+#[test]
+fn subtree_kind_counts() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/d", "/a/e/f", "/g"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    let counts = tree.subtree_kind_counts(tree.root_node_id()).unwrap();
+    assert_eq!(4, counts.inner_nodes_count);
+    assert_eq!(4, counts.leaf_nodes_count);
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    let counts = tree.subtree_kind_counts(a_node_id).unwrap();
+    assert_eq!(3, counts.inner_nodes_count);
+    assert_eq!(3, counts.leaf_nodes_count);
+    let g_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/g")))
+        .unwrap()
+        .id;
+    let counts = tree.subtree_kind_counts(g_node_id).unwrap();
+    assert_eq!(0, counts.inner_nodes_count);
+    assert_eq!(1, counts.leaf_nodes_count);
+    assert!(tree.subtree_kind_counts(usize::MAX).is_none());
+
+    let assert_index_synced = |index: &crate::SubtreeWeightIndex<_>, tree: &PathTree| {
+        for node in tree.nodes() {
+            assert_eq!(
+                tree.subtree_kind_counts(node.id),
+                index.subtree_kind_counts(node.id)
+            );
+        }
+    };
+    let mut index = crate::SubtreeWeightIndex::new(&tree);
+    assert_index_synced(&index, &tree);
+
+    // Only the modified node and its ancestors are recomputed.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/e/f")),
+        NodeValue::Leaf(2),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(4, index.sync(&tree));
+    assert_index_synced(&index, &tree);
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/e/f/h")),
+        NodeValue::Leaf(3),
+        &mut || unreachable!(),
+        |_| Some(-1),
+    )
+    .unwrap();
+    assert_eq!(5, index.sync(&tree));
+    assert_index_synced(&index, &tree);
+    let counts = index.subtree_kind_counts(a_node_id).unwrap();
+    assert_eq!(4, counts.inner_nodes_count);
+    assert_eq!(3, counts.leaf_nodes_count);
+    tree.remove_subtree_by_id(g_node_id).unwrap();
+    assert_eq!(1, index.sync(&tree));
+    assert_index_synced(&index, &tree);
+    assert!(index.subtree_kind_counts(g_node_id).is_none());
+    assert_eq!(0, index.sync(&tree));
+}
+
+#[test]
+fn subtree_paths() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c/d", "/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let a_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap();
+    let mut paths = tree
+        .subtree_paths(a_node)
+        .map(|(path, node)| (path.join("/"), node.node.to_value()))
+        .collect::<Vec<_>>();
+    paths.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+    assert_eq!(
+        vec!["b", "c", "c/d"],
+        paths
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    // Export the subtree to a new location.
+    for (path, value) in paths {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Owned(format!("/x/{path}"))),
+            value,
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    for path in ["/x/b", "/x/c", "/x/c/d"] {
+        assert!(tree
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .is_some());
+    }
+
+    let e_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/e")))
+        .unwrap();
+    assert_eq!(0, tree.subtree_paths(e_node).count());
+}
+
+#[test]
+fn path_to_node() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b/c")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let c_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b/c")))
+        .unwrap();
+    let path = tree.path_to_node(c_node);
+    assert_eq!(3, path.len());
+    assert_eq!(
+        vec![
+            (
+                "a",
+                tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+                    .unwrap()
+                    .id
+            ),
+            (
+                "b",
+                tree.find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+                    .unwrap()
+                    .id
+            ),
+            ("c", c_node.id),
+        ],
+        path.map(|HalfEdgeTreeNode { path_segment, node }| (path_segment, node.id))
+            .collect::<Vec<_>>()
+    );
+    // The given node is yielded last.
+    assert_eq!(
+        Some("c"),
+        tree.path_to_node(c_node)
+            .next_back()
+            .map(|edge| edge.path_segment)
+    );
+    assert_eq!(0, tree.path_to_node(tree.root_node()).len());
+}
+
+#[test]
+fn snapshot_token() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b/c")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let token = tree.snapshot_token();
+    let snapshot = tree.clone();
+    assert_eq!(token, snapshot.snapshot_token());
+    assert_eq!(token, tree.deep_clone().snapshot_token());
+
+    // Deep modifications don't affect the root node, but change the token.
+    let root_node = Arc::clone(tree.root_node());
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b/c")),
+        NodeValue::Leaf(2),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    assert!(Arc::ptr_eq(&root_node, tree.root_node()));
+    assert_ne!(token, tree.snapshot_token());
+    assert_eq!(token, snapshot.snapshot_token());
+
+    // Independent modifications of clones result in different tokens.
+    let mut other = snapshot.clone();
+    other
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c")),
+            NodeValue::Leaf(2),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert_ne!(tree.snapshot_token(), other.snapshot_token());
+
+    // Rolling back restores the token.
+    let token = tree.snapshot_token();
+    let _ = tree.with_rollback(|tree| {
+        tree.set_root_value(NodeValue::Inner(1))?;
+        Err::<(), _>(crate::UpdateNodeValueError::ValueTypeMismatch {
+            value: NodeValue::Inner(2),
+        })
+    });
+    assert_eq!(token, tree.snapshot_token());
+}
+
+#[test]
+fn filter_projection() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [
+        ("/a/b/c", 1),
+        ("/a/b/d", 2),
+        ("/a/e", 1),
+        ("/f/g", 1),
+        ("/h/i", 2),
+    ] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let snapshot = tree.clone();
+
+    let projection = tree.filter(|node| matches!(node.node, Node::Leaf(LeafNode { value: 2 })));
+    assert_same_nodes(&snapshot, &tree);
+    let mut paths = projection
+        .nodes_with_paths(projection.root_node())
+        .map(|(path, _)| path.join("/"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(vec!["", "a", "a/b", "a/b/d", "h", "h/i"], paths);
+
+    // Unaffected nodes are shared with the source tree.
+    for path in ["/h", "/h/i", "/a/b/d"] {
+        let path = SlashPath::new(Cow::Borrowed(path));
+        assert!(Arc::ptr_eq(
+            tree.find_node(&path).unwrap(),
+            projection.find_node(&path).unwrap()
+        ));
+    }
+    for path in ["/", "/a", "/a/b"] {
+        let path = SlashPath::new(Cow::Borrowed(path));
+        assert!(!Arc::ptr_eq(
+            tree.find_node(&path).unwrap(),
+            projection.find_node(&path).unwrap()
+        ));
+        assert_eq!(
+            tree.find_node(&path).unwrap().id,
+            projection.find_node(&path).unwrap().id
+        );
+    }
+
+    // The root node is always included.
+    let projection = tree.filter(|_| false);
+    assert_eq!(1, projection.nodes_count().get());
+    assert_eq!(tree.root_node_id(), projection.root_node_id());
+}
+
+#[test]
+fn descendant_nodes_size_hint_and_reverse() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/b/d", "/a/e", "/f", "/g/h"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let root_node = tree.root_node();
+    let node_ids = tree
+        .descendant_nodes(root_node)
+        .map(|child| child.node_id)
+        .collect::<Vec<_>>();
+
+    // The pending children of the root node are a lower bound.
+    let (lower_bound, upper_bound) = tree.descendant_nodes(root_node).size_hint();
+    assert_eq!(3, lower_bound);
+    assert_eq!(None, upper_bound);
+    let leaf_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/f")))
+        .unwrap();
+    assert_eq!((0, Some(0)), tree.descendant_nodes(leaf_node).size_hint());
+
+    let mut counted = tree.descendant_nodes_counted(root_node);
+    assert_eq!(node_ids.len(), counted.len());
+    counted.next().unwrap();
+    assert_eq!(node_ids.len() - 1, counted.len());
+    assert_eq!(
+        node_ids,
+        tree.descendant_nodes_counted(root_node)
+            .map(|child| child.node_id)
+            .collect::<Vec<_>>()
+    );
+
+    let mut reversed_node_ids = tree
+        .descendant_nodes_rev(root_node)
+        .map(|child| child.node_id)
+        .collect::<Vec<_>>();
+    reversed_node_ids.reverse();
+    assert_eq!(node_ids, reversed_node_ids);
+    assert_eq!(
+        node_ids.last(),
+        tree.descendant_nodes_rev(root_node)
+            .next()
+            .map(|child| child.node_id)
+            .as_ref()
+    );
+    assert_eq!(0, tree.descendant_nodes_rev(leaf_node).count());
+}
+
+#[test]
+fn removed_since() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/b/d", "/a/e", "/f"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let older = tree.clone();
+    assert_eq!(1, tree.removed_since(&older).nodes_count().get());
+
+    let b_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .unwrap()
+        .id;
+    tree.remove_subtree_by_id(b_node_id).unwrap();
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/g")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+
+    let removed = tree.removed_since(&older);
+    let mut paths = removed
+        .nodes_with_paths(removed.root_node())
+        .map(|(path, _)| path.join("/"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(vec!["", "a", "a/b", "a/b/c", "a/b/d"], paths);
+    let path = SlashPath::new(Cow::Borrowed("/a/b/c"));
+    assert!(Arc::ptr_eq(
+        older.find_node(&path).unwrap(),
+        removed.find_node(&path).unwrap()
+    ));
+}
+
+#[test]
+fn case_insensitive_child_keys() {
+    #[derive(Debug, Clone, Default)]
+    struct CaseInsensitivePathTreeTypes;
+
+    impl crate::PathTreeTypes for CaseInsensitivePathTreeTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = isize;
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            if path_segment.chars().any(char::is_uppercase) {
+                Cow::Owned(path_segment.to_lowercase())
+            } else {
+                Cow::Borrowed(path_segment)
+            }
+        }
+    }
+
+    let mut tree = crate::PathTree::<CaseInsensitivePathTreeTypes>::new(
+        Default::default(),
+        crate::NodeValue::Inner(0),
+    );
+    let node_id = tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/Docs/ReadMe")),
+            crate::NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap()
+        .node
+        .id;
+    let nodes_count = tree.nodes_count().get();
+
+    // Lookups ignore the case.
+    let node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/docs/README")))
+        .unwrap();
+    assert_eq!(node_id, node.id);
+
+    // Updates retain the original path segments.
+    let updated_node = tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/DOCS/readme")),
+            crate::NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap()
+        .node;
+    assert_eq!(node_id, updated_node.id);
+    assert_eq!(nodes_count, tree.nodes_count().get());
+    assert_eq!(
+        vec!["Docs", "ReadMe"],
+        tree.path_to_node(&updated_node)
+            .map(|HalfEdgeTreeNode { path_segment, .. }| path_segment)
+            .collect::<Vec<_>>()
+    );
+    let HalfEdge {
+        path_segment,
+        child_key,
+        node_id: _,
+    } = tree.root_node().node.children().next().unwrap();
+    assert_eq!("Docs", path_segment);
+    assert_eq!("docs", child_key);
+
+    // Renaming within the same child key only replaces the path segment.
+    let docs_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/docs")))
+            .unwrap(),
+    );
+    let renamed_node = tree
+        .insert_or_update_child_node_value(
+            &docs_node,
+            "README",
+            Some("readme"),
+            crate::NodeValue::Leaf(3),
+        )
+        .unwrap()
+        .node;
+    assert_eq!(node_id, renamed_node.id);
+    assert_eq!(nodes_count, tree.nodes_count().get());
+    assert_eq!(
+        "README",
+        renamed_node.parent.as_ref().unwrap().path_segment.as_ref()
+    );
+    assert!(tree.validate().is_ok());
+}
+
+#[test]
+fn copy_subtree() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/src/a/b", 1), ("/src/a/c/d", 2), ("/dst/e", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let nodes_count = tree.nodes_count().get();
+    let a_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/src/a")))
+            .unwrap(),
+    );
+    let dst_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/dst")))
+            .unwrap(),
+    );
+    let tree_before = tree.clone();
+
+    let copied = tree.copy_subtree(&a_node, &dst_node, "copy").unwrap();
+    assert_eq!(nodes_count + 4, tree.nodes_count().get());
+    assert_ne!(a_node.id, copied.child_node_id);
+    let copied_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/dst/copy")))
+        .unwrap();
+    assert_eq!(copied.child_node_id, copied_node.id);
+    let d_node = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/dst/copy/c/d")))
+        .unwrap();
+    assert!(tree_before.lookup_node(d_node.id).is_none());
+    assert!(matches!(d_node.node, Node::Leaf(LeafNode { value: 2 })));
+    // The source subtree remains unchanged.
+    assert!(Arc::ptr_eq(
+        &a_node,
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/src/a")))
+            .unwrap()
+    ));
+
+    // Copy a subtree into itself.
+    tree.copy_subtree(&a_node, &a_node, "nested").unwrap();
+    assert_eq!(nodes_count + 8, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/a/nested/c/d")))
+        .is_some());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/src/a/nested/nested")))
+        .is_none());
+    assert!(tree.validate().is_ok());
+}
+
+#[test]
+fn find_ancestor() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/repo/.git", "/repo/src/module/file", "/other/file"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let is_repo = |node: &Arc<TreeNode>| node.node.find_child(".git").is_some();
+
+    let file_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/repo/src/module/file")))
+            .unwrap(),
+    );
+    let repo_node = tree.find_ancestor(&file_node, is_repo).unwrap();
+    assert_eq!(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/repo")))
+            .unwrap()
+            .id,
+        repo_node.id
+    );
+    // The given node itself is not visited.
+    assert!(tree.find_ancestor(repo_node, is_repo).is_none());
+
+    let other_file_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/other/file")))
+            .unwrap(),
+    );
+    assert!(tree.find_ancestor(&other_file_node, is_repo).is_none());
+    // The root node is visited last.
+    let mut visited_node_ids = Vec::new();
+    let root_node = tree.find_ancestor(&other_file_node, |node| {
+        visited_node_ids.push(node.id);
+        node.parent.is_none()
+    });
+    assert_eq!(Some(tree.root_node_id()), root_node.map(|node| node.id));
+    assert_eq!(2, visited_node_ids.len());
+}
+
+#[test]
+fn merge() {
+    fn new_tree(leaves: &[(&'static str, usize)]) -> PathTree {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        for (path, value) in leaves {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(*value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+        }
+        tree
+    }
+    let find_leaf_value = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .and_then(|node| match node.node {
+                Node::Leaf(LeafNode { value }) => Some(value),
+                Node::Inner(_) => None,
+            })
+    };
+
+    let ours = new_tree(&[("/a/x", 1), ("/a/same", 0), ("/b", 2), ("/c/d", 3)]);
+    let theirs = new_tree(&[
+        ("/a/x", 5),
+        ("/a/same", 0),
+        ("/a/y", 4),
+        ("/b/z", 6),
+        ("/c", 7),
+        ("/e/f", 8),
+    ]);
+
+    // Rejected conflicts are reported and leave the tree unchanged.
+    let mut tree = ours.clone();
+    let err = tree.merge(&theirs, &mut RejectConflicts).unwrap_err();
+    let crate::MergeError::Unresolved { mut paths } = err else {
+        unreachable!();
+    };
+    paths.sort();
+    assert_eq!(
+        vec![vec!["a", "x"], vec!["b"], vec!["c"]],
+        paths
+            .iter()
+            .map(|path| path.iter().map(AsRef::as_ref).collect::<Vec<&str>>())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(0, tree.rewritten_nodes_count(&ours));
+
+    let mut tree = ours.clone();
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    tree.merge(&theirs, &mut PreferOurs).unwrap();
+    assert_eq!(Some(1), find_leaf_value(&tree, "/a/x"));
+    assert_eq!(Some(4), find_leaf_value(&tree, "/a/y"));
+    assert_eq!(Some(2), find_leaf_value(&tree, "/b"));
+    assert_eq!(Some(3), find_leaf_value(&tree, "/c/d"));
+    assert_eq!(Some(8), find_leaf_value(&tree, "/e/f"));
+    assert_eq!(
+        a_node_id,
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+            .unwrap()
+            .id
+    );
+
+    let mut tree = ours.clone();
+    let mut conflicts_count = 0;
+    tree.merge(&theirs, &mut |conflict: crate::ValueConflict<
+        '_,
+        PathTreeTypes,
+    >| {
+        conflicts_count += 1;
+        if conflict.path == [Cow::Borrowed("a"), Cow::Borrowed("x")] {
+            ConflictResolution::Value(NodeValue::Leaf(9))
+        } else {
+            ConflictResolution::Theirs
+        }
+    })
+    .unwrap();
+    assert_eq!(3, conflicts_count);
+    assert_eq!(Some(9), find_leaf_value(&tree, "/a/x"));
+    assert_eq!(Some(0), find_leaf_value(&tree, "/a/same"));
+    // Leaf nodes are replaced by inner nodes and vice versa.
+    assert_eq!(Some(6), find_leaf_value(&tree, "/b/z"));
+    assert_eq!(Some(7), find_leaf_value(&tree, "/c"));
+    assert!(find_leaf_value(&tree, "/c/d").is_none());
+    assert!(tree.validate().is_ok());
+}
+
+#[test]
+fn merge_unchanged() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/x", 1), ("/a/y", 2), ("/b", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    // Shared nodes are never in conflict.
+    let snapshot = tree.clone();
+    tree.merge(&snapshot, &mut RejectConflicts).unwrap();
+    assert_eq!(0, tree.rewritten_nodes_count(&snapshot));
+
+    // Modified descendants of shared nodes are still merged.
+    let mut theirs = snapshot.clone();
+    theirs
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/b")),
+            NodeValue::Leaf(4),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert!(Arc::ptr_eq(tree.root_node(), theirs.root_node()));
+    let mut merged = tree.clone();
+    assert!(merged.merge(&theirs, &mut RejectConflicts).is_err());
+    merged.merge(&theirs, &mut PreferTheirs).unwrap();
+    assert_eq!(
+        Some(&4),
+        merged
+            .find_node(&SlashPath::new(Cow::Borrowed("/b")))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+
+    // Nodes with equal values are not in conflict.
+    let mut other = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/y", 2), ("/a/x", 1), ("/b", 3)] {
+        other
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                NodeValue::Leaf(value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+    }
+    tree.merge(&other, &mut RejectConflicts).unwrap();
+    assert_eq!(0, tree.rewritten_nodes_count(&snapshot));
+}
+
+#[test]
+fn root_path_policy() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert_eq!(RootPathPolicy::UpdateValue, tree.root_path_policy());
+    let updated = tree
+        .insert_or_update_node_value(&SlashPath::ROOT, NodeValue::Inner(1), &mut || -1, |_| None)
+        .unwrap();
+    assert!(updated.is_root());
+    assert!(updated.parent.is_none());
+    let inserted = tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert!(!inserted.is_root());
+
+    tree.set_root_path_policy(RootPathPolicy::Reject);
+    let err = tree
+        .insert_or_update_node_value(&SlashPath::ROOT, NodeValue::Inner(2), &mut || -1, |_| None)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        crate::InsertOrUpdateNodeValueError::RootPath {
+            value: NodeValue::Inner(2)
+        }
+    ));
+    assert!(matches!(
+        tree.try_insert_or_update_node_value(
+            &SlashPath::ROOT,
+            NodeValue::Inner(2),
+            &mut || Ok::<_, ()>(-1),
+            |_| Ok(None),
+        ),
+        Err(TryInsertOrUpdateNodeValueError::RootPath { .. })
+    ));
+    assert_eq!(Some(&1), tree.root_node().node.inner_value());
+    // Other paths and explicit updates of the root node are not affected.
+    assert!(tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a")),
+            NodeValue::Leaf(2),
+            &mut || -1,
+            |_| None,
+        )
+        .is_ok());
+    tree.set_root_value(NodeValue::Inner(3)).unwrap();
+    assert_eq!(Some(&3), tree.root_node().node.inner_value());
+    // The policy is retained by clones.
+    assert_eq!(RootPathPolicy::Reject, tree.clone().root_path_policy());
+}
+
+#[test]
+fn splice_node() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/b/d/e", "/a/x", "/p/q/x", "/p/x", "/m/n/n"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let find_node_id = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .map(|node| node.id)
+    };
+    let nodes_count = tree.nodes_count().get();
+    let b_node_id = find_node_id(&tree, "/a/b").unwrap();
+    let c_node_id = find_node_id(&tree, "/a/b/c").unwrap();
+    let e_node_id = find_node_id(&tree, "/a/b/d/e").unwrap();
+
+    let removed_node = tree.splice_node(b_node_id).unwrap();
+    assert_eq!(b_node_id, removed_node.id);
+    assert_eq!(nodes_count - 1, tree.nodes_count().get());
+    assert!(find_node_id(&tree, "/a/b").is_none());
+    assert_eq!(Some(c_node_id), find_node_id(&tree, "/a/c"));
+    assert_eq!(Some(e_node_id), find_node_id(&tree, "/a/d/e"));
+
+    // A child node with the same path segment as the removed node.
+    let inner_n_node_id = find_node_id(&tree, "/m/n/n").unwrap();
+    tree.splice_node(find_node_id(&tree, "/m/n").unwrap())
+        .unwrap();
+    assert_eq!(Some(inner_n_node_id), find_node_id(&tree, "/m/n"));
+    assert!(tree.validate().is_ok());
+
+    let tree_before = tree.clone();
+    assert!(matches!(
+        tree.splice_node(find_node_id(&tree, "/p/q").unwrap()),
+        Err(crate::SpliceNodeError::Occupied { node_id }) if Some(node_id) == find_node_id(&tree, "/p/x")
+    ));
+    assert!(matches!(
+        tree.splice_node(tree.root_node_id()),
+        Err(crate::SpliceNodeError::RootNode)
+    ));
+    assert!(matches!(
+        tree.splice_node(b_node_id),
+        Err(crate::SpliceNodeError::NotFound)
+    ));
+    let a_node_id = find_node_id(&tree, "/a").unwrap();
+    tree.freeze_subtree(e_node_id);
+    assert!(matches!(
+        tree.splice_node(a_node_id),
+        Err(crate::SpliceNodeError::SubtreeFrozen { node_id }) if node_id == e_node_id
+    ));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-invariants"))]
+#[should_panic(expected = "duplicate node id 0")]
+fn duplicate_node_id() {
+    #[derive(Debug, Clone, Default)]
+    struct ConstantNodeId;
+
+    impl crate::NewNodeId<usize> for ConstantNodeId {
+        fn new_node_id(&mut self) -> usize {
+            0
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct ConstantNodeIdTypes;
+
+    impl crate::PathTreeTypes for ConstantNodeIdTypes {
+        type NodeId = usize;
+        type NewNodeId = ConstantNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = isize;
+        type LeafValue = usize;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+    }
+
+    let mut tree =
+        crate::PathTree::<ConstantNodeIdTypes>::new(Default::default(), crate::NodeValue::Inner(0));
+    // The new node would silently replace the root node.
+    drop(tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a")),
+        crate::NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    ));
+}
+
+#[test]
+fn extend_from_iter() {
+    let entries = [
+        ("/b/y", NodeValue::Leaf(1)),
+        ("/a/x", NodeValue::Leaf(2)),
+        ("/b/x", NodeValue::Leaf(3)),
+        ("/a", NodeValue::Inner(4)),
+        ("/b/y", NodeValue::Leaf(5)),
+        ("/a/y/z", NodeValue::Leaf(6)),
+    ];
+    let tree = PathTree::try_from_iter(
+        Default::default(),
+        NodeValue::Inner(0),
+        entries
+            .iter()
+            .map(|(path, value)| (SlashPath::new(Cow::Borrowed(*path)), value.clone())),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let mut expected_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in &entries {
+        expected_tree
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed(path)),
+                value.clone(),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+    }
+    assert_eq!(expected_tree.nodes_count(), tree.nodes_count());
+    for path in ["/a", "/a/x", "/a/y", "/a/y/z", "/b", "/b/x", "/b/y"] {
+        let path = SlashPath::new(Cow::Borrowed(path));
+        let expected_node = expected_tree.find_node(&path).unwrap();
+        let node = tree.find_node(&path).unwrap();
+        assert_eq!(expected_node.node.inner_value(), node.node.inner_value());
+        assert_eq!(expected_node.node.leaf_value(), node.node.leaf_value());
+    }
+    // The last value of duplicate paths wins.
+    assert_eq!(
+        Some(&5),
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/b/y")))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+    assert!(tree.validate().is_ok());
+
+    // All or nothing.
+    let mut tree = tree;
+    let tree_before = tree.clone();
+    assert!(matches!(
+        tree.extend_from_iter(
+            [
+                (SlashPath::new(Cow::Borrowed("/c")), NodeValue::Leaf(7)),
+                (SlashPath::new(Cow::Borrowed("/b/x/z")), NodeValue::Leaf(8)),
+            ],
+            &mut || -1,
+            |_| None,
+        ),
+        Err(crate::InsertOrUpdateNodeValueError::PathConflict {
+            value: NodeValue::Leaf(8),
+            ..
+        })
+    ));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/c")))
+        .is_none());
+}
+
+#[test]
+fn reassign_ids() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/x", "/a/b/c", "/a/b/d", "/a/e"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    let SubtreeRemoved {
+        removed_subtree: mut subtree,
+        ..
+    } = tree.remove_subtree_by_id(a_node_id).unwrap();
+    let b_node_id = subtree
+        .find_node(&SlashPath::new(Cow::Borrowed("/b")))
+        .unwrap()
+        .id;
+    subtree.freeze_subtree(b_node_id);
+    let subtree_before = subtree.clone();
+
+    let id_map = subtree.reassign_ids(Default::default());
+    assert_eq!(subtree_before.nodes_count().get(), id_map.len());
+    assert_eq!(Some(&0), id_map.get(&a_node_id));
+    assert_eq!(0, subtree.root_node_id());
+    let mut node_ids = subtree.nodes().map(|node| node.id).collect::<Vec<_>>();
+    node_ids.sort_unstable();
+    assert_eq!((0..id_map.len()).collect::<Vec<_>>(), node_ids);
+    for old_node in subtree_before.nodes() {
+        let new_node = subtree.lookup_node(id_map[&old_node.id]).unwrap();
+        assert_eq!(
+            old_node
+                .parent
+                .as_ref()
+                .map(|parent| id_map[&parent.node_id]),
+            new_node.parent.as_ref().map(|parent| parent.node_id)
+        );
+        assert_eq!(old_node.node.inner_value(), new_node.node.inner_value());
+        assert_eq!(old_node.node.leaf_value(), new_node.node.leaf_value());
+    }
+    assert_eq!(
+        vec![&id_map[&b_node_id]],
+        subtree.frozen_node_ids().collect::<Vec<_>>()
+    );
+    assert!(subtree.validate().is_ok());
+
+    // The generator continues after the reassigned ids.
+    let NodeInsertedOrUpdated { node, .. } = subtree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/f")),
+            NodeValue::Leaf(2),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    assert_eq!(id_map.len(), node.id);
+}
+
+#[test]
+fn export_order() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/c", "/a/z", "/b", "/a/y", "/e", "/a/x", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let expected_paths = ["", "/a", "/a/x", "/a/y", "/a/z", "/b", "/c", "/d", "/e"];
+    assert_eq!(
+        expected_paths.to_vec(),
+        tree.to_materialized_paths('/', crate::ExportOrder::sorted())
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>()
+    );
+    let row_paths = tree
+        .to_rows(crate::ExportOrder::sorted())
+        .map(|row| {
+            let node = tree.lookup_node(row.node_id).unwrap();
+            let mut path_segments = tree
+                .ancestor_nodes(node)
+                .map(|ancestor| ancestor.path_segment)
+                .collect::<Vec<_>>();
+            path_segments.reverse();
+            path_segments
+                .into_iter()
+                .fold(String::new(), |mut path, path_segment| {
+                    path.push('/');
+                    path.push_str(path_segment);
+                    path
+                })
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(expected_paths.to_vec(), row_paths);
+    // The closure table starts with the pairs of each node at depth 0.
+    assert_eq!(
+        tree.to_rows(crate::ExportOrder::sorted())
+            .map(|row| row.node_id)
+            .collect::<Vec<_>>(),
+        tree.to_closure_table(crate::ExportOrder::sorted())
+            .filter(|row| row.depth == 0)
+            .map(|row| row.descendant_node_id)
+            .collect::<Vec<_>>()
+    );
+
+    // Custom order
+    assert_eq!(
+        ["", "/e", "/d", "/c", "/b", "/a", "/a/z", "/a/y", "/a/x"].to_vec(),
+        tree.to_materialized_paths(
+            '/',
+            crate::ExportOrder::SortedBy(|lhs: &str, rhs: &str| rhs.cmp(lhs))
+        )
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>()
+    );
+
+    let mut paths = tree
+        .to_materialized_paths('/', crate::ExportOrder::Unordered)
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    assert_eq!("", paths[0]);
+    paths.sort_unstable();
+    assert_eq!(expected_paths.to_vec(), paths);
+}
+
+#[test]
+fn debug_output_is_reproducible() {
+    let new_tree = || {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        for i in 0..20 {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Owned(format!("/a/{i}"))),
+                NodeValue::Leaf(i),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+        }
+        let node_id = tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/1")))
+            .unwrap()
+            .id;
+        tree.freeze_subtree(node_id);
+        tree
+    };
+    assert_eq!(format!("{:?}", new_tree()), format!("{:?}", new_tree()));
+}
+
+#[test]
+fn transaction() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let tree_before = tree.clone();
+    let err = tree
+        .transaction(|tx| {
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo")),
+                NodeValue::Leaf(1),
+                &mut || unreachable!(),
+                |_| None,
+            )?;
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo/bar")),
+                NodeValue::Leaf(2),
+                &mut || unreachable!(),
+                |_| None,
+            )
+        })
+        .unwrap_err();
+    assert!(matches!(err.into_value(), NodeValue::Leaf(2)));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    // The tree remains unchanged if the closure panics.
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        tree.with_rollback(|tx| {
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo")),
+                NodeValue::Leaf(1),
+                &mut || unreachable!(),
+                |_| None,
+            )?;
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/bar/baz")),
+                NodeValue::Leaf(2),
+                &mut || unreachable!(),
+                |_| None,
+            )
+        })
+    }))
+    .is_err();
+    assert!(panicked);
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    let removed_nodes_count = tree
+        .transaction(|tx| {
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/foo/bar")),
+                NodeValue::Leaf(1),
+                &mut || -1,
+                |_| None,
+            )?;
+            let foo_node_id = tx
+                .find_node(&SlashPath::new(Cow::Borrowed("/foo")))
+                .unwrap()
+                .id;
+            tx.insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/baz")),
+                NodeValue::Leaf(2),
+                &mut || unreachable!(),
+                |_| None,
+            )?;
+            Ok::<_, crate::InsertOrUpdateNodeValueError<PathTreeTypes>>(
+                tx.remove_subtree_by_id(foo_node_id)
+                    .unwrap()
+                    .removed_subtree
+                    .nodes_count()
+                    .get(),
+            )
+        })
+        .unwrap();
+    assert_eq!(2, removed_nodes_count);
+    assert_eq!(2, tree.nodes_count().get());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/baz")))
+        .is_some());
+}
+
+#[test]
+fn value_sharing_report() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let snapshot = tree.clone();
+    assert_eq!(
+        crate::ValueSharingReport {
+            shared_inner_nodes_count: 2,
+            unique_inner_nodes_count: 0,
+            shared_leaf_nodes_count: 3,
+            unique_leaf_nodes_count: 0,
+        },
+        tree.value_sharing_report(&snapshot)
+    );
+
+    // Updates the leaf node in place, i.e. its parent node remains shared.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        NodeValue::Leaf(2),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let report = tree.value_sharing_report(&snapshot);
+    assert_eq!(0, report.unique_inner_nodes_count);
+    assert_eq!(1, report.unique_leaf_nodes_count);
+    assert_eq!(4, report.shared_nodes_count());
+
+    // A deep clone does not share any nodes.
+    let report = tree.deep_clone().value_sharing_report(&tree);
+    assert_eq!(0, report.shared_nodes_count());
+    assert_eq!(tree.nodes_count().get(), report.unique_nodes_count());
+}
+
+#[test]
+fn map_values() {
+    #[derive(Debug, Clone, Default)]
+    struct StringValueTypes;
+
+    impl crate::PathTreeTypes for StringValueTypes {
+        type NodeId = usize;
+        type NewNodeId = NewNodeId;
+        type PathSegmentOwned = Cow<'static, str>;
+        type PathSegment = str;
+        type RootPath = SlashPath<'static>;
+        type ChildKey = str;
+        type ChildKeyOwned = String;
+        type InnerValue = String;
+        type LeafValue = String;
+
+        fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned {
+            Cow::Owned(path_segment.to_owned())
+        }
+
+        fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey> {
+            Cow::Borrowed(path_segment)
+        }
+    }
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/a/c", 2), ("/d", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let a_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    tree.freeze_subtree(a_node_id);
+    let snapshot = tree.clone();
+
+    let mut mapped_tree = tree.map_values::<StringValueTypes>(
+        |value| format!("inner {value}"),
+        |value| format!("leaf {value}"),
+    );
+    assert!(mapped_tree.validate().is_ok());
+    assert_eq!(snapshot.root_node_id(), mapped_tree.root_node_id());
+    assert_eq!(snapshot.nodes_count(), mapped_tree.nodes_count());
+    for node in snapshot.nodes() {
+        let mapped_node = mapped_tree.lookup_node(node.id).unwrap();
+        assert_eq!(
+            node.parent
+                .as_ref()
+                .map(|parent| (&parent.path_segment, parent.node_id)),
+            mapped_node
+                .parent
+                .as_ref()
+                .map(|parent| (&parent.path_segment, parent.node_id))
+        );
+        assert_eq!(
+            node.node
+                .inner_value()
+                .map(|value| format!("inner {value}")),
+            mapped_node.node.inner_value().cloned()
+        );
+        assert_eq!(
+            node.node.leaf_value().map(|value| format!("leaf {value}")),
+            mapped_node.node.leaf_value().cloned()
+        );
+    }
+    assert_eq!(
+        Some("leaf 2"),
+        mapped_tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/c")))
+            .and_then(|node| node.node.leaf_value())
+            .map(String::as_str)
+    );
+    assert_eq!(
+        vec![&a_node_id],
+        mapped_tree.frozen_node_ids().collect::<Vec<_>>()
+    );
+
+    // The id generator continues after the existing ids.
+    let NodeInsertedOrUpdated { node, .. } = mapped_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/e")),
+            crate::NodeValue::Leaf("e".to_owned()),
+            &mut String::new,
+            |_| None,
+        )
+        .unwrap();
+    assert!(snapshot.lookup_node(node.id).is_none());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn simple_json_tree() {
+    use serde_json::json;
+
+    use crate::{SimpleJsonTree, SimplePath};
+
+    let mut tree = SimpleJsonTree::new(Default::default(), crate::NodeValue::Inner(json!({})));
+    tree.insert_or_update_node_value(
+        &SimplePath::new(["a", "b"]),
+        crate::NodeValue::Leaf(json!([1, 2])),
+        &mut || json!(null),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(3, tree.nodes_count().get());
+    let a_node = tree.find_node(&SimplePath::new(["a"])).unwrap();
+    assert_eq!(Some(&json!(null)), a_node.node.inner_value());
+
+    // Updating a node with an equal value does not modify the tree.
+    let tree_before = tree.clone();
+    tree.insert_or_update_node_value(
+        &SimplePath::new(["a", "b"]),
+        crate::NodeValue::Leaf(json!([1, 2])),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    assert!(tree
+        .find_node(&SimplePath::default())
+        .is_some_and(|node| node.id == tree.root_node_id()));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serialize_events() {
+    use serde_json::json;
+
+    use crate::{SimpleJsonTree, SimpleJsonTreeTypes, SimplePath};
+
+    type EventSourcedTree = crate::EventSourcedTree<SimpleJsonTreeTypes>;
+
+    let initial_tree = SimpleJsonTree::new(Default::default(), crate::NodeValue::Inner(json!({})));
+    let mut event_sourced_tree = EventSourcedTree::new(initial_tree.clone());
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SimplePath::new(["a", "b"]),
+            crate::NodeValue::Leaf(json!(1)),
+            &mut || json!({ "created": true }),
+            |_| None,
+        )
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SimplePath::new(["a", "b", "c"]),
+            crate::NodeValue::Leaf(json!("c")),
+            &mut || json!(null),
+            |leaf_value| Some(json!({ "leaf": leaf_value })),
+        )
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_child_node_value(
+            &SimplePath::new(["a"]),
+            "d",
+            Some("b"),
+            crate::NodeValue::Inner(json!([])),
+        )
+        .unwrap()
+        .unwrap();
+    event_sourced_tree
+        .insert_or_update_node_value(
+            &SimplePath::new(["e"]),
+            crate::NodeValue::Leaf(json!(2)),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    event_sourced_tree
+        .remove_subtree(&SimplePath::new(["e"]))
+        .unwrap();
+    assert_eq!(5, event_sourced_tree.events().len());
+
+    let json = serde_json::to_string(event_sourced_tree.events()).unwrap();
+    let events: Vec<crate::PathTreeEvent<SimpleJsonTreeTypes>> =
+        serde_json::from_str(&json).unwrap();
+    assert_eq!(json, serde_json::to_string(&events).unwrap());
+
+    let replayed_tree = EventSourcedTree::replay(initial_tree, events)
+        .unwrap()
+        .into_tree_and_events()
+        .0;
+    let tree = event_sourced_tree.tree();
+    assert_eq!(tree.nodes_count(), replayed_tree.nodes_count());
+    for node in tree.nodes() {
+        let replayed_node = replayed_tree.lookup_node(node.id).unwrap();
+        assert_eq!(node.parent, replayed_node.parent);
+        assert_eq!(node.node.inner_value(), replayed_node.node.inner_value());
+        assert_eq!(node.node.leaf_value(), replayed_node.node.leaf_value());
+    }
+    let renamed_node = replayed_tree
+        .find_node(&SimplePath::new(["a", "d"]))
+        .unwrap();
+    assert_eq!(Some(&json!([])), renamed_node.node.inner_value());
+}
+
+#[test]
+fn filter_map_leaves() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [
+        ("/a/b/c", 1),
+        ("/a/b/d", 3),
+        ("/a/e", 2),
+        ("/f/g", 5),
+        ("/h/i", 7),
+    ] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/empty")),
+        NodeValue::Inner(-1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    let h_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/h")))
+        .unwrap()
+        .id;
+    tree.freeze_subtree(h_node_id);
+    let find_leaf_value = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .and_then(|node| node.node.leaf_value().copied())
+    };
+    let contains_node = |tree: &PathTree, path| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .is_some()
+    };
+
+    // Remove odd values and double even values.
+    let map_leaf_value = |value: &usize| (value % 2 == 0).then_some(value * 2);
+    let mut retained_tree = tree.clone();
+    assert_eq!(
+        3,
+        retained_tree.filter_map_leaves(map_leaf_value, crate::EmptyInnerNodes::Retain)
+    );
+    assert_eq!(Some(4), find_leaf_value(&retained_tree, "/a/e"));
+    assert!(contains_node(&retained_tree, "/a/b"));
+    assert!(!contains_node(&retained_tree, "/a/b/c"));
+    assert!(contains_node(&retained_tree, "/f"));
+    assert!(contains_node(&retained_tree, "/empty"));
+    // Frozen subtrees remain unchanged.
+    assert_eq!(Some(7), find_leaf_value(&retained_tree, "/h/i"));
+    assert!(retained_tree.validate().is_ok());
+
+    let mut pruned_tree = tree.clone();
+    assert_eq!(
+        5,
+        pruned_tree.filter_map_leaves(map_leaf_value, crate::EmptyInnerNodes::Prune)
+    );
+    assert_eq!(Some(4), find_leaf_value(&pruned_tree, "/a/e"));
+    assert!(!contains_node(&pruned_tree, "/a/b"));
+    assert!(!contains_node(&pruned_tree, "/f"));
+    // Inner nodes that have been empty before are retained.
+    assert!(contains_node(&pruned_tree, "/empty"));
+    assert_eq!(Some(7), find_leaf_value(&pruned_tree, "/h/i"));
+    assert_eq!(
+        tree.nodes_count().get() - 5,
+        pruned_tree.nodes_count().get()
+    );
+    assert!(pruned_tree.validate().is_ok());
+}
+
+#[test]
+fn operation_recorder() {
+    let mut recorder =
+        crate::OperationRecorder::new(PathTree::new(Default::default(), NodeValue::Inner(0)));
+    recorder
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    // Failed operations are recorded as well.
+    assert!(recorder
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c")),
+            NodeValue::Leaf(2),
+            &mut || -1,
+            |_| None,
+        )
+        .is_err());
+    assert!(recorder
+        .insert_or_update_child_node_value(
+            &SlashPath::new(Cow::Borrowed("/x")),
+            "y",
+            None,
+            NodeValue::Leaf(3)
+        )
+        .unwrap()
+        .is_none());
+    recorder
+        .insert_or_update_child_node_value(
+            &SlashPath::new(Cow::Borrowed("/a")),
+            "d",
+            Some("b"),
+            NodeValue::Leaf(4),
+        )
+        .unwrap()
+        .unwrap();
+    assert!(recorder
+        .remove_subtree(&SlashPath::new(Cow::Borrowed("/a/b")))
+        .is_none());
+    recorder
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/e/f")),
+            NodeValue::Leaf(5),
+            &mut || -2,
+            |_| None,
+        )
+        .unwrap();
+    assert!(recorder
+        .remove_subtree(&SlashPath::new(Cow::Borrowed("/e")))
+        .is_some());
+
+    let initial_tree = recorder.initial_tree().clone();
+    let tree = recorder.tree().clone();
+    let script = recorder.into_script();
+    assert_eq!(
+        vec![true, false, false, true, false, true, true],
+        script
+            .operations
+            .iter()
+            .map(|operation| operation.succeeded)
+            .collect::<Vec<_>>()
+    );
+    let replayed_tree = script.replay(initial_tree.clone()).unwrap();
+    assert_same_nodes(&tree, &replayed_tree);
+
+    // Replaying the script on a different tree diverges.
+    let mut different_tree = initial_tree;
+    different_tree
+        .insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/x")),
+            NodeValue::Inner(-1),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    let err = script.replay(different_tree).unwrap_err();
+    assert_eq!(2, err.index);
+    assert_eq!(4, err.tree.nodes_count().get());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serialize_operation_script() {
+    use serde_json::json;
+
+    use crate::{
+        OperationRecorder, OperationScript, SimpleJsonTree, SimpleJsonTreeTypes, SimplePath,
+    };
+
+    let mut recorder = OperationRecorder::new(SimpleJsonTree::new(
+        Default::default(),
+        crate::NodeValue::Inner(json!({})),
+    ));
+    recorder
+        .insert_or_update_node_value(
+            &SimplePath::new(["a", "b"]),
+            crate::NodeValue::Leaf(json!(1)),
+            &mut || json!(null),
+            |_| None,
+        )
+        .unwrap();
+    assert!(recorder
+        .insert_or_update_node_value(
+            &SimplePath::new(["a", "b", "c"]),
+            crate::NodeValue::Leaf(json!(2)),
+            &mut || json!(null),
+            |_| None,
+        )
+        .is_err());
+    let initial_tree = recorder.initial_tree().clone();
+    let tree = recorder.tree().clone();
+
+    let json = serde_json::to_string(recorder.script()).unwrap();
+    let script: OperationScript<SimpleJsonTreeTypes> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        vec![true, false],
+        script
+            .operations
+            .iter()
+            .map(|operation| operation.succeeded)
+            .collect::<Vec<_>>()
+    );
+    let replayed_tree = script.replay(initial_tree).unwrap();
+    assert_eq!(tree.nodes_count(), replayed_tree.nodes_count());
+    let node = replayed_tree
+        .find_node(&SimplePath::new(["a", "b"]))
+        .unwrap();
+    assert_eq!(Some(&json!(1)), node.node.leaf_value());
+}
+
+#[test]
+fn entry() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let path = SlashPath::new(Cow::Borrowed("/a/b"));
+
+    // Vacant: Inserted with missing ancestors.
+    let node = tree
+        .entry(&path)
+        .and_modify(|_| unreachable!())
+        .unwrap()
+        .or_insert_with(|| NodeValue::Leaf(1), &mut || -1)
+        .unwrap();
+    assert_eq!(Some(&1), node.node.leaf_value());
+    assert_eq!(3, tree.nodes_count().get());
+    assert_eq!(
+        Some(&-1),
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+            .unwrap()
+            .node
+            .inner_value()
+    );
+
+    // Occupied: Modified in place.
+    let node = tree
+        .entry(&path)
+        .and_modify(|value| {
+            let NodeValue::Leaf(value) = value else {
+                unreachable!();
+            };
+            *value += 1;
+        })
+        .unwrap()
+        .or_insert_with(|| unreachable!(), &mut || unreachable!())
+        .unwrap();
+    assert_eq!(Some(&2), node.node.leaf_value());
+    assert_eq!(Some(&2), tree.find_node(&path).unwrap().node.leaf_value());
+
+    // Changing the node type of a leaf with children is rejected.
+    assert!(tree
+        .entry(&SlashPath::new(Cow::Borrowed("/a")))
+        .and_modify(|value| *value = NodeValue::Leaf(3))
+        .is_err());
+
+    // Leaf nodes on the path are not replaced.
+    let crate::Entry::Vacant(entry) = tree.entry(&SlashPath::new(Cow::Borrowed("/a/b/c"))) else {
+        unreachable!();
+    };
+    assert_eq!(&SlashPath::new(Cow::Borrowed("/a/b/c")), entry.path());
+    assert!(entry.insert(NodeValue::Leaf(4), &mut || -1).is_err());
+    assert_eq!(3, tree.nodes_count().get());
+
+    // Occupied: Removed with its subtree.
+    let crate::Entry::Occupied(entry) = tree.entry(&SlashPath::new(Cow::Borrowed("/a"))) else {
+        unreachable!();
+    };
+    assert_eq!(Some(&-1), entry.node().node.inner_value());
+    let removed = entry.remove().unwrap();
+    assert_eq!(2, removed.removed_subtree.nodes_count().get());
+    assert_eq!(1, tree.nodes_count().get());
+    assert!(matches!(tree.entry(&path), crate::Entry::Vacant(_)));
+}
+
+#[test]
+fn update_node_value_with() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let path = SlashPath::new(Cow::Borrowed("/a/b"));
+    let increment = |value: Option<&NodeValue>| match value {
+        None => NodeValue::Leaf(1),
+        Some(NodeValue::Leaf(value)) => NodeValue::Leaf(value + 1),
+        Some(NodeValue::Inner(_)) => unreachable!(),
+    };
+
+    // Missing nodes are inserted.
+    let inserted = tree
+        .update_node_value_with(&path, increment, &mut || -1, |_| None)
+        .unwrap();
+    assert_eq!(Some(&1), inserted.node.node.leaf_value());
+    assert_eq!(3, tree.nodes_count().get());
+
+    // Existing nodes are updated, starting from their current value.
+    let updated = tree
+        .update_node_value_with(&path, increment, &mut || unreachable!(), |_| None)
+        .unwrap();
+    assert_eq!(inserted.node.id, updated.node.id);
+    assert_eq!(Some(&2), updated.node.node.leaf_value());
+    assert_eq!(Some(&2), tree.find_node(&path).unwrap().node.leaf_value());
+
+    // The root node is addressed by the root path.
+    tree.update_node_value_with(
+        &SlashPath::new(Cow::Borrowed("/")),
+        |value| match value {
+            Some(NodeValue::Inner(value)) => NodeValue::Inner(value - 1),
+            _ => unreachable!(),
+        },
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(Some(&-1), tree.root_node().node.inner_value());
+}
+
+#[test]
+fn stale_node_reference() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a/b")),
+        NodeValue::Leaf(1),
+        &mut || -1,
+        |_| None,
+    )
+    .unwrap();
+    let stale_parent_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+            .unwrap(),
+    );
+    let stale_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+            .unwrap(),
+    );
+
+    // Adding a child node replaces the parent node.
+    tree.insert_or_update_child_node_value(&stale_parent_node, "c", None, NodeValue::Leaf(2))
+        .unwrap();
+    // Updating a value replaces the node.
+    tree.update_node_value(&stale_node, NodeValue::Leaf(3))
+        .unwrap();
+    let tree_before = tree.clone();
+
+    assert!(matches!(
+        tree.update_node_value(&stale_node, NodeValue::Leaf(4)),
+        Err(crate::UpdateNodeValueError::StaleNodeReference { node_id, value: NodeValue::Leaf(4) })
+            if node_id == stale_node.id
+    ));
+    assert!(matches!(
+        tree.insert_or_update_child_node_value(&stale_parent_node, "d", None, NodeValue::Leaf(5)),
+        Err(crate::InsertOrUpdateNodeValueError::StaleNodeReference { node_id, value: NodeValue::Leaf(5) })
+            if node_id == stale_parent_node.id
+    ));
+    assert!(matches!(
+        tree.insert_or_replace_subtree(
+            &stale_parent_node,
+            "d",
+            None,
+            PathTree::new(Default::default(), NodeValue::Inner(6)),
+        ),
+        Err(crate::InsertOrUpdateNodeValueError::StaleNodeReference { node_id, value: NodeValue::Inner(6) })
+            if node_id == stale_parent_node.id
+    ));
+    let root_node = Arc::clone(tree.root_node());
+    assert!(matches!(
+        tree.copy_subtree(&stale_node, &root_node, "d"),
+        Err(crate::InsertOrUpdateNodeValueError::StaleNodeReference { node_id, value: NodeValue::Leaf(1) })
+            if node_id == stale_node.id
+    ));
+    assert!(tree.clone().into_append_cursor(&stale_parent_node).is_err());
+
+    // The tree remains unchanged.
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+    assert_same_nodes(&tree_before, &tree);
+
+    // Current nodes are accepted.
+    let node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+            .unwrap(),
+    );
+    assert_eq!(
+        Some(&4),
+        tree.update_node_value(&node, NodeValue::Leaf(4))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+}
+
+#[test]
+fn swap_subtrees() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b/c", 1), ("/a/d", 2), ("/e", 3)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let find_node_id = |tree: &PathTree, path: &'static str| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .map(|node| node.id)
+    };
+    let a_id = find_node_id(&tree, "/a").unwrap();
+    let b_id = find_node_id(&tree, "/a/b").unwrap();
+    let c_id = find_node_id(&tree, "/a/b/c").unwrap();
+    let d_id = find_node_id(&tree, "/a/d").unwrap();
+    let e_id = find_node_id(&tree, "/e").unwrap();
+    let tree_before = tree.clone();
+
+    // Overlapping subtrees are rejected.
+    assert!(matches!(
+        tree.swap_subtrees(a_id, c_id),
+        Err(crate::RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.swap_subtrees(c_id, a_id),
+        Err(crate::RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.swap_subtrees(b_id, b_id),
+        Err(crate::RewritePrefixError::NestedPrefix)
+    ));
+    assert!(matches!(
+        tree.swap_subtrees(tree.root_node_id(), b_id),
+        Err(crate::RewritePrefixError::RootNode)
+    ));
+    assert!(matches!(
+        tree.swap_subtrees(b_id, 42),
+        Err(crate::RewritePrefixError::NotFound)
+    ));
+    assert_same_nodes(&tree_before, &tree);
+
+    // Siblings
+    tree.swap_subtrees(b_id, d_id).unwrap();
+    assert_eq!(Some(d_id), find_node_id(&tree, "/a/b"));
+    assert_eq!(Some(b_id), find_node_id(&tree, "/a/d"));
+    assert_eq!(Some(c_id), find_node_id(&tree, "/a/d/c"));
+    // Only the swapped nodes and their common parent node are rewritten.
+    assert_eq!(3, tree.rewritten_nodes_count(&tree_before));
+
+    // Different levels
+    tree.swap_subtrees(e_id, b_id).unwrap();
+    assert_eq!(Some(b_id), find_node_id(&tree, "/e"));
+    assert_eq!(Some(c_id), find_node_id(&tree, "/e/c"));
+    assert_eq!(Some(e_id), find_node_id(&tree, "/a/d"));
+    assert_eq!(
+        Some(&3),
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a/d")))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+    assert_eq!(6, tree.nodes_count().get());
+
+    // Frozen subtrees are not modified.
+    assert!(tree.freeze_subtree(e_id));
+    assert!(matches!(
+        tree.swap_subtrees(d_id, e_id),
+        Err(crate::RewritePrefixError::SubtreeFrozen { .. })
+    ));
+}
+
+#[test]
+fn traversal_scratch() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b/c", "/a/b/d", "/a/e", "/f/g", "/h"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let mut expected_tree = tree.clone();
+    let mut scratch = crate::TraversalScratch::new();
+
+    for path in ["/a/b", "/f", "/h"] {
+        let node_id = tree
+            .find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap()
+            .id;
+        let removed = tree
+            .remove_subtree_by_id_with_scratch(node_id, &mut scratch)
+            .unwrap();
+        let expected_removed = expected_tree.remove_subtree_by_id(node_id).unwrap();
+        assert_eq!(expected_removed.affected_nodes, removed.affected_nodes);
+        assert_same_nodes(&expected_removed.removed_subtree, &removed.removed_subtree);
+        assert_same_nodes(&expected_tree, &tree);
+    }
+
+    let predicate = |node: &TreeNode| node.node.leaf_value().is_none();
+    tree.retain_nodes_with_scratch(predicate, &mut scratch);
+    expected_tree.retain_nodes(predicate);
+    assert_eq!(2, tree.nodes_count().get());
+    assert_same_nodes(&expected_tree, &tree);
+
+    // The scratch could be reused after releasing its memory.
+    scratch.shrink_to_fit();
+    let node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    assert!(tree
+        .remove_subtree_by_id_with_scratch(node_id, &mut scratch)
+        .is_some());
+    assert_eq!(1, tree.nodes_count().get());
+}
+
+#[cfg(feature = "insertion-order")]
+#[test]
+fn insertion_ordered_children() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let path_segments = ["d", "b", "e", "a", "c"];
+    for (value, path_segment) in path_segments.into_iter().enumerate() {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Owned(format!("/{path_segment}"))),
+            NodeValue::Leaf(value),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+    }
+    let child_path_segments = |tree: &PathTree| {
+        tree.root_node()
+            .node
+            .children()
+            .map(|HalfEdge { path_segment, .. }| path_segment.to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(path_segments.to_vec(), child_path_segments(&tree));
+
+    // Updating a child node retains its position.
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/b")),
+        NodeValue::Leaf(42),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(path_segments.to_vec(), child_path_segments(&tree));
+
+    // Swapping child nodes retains their positions.
+    let find_node_id = |tree: &PathTree, path: &'static str| {
+        tree.find_node(&SlashPath::new(Cow::Borrowed(path)))
+            .unwrap()
+            .id
+    };
+    let e_id = find_node_id(&tree, "/e");
+    let a_id = find_node_id(&tree, "/a");
+    tree.swap_subtrees(e_id, a_id).unwrap();
+    assert_eq!(path_segments.to_vec(), child_path_segments(&tree));
+    assert_eq!(a_id, find_node_id(&tree, "/e"));
+
+    // Re-inserted child nodes are appended.
+    let b_id = find_node_id(&tree, "/b");
+    tree.remove_subtree_by_id(b_id).unwrap();
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/b")),
+        NodeValue::Leaf(1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(vec!["d", "e", "a", "c", "b"], child_path_segments(&tree));
+
+    // The order is preserved when cloning nodes.
+    assert_eq!(
+        child_path_segments(&tree),
+        child_path_segments(&tree.deep_clone())
+    );
+}
+
+#[test]
+fn descends_from() {
+    let insert = |tree: &mut PathTree, path: &'static str, value| {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    };
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let snapshot0 = tree.clone();
+    assert!(tree.descends_from(&snapshot0));
+    assert!(snapshot0.descends_from(&tree));
+
+    insert(&mut tree, "/a", 1);
+    assert!(tree.descends_from(&snapshot0));
+    assert!(!snapshot0.descends_from(&tree));
+
+    let snapshot1 = tree.clone();
+    insert(&mut tree, "/b", 2);
+    insert(&mut tree, "/c", 3);
+    assert!(tree.descends_from(&snapshot0));
+    assert!(tree.descends_from(&snapshot1));
+    assert!(snapshot1.descends_from(&snapshot0));
+    assert!(!snapshot1.descends_from(&tree));
+
+    // Concurrent modifications of the same snapshot.
+    let mut branch = snapshot1.clone();
+    insert(&mut branch, "/d", 4);
+    assert!(branch.descends_from(&snapshot0));
+    assert!(branch.descends_from(&snapshot1));
+    assert!(!branch.descends_from(&tree));
+    assert!(!tree.descends_from(&branch));
+
+    // The snapshot has been modified after the tree diverged from it.
+    let mut snapshot1 = snapshot1;
+    insert(&mut snapshot1, "/e", 5);
+    assert!(snapshot1.descends_from(&snapshot0));
+    assert!(!tree.descends_from(&snapshot1));
+    assert!(!branch.descends_from(&snapshot1));
+
+    // Trees with the same content.
+    assert!(tree.deep_clone().descends_from(&tree));
+
+    // Unrelated trees, even with the same content.
+    let other_tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(!other_tree.descends_from(&snapshot0));
+    assert!(!snapshot0.descends_from(&other_tree));
+
+    // Different lineages.
+    let mut forked_tree = tree.fork(crate::TreeId(1));
+    assert!(forked_tree.descends_from(&tree));
+    forked_tree.assign_tree_id(crate::TreeId(2));
+    insert(&mut forked_tree, "/f", 6);
+    assert!(!forked_tree.descends_from(&tree.fork(crate::TreeId(3))));
+}
+
+#[test]
+fn degenerate_shapes() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    assert!(tree.is_root_only());
+    assert!(tree.single_leaf().is_none());
+    assert!(tree.is_flat());
+
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a")),
+        NodeValue::Leaf(1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(!tree.is_root_only());
+    assert_eq!(
+        Some(&1),
+        tree.single_leaf().and_then(|node| node.node.leaf_value())
+    );
+    assert!(tree.is_flat());
+
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/b")),
+        NodeValue::Leaf(2),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.single_leaf().is_none());
+    assert!(tree.is_flat());
+
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/b/c")),
+        NodeValue::Leaf(3),
+        &mut || unreachable!(),
+        |_| Some(-2),
+    )
+    .unwrap();
+    assert!(!tree.is_flat());
+
+    // A single child of the root node that is an inner node.
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    tree.insert_or_update_node_value(
+        &SlashPath::new(Cow::Borrowed("/a")),
+        NodeValue::Inner(-1),
+        &mut || unreachable!(),
+        |_| None,
+    )
+    .unwrap();
+    assert!(tree.single_leaf().is_none());
+    assert!(tree.is_flat());
+
+    // A root node with a leaf value.
+    let tree = PathTree::new(Default::default(), NodeValue::Leaf(1));
+    assert!(tree.is_root_only());
+    assert!(tree.single_leaf().is_none());
+}
+
+#[test]
+fn rename_child_segment() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for (path, value) in [("/a/b", 1), ("/c", 2)] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(value),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+    let root_node_id = tree.root_node_id();
+    let a_node = Arc::clone(
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/a")))
+            .unwrap(),
+    );
+    let tree_before = tree.clone();
+
+    // Errors
+    assert!(matches!(
+        tree.rename_child_segment(root_node_id, "x", "y"),
+        Err(crate::RewritePrefixError::NotFound)
+    ));
+    assert!(matches!(
+        tree.rename_child_segment(42, "a", "y"),
+        Err(crate::RewritePrefixError::NotFound)
+    ));
+    assert!(matches!(
+        tree.rename_child_segment(root_node_id, "a", "c"),
+        Err(crate::RewritePrefixError::Occupied { .. })
+    ));
+    assert_same_nodes(&tree_before, &tree);
+
+    // Unchanged
+    let renamed_node = tree.rename_child_segment(root_node_id, "a", "a").unwrap();
+    assert!(Arc::ptr_eq(&a_node, &renamed_node));
+    assert_eq!(0, tree.rewritten_nodes_count(&tree_before));
+
+    let renamed_node = tree.rename_child_segment(root_node_id, "a", "x").unwrap();
+    assert_eq!(a_node.id, renamed_node.id);
+    assert_eq!(Some(&-1), renamed_node.node.inner_value());
+    assert!(tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .is_none());
+    assert_eq!(
+        Some(&1),
+        tree.find_node(&SlashPath::new(Cow::Borrowed("/x/b")))
+            .unwrap()
+            .node
+            .leaf_value()
+    );
+    // Only the parent node and the renamed node have been rewritten.
+    assert_eq!(2, tree.rewritten_nodes_count(&tree_before));
+    assert_eq!(tree_before.nodes_count(), tree.nodes_count());
+
+    // Frozen subtrees are not modified.
+    let b_node_id = tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/x/b")))
+        .unwrap()
+        .id;
+    assert!(tree.freeze_subtree(b_node_id));
+    assert!(matches!(
+        tree.rename_child_segment(root_node_id, "x", "y"),
+        Err(crate::RewritePrefixError::SubtreeFrozen { node_id }) if node_id == b_node_id
+    ));
+}
+
+#[test]
+fn format_node_path_truncated() {
+    use crate::{TruncatedNodePath, TruncationStrategy};
+
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    let mut insert_leaf = |path: &'static str| {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap()
+        .node
+        .id
+    };
+    let file_node_id = insert_leaf("/home/user/docs/file.txt");
+    let umlaut_node_id = insert_leaf("/ä/ö");
+    let format = |node_id, max_len, strategy| {
+        tree.format_node_path_truncated(node_id, max_len, strategy)
+            .map(
+                |TruncatedNodePath {
+                     path,
+                     elided_segments,
+                 }| (path, elided_segments),
+            )
+    };
+
+    assert_eq!(
+        Some(("/".to_owned(), 0..0)),
+        format(tree.root_node_id(), 0, TruncationStrategy::Middle)
+    );
+    assert_eq!(None, format(usize::MAX, 100, TruncationStrategy::Middle));
+
+    // Fits
+    for strategy in [
+        TruncationStrategy::Middle,
+        TruncationStrategy::Head,
+        TruncationStrategy::Tail,
+    ] {
+        let truncated = tree
+            .format_node_path_truncated(file_node_id, 24, strategy)
+            .unwrap();
+        assert!(!truncated.is_truncated());
+        assert_eq!("/home/user/docs/file.txt", truncated.path);
+        assert_eq!(4..4, truncated.elided_segments);
+    }
+
+    assert_eq!(
+        Some(("/home/…/file.txt".to_owned(), 1..3)),
+        format(file_node_id, 20, TruncationStrategy::Middle)
+    );
+    assert_eq!(
+        Some(("/…/docs/file.txt".to_owned(), 0..2)),
+        format(file_node_id, 20, TruncationStrategy::Head)
+    );
+    assert_eq!(
+        Some(("/home/user/docs/…".to_owned(), 3..4)),
+        format(file_node_id, 20, TruncationStrategy::Tail)
+    );
+
+    // The last segment is retained even if it doesn't fit.
+    assert_eq!(
+        Some(("/…/file.txt".to_owned(), 0..3)),
+        format(file_node_id, 1, TruncationStrategy::Middle)
+    );
+    assert_eq!(
+        Some(("/…/file.txt".to_owned(), 0..3)),
+        format(file_node_id, 1, TruncationStrategy::Head)
+    );
+    assert_eq!(
+        Some(("/…".to_owned(), 0..4)),
+        format(file_node_id, 1, TruncationStrategy::Tail)
+    );
+
+    // Lengths are measured in chars, not bytes.
+    assert_eq!(
+        Some(("/ä/ö".to_owned(), 2..2)),
+        format(umlaut_node_id, 4, TruncationStrategy::Middle)
+    );
+    assert_eq!(
+        Some(("/…/ö".to_owned(), 0..1)),
+        format(umlaut_node_id, 3, TruncationStrategy::Middle)
+    );
+}
+
+#[test]
+fn diff_patch_is_reproducible() {
+    fn new_tree(leaves: impl IntoIterator<Item = (String, usize)>) -> PathTree {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        for (path, value) in leaves {
+            tree.insert_or_update_node_value(
+                &SlashPath::new(Cow::Owned(path)),
+                NodeValue::Leaf(value),
+                &mut || -1,
+                |_| None,
+            )
+            .unwrap();
+        }
+        tree
+    }
+    fn old_leaves() -> impl Iterator<Item = (String, usize)> {
+        (0..10).flat_map(|i| [(format!("/a/{i}"), i), (format!("/b/{i}"), i)])
+    }
+    fn new_leaves() -> impl Iterator<Item = (String, usize)> {
+        old_leaves()
+            .filter(|(path, _)| path != "/a/3" && path != "/b/7")
+            .map(|(path, value)| match path.as_str() {
+                "/a/5" => (path, 50),
+                "/b/2" => (path, 20),
+                _ => (path, value),
+            })
+            .chain((0..5).rev().map(|i| (format!("/c/{i}"), i)))
+            .chain(std::iter::once(("/d/x/y".to_owned(), 1)))
+    }
+
+    // Trees with the same contents but independent iteration orders.
+    let patch_text = new_tree(old_leaves())
+        .diff_patch(&new_tree(new_leaves()))
+        .to_string();
+    let other_patch_text = new_tree(old_leaves().collect::<Vec<_>>().into_iter().rev())
+        .diff_patch(&new_tree(
+            new_leaves().collect::<Vec<_>>().into_iter().rev(),
+        ))
+        .to_string();
+    assert_eq!(patch_text, other_patch_text);
+    assert_eq!(
+        "- /a/3\n- /b/7\n~ /a/5 leaf 50\n~ /b/2 leaf 20\n\
+         + /c inner -1\n+ /c/0 leaf 0\n+ /c/1 leaf 1\n+ /c/2 leaf 2\n+ /c/3 leaf 3\n+ /c/4 leaf 4\n\
+         + /d inner -1\n+ /d/x inner -1\n+ /d/x/y leaf 1\n",
+        patch_text
+    );
+}
+
+#[test]
+fn diff_patch_matches_nodes_by_path() {
+    let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+    for path in ["/a/b", "/a/c", "/d"] {
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed(path)),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+    }
+
+    // Equal paths and values, but different node ids.
+    let mut reassigned_tree = tree.clone();
+    reassigned_tree.reassign_ids(NewNodeId { next_node_id: 1000 });
+    // In contrast to the id-based diff.
+    assert!(!tree.diff(&reassigned_tree).removed.is_empty());
+    assert!(tree.diff_patch(&reassigned_tree).is_empty());
+
+    // Equal node ids, but different paths.
+    let mut moved_tree = tree.clone();
+    let a_node_id = moved_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/a")))
+        .unwrap()
+        .id;
+    let d_node_id = moved_tree
+        .find_node(&SlashPath::new(Cow::Borrowed("/d")))
+        .unwrap()
+        .id;
+    moved_tree
+        .update_node_value(
+            &Arc::clone(moved_tree.get_node(d_node_id)),
+            NodeValue::Inner(-1),
+        )
+        .unwrap();
+    moved_tree.move_subtree(a_node_id, d_node_id, "a").unwrap();
+    assert_eq!(
+        "- /a\n~ /d inner -1\n+ /d/a inner -1\n+ /d/a/b leaf 1\n+ /d/a/c leaf 1\n",
+        tree.diff_patch(&moved_tree).to_string()
+    );
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+#[allow(clippy::too_many_lines)]
+fn metrics() {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Mutex,
+        },
+    };
+
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString,
+        Unit,
+    };
+
+    #[derive(Default)]
+    struct Samples(Mutex<Vec<f64>>);
+
+    impl HistogramFn for Samples {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    #[derive(Default)]
+    struct TestRecorder {
+        counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+        histograms: Mutex<HashMap<String, Arc<Samples>>>,
+    }
+
+    impl TestRecorder {
+        fn counter(&self, name: &str) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .get(name)
+                .map_or(0, |counter| counter.load(Ordering::Relaxed))
+        }
+
+        fn histogram(&self, name: &str) -> Vec<f64> {
+            self.histograms
+                .lock()
+                .unwrap()
+                .get(name)
+                .map(|samples| samples.0.lock().unwrap().clone())
+                .unwrap_or_default()
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let mut counters = self.counters.lock().unwrap();
+            let counter = counters.entry(key.name().to_owned()).or_default();
+            Counter::from_arc(Arc::clone(counter))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            let mut histograms = self.histograms.lock().unwrap();
+            let samples = histograms.entry(key.name().to_owned()).or_default();
+            Histogram::from_arc(Arc::clone(samples))
+        }
+    }
+
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        // Implicitly created ancestor nodes are counted.
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c")),
+            NodeValue::Leaf(1),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(3, recorder.counter("rpds_pathtree_inserts_total"));
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/a/b/c")),
+            NodeValue::Leaf(2),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(4, recorder.counter("rpds_pathtree_inserts_total"));
+
+        assert!(tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+            .is_some());
+        assert!(tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/x")))
+            .is_none());
+        assert_eq!(2, recorder.counter("rpds_pathtree_lookups_total"));
+        // Failed lookups are not recorded as resolved depth.
+        assert_eq!(1, recorder.counter("rpds_pathtree_failed_lookups_total"));
+        assert_eq!(
+            vec![2.0],
+            recorder.histogram("rpds_pathtree_resolved_depth")
+        );
+
+        let b_node_id = tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/a/b")))
+            .unwrap()
+            .id;
+        // All nodes of the removed subtree are counted.
+        tree.remove_subtree_by_id(b_node_id).unwrap();
+        assert_eq!(2, recorder.counter("rpds_pathtree_removals_total"));
+
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/x/y")),
+            NodeValue::Leaf(3),
+            &mut || -1,
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(6, recorder.counter("rpds_pathtree_inserts_total"));
+        let x_node_id = tree
+            .find_node(&SlashPath::new(Cow::Borrowed("/x")))
+            .unwrap()
+            .id;
+        // Promoted child nodes are neither inserted nor removed.
+        tree.splice_node(x_node_id).unwrap();
+        assert_eq!(6, recorder.counter("rpds_pathtree_inserts_total"));
+        assert_eq!(3, recorder.counter("rpds_pathtree_removals_total"));
+    });
+}
+
+#[test]
+fn insert_or_replace_subtree_with_policy() {
+    let new_tree = || {
+        let mut tree = PathTree::new(Default::default(), NodeValue::Inner(0));
+        tree.insert_or_update_node_value(
+            &SlashPath::new(Cow::Borrowed("/foo")),
+            NodeValue::Inner(1),
+            &mut || unreachable!(),
+            |_| None,
+        )
+        .unwrap();
+        tree
+    };
+    let new_subtree = || {
+        let mut subtree = PathTree::new(Default::default(), NodeValue::Inner(2));
+        subtree
+            .insert_or_update_node_value(
+                &SlashPath::new(Cow::Borrowed("/bar")),
+                NodeValue::Leaf(3),
+                &mut || unreachable!(),
+                |_| None,
+            )
+            .unwrap();
+        subtree
+    };
+    let foo_path = SlashPath::new(Cow::Borrowed("/foo"));
+    let bar_path = SlashPath::new(Cow::Borrowed("/foo/bar"));
+
+    for (resolution, expected_value) in [
+        (ConflictResolution::Ours, 1),
+        (ConflictResolution::Theirs, 2),
+        (ConflictResolution::Value(NodeValue::Inner(4)), 4),
+    ] {
+        let mut tree = new_tree();
+        let root_node = Arc::clone(tree.root_node());
+        tree.insert_or_replace_subtree_with_policy(
+            &root_node,
+            "foo",
+            None,
+            new_subtree(),
+            &mut |conflict: ValueConflict<'_, PathTreeTypes>| {
+                assert_eq!(&[Cow::Borrowed("foo")], conflict.path);
+                assert!(matches!(conflict.ours, NodeValue::Inner(1)));
+                assert!(matches!(conflict.theirs, NodeValue::Inner(2)));
+                resolution.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(3, tree.nodes_count().get());
+        assert_eq!(
+            Some(&expected_value),
+            tree.find_node(&foo_path).unwrap().node.inner_value()
+        );
+        assert_eq!(
+            Some(&3),
+            tree.find_node(&bar_path).unwrap().node.leaf_value()
+        );
+    }
+
+    let mut tree = new_tree();
+    let root_node = Arc::clone(tree.root_node());
+    let foo_node_id = tree.find_node(&foo_path).unwrap().id;
+    let err = tree
+        .insert_or_replace_subtree_with_policy(
+            &root_node,
+            "foo",
+            None,
+            new_subtree(),
+            &mut RejectConflicts,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        crate::InsertOrUpdateNodeValueError::ConflictRejected {
+            node_id,
+            value: NodeValue::Inner(2),
+        } if node_id == foo_node_id
+    ));
+    assert_eq!(2, tree.nodes_count().get());
+
+    // Vacant path segments do not conflict.
+    tree.insert_or_replace_subtree_with_policy(
+        &root_node,
+        "baz",
+        None,
+        new_subtree(),
+        &mut RejectConflicts,
+    )
+    .unwrap();
+    assert_eq!(4, tree.nodes_count().get());
+}
@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::{PathTree, PathTreeTypes};
+
+/// Identity of the content of a tree.
+///
+/// Equal tokens guarantee equal content, i.e. the tree has not been
+/// modified since the token has been obtained. Clones of a tree share the
+/// same token until either of them is modified. Each modification assigns
+/// a new token that is unique within the process, even if the content has
+/// not changed effectively.
+///
+/// Intended as a cheap key for memoizing computations across frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SnapshotToken(u64);
+
+impl SnapshotToken {
+    pub(crate) fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The number of preceding branches that are remembered by a tree.
+///
+/// See [`PathTree::descends_from()`].
+const MAX_ANCESTRY_LEN: usize = 16;
+
+/// The linear history of a tree since it has diverged from other trees.
+///
+/// Shared by all clones of a tree until either of them is modified. The
+/// modified tree then starts a new branch, while the unmodified clones
+/// stay on the old branch.
+#[derive(Debug)]
+pub(crate) struct SnapshotLineage {
+    branch_id: u64,
+
+    /// The preceding branches and the tokens at which they have been left,
+    /// the most recent first.
+    ancestry: Vec<(u64, SnapshotToken)>,
+}
+
+impl SnapshotLineage {
+    fn next_branch_id() -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn new() -> Self {
+        Self {
+            branch_id: Self::next_branch_id(),
+            ancestry: Vec::new(),
+        }
+    }
+
+    fn branch(&self, snapshot_token: SnapshotToken) -> Self {
+        let ancestry = std::iter::once((self.branch_id, snapshot_token))
+            .chain(self.ancestry.iter().copied())
+            .take(MAX_ANCESTRY_LEN)
+            .collect();
+        Self {
+            branch_id: Self::next_branch_id(),
+            ancestry,
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Invalidate the current [`SnapshotToken`] before modifying the tree.
+    ///
+    /// Starts a new branch if the current branch is shared with other trees.
+    pub(crate) fn invalidate_snapshot_token(&mut self) {
+        if Arc::get_mut(&mut self.snapshot_lineage).is_none() {
+            self.snapshot_lineage = Arc::new(self.snapshot_lineage.branch(self.snapshot_token));
+        }
+        self.snapshot_token = SnapshotToken::next();
+    }
+
+    /// Check if this tree has evolved from another snapshot.
+    ///
+    /// Returns `true` if the content of this tree is the result of modifying
+    /// the given snapshot or if both have the same content, see [`Self::snapshot_token()`].
+    /// Merging the snapshot into this tree could then be skipped, i.e. this
+    /// tree is a fast-forward of the snapshot.
+    ///
+    /// Returns `false` if the snapshot has been modified independently,
+    /// e.g. after this tree has been cloned from it, or if the trees are
+    /// unrelated. Trees from different lineages are never related, see
+    /// [`Self::check_same_lineage()`].
+    ///
+    /// Only the 16 most recent branches are remembered, i.e. the tree
+    /// might not be recognized as a descendant of a distant ancestor.
+    /// Each modification of a tree that shares its nodes with a clone
+    /// starts a new branch. False positives are not possible.
+    ///
+    /// In constant time, i.e. O(1).
+    #[must_use]
+    pub fn descends_from(&self, ancestor: &Self) -> bool {
+        if self.snapshot_token == ancestor.snapshot_token {
+            return true;
+        }
+        if self.check_same_lineage(ancestor).is_err() {
+            return false;
+        }
+        if self.snapshot_lineage.branch_id == ancestor.snapshot_lineage.branch_id {
+            // Trees on the same branch are never modified independently.
+            return self.snapshot_token > ancestor.snapshot_token;
+        }
+        self.snapshot_lineage
+            .ancestry
+            .iter()
+            .find(|(branch_id, _)| *branch_id == ancestor.snapshot_lineage.branch_id)
+            .is_some_and(|(_, branch_snapshot_token)| {
+                ancestor.snapshot_token <= *branch_snapshot_token
+            })
+    }
+
+    /// The identity of the current content of the tree.
+    ///
+    /// See [`SnapshotToken`].
+    #[must_use]
+    pub const fn snapshot_token(&self) -> SnapshotToken {
+        self.snapshot_token
+    }
+}
@@ -0,0 +1,225 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, sync::Arc};
+
+use crate::{HalfEdge, PathTree, PathTreeTypes, TreeNode};
+
+/// Event of a depth-first traversal.
+///
+/// Returned by [`DepthFirstTraversalIter`].
+#[derive(Debug, Clone)]
+pub enum TraversalEvent<'a, T>
+where
+    T: PathTreeTypes,
+{
+    /// The node is visited before its descendants.
+    Enter(&'a Arc<TreeNode<T>>),
+
+    /// The node is visited after its descendants.
+    Leave(&'a Arc<TreeNode<T>>),
+}
+
+impl<'a, T> TraversalEvent<'a, T>
+where
+    T: PathTreeTypes,
+{
+    #[must_use]
+    pub const fn node(&self) -> &'a Arc<TreeNode<T>> {
+        match self {
+            Self::Enter(node) | Self::Leave(node) => node,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PendingEvent<'a, T>
+where
+    T: PathTreeTypes,
+{
+    Enter(T::NodeId),
+    Leave(&'a Arc<TreeNode<T>>),
+}
+
+/// Depth-first traversal with enter and leave events.
+///
+/// Each node is entered before and left after all its descendants,
+/// i.e. the events are properly nested like the tags of an XML document.
+/// Callers could maintain per-branch state without recursion.
+///
+/// Returned by [`PathTree::depth_first_traversal()`].
+#[derive(Debug)]
+pub struct DepthFirstTraversalIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a PathTree<T>,
+    pending_events: Vec<PendingEvent<'a, T>>,
+}
+
+impl<'a, T> Iterator for DepthFirstTraversalIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    type Item = TraversalEvent<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node_id = match self.pending_events.pop()? {
+            PendingEvent::Enter(node_id) => node_id,
+            PendingEvent::Leave(node) => {
+                return Some(TraversalEvent::Leave(node));
+            }
+        };
+        let node = self.tree.get_node(node_id);
+        self.pending_events.push(PendingEvent::Leave(node));
+        let len_before = self.pending_events.len();
+        self.pending_events.extend(
+            node.node
+                .children()
+                .map(|HalfEdge { node_id, .. }| PendingEvent::Enter(node_id)),
+        );
+        // Reverse the order of children so that the first child ends up at the top of the stack.
+        self.pending_events[len_before..].reverse();
+        Some(TraversalEvent::Enter(node))
+    }
+}
+
+/// Depth-first traversal in post-order.
+///
+/// Returned by [`PathTree::post_order_nodes()`].
+#[derive(Debug)]
+pub struct PostOrderNodesIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    traversal: DepthFirstTraversalIter<'a, T>,
+}
+
+impl<'a, T> Iterator for PostOrderNodesIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    type Item = &'a Arc<TreeNode<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.traversal.find_map(|event| match event {
+            TraversalEvent::Enter(_) => None,
+            TraversalEvent::Leave(node) => Some(node),
+        })
+    }
+}
+
+/// Depth-first traversal that yields nodes together with their paths.
+///
+/// Returned by [`PathTree::nodes_with_paths()`].
+#[derive(Debug)]
+pub struct NodesWithPathsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    traversal: DepthFirstTraversalIter<'a, T>,
+    path: Vec<&'a T::PathSegment>,
+    depth: usize,
+}
+
+impl<'a, T> Iterator for NodesWithPathsIter<'a, T>
+where
+    T: PathTreeTypes,
+{
+    type Item = (Vec<&'a T::PathSegment>, &'a Arc<TreeNode<T>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.traversal.next()? {
+                TraversalEvent::Enter(node) => {
+                    if self.depth > 0 {
+                        let parent = node.parent.as_ref().expect("has parent");
+                        self.path.push(parent.path_segment.borrow());
+                    }
+                    self.depth += 1;
+                    return Some((self.path.clone(), node));
+                }
+                TraversalEvent::Leave(_) => {
+                    self.depth -= 1;
+                    if self.depth > 0 {
+                        self.path.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Traverse a subtree depth-first with enter and leave events.
+    ///
+    /// Starts by entering the given node and finishes by leaving it.
+    /// Children are visited in the same order as by [`Self::descendant_nodes()`].
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn depth_first_traversal<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> DepthFirstTraversalIter<'a, T> {
+        debug_assert!(self.contains_node(node));
+        DepthFirstTraversalIter {
+            tree: self,
+            pending_events: vec![PendingEvent::Enter(node.id.clone())],
+        }
+    }
+
+    /// Traverse a subtree depth-first in post-order.
+    ///
+    /// All children are yielded before their parent node, e.g. for
+    /// aggregating values bottom-up. The given node is yielded last.
+    /// Children are visited in the same order as by [`Self::descendant_nodes()`].
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn post_order_nodes<'a>(&'a self, node: &'a Arc<TreeNode<T>>) -> PostOrderNodesIter<'a, T> {
+        PostOrderNodesIter {
+            traversal: self.depth_first_traversal(node),
+        }
+    }
+
+    /// Traverse a subtree depth-first and yield all nodes with their paths.
+    ///
+    /// The paths are relative to the given node, i.e. the given node is
+    /// yielded first with an empty path. Paths are built incrementally while
+    /// traversing the tree and each node is only visited once, i.e. in
+    /// O(n·d) with n the number of nodes and d the depth of the subtree for
+    /// copying the paths. In contrast to walking [`Self::ancestor_nodes()`]
+    /// for each node no additional lookups are needed.
+    ///
+    /// Children are visited in the same order as by [`Self::descendant_nodes()`].
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn nodes_with_paths<'a>(&'a self, node: &'a Arc<TreeNode<T>>) -> NodesWithPathsIter<'a, T> {
+        NodesWithPathsIter {
+            traversal: self.depth_first_traversal(node),
+            path: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// All descendants of a node with their paths relative to this node.
+    ///
+    /// Same as [`Self::nodes_with_paths()`], but excludes the given node
+    /// itself, i.e. all yielded paths are non-empty. Intended for exporting
+    /// a subtree to a different location without rebasing the paths.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn subtree_paths<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> impl Iterator<Item = (Vec<&'a T::PathSegment>, &'a Arc<TreeNode<T>>)> {
+        self.nodes_with_paths(node).skip(1)
+    }
+}
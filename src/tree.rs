@@ -1,22 +1,52 @@
 // SPDX-FileCopyrightText: The rpds-pathtree authors
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{borrow::Borrow, fmt, hash::Hash, marker::PhantomData, num::NonZeroUsize, sync::Arc};
+use std::{
+    borrow::{Borrow, Cow},
+    cmp::Ordering,
+    convert::Infallible,
+    fmt,
+    hash::Hash,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    sync::Arc,
+};
 
 use derive_more::{Display, Error};
 
 use crate::{
-    new_hash_map, HalfEdge, HalfEdgeOwned, HalfEdgeTreeNode, HashMap, InnerNode, LeafNode, Node,
-    NodeValue, PathSegment, RootPath, SegmentedPath as _,
+    debug::{SortedDebugMap, SortedDebugSet},
+    new_hash_map, new_hash_set,
+    token::SnapshotLineage,
+    ConflictPolicy, ConflictResolution, CountedDescendantsIter, HalfEdge, HalfEdgeOwned,
+    HalfEdgeTreeNode, HashMap, HashSet, InnerNode, LeafNode, Node, NodeValue,
+    OrderedDescendantsIter, PathSegment, PathSegmentCmp, ReverseDescendantsIter, RootPath,
+    SegmentedPath as _, SnapshotToken, TraversalScratch, TreeProvenance, ValueConflict,
 };
 
 pub trait NewNodeId<T> {
     fn new_node_id(&mut self) -> T;
 }
 
+/// Mapping from old to new node ids.
+///
+/// Returned by [`PathTree::reassign_ids()`].
+pub type IdMap<T> =
+    std::collections::HashMap<<T as PathTreeTypes>::NodeId, <T as PathTreeTypes>::NodeId>;
+
+/// Node id generator that could be partitioned for generating ids concurrently.
+pub trait PartitionNewNodeId<T>: NewNodeId<T> + Sized {
+    /// Split off independent generators.
+    ///
+    /// Afterwards, all returned generators and this generator must generate
+    /// disjoint ids.
+    #[must_use]
+    fn partition(&mut self, count: usize) -> Vec<Self>;
+}
+
 /// Type system for [`PathTree`].
 pub trait PathTreeTypes: Clone + Default + fmt::Debug {
-    type NodeId: Clone + Copy + Eq + Hash + fmt::Debug + fmt::Display;
+    type NodeId: Clone + Eq + Hash + fmt::Debug + fmt::Display;
     type NewNodeId: NewNodeId<Self::NodeId> + Clone + fmt::Debug;
     type InnerValue: Clone + fmt::Debug;
     type LeafValue: Clone + fmt::Debug;
@@ -24,7 +54,176 @@ pub trait PathTreeTypes: Clone + Default + fmt::Debug {
     type PathSegment: PathSegment + ?Sized;
     type RootPath: RootPath<Self::PathSegment> + ?Sized;
 
+    /// Key for looking up child nodes by their path segment.
+    ///
+    /// Path segments with equal keys address the same child node, e.g.
+    /// for case-insensitive but case-preserving paths. The original path
+    /// segment of a child node is preserved.
+    ///
+    /// Use [`Self::PathSegment`] if path segments should be compared
+    /// verbatim.
+    type ChildKey: ToOwned<Owned = Self::ChildKeyOwned> + Eq + Hash + fmt::Debug + ?Sized;
+    type ChildKeyOwned: Clone + Eq + Hash + fmt::Debug + Borrow<Self::ChildKey>;
+
     fn path_segment_to_owned(path_segment: &Self::PathSegment) -> Self::PathSegmentOwned;
+
+    /// Derive the lookup key of a child node from its path segment.
+    ///
+    /// Invoked for every lookup of a child node. Should borrow the path
+    /// segment if it doesn't need to be transformed.
+    #[must_use]
+    fn child_key(path_segment: &Self::PathSegment) -> Cow<'_, Self::ChildKey>;
+
+    /// Weight of an inner value, e.g. its payload size in bytes.
+    ///
+    /// Ignored by default.
+    #[must_use]
+    fn inner_value_weight(_value: &Self::InnerValue) -> usize {
+        0
+    }
+
+    /// Weight of a leaf value, e.g. its payload size in bytes.
+    ///
+    /// Ignored by default.
+    #[must_use]
+    fn leaf_value_weight(_value: &Self::LeafValue) -> usize {
+        0
+    }
+
+    /// Check if an existing inner value equals a new inner value.
+    ///
+    /// Updating a node with an equal value is a no-op that retains the
+    /// node, i.e. no new node is created. Keeps identity-based diffs like
+    /// [`PathTree::diff()`] precise and avoids spurious change events.
+    ///
+    /// Returns `false` by default, i.e. values are always updated.
+    #[must_use]
+    fn inner_values_equal(_old_value: &Self::InnerValue, _new_value: &Self::InnerValue) -> bool {
+        false
+    }
+
+    /// Check if an existing leaf value equals a new leaf value.
+    ///
+    /// See also: [`Self::inner_values_equal()`]
+    ///
+    /// Returns `false` by default, i.e. values are always updated.
+    #[must_use]
+    fn leaf_values_equal(_old_value: &Self::LeafValue, _new_value: &Self::LeafValue) -> bool {
+        false
+    }
+
+    /// Fingerprint of the types for detecting incompatible serialized data.
+    ///
+    /// Written into and verified when parsing self-describing formats, e.g.
+    /// by [`TreePatch::to_string_with_header()`](crate::TreePatch::to_string_with_header)
+    /// and by snapshot files. Unversioned formats like rows or materialized paths
+    /// do not contain it and the tag should be stored alongside them.
+    ///
+    /// Defaults to the type names of the path segments and values. These are
+    /// not guaranteed to be stable across compiler versions. Override this
+    /// method with a user-provided tag that should be changed whenever the
+    /// serialized representation of the types changes incompatibly.
+    #[must_use]
+    fn schema_tag() -> String {
+        format!(
+            "{path_segment}:{inner_value}:{leaf_value}",
+            path_segment = std::any::type_name::<Self::PathSegmentOwned>(),
+            inner_value = std::any::type_name::<Self::InnerValue>(),
+            leaf_value = std::any::type_name::<Self::LeafValue>(),
+        )
+    }
+
+    /// Intern a new leaf value before it is stored in the tree.
+    ///
+    /// Invoked whenever a leaf value is inserted or updated. Implementations
+    /// could return a shared payload for equal values, e.g. with a
+    /// [`ValueInterner`](crate::ValueInterner) if the leaf value is an `Arc`.
+    ///
+    /// Returns the value unmodified by default.
+    #[must_use]
+    fn intern_leaf_value(value: Self::LeafValue) -> Self::LeafValue {
+        value
+    }
+}
+
+/// Behavior when inserting or updating a node at the root path.
+///
+/// See also [`PathTree::set_root_path_policy()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RootPathPolicy {
+    /// Update the value of the root node.
+    #[default]
+    UpdateValue,
+
+    /// Fail with a `RootPath` error.
+    ///
+    /// The value of the root node could only be updated explicitly,
+    /// e.g. by [`PathTree::set_root_value()`].
+    Reject,
+}
+
+/// Order of sibling nodes in exports.
+///
+/// Passed to exports like [`PathTree::to_rows()`]. Parent nodes are always
+/// exported before their children.
+#[derive(Debug)]
+pub enum ExportOrder<T>
+where
+    T: PathTreeTypes,
+{
+    /// Sort siblings by their path segments.
+    ///
+    /// Exports of trees with equal content are identical, independent
+    /// of the iteration order of the internal hash maps.
+    SortedBy(PathSegmentCmp<T>),
+
+    /// Export siblings in the iteration order of the internal hash maps.
+    ///
+    /// Avoids sorting the children of each node. Preserves the insertion
+    /// order if the feature "insertion-order" is enabled.
+    Unordered,
+}
+
+impl<T> ExportOrder<T>
+where
+    T: PathTreeTypes,
+    T::PathSegment: Ord,
+{
+    /// Sort siblings by the natural order of their path segments.
+    ///
+    /// Recommended for reproducible exports.
+    #[must_use]
+    pub const fn sorted() -> Self {
+        Self::SortedBy(Ord::cmp as PathSegmentCmp<T>)
+    }
+}
+
+// Deriving would require `T: Clone + Copy`.
+#[allow(clippy::expl_impl_clone_on_copy)]
+impl<T> Clone for ExportOrder<T>
+where
+    T: PathTreeTypes,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ExportOrder<T> where T: PathTreeTypes {}
+
+/// Handling of inner nodes whose children have all been removed.
+///
+/// See also [`PathTree::filter_map_leaves()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyInnerNodes {
+    /// Retain the inner node without any children.
+    #[default]
+    Retain,
+
+    /// Remove the inner node recursively.
+    ///
+    /// Inner nodes that have been empty before are retained.
+    Prune,
 }
 
 /// A conflicting path from a parent to a child node.
@@ -49,6 +248,32 @@ where
     },
     #[display("value type mismatch")]
     ValueTypeMismatch { value: NodeValue<T> },
+    /// The node is part of a frozen subtree, see [`PathTree::freeze_subtree()`].
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+    /// The root path has been rejected, see [`RootPathPolicy::Reject`].
+    #[display("root path rejected")]
+    RootPath { value: NodeValue<T> },
+    /// The given node does not belong to the tree, e.g. it has been
+    /// obtained from an older snapshot.
+    #[display("stale reference to node {node_id}")]
+    StaleNodeReference {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+    /// Replacing the value of an existing node has been rejected by
+    /// a [`ConflictPolicy`].
+    #[display("conflicting value of node {node_id} rejected")]
+    ConflictRejected {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
 }
 
 #[derive(Debug, Display, Error)]
@@ -58,6 +283,21 @@ where
 {
     #[display("value type mismatch")]
     ValueTypeMismatch { value: NodeValue<T> },
+    /// The node is part of a frozen subtree, see [`PathTree::freeze_subtree()`].
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+    /// The given node does not belong to the tree, e.g. it has been
+    /// obtained from an older snapshot.
+    #[display("stale reference to node {node_id}")]
+    StaleNodeReference {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
 }
 
 impl<T> From<UpdateNodeValueError<T>> for InsertOrUpdateNodeValueError<T>
@@ -65,11 +305,130 @@ where
     T: PathTreeTypes,
 {
     fn from(from: UpdateNodeValueError<T>) -> Self {
-        let UpdateNodeValueError::ValueTypeMismatch { value } = from;
-        Self::ValueTypeMismatch { value }
+        match from {
+            UpdateNodeValueError::ValueTypeMismatch { value } => Self::ValueTypeMismatch { value },
+            UpdateNodeValueError::SubtreeFrozen { node_id, value } => {
+                Self::SubtreeFrozen { node_id, value }
+            }
+            UpdateNodeValueError::StaleNodeReference { node_id, value } => {
+                Self::StaleNodeReference { node_id, value }
+            }
+        }
+    }
+}
+
+/// Error of [`PathTree::try_insert_or_update_node_value()`].
+#[derive(Debug, Display, Error)]
+pub enum TryInsertOrUpdateNodeValueError<T, E>
+where
+    T: PathTreeTypes,
+{
+    #[display("path conflict")]
+    PathConflict {
+        conflict: TreeNodeParentChildPathConflict<T>,
+        value: NodeValue<T>,
+    },
+    #[display("value type mismatch")]
+    ValueTypeMismatch { value: NodeValue<T> },
+    #[display("failed to create inner value")]
+    NewValue { source: E, value: NodeValue<T> },
+    /// The node is part of a frozen subtree, see [`PathTree::freeze_subtree()`].
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+    /// The root path has been rejected, see [`RootPathPolicy::Reject`].
+    #[display("root path rejected")]
+    RootPath { value: NodeValue<T> },
+    /// The given node does not belong to the tree, e.g. it has been
+    /// obtained from an older snapshot.
+    #[display("stale reference to node {node_id}")]
+    StaleNodeReference {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+    /// Replacing the value of an existing node has been rejected by
+    /// a [`ConflictPolicy`].
+    #[display("conflicting value of node {node_id} rejected")]
+    ConflictRejected {
+        #[error(not(source))]
+        node_id: T::NodeId,
+        value: NodeValue<T>,
+    },
+}
+
+impl<T, E> TryInsertOrUpdateNodeValueError<T, E>
+where
+    T: PathTreeTypes,
+{
+    pub fn into_value(self) -> NodeValue<T> {
+        match self {
+            Self::PathConflict { value, .. }
+            | Self::ValueTypeMismatch { value }
+            | Self::NewValue { value, .. }
+            | Self::SubtreeFrozen { value, .. }
+            | Self::RootPath { value }
+            | Self::StaleNodeReference { value, .. }
+            | Self::ConflictRejected { value, .. } => value,
+        }
+    }
+}
+
+impl<T, E> From<InsertOrUpdateNodeValueError<T>> for TryInsertOrUpdateNodeValueError<T, E>
+where
+    T: PathTreeTypes,
+{
+    fn from(from: InsertOrUpdateNodeValueError<T>) -> Self {
+        match from {
+            InsertOrUpdateNodeValueError::PathConflict { conflict, value } => {
+                Self::PathConflict { conflict, value }
+            }
+            InsertOrUpdateNodeValueError::ValueTypeMismatch { value } => {
+                Self::ValueTypeMismatch { value }
+            }
+            InsertOrUpdateNodeValueError::SubtreeFrozen { node_id, value } => {
+                Self::SubtreeFrozen { node_id, value }
+            }
+            InsertOrUpdateNodeValueError::RootPath { value } => Self::RootPath { value },
+            InsertOrUpdateNodeValueError::StaleNodeReference { node_id, value } => {
+                Self::StaleNodeReference { node_id, value }
+            }
+            InsertOrUpdateNodeValueError::ConflictRejected { node_id, value } => {
+                Self::ConflictRejected { node_id, value }
+            }
+        }
+    }
+}
+
+impl<T, E> From<UpdateNodeValueError<T>> for TryInsertOrUpdateNodeValueError<T, E>
+where
+    T: PathTreeTypes,
+{
+    fn from(from: UpdateNodeValueError<T>) -> Self {
+        match from {
+            UpdateNodeValueError::ValueTypeMismatch { value } => Self::ValueTypeMismatch { value },
+            UpdateNodeValueError::SubtreeFrozen { node_id, value } => {
+                Self::SubtreeFrozen { node_id, value }
+            }
+            UpdateNodeValueError::StaleNodeReference { node_id, value } => {
+                Self::StaleNodeReference { node_id, value }
+            }
+        }
     }
 }
 
+#[derive(Debug)]
+enum CreateMissingAncestorNodesError<T, E>
+where
+    T: PathTreeTypes,
+{
+    PathConflict(TreeNodeParentChildPathConflict<T>),
+    NewValue(E),
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeInsertedOrUpdated<T>
 where
@@ -85,6 +444,27 @@ where
     /// `None` if the node has no parent (i.e. is the root node of the tree)
     /// or if the parent node has not been updated.
     pub parent: Option<ParentNodeUpdated<T>>,
+
+    /// Number of affected nodes.
+    ///
+    /// Counts all nodes that have been inserted, updated, or removed,
+    /// including implicitly created ancestor nodes and the nodes of a
+    /// removed subtree. Each node is counted only once.
+    pub affected_nodes: usize,
+}
+
+impl<T> NodeInsertedOrUpdated<T>
+where
+    T: PathTreeTypes,
+{
+    /// Check if the root node has been updated.
+    ///
+    /// The parent node is `None` in this case, but it is also `None` if
+    /// the parent node of an updated child node remained unchanged.
+    #[must_use]
+    pub fn is_root(&self) -> bool {
+        self.node.parent.is_none()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -120,6 +500,38 @@ where
     ///
     /// A new tree built from the removed node and all its descendants.
     pub removed_subtree: PathTree<T>,
+
+    /// Number of affected nodes.
+    ///
+    /// Counts all removed nodes and the updated parent node.
+    pub affected_nodes: usize,
+}
+
+/// Outcome of removing a single path by [`PathTree::remove_paths()`].
+#[derive(Debug, Clone)]
+pub enum PathRemoved<T>
+where
+    T: PathTreeTypes,
+{
+    /// The subtree has been removed.
+    Removed(SubtreeRemoved<T>),
+
+    /// The node has already been removed together with another path.
+    ///
+    /// Either an ancestor path or the same path has been requested before.
+    Included,
+
+    /// The path does not exist.
+    NotFound,
+
+    /// The root node cannot be removed.
+    RootNode,
+
+    /// The subtree is frozen or contains a frozen subtree, see [`PathTree::freeze_subtree()`].
+    Frozen {
+        /// The root node of the frozen subtree.
+        node_id: T::NodeId,
+    },
 }
 
 /// Return type when inserting or replacing a subtree.
@@ -135,6 +547,67 @@ where
     ///
     /// Updated parent node that contains the subtree as child.
     pub parent: ParentNodeUpdated<T>,
+
+    /// Number of affected nodes.
+    ///
+    /// Counts all inserted nodes, the updated parent node, and
+    /// the nodes of a replaced subtree. Each node is counted only once.
+    pub affected_nodes: usize,
+}
+
+/// Error of [`PathTree::rewrite_prefix()`] and [`PathTree::move_subtree()`].
+#[derive(Debug, Display, Error)]
+pub enum RewritePrefixError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("path not found")]
+    NotFound,
+    #[display("cannot move the root node")]
+    RootNode,
+    /// The new prefix equals or starts with the old prefix.
+    #[display("cannot move a node below itself")]
+    NestedPrefix,
+    #[display("path conflict")]
+    PathConflict {
+        conflict: TreeNodeParentChildPathConflict<T>,
+    },
+    /// A node already exists at the new prefix.
+    #[display("node {node_id} already exists")]
+    Occupied {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    /// The moved or the new parent node is part of a frozen subtree.
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+}
+
+/// Error of [`PathTree::splice_node()`].
+#[derive(Debug, Display, Error)]
+pub enum SpliceNodeError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("node not found")]
+    NotFound,
+    #[display("cannot remove the root node")]
+    RootNode,
+    /// A child node collides with a sibling of the removed node.
+    #[display("node {node_id} already exists")]
+    Occupied {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    /// The removed node is part of or contains a frozen subtree.
+    #[display("subtree of node {node_id} is frozen")]
+    SubtreeFrozen {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
 }
 
 impl<T> InsertOrUpdateNodeValueError<T>
@@ -143,7 +616,12 @@ where
 {
     pub fn into_value(self) -> NodeValue<T> {
         match self {
-            Self::PathConflict { value, .. } | Self::ValueTypeMismatch { value } => value,
+            Self::PathConflict { value, .. }
+            | Self::ValueTypeMismatch { value }
+            | Self::SubtreeFrozen { value, .. }
+            | Self::RootPath { value }
+            | Self::StaleNodeReference { value, .. }
+            | Self::ConflictRejected { value, .. } => value,
         }
     }
 }
@@ -151,7 +629,27 @@ where
 /// Cheaply clonable path tree structure.
 ///
 /// Could be shared safely between multiple threads.
-#[derive(Debug, Clone)]
+///
+/// # Cloning
+///
+/// Cloning a tree with [`Clone::clone()`] is executed in constant time, i.e. O(1).
+/// The clone shares all nodes with the original tree. Both trees could be
+/// modified independently, only the modified nodes and their parent nodes
+/// are copied on write.
+///
+/// # Write amplification
+///
+/// Nodes reference their children by id. Inserting or updating a single node
+/// rewrites at most this node and its parent node, independent of the depth
+/// of the node. Implicitly created ancestor nodes and removed subtrees are
+/// accounted for additionally. Moving, renaming, or swapping subtrees only
+/// rewrites the affected nodes and their parent nodes, but not their
+/// descendants. The number of rewritten nodes could be verified
+/// with [`Self::rewritten_nodes_count()`] and [`Self::assert_write_amplification()`].
+///
+/// Use [`Self::deep_clone()`] for creating an isolated copy that does not share
+/// any nodes with the original tree.
+#[derive(Clone)]
 pub struct PathTree<T>
 where
     T: PathTreeTypes,
@@ -159,9 +657,46 @@ where
     root_node_id: T::NodeId,
     nodes: HashMap<T::NodeId, Arc<TreeNode<T>>>,
     new_node_id: T::NewNodeId,
+    pub(crate) provenance: Option<Arc<TreeProvenance>>,
+    pub(crate) frozen_node_ids: HashSet<T::NodeId>,
+    pub(crate) snapshot_token: SnapshotToken,
+    pub(crate) snapshot_lineage: Arc<SnapshotLineage>,
+    root_path_policy: RootPathPolicy,
     _types: PhantomData<T>,
 }
 
+// The snapshot token and lineage are omitted, because they differ between
+// trees with equal content.
+impl<T> fmt::Debug for PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            root_node_id,
+            nodes,
+            new_node_id,
+            provenance,
+            frozen_node_ids,
+            snapshot_token: _,
+            snapshot_lineage: _,
+            root_path_policy,
+            _types,
+        } = self;
+        f.debug_struct("PathTree")
+            .field("root_node_id", root_node_id)
+            .field("nodes", &SortedDebugMap::new(nodes.iter()))
+            .field("new_node_id", new_node_id)
+            .field("provenance", provenance)
+            .field(
+                "frozen_node_ids",
+                &SortedDebugSet::new(frozen_node_ids.iter()),
+            )
+            .field("root_path_policy", root_path_policy)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Default)]
 struct TreeNodeParentChildContext<'a, T>
 where
@@ -169,6 +704,10 @@ where
 {
     parent_node: Option<Arc<TreeNode<T>>>,
     child_path_segment: Option<&'a T::PathSegment>,
+    /// Number of nodes that have been inserted or updated.
+    affected_nodes: usize,
+    /// Whether the parent node is included in `affected_nodes`.
+    parent_node_affected: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -193,6 +732,19 @@ pub enum NodePathMatched {
     },
 }
 
+impl NodePathMatched {
+    /// Number of matched path segments.
+    #[must_use]
+    pub const fn number_of_matched_segments(&self) -> usize {
+        match self {
+            Self::Full { number_of_segments } => *number_of_segments,
+            Self::Partial {
+                number_of_matched_segments,
+            } => number_of_matched_segments.get(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NodePathResolved<'a, T>
 where
@@ -208,49 +760,207 @@ impl<T: PathTreeTypes> PathTree<T> {
     pub fn new(mut new_node_id: T::NewNodeId, root_node_value: NodeValue<T>) -> Self {
         let root_node_id = new_node_id.new_node_id();
         let root_node = TreeNode {
-            id: root_node_id,
+            id: root_node_id.clone(),
             parent: None,
             node: Node::from_value_without_children(root_node_value),
         };
         let mut nodes = new_hash_map();
-        nodes.insert_mut(root_node_id, Arc::new(root_node));
+        nodes.insert_mut(root_node_id.clone(), Arc::new(root_node));
         Self {
             root_node_id,
             new_node_id,
             nodes,
+            provenance: None,
+            frozen_node_ids: new_hash_set(),
+            snapshot_token: SnapshotToken::next(),
+            snapshot_lineage: Arc::new(SnapshotLineage::new()),
+            root_path_policy: RootPathPolicy::default(),
             _types: PhantomData,
         }
     }
 
-    fn new_node_id(&mut self) -> T::NodeId {
-        self.new_node_id.new_node_id()
+    pub(crate) fn new_node_id(&mut self) -> T::NodeId {
+        let node_id = self.new_node_id.new_node_id();
+        // A faulty id generator would silently replace existing nodes.
+        strict_assert!(
+            !self.nodes.contains_key(&node_id),
+            "duplicate node id {node_id}"
+        );
+        node_id
+    }
+
+    /// Mutable access to the nodes for modifying the tree.
+    ///
+    /// Invalidates the current [`SnapshotToken`]. Nodes must only be added
+    /// or removed through [`Self::insert_node()`] and [`Self::remove_node()`]
+    /// respectively.
+    fn nodes_mut(&mut self) -> &mut HashMap<T::NodeId, Arc<TreeNode<T>>> {
+        self.invalidate_snapshot_token();
+        &mut self.nodes
+    }
+
+    /// Insert a new or replace an existing node.
+    ///
+    /// Only new nodes are recorded as inserted.
+    fn insert_node(&mut self, node: Arc<TreeNode<T>>) {
+        let inserted = !self.nodes.contains_key(&node.id);
+        self.nodes_mut().insert_mut(node.id.clone(), node);
+        if inserted {
+            crate::metrics::record_insert(self.nodes.size());
+        }
+    }
+
+    /// Remove a single node without its children.
+    ///
+    /// Returns `true` if the node has been removed.
+    fn remove_node(&mut self, node_id: &T::NodeId) -> bool {
+        let removed = self.nodes_mut().remove_mut(node_id);
+        if removed {
+            crate::metrics::record_removal(self.nodes.size());
+        }
+        removed
+    }
+
+    /// Insert a new or replace an existing node.
+    ///
+    /// The caller is responsible for keeping the tree consistent.
+    pub(crate) fn put_node(&mut self, node: TreeNode<T>) -> Arc<TreeNode<T>> {
+        let node = Arc::new(node);
+        self.insert_node(Arc::clone(&node));
+        node
+    }
+
+    /// Mount a subtree as a new child of an inner node.
+    ///
+    /// All nodes of the subtree retain their ids. The caller is responsible
+    /// for ensuring that the ids of both trees are disjoint and that the
+    /// child path segment is vacant.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn mount_subtree(
+        &mut self,
+        parent_node_id: T::NodeId,
+        child_path_segment: T::PathSegmentOwned,
+        subtree: Self,
+    ) {
+        let parent_node = Arc::clone(self.get_node(parent_node_id.clone()));
+        let Node::Inner(inner_node) = &parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let Self {
+            root_node_id: subtree_root_node_id,
+            nodes: subtree_nodes,
+            ..
+        } = subtree;
+        for (node_id, node) in &subtree_nodes {
+            debug_assert!(!self.nodes.contains_key(node_id));
+            let node = if *node_id == subtree_root_node_id {
+                Arc::new(TreeNode {
+                    id: node_id.clone(),
+                    parent: Some(HalfEdgeOwned {
+                        path_segment: child_path_segment.clone(),
+                        node_id: parent_node_id.clone(),
+                    }),
+                    node: node.node.clone(),
+                })
+            } else {
+                Arc::clone(node)
+            };
+            self.insert_node(node);
+        }
+        let mut inner_node = inner_node.clone();
+        strict_assert!(!inner_node
+            .children
+            .contains_key(&*T::child_key(child_path_segment.borrow())));
+        inner_node.children.insert_mut(
+            T::child_key(child_path_segment.borrow()).into_owned(),
+            HalfEdgeOwned {
+                path_segment: child_path_segment,
+                node_id: subtree_root_node_id,
+            },
+        );
+        self.put_node(TreeNode {
+            id: parent_node_id,
+            parent: parent_node.parent.clone(),
+            node: Node::Inner(inner_node),
+        });
     }
 
     #[must_use]
-    pub const fn root_node_id(&self) -> T::NodeId {
-        self.root_node_id
+    pub fn root_node_id(&self) -> T::NodeId {
+        self.root_node_id.clone()
     }
 
     #[must_use]
     pub fn root_node(&self) -> &Arc<TreeNode<T>> {
-        self.get_node(self.root_node_id)
+        self.get_node(self.root_node_id.clone())
     }
 
+    /// Clone the value of the root node.
     #[must_use]
-    pub fn lookup_node(&self, id: T::NodeId) -> Option<&Arc<TreeNode<T>>> {
-        self.nodes.get(&id)
+    pub fn root_value(&self) -> NodeValue<T> {
+        self.root_node().node.to_value()
     }
 
-    /// Get an existing node by its id.
+    /// Replace the value of the root node.
     ///
-    /// Only used internally for node ids that must exist. If the node does not exist
-    /// the tree is probably in an inconsistent state!
+    /// Returns the updated root node.
     ///
-    /// # Panics
+    /// Fails if the root node has children and the new value is a leaf value.
+    /// In case of an error, the new value is returned back to the caller.
+    pub fn set_root_value(
+        &mut self,
+        new_value: NodeValue<T>,
+    ) -> Result<Arc<TreeNode<T>>, UpdateNodeValueError<T>> {
+        let root_node = Arc::clone(self.root_node());
+        self.update_node_value(&root_node, new_value)
+    }
+
+    /// Update the value of the root node with a closure.
+    ///
+    /// The closure receives a clone of the current value and returns
+    /// the new value.
+    ///
+    /// See also: [`Self::set_root_value()`]
+    pub fn update_root_value_with(
+        &mut self,
+        update_value: impl FnOnce(NodeValue<T>) -> NodeValue<T>,
+    ) -> Result<Arc<TreeNode<T>>, UpdateNodeValueError<T>> {
+        let new_value = update_value(self.root_value());
+        self.set_root_value(new_value)
+    }
+
+    /// Behavior when inserting or updating a node at the root path.
+    #[must_use]
+    pub const fn root_path_policy(&self) -> RootPathPolicy {
+        self.root_path_policy
+    }
+
+    /// Configure the behavior when inserting or updating a node at the root path.
+    ///
+    /// Affects [`Self::insert_or_update_node_value()`] and
+    /// [`Self::try_insert_or_update_node_value()`]. Updating the root node
+    /// explicitly, e.g. by [`Self::set_root_value()`], is always permitted.
+    pub fn set_root_path_policy(&mut self, root_path_policy: RootPathPolicy) {
+        self.root_path_policy = root_path_policy;
+    }
+
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn lookup_node(&self, id: T::NodeId) -> Option<&Arc<TreeNode<T>>> {
+        self.nodes.get(&id)
+    }
+
+    /// Get an existing node by its id.
+    ///
+    /// Only used internally for node ids that must exist. If the node does not exist
+    /// the tree is probably in an inconsistent state!
+    ///
+    /// # Panics
     ///
     /// Panics if the node does not exist.
     #[must_use]
-    fn get_node(&self, id: T::NodeId) -> &Arc<TreeNode<T>> {
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub(crate) fn get_node(&self, id: T::NodeId) -> &Arc<TreeNode<T>> {
         self.nodes.get(&id).expect("node exists")
     }
 
@@ -273,8 +983,8 @@ impl<T: PathTreeTypes> PathTree<T> {
 
     #[must_use]
     pub fn contains_node(&self, node: &Arc<TreeNode<T>>) -> bool {
-        self.lookup_node(node.id)
-            .map_or(false, |tree_node| Arc::ptr_eq(tree_node, node))
+        self.lookup_node(node.id.clone())
+            .is_some_and(|tree_node| Arc::ptr_eq(tree_node, node))
     }
 
     /// Find a node by its path.
@@ -286,9 +996,23 @@ impl<T: PathTreeTypes> PathTree<T> {
         &'a self,
         path: &T::RootPath,
         match_path: MatchNodePath,
+    ) -> Option<NodePathResolved<'a, T>> {
+        let resolved = self.resolve_node_path_segments(path, match_path);
+        crate::metrics::record_lookup(
+            resolved
+                .as_ref()
+                .map(|resolved| resolved.matched_path.number_of_matched_segments()),
+        );
+        resolved
+    }
+
+    fn resolve_node_path_segments<'a>(
+        &'a self,
+        path: &T::RootPath,
+        match_path: MatchNodePath,
     ) -> Option<NodePathResolved<'a, T>> {
         // TODO: Use a trie data structure and Aho-Corasick algo for faster lookup?
-        let root_node = self.get_node(self.root_node_id);
+        let root_node = self.get_node(self.root_node_id.clone());
         let mut last_visited_node = root_node;
         let mut number_of_matched_path_segments = 0;
         let mut partial_path_match = false;
@@ -309,9 +1033,8 @@ impl<T: PathTreeTypes> PathTree<T> {
                 }
                 Node::Inner(inner_node) => {
                     let child_node = inner_node
-                        .children
-                        .get(path_segment)
-                        .map(|node_id| self.get_node(*node_id));
+                        .find_child(path_segment)
+                        .map(|node_id| self.get_node(node_id));
                     if let Some(child_node) = child_node {
                         last_visited_node = child_node;
                         number_of_matched_path_segments += 1;
@@ -330,13 +1053,15 @@ impl<T: PathTreeTypes> PathTree<T> {
                 }
             }
             debug_assert_eq!(
-                path_segment,
-                last_visited_node
-                    .parent
-                    .as_ref()
-                    .expect("has parent")
-                    .path_segment
-                    .borrow()
+                T::child_key(path_segment),
+                T::child_key(
+                    last_visited_node
+                        .parent
+                        .as_ref()
+                        .expect("has parent")
+                        .path_segment
+                        .borrow()
+                )
             );
         }
         let matched_path = if partial_path_match {
@@ -358,104 +1083,82 @@ impl<T: PathTreeTypes> PathTree<T> {
         })
     }
 
-    fn create_missing_ancestor_nodes<'a>(
+    fn create_missing_ancestor_nodes<'a, E>(
         &mut self,
         child_path: &'a T::RootPath,
-        mut new_inner_value: impl FnMut() -> T::InnerValue,
-        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
-    ) -> Result<TreeNodeParentChildContext<'a, T>, TreeNodeParentChildPathConflict<T>> {
+        mut new_inner_value: impl FnMut() -> Result<T::InnerValue, E>,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Result<Option<T::InnerValue>, E>,
+    ) -> Result<TreeNodeParentChildContext<'a, T>, CreateMissingAncestorNodesError<T, E>> {
         if child_path.is_root() {
             return Ok(TreeNodeParentChildContext {
                 parent_node: None,
                 child_path_segment: None,
+                affected_nodes: 0,
+                parent_node_affected: false,
             });
         }
         let mut try_clone_leaf_into_inner_value = Some(try_clone_leaf_into_inner_value);
         let mut next_parent_node = Arc::clone(self.root_node());
+        let mut next_parent_node_affected = false;
+        let mut affected_nodes = 0;
         let (parent_path_segments, child_path_segment) = child_path.parent_child_segments();
         debug_assert!(child_path_segment.is_some());
         for path_segment in parent_path_segments {
-            next_parent_node = match try_replace_leaf_with_inner_node(
-                &mut self.nodes,
+            let replace_leaf = matches!(next_parent_node.node, Node::Leaf(_));
+            next_parent_node = self.try_replace_leaf_parent_node(
                 next_parent_node,
+                path_segment,
                 &mut try_clone_leaf_into_inner_value,
-            ) {
-                Ok(next_parent_node) => next_parent_node,
-                Err(parent_node) => {
-                    return Err(TreeNodeParentChildPathConflict {
-                        parent_node,
-                        child_path_segment: T::path_segment_to_owned(path_segment),
-                    });
-                }
-            };
+            )?;
+            if replace_leaf {
+                affected_nodes += 1;
+                next_parent_node_affected = true;
+            }
             let Node::Inner(inner_node) = &next_parent_node.node else {
                 break;
             };
             let child_node = inner_node
-                .children
-                .get(path_segment)
-                .map(|node_id| self.get_node(*node_id));
+                .find_child(path_segment)
+                .map(|node_id| self.get_node(node_id));
             if let Some(child_node) = child_node {
-                log::debug!("Found child node {child_node:?} for path segment {path_segment:?}");
+                log_mutation!("Found child node {child_node:?} for path segment {path_segment:?}");
                 next_parent_node = Arc::clone(child_node);
+                next_parent_node_affected = false;
             } else {
                 // Add new, empty inner node
-                let child_node_id = self.new_node_id();
-                debug_assert_ne!(child_node_id, next_parent_node.id);
-                let child_node = TreeNode {
-                    id: child_node_id,
-                    parent: Some(HalfEdgeOwned {
-                        path_segment: T::path_segment_to_owned(path_segment),
-                        node_id: next_parent_node.id,
-                    }),
-                    node: Node::Inner(InnerNode::new(new_inner_value())),
-                };
-                log::debug!(
-                    "Inserting new child node {child_node:?} for path segment {path_segment:?}"
-                );
-                let child_node = Arc::new(child_node);
-                let new_next_parent_node = Arc::clone(&child_node);
-                self.nodes.insert_mut(child_node.id, child_node);
-                let mut inner_node = inner_node.clone();
-                inner_node
-                    .children
-                    .insert_mut(T::path_segment_to_owned(path_segment), child_node_id);
-                // Replace the parent node with the modified one.
-                update_parent_node(
-                    &mut self.nodes,
-                    TreeNode {
-                        id: next_parent_node.id,
-                        parent: next_parent_node.parent.clone(),
-                        node: inner_node.into(),
-                    },
+                let child_inner_value =
+                    new_inner_value().map_err(CreateMissingAncestorNodesError::NewValue)?;
+                let new_next_parent_node = self.insert_empty_inner_child_node(
+                    &next_parent_node,
+                    path_segment,
+                    child_inner_value,
                 );
+                affected_nodes += if next_parent_node_affected { 1 } else { 2 };
                 next_parent_node = new_next_parent_node;
+                next_parent_node_affected = true;
             }
-            debug_assert_eq!(
-                path_segment,
-                next_parent_node
-                    .parent
-                    .as_ref()
-                    .expect("has parent")
-                    .path_segment
-                    .borrow()
+            strict_assert_eq!(
+                T::child_key(path_segment),
+                T::child_key(
+                    next_parent_node
+                        .parent
+                        .as_ref()
+                        .expect("has parent")
+                        .path_segment
+                        .borrow()
+                )
             );
         }
-        let next_parent_node = match try_replace_leaf_with_inner_node(
-            &mut self.nodes,
+        let replace_leaf = matches!(next_parent_node.node, Node::Leaf(_));
+        let next_parent_node = self.try_replace_leaf_parent_node(
             next_parent_node,
+            child_path_segment.expect("child path segment should exist"),
             &mut try_clone_leaf_into_inner_value,
-        ) {
-            Ok(next_parent_node) => next_parent_node,
-            Err(parent_node) => {
-                return Err(TreeNodeParentChildPathConflict {
-                    parent_node,
-                    child_path_segment: child_path_segment
-                        .map(T::path_segment_to_owned)
-                        .expect("child path segment should exist"),
-                });
-            }
-        };
+        )?;
+        if replace_leaf {
+            affected_nodes += 1;
+            next_parent_node_affected = true;
+        }
         let parent_node = match next_parent_node.node {
             Node::Inner(_) => Some(next_parent_node),
             Node::Leaf(_) => None,
@@ -463,9 +1166,75 @@ impl<T: PathTreeTypes> PathTree<T> {
         Ok(TreeNodeParentChildContext {
             parent_node,
             child_path_segment,
+            affected_nodes,
+            parent_node_affected: next_parent_node_affected,
+        })
+    }
+
+    /// Replace a leaf node with an inner node before inserting a child node.
+    ///
+    /// Returns the given node if it is already an inner node.
+    fn try_replace_leaf_parent_node<E>(
+        &mut self,
+        parent_node: Arc<TreeNode<T>>,
+        child_path_segment: &T::PathSegment,
+        try_clone_leaf_into_inner_value: &mut Option<
+            impl FnOnce(&T::LeafValue) -> Result<Option<T::InnerValue>, E>,
+        >,
+    ) -> Result<Arc<TreeNode<T>>, CreateMissingAncestorNodesError<T, E>> {
+        try_replace_leaf_with_inner_node(
+            self.nodes_mut(),
+            parent_node,
+            try_clone_leaf_into_inner_value,
+        )
+        .map_err(CreateMissingAncestorNodesError::NewValue)?
+        .map_err(|parent_node| {
+            CreateMissingAncestorNodesError::PathConflict(TreeNodeParentChildPathConflict {
+                parent_node,
+                child_path_segment: T::path_segment_to_owned(child_path_segment),
+            })
         })
     }
 
+    /// Insert a new inner node without children below an existing inner node.
+    ///
+    /// Returns the new child node. The parent node is replaced.
+    fn insert_empty_inner_child_node(
+        &mut self,
+        parent_node: &Arc<TreeNode<T>>,
+        path_segment: &T::PathSegment,
+        child_inner_value: T::InnerValue,
+    ) -> Arc<TreeNode<T>> {
+        let Node::Inner(inner_node) = &parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let child_node_id = self.new_node_id();
+        debug_assert_ne!(child_node_id, parent_node.id);
+        let child_node = TreeNode {
+            id: child_node_id.clone(),
+            parent: Some(HalfEdgeOwned {
+                path_segment: T::path_segment_to_owned(path_segment),
+                node_id: parent_node.id.clone(),
+            }),
+            node: Node::Inner(InnerNode::new(child_inner_value)),
+        };
+        log_mutation!("Inserting new child node {child_node:?} for path segment {path_segment:?}");
+        let child_node = Arc::new(child_node);
+        self.insert_node(Arc::clone(&child_node));
+        let mut inner_node = inner_node.clone();
+        inner_node.insert_child(path_segment, child_node_id);
+        // Replace the parent node with the modified one.
+        update_parent_node(
+            self.nodes_mut(),
+            TreeNode {
+                id: parent_node.id.clone(),
+                parent: parent_node.parent.clone(),
+                node: inner_node.into(),
+            },
+        );
+        child_node
+    }
+
     /// Insert or update a node in the tree.
     ///
     /// All missing parent nodes are created recursively and initialized
@@ -475,10 +1244,11 @@ impl<T: PathTreeTypes> PathTree<T> {
     /// with an inner node by calling `try_clone_leaf_into_inner_value`.
     ///
     /// Returns the updated parent node and the inserted/updated child node.
-    /// The parent node is `None` if the root node has been updated.
+    /// The parent node is `None` if the root node has been updated, see also
+    /// [`NodeInsertedOrUpdated::is_root()`]. Updating the root node could be
+    /// rejected by [`Self::set_root_path_policy()`].
     ///
     /// In case of an error, the new value is returned back to the caller.
-    #[allow(clippy::missing_panics_doc)] // Never panics
     pub fn insert_or_update_node_value(
         &mut self,
         path: &T::RootPath,
@@ -486,21 +1256,157 @@ impl<T: PathTreeTypes> PathTree<T> {
         new_inner_value: &mut impl FnMut() -> T::InnerValue,
         try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
     ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
+        self.insert_or_update_node_value_with_fallible_closures(
+            path,
+            new_value,
+            || Ok::<_, Infallible>(new_inner_value()),
+            |leaf_value| Ok(try_clone_leaf_into_inner_value(leaf_value)),
+        )
+        .map_err(|err| match err {
+            TryInsertOrUpdateNodeValueError::PathConflict { conflict, value } => {
+                InsertOrUpdateNodeValueError::PathConflict { conflict, value }
+            }
+            TryInsertOrUpdateNodeValueError::ValueTypeMismatch { value } => {
+                InsertOrUpdateNodeValueError::ValueTypeMismatch { value }
+            }
+            TryInsertOrUpdateNodeValueError::NewValue { source, .. } => match source {},
+            TryInsertOrUpdateNodeValueError::SubtreeFrozen { node_id, value } => {
+                InsertOrUpdateNodeValueError::SubtreeFrozen { node_id, value }
+            }
+            TryInsertOrUpdateNodeValueError::RootPath { value } => {
+                InsertOrUpdateNodeValueError::RootPath { value }
+            }
+            TryInsertOrUpdateNodeValueError::StaleNodeReference { node_id, value } => {
+                InsertOrUpdateNodeValueError::StaleNodeReference { node_id, value }
+            }
+            TryInsertOrUpdateNodeValueError::ConflictRejected { node_id, value } => {
+                InsertOrUpdateNodeValueError::ConflictRejected { node_id, value }
+            }
+        })
+    }
+
+    /// Insert or update a node in the tree with a closure.
+    ///
+    /// The closure receives a clone of the current value or `None` if the
+    /// node does not exist yet and returns the new value. The node is looked
+    /// up by its path in the current tree, i.e. no stale node is involved.
+    ///
+    /// Otherwise the same as [`Self::insert_or_update_node_value()`].
+    ///
+    /// See also: [`Self::update_root_value_with()`]
+    pub fn update_node_value_with(
+        &mut self,
+        path: &T::RootPath,
+        update_value: impl FnOnce(Option<&NodeValue<T>>) -> NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+    ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
+        let old_value = self.find_node(path).map(|node| node.node.to_value());
+        let new_value = update_value(old_value.as_ref());
+        self.insert_or_update_node_value(
+            path,
+            new_value,
+            new_inner_value,
+            try_clone_leaf_into_inner_value,
+        )
+    }
+
+    /// Insert or update a node in the tree with fallible closures.
+    ///
+    /// Same as [`Self::insert_or_update_node_value()`], but the closures
+    /// for creating new inner values of missing parent nodes could fail.
+    ///
+    /// The tree remains unchanged if any closure fails. The error is
+    /// returned together with the new value.
+    pub fn try_insert_or_update_node_value<E>(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        new_inner_value: &mut impl FnMut() -> Result<T::InnerValue, E>,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Result<Option<T::InnerValue>, E>,
+    ) -> Result<NodeInsertedOrUpdated<T>, TryInsertOrUpdateNodeValueError<T, E>> {
+        // Restores missing ancestor nodes that might have been created
+        // before a closure failed.
+        self.with_rollback(|tree| {
+            tree.insert_or_update_node_value_with_fallible_closures(
+                path,
+                new_value,
+                new_inner_value,
+                try_clone_leaf_into_inner_value,
+            )
+        })
+    }
+
+    /// Apply multiple modifications atomically.
+    ///
+    /// The closure operates on a clone of the tree, which only replaces this
+    /// tree if the closure succeeds. Otherwise the clone is discarded and the
+    /// error is returned. This tree also remains unchanged if the closure panics.
+    ///
+    /// Cloning the tree is cheap, because all nodes are shared until they
+    /// are modified, see [`Self::clone()`].
+    pub fn with_rollback<R, E>(
+        &mut self,
+        modify: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut tree = self.clone();
+        let result = modify(&mut tree).inspect_err(|_| {
+            log::debug!("Rolling back modifications");
+        })?;
+        *self = tree;
+        Ok(result)
+    }
+
+    /// Apply multiple modifications atomically.
+    ///
+    /// Same as [`Self::with_rollback()`].
+    pub fn transaction<R, E>(
+        &mut self,
+        modify: impl FnOnce(&mut Self) -> Result<R, E>,
+    ) -> Result<R, E> {
+        self.with_rollback(modify)
+    }
+
+    fn insert_or_update_node_value_with_fallible_closures<E>(
+        &mut self,
+        path: &T::RootPath,
+        new_value: NodeValue<T>,
+        new_inner_value: impl FnMut() -> Result<T::InnerValue, E>,
+        try_clone_leaf_into_inner_value: impl FnOnce(&T::LeafValue) -> Result<Option<T::InnerValue>, E>,
+    ) -> Result<NodeInsertedOrUpdated<T>, TryInsertOrUpdateNodeValueError<T, E>> {
+        if path.is_root() && self.root_path_policy == RootPathPolicy::Reject {
+            log::debug!("Rejecting insert or update at the root path");
+            return Err(TryInsertOrUpdateNodeValueError::RootPath { value: new_value });
+        }
+        if let Some(node_id) = self.find_frozen_node_on_path(path) {
+            return Err(TryInsertOrUpdateNodeValueError::SubtreeFrozen {
+                node_id,
+                value: new_value,
+            });
+        }
         let TreeNodeParentChildContext {
             parent_node,
             child_path_segment,
+            affected_nodes,
+            parent_node_affected,
         } = match self.create_missing_ancestor_nodes(
             path,
             new_inner_value,
             try_clone_leaf_into_inner_value,
         ) {
             Ok(context) => context,
-            Err(conflict) => {
-                return Err(InsertOrUpdateNodeValueError::PathConflict {
+            Err(CreateMissingAncestorNodesError::PathConflict(conflict)) => {
+                return Err(TryInsertOrUpdateNodeValueError::PathConflict {
                     conflict,
                     value: new_value,
                 });
             }
+            Err(CreateMissingAncestorNodesError::NewValue(source)) => {
+                return Err(TryInsertOrUpdateNodeValueError::NewValue {
+                    source,
+                    value: new_value,
+                });
+            }
         };
         let Some(parent_node) = parent_node else {
             // Update the root node.
@@ -509,11 +1415,23 @@ impl<T: PathTreeTypes> PathTree<T> {
             return Ok(NodeInsertedOrUpdated {
                 node: new_root_node,
                 parent: None,
+                affected_nodes: affected_nodes + 1,
             });
         };
         debug_assert!(matches!(parent_node.node, Node::Inner(_)));
         let child_path_segment = child_path_segment.expect("should never be empty");
-        self.insert_or_update_child_node_value(&parent_node, child_path_segment, None, new_value)
+        let mut inserted_or_updated = self.insert_or_update_child_node_value(
+            &parent_node,
+            child_path_segment,
+            None,
+            new_value,
+        )?;
+        inserted_or_updated.affected_nodes += affected_nodes;
+        if parent_node_affected && inserted_or_updated.parent.is_some() {
+            // The parent node must only be counted once.
+            inserted_or_updated.affected_nodes -= 1;
+        }
+        Ok(inserted_or_updated)
     }
 
     /// Insert or update a child node in the tree.
@@ -521,10 +1439,17 @@ impl<T: PathTreeTypes> PathTree<T> {
     /// The parent node must exist and it must be an inner node.
     ///
     /// By providing `old_child_path_segment` an existing node could
-    /// be renamed and updated. This will retain its `NodeId`.
+    /// be renamed and updated. This will retain its `NodeId`. If no
+    /// child node exists at `old_child_path_segment` the node at
+    /// `child_path_segment` is inserted or updated instead. Renaming
+    /// a node to a path segment with the same [`PathTreeTypes::ChildKey`]
+    /// only replaces its path segment.
     ///
     /// Returns the updated parent node and the inserted/updated child node.
     ///
+    /// Fails if the given parent node does not belong to the tree, e.g. if it
+    /// has been obtained from an older snapshot.
+    ///
     /// In case of an error, the new value is returned back to the caller.
     #[allow(clippy::missing_panics_doc)] // Never panics
     #[allow(clippy::too_many_lines)] // TODO
@@ -535,7 +1460,12 @@ impl<T: PathTreeTypes> PathTree<T> {
         old_child_path_segment: Option<&T::PathSegment>,
         new_value: NodeValue<T>,
     ) -> Result<NodeInsertedOrUpdated<T>, InsertOrUpdateNodeValueError<T>> {
-        debug_assert!(self.contains_node(parent_node));
+        if !self.contains_node(parent_node) {
+            return Err(InsertOrUpdateNodeValueError::StaleNodeReference {
+                node_id: parent_node.id.clone(),
+                value: new_value,
+            });
+        }
         debug_assert!(matches!(parent_node.node, Node::Inner(_)));
         let Node::Inner(inner_node) = &parent_node.node else {
             return Err(InsertOrUpdateNodeValueError::PathConflict {
@@ -546,38 +1476,65 @@ impl<T: PathTreeTypes> PathTree<T> {
                 value: new_value,
             });
         };
-        let old_child_path_segment = old_child_path_segment.unwrap_or(child_path_segment);
-        let (child_node, inner_node_and_removed_subtree) = if let Some(child_node) = inner_node
-            .children
-            .get(old_child_path_segment)
-            .map(|node_id| self.get_node(*node_id))
+        let old_child_path_segment = old_child_path_segment
+            .filter(|old_child_path_segment| {
+                inner_node
+                    .children
+                    .contains_key(&*T::child_key(old_child_path_segment))
+            })
+            // Update the value of an existing child node if the old child node does not exist.
+            .unwrap_or(child_path_segment);
+        if let Some(node_id) = self.find_frozen_node_for_child_update(
+            parent_node,
+            child_path_segment,
+            old_child_path_segment,
+        ) {
+            return Err(InsertOrUpdateNodeValueError::SubtreeFrozen {
+                node_id,
+                value: new_value,
+            });
+        }
+        let (child_node, inner_node_and_removed_subtree, affected_nodes) = if let Some(child_node) =
+            inner_node
+                .children
+                .get(&*T::child_key(old_child_path_segment))
+                .map(|HalfEdgeOwned { node_id, .. }| self.get_node(node_id.clone()))
         {
-            let child_node_id = child_node.id;
-            log::debug!("Updating value of existing child node {child_node_id}");
+            let child_node_id = child_node.id.clone();
+            log_mutation!("Updating value of existing child node {child_node_id}");
             let old_child_node = Arc::clone(child_node);
             if old_child_path_segment == child_path_segment {
                 // No renaming.
                 let new_child_node = self.update_node_value(&old_child_node, new_value)?;
-                (new_child_node, None)
+                (new_child_node, None, 1)
             } else {
                 let new_parent = HalfEdgeOwned {
                     path_segment: T::path_segment_to_owned(child_path_segment),
-                    node_id: parent_node.id,
+                    node_id: parent_node.id.clone(),
                 };
                 let updated_child_node =
                     old_child_node.try_clone_with_parent_and_value(Some(new_parent), new_value)?;
+                // Renaming the child node within the same child key must not
+                // remove the child node itself.
                 let (mut inner_node, removed_subtree) = if let Some(subtree_root_node_id) =
-                    parent_node.node.find_child(child_path_segment)
+                    parent_node
+                        .node
+                        .find_child(child_path_segment)
+                        .filter(|node_id| *node_id != child_node_id)
                 {
-                    log::debug!("Removing child node {child_node_id} with subtree from {child_path_segment:?}");
+                    log_mutation!("Removing child node {child_node_id} with subtree from {child_path_segment:?}");
                     let removed_subtree = self.remove_subtree_by_id(subtree_root_node_id);
                     debug_assert!(removed_subtree.is_some());
                     let SubtreeRemoved {
                         parent_node,
                         child_path_segment: removed_child_path_segment,
                         removed_subtree,
+                        affected_nodes: _,
                     } = removed_subtree.expect("subtree has been removed");
-                    debug_assert_eq!(removed_child_path_segment.borrow(), child_path_segment);
+                    strict_assert_eq!(
+                        T::child_key(removed_child_path_segment.borrow()),
+                        T::child_key(child_path_segment)
+                    );
                     let Node::Inner(inner_node) = &parent_node.node else {
                         unreachable!();
                     };
@@ -587,54 +1544,56 @@ impl<T: PathTreeTypes> PathTree<T> {
                     (inner_node.clone(), None)
                 };
                 // Move the updated node to the new, empty location.
-                log::debug!("Moving child node {child_node_id} from {old_child_path_segment:?} to {child_path_segment:?}");
-                inner_node.children.remove_mut(old_child_path_segment);
-                debug_assert!(self.nodes.contains_key(&child_node_id));
-                let child_node_id = updated_child_node.id;
-                let new_child_node = Arc::new(updated_child_node);
-                self.nodes
-                    .insert_mut(child_node_id, Arc::clone(&new_child_node));
-                debug_assert!(!inner_node.children.contains_key(child_path_segment));
+                log_mutation!("Moving child node {child_node_id} from {old_child_path_segment:?} to {child_path_segment:?}");
                 inner_node
                     .children
-                    .insert_mut(T::path_segment_to_owned(child_path_segment), child_node_id);
-                (new_child_node, Some((inner_node, removed_subtree)))
+                    .remove_mut(&*T::child_key(old_child_path_segment));
+                debug_assert!(self.nodes.contains_key(&child_node_id));
+                let child_node_id = updated_child_node.id.clone();
+                let new_child_node = Arc::new(updated_child_node);
+                self.insert_node(Arc::clone(&new_child_node));
+                // Only new nodes are recorded implicitly, but not updated values.
+                crate::metrics::record_insert(self.nodes.size());
+                inner_node.insert_child(child_path_segment, child_node_id);
+                // The renamed child node and its parent node are affected.
+                let affected_nodes = 2 + removed_subtree
+                    .as_ref()
+                    .map_or(0, |subtree| subtree.nodes_count().get());
+                (
+                    new_child_node,
+                    Some((inner_node, removed_subtree)),
+                    affected_nodes,
+                )
             }
         } else {
             let child_node_id = self.new_node_id();
-            log::debug!("Adding new child node {child_node_id}");
+            log_mutation!("Adding new child node {child_node_id}");
             debug_assert!(!self.nodes.contains_key(&child_node_id));
             let new_child_node = TreeNode {
                 id: child_node_id,
                 parent: Some(HalfEdgeOwned {
                     path_segment: T::path_segment_to_owned(child_path_segment),
-                    node_id: parent_node.id,
+                    node_id: parent_node.id.clone(),
                 }),
                 node: Node::from_value_without_children(new_value),
             };
-            let child_node_id = new_child_node.id;
+            let child_node_id = new_child_node.id.clone();
             let new_child_node = Arc::new(new_child_node);
-            self.nodes
-                .insert_mut(child_node_id, Arc::clone(&new_child_node));
-            log::debug!(
+            self.insert_node(Arc::clone(&new_child_node));
+            log_mutation!(
                 "Inserted new child node {new_child_node:?}",
                 new_child_node = *new_child_node,
             );
             let mut inner_node = inner_node.clone();
-            if let Some(child_node_id_mut) = inner_node.children.get_mut(child_path_segment) {
-                *child_node_id_mut = child_node_id;
-            } else {
-                inner_node
-                    .children
-                    .insert_mut(T::path_segment_to_owned(child_path_segment), child_node_id);
-            }
-            (new_child_node, Some((inner_node, None)))
+            inner_node.insert_child(child_path_segment, child_node_id);
+            // The new child node and its parent node are affected.
+            (new_child_node, Some((inner_node, None)), 2)
         };
         let parent = inner_node_and_removed_subtree.map(|(inner_node, removed_subtree)| {
             let new_parent_node = update_parent_node(
-                &mut self.nodes,
+                self.nodes_mut(),
                 TreeNode {
-                    id: parent_node.id,
+                    id: parent_node.id.clone(),
                     parent: parent_node.parent.clone(),
                     node: Node::Inner(inner_node),
                 },
@@ -647,6 +1606,7 @@ impl<T: PathTreeTypes> PathTree<T> {
         Ok(NodeInsertedOrUpdated {
             node: child_node,
             parent,
+            affected_nodes,
         })
     }
 
@@ -654,22 +1614,40 @@ impl<T: PathTreeTypes> PathTree<T> {
     ///
     /// Inner nodes with children could only be updated with an inner value.
     ///
-    /// Returns the updated node with the new value.
+    /// Returns the updated node with the new value. The node remains unchanged
+    /// if the new value is equal to the current value, see also
+    /// [`PathTreeTypes::inner_values_equal()`] and [`PathTreeTypes::leaf_values_equal()`].
     ///
-    /// In case of an error, the new value is returned back to the caller.
+    /// Fails if the given node does not belong to the tree, e.g. if it
+    /// has been obtained from an older snapshot.
     ///
-    /// Undefined behavior if the given node does not belong to the tree.
-    /// This precondition is only checked by debug assertions.
+    /// In case of an error, the new value is returned back to the caller.
     #[allow(clippy::missing_panics_doc)] // Never panics
     pub fn update_node_value(
         &mut self,
         node: &Arc<TreeNode<T>>,
         new_value: NodeValue<T>,
     ) -> Result<Arc<TreeNode<T>>, UpdateNodeValueError<T>> {
-        debug_assert!(self.contains_node(node));
+        if !self.contains_node(node) {
+            return Err(UpdateNodeValueError::StaleNodeReference {
+                node_id: node.id.clone(),
+                value: new_value,
+            });
+        }
+        if node.node.has_equal_value(&new_value) {
+            return Ok(Arc::clone(node));
+        }
+        if let Some(node_id) = self.find_frozen_ancestor_or_self(node) {
+            return Err(UpdateNodeValueError::SubtreeFrozen {
+                node_id,
+                value: new_value,
+            });
+        }
         let new_node = Arc::new(node.try_clone_with_value(new_value)?);
-        self.nodes.insert_mut(node.id, Arc::clone(&new_node));
-        log::debug!("Updated node value: {node:?} -> {new_node:?}");
+        self.insert_node(Arc::clone(&new_node));
+        log_mutation!("Updated node value: {node:?} -> {new_node:?}");
+        // Only new nodes are recorded implicitly, but not updated values.
+        crate::metrics::record_insert(self.nodes.size());
         Ok(new_node)
     }
 
@@ -678,37 +1656,48 @@ impl<T: PathTreeTypes> PathTree<T> {
     /// Removes and returns the entire subtree rooted at the given node.
     ///
     /// The root node cannot be removed and the tree remains unchanged.
+    /// The same applies to frozen subtrees and subtrees that contain or
+    /// are contained in a frozen subtree, see [`Self::freeze_subtree()`].
     ///
     /// Returns the removed subtree or `None` if unchanged.
     /// The node ids in the removed subtree remain unchanged.
-    #[allow(clippy::missing_panics_doc)] // Never panics
+    ///
+    /// See also: [`Self::remove_subtree_by_id_with_scratch()`]
     pub fn remove_subtree_by_id(&mut self, node_id: T::NodeId) -> Option<SubtreeRemoved<T>> {
+        self.remove_subtree_by_id_with_scratch(node_id, &mut TraversalScratch::new())
+    }
+
+    /// Remove a node and its children from the tree, reusing temporary buffers.
+    ///
+    /// Same as [`Self::remove_subtree_by_id()`].
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn remove_subtree_by_id_with_scratch(
+        &mut self,
+        node_id: T::NodeId,
+        scratch: &mut TraversalScratch<T>,
+    ) -> Option<SubtreeRemoved<T>> {
         if node_id == self.root_node_id {
             // Cannot remove the root node.
             return None;
         }
         let nodes_count_before = self.nodes_count();
         let node = self.nodes.get(&node_id).map(Arc::clone)?;
-        let removed = self.nodes.remove_mut(&node_id);
+        if let Some(frozen_node_id) = self.find_frozen_node_affected_by_removal(&node) {
+            log::debug!("Cannot remove node {node_id}: subtree of node {frozen_node_id} is frozen");
+            return None;
+        }
+        let removed = self.remove_node(&node_id);
         debug_assert!(removed);
         // The descendants of the removed node could still be collected,
         // even though the tree is already incomplete.
-        let descendant_node_ids = node
-            .node
-            .descendants(self)
-            .map(
-                |HalfEdge {
-                     path_segment: _,
-                     node_id,
-                 }| node_id,
-            )
-            .collect::<Vec<_>>();
+        scratch.collect_descendants(self, &node.node);
         // Split off the nodes of the subtree from the remaining nodes.
-        let mut subtree_nodes: HashMap<_, _> = descendant_node_ids
-            .into_iter()
+        let mut subtree_nodes: HashMap<_, _> = scratch
+            .descendants
+            .drain(..)
             .filter_map(|node_id| {
                 let node = self.nodes.get(&node_id).map(Arc::clone)?;
-                let removed = self.nodes.remove_mut(&node_id);
+                let removed = self.remove_node(&node_id);
                 debug_assert!(removed);
                 Some((node_id, node))
             })
@@ -727,14 +1716,16 @@ impl<T: PathTreeTypes> PathTree<T> {
                 unreachable!();
             };
             let mut inner_node = inner_node.clone();
-            inner_node.children.remove_mut(parent_path_segment.borrow());
+            inner_node
+                .children
+                .remove_mut(&*T::child_key(parent_path_segment.borrow()));
             TreeNode {
-                id: parent_node.id,
+                id: parent_node.id.clone(),
                 parent: parent_node.parent.clone(),
                 node: Node::Inner(inner_node),
             }
         };
-        let new_parent_node = update_parent_node(&mut self.nodes, new_parent_node);
+        let new_parent_node = update_parent_node(self.nodes_mut(), new_parent_node);
         // The tree is now back in a consistent state and we can use the public API again.
         let nodes_count_after = self.nodes_count();
         debug_assert!(nodes_count_before >= nodes_count_after);
@@ -748,11 +1739,16 @@ impl<T: PathTreeTypes> PathTree<T> {
             parent: None,
             node,
         });
-        subtree_nodes.insert_mut(node_id, subtree_root_node);
+        subtree_nodes.insert_mut(node_id.clone(), subtree_root_node);
         let removed_subtree = Self {
             root_node_id: node_id,
             nodes: subtree_nodes,
             new_node_id: self.new_node_id.clone(),
+            provenance: None,
+            frozen_node_ids: new_hash_set(),
+            snapshot_token: SnapshotToken::next(),
+            snapshot_lineage: Arc::new(SnapshotLineage::new()),
+            root_path_policy: self.root_path_policy,
             _types: PhantomData,
         };
         debug_assert_eq!(removed_nodes_count, removed_subtree.nodes_count().get());
@@ -760,6 +1756,8 @@ impl<T: PathTreeTypes> PathTree<T> {
             parent_node: new_parent_node,
             child_path_segment,
             removed_subtree,
+            // The removed nodes and the updated parent node.
+            affected_nodes: removed_nodes_count + 1,
         })
     }
 
@@ -775,134 +1773,1081 @@ impl<T: PathTreeTypes> PathTree<T> {
     /// By providing `old_child_path_segment` an existing node could
     /// be renamed and replaced by the subtree. This will retain its
     /// `NodeId`.
-    #[allow(clippy::missing_panics_doc)] // Never panics
+    ///
+    /// Fails if the given parent node does not belong to the tree, e.g. if it
+    /// has been obtained from an older snapshot.
+    ///
+    /// The subtree is either inserted completely or the tree remains
+    /// unchanged. In case of an error, the value of the node that could
+    /// not be inserted is returned back to the caller.
+    ///
+    /// The value of a replaced node is always overwritten by the value of the
+    /// root node of the subtree like [`crate::PreferTheirs`]. See also
+    /// [`Self::insert_or_replace_subtree_with_policy()`].
     pub fn insert_or_replace_subtree(
         &mut self,
         parent_node: &Arc<TreeNode<T>>,
         child_path_segment: &T::PathSegment,
         old_child_path_segment: Option<&T::PathSegment>,
-        mut subtree: Self,
+        subtree: Self,
     ) -> Result<SubtreeInsertedOrReplaced<T>, InsertOrUpdateNodeValueError<T>> {
-        debug_assert!(self.contains_node(parent_node));
-        // Initialized with the old node id, which will be replaced with the new node id
-        // after the root node of the subtree has been inserted/replaced.
-        let mut subtree_root_node_id = subtree.root_node_id();
-        let mut subtree_root_parent_updated = None;
-        {
-            let subtree_node_ids = std::iter::once(subtree.root_node_id())
-                .chain(subtree.root_node().node.descendants(&subtree).map(
-                    |HalfEdge {
-                         path_segment: _,
-                         node_id,
-                     }| node_id,
-                ))
-                .collect::<Vec<_>>();
-            let mut old_to_new_node_id =
-                std::collections::HashMap::<T::NodeId, T::NodeId>::with_capacity(
-                    subtree_node_ids.len(),
-                );
-            for old_node_id in subtree_node_ids {
-                let old_node = subtree
-                    .nodes
-                    .get(&old_node_id)
-                    .map(Arc::clone)
-                    .expect("node exists");
-                let removed = subtree.nodes.remove_mut(&old_node_id);
-                debug_assert!(removed);
-                // Ideally, the nodes in the subtree are not referenced in the outer
-                // context to avoid cloning them. For most use cases this assumption
-                // should be valid.
-                let TreeNode {
-                    id: _,
-                    parent,
-                    node,
-                } = Arc::unwrap_or_clone(old_node);
-                // TODO: This could be optimized when not reusing insert_or_update_child_node_value()
-                // and instead inserting or replacing the node directly.
-                let (parent_node, child_path_segment, old_child_path_segment) =
-                    if let Some(parent) = parent {
-                        debug_assert!(old_to_new_node_id.contains_key(&parent.node_id));
-                        let parent_node_id = old_to_new_node_id
-                            .get(&parent.node_id)
-                            .copied()
-                            .expect("parent node has already been inserted");
-                        let parent_node = self
-                            .nodes
-                            .get(&parent_node_id)
-                            .expect("parent node has already been inserted");
-                        (parent_node, parent.path_segment, None)
-                    } else {
-                        // Root node.
-                        debug_assert_eq!(old_node_id, subtree.root_node_id());
-                        (
-                            parent_node,
-                            T::path_segment_to_owned(child_path_segment),
-                            old_child_path_segment,
-                        )
-                    };
-                let node_value = match node {
-                    Node::Inner(inner) => NodeValue::Inner(inner.value),
-                    Node::Leaf(leaf) => NodeValue::Leaf(leaf.value),
-                };
-                let NodeInsertedOrUpdated {
-                    node: child_node,
-                    parent,
-                } = self
-                    .insert_or_update_child_node_value(
-                        &Arc::clone(parent_node),
-                        child_path_segment.borrow(),
-                        old_child_path_segment,
-                        node_value,
-                    )
-                    .inspect_err(|_| {
-                        // Insertion could only fail for the first node,
-                        // which is the root node of the subtree. This ensures
-                        // that `self` remains unchanged on error.
-                        debug_assert_eq!(old_node_id, subtree.root_node_id());
-                    })?;
-                let child_node_id = child_node.id;
-                if old_node_id == subtree.root_node_id() {
-                    // Subtree root node inserted/updated.
-                    subtree_root_node_id = child_node_id;
-                    subtree_root_parent_updated = parent;
-                }
-                debug_assert!(!old_to_new_node_id.contains_key(&old_node_id));
-                old_to_new_node_id.insert(old_node_id, child_node_id);
-            }
-        }
-        Ok(SubtreeInsertedOrReplaced {
-            child_node_id: subtree_root_node_id,
-            parent: subtree_root_parent_updated.expect("parent node has been updated"),
+        // Inserting the descendants of the subtree root node might fail
+        // after the root node has already been inserted.
+        self.with_rollback(|tree| {
+            tree.insert_or_replace_subtree_nodes(
+                parent_node,
+                child_path_segment,
+                old_child_path_segment,
+                subtree,
+            )
         })
     }
 
-    /// Retain only the nodes that match the given predicate.
+    /// Insert a subtree and resolve the value of a replaced node.
     ///
-    /// The root node is always retained and cannot be removed.
+    /// Same as [`Self::insert_or_replace_subtree()`], but if the root node
+    /// of the subtree replaces an existing node, i.e. the node at
+    /// `old_child_path_segment` or otherwise at `child_path_segment`,
+    /// both values are resolved by the `policy`:
     ///
-    /// Returns the number of nodes that have been removed.
-    #[allow(clippy::missing_panics_doc)] // Never panics
-    pub fn retain_nodes(&mut self, mut predicate: impl FnMut(&TreeNode<T>) -> bool) {
+    /// - [`ConflictResolution::Ours`] retains the value of the existing node.
+    /// - [`ConflictResolution::Theirs`] takes the value of the subtree root node.
+    /// - [`ConflictResolution::Value`] takes the given value.
+    /// - [`ConflictResolution::Reject`] fails with
+    ///   [`InsertOrUpdateNodeValueError::ConflictRejected`].
+    ///
+    /// The descendants of the subtree root node are inserted in any case,
+    /// unless the conflict is rejected.
+    pub fn insert_or_replace_subtree_with_policy(
+        &mut self,
+        parent_node: &Arc<TreeNode<T>>,
+        child_path_segment: &T::PathSegment,
+        old_child_path_segment: Option<&T::PathSegment>,
+        mut subtree: Self,
+        policy: &mut impl ConflictPolicy<T>,
+    ) -> Result<SubtreeInsertedOrReplaced<T>, InsertOrUpdateNodeValueError<T>> {
+        let replaced_node = self
+            .contains_node(parent_node)
+            .then(|| {
+                parent_node
+                    .node
+                    .find_child(old_child_path_segment.unwrap_or(child_path_segment))
+            })
+            .flatten()
+            .map(|node_id| Arc::clone(self.get_node(node_id)));
+        if let Some(replaced_node) = replaced_node {
+            let path = self
+                .path_to_node(&replaced_node)
+                .map(|HalfEdgeTreeNode { path_segment, .. }| T::path_segment_to_owned(path_segment))
+                .collect::<Vec<_>>();
+            let ours = replaced_node.node.to_value();
+            let theirs = subtree.root_value();
+            let value = match policy.resolve(ValueConflict {
+                path: &path,
+                ours: &ours,
+                theirs: &theirs,
+            }) {
+                ConflictResolution::Ours => Some(ours),
+                ConflictResolution::Theirs => None,
+                ConflictResolution::Value(value) => Some(value),
+                ConflictResolution::Reject => {
+                    return Err(InsertOrUpdateNodeValueError::ConflictRejected {
+                        node_id: replaced_node.id.clone(),
+                        value: theirs,
+                    });
+                }
+            };
+            if let Some(value) = value {
+                subtree.set_root_value(value)?;
+            }
+        }
+        self.insert_or_replace_subtree(
+            parent_node,
+            child_path_segment,
+            old_child_path_segment,
+            subtree,
+        )
+    }
+
+    /// Copy a subtree to a new location within the tree.
+    ///
+    /// The source node and all its descendants are inserted as a child of
+    /// `dst_parent_node` at `dst_child_path_segment` like
+    /// [`Self::insert_or_replace_subtree()`]. All copied nodes are assigned
+    /// new ids. The source subtree remains unchanged, unless it is replaced
+    /// by its copy.
+    ///
+    /// Fails if any of the given nodes does not belong to the tree, e.g. if
+    /// it has been obtained from an older snapshot. The value of the source
+    /// node is then returned back to the caller.
+    pub fn copy_subtree(
+        &mut self,
+        src_node: &Arc<TreeNode<T>>,
+        dst_parent_node: &Arc<TreeNode<T>>,
+        dst_child_path_segment: &T::PathSegment,
+    ) -> Result<SubtreeInsertedOrReplaced<T>, InsertOrUpdateNodeValueError<T>> {
+        if !self.contains_node(src_node) {
+            return Err(InsertOrUpdateNodeValueError::StaleNodeReference {
+                node_id: src_node.id.clone(),
+                value: src_node.node.to_value(),
+            });
+        }
+        let subtree = self.clone_subtree(src_node);
+        log::debug!(
+            "Copying subtree of node {src_node_id} with {nodes_count} node(s)",
+            src_node_id = src_node.id,
+            nodes_count = subtree.nodes_count(),
+        );
+        self.insert_or_replace_subtree(dst_parent_node, dst_child_path_segment, None, subtree)
+    }
+
+    /// Build a new tree from a node and all its descendants.
+    ///
+    /// The nodes are shared with this tree and retain their ids.
+    pub(crate) fn clone_subtree(&self, node: &Arc<TreeNode<T>>) -> Self {
+        let subtree_root_node = Arc::new(TreeNode {
+            id: node.id.clone(),
+            parent: None,
+            node: node.node.clone(),
+        });
+        let nodes = std::iter::once((node.id.clone(), subtree_root_node))
+            .chain(node.node.descendants(self).map(
+                |HalfEdge {
+                     path_segment: _,
+                     child_key: _,
+                     node_id,
+                 }| {
+                    let node = Arc::clone(self.get_node(node_id.clone()));
+                    (node_id, node)
+                },
+            ))
+            .collect();
+        Self {
+            root_node_id: node.id.clone(),
+            nodes,
+            new_node_id: self.new_node_id.clone(),
+            provenance: None,
+            frozen_node_ids: new_hash_set(),
+            snapshot_token: SnapshotToken::next(),
+            snapshot_lineage: Arc::new(SnapshotLineage::new()),
+            root_path_policy: self.root_path_policy,
+            _types: PhantomData,
+        }
+    }
+
+    /// The ids of all nodes, parent nodes before their children.
+    fn node_ids_depth_first(&self) -> Vec<T::NodeId> {
+        std::iter::once(self.root_node_id())
+            .chain(self.root_node().node.descendants(self).map(
+                |HalfEdge {
+                     path_segment: _,
+                     child_key: _,
+                     node_id,
+                 }| node_id,
+            ))
+            .collect()
+    }
+
+    /// Assign new ids to all nodes.
+    ///
+    /// The new ids are generated by `new_node_id` in depth-first order,
+    /// starting with the root node. Afterwards, `new_node_id` replaces the
+    /// id generator of the tree.
+    ///
+    /// Useful for preparing a detached subtree ahead of time, e.g. for
+    /// generating dense ids before serializing it or for generating ids
+    /// that do not collide with the nodes of another tree. The structure
+    /// and the values of the tree remain unchanged. Frozen subtrees
+    /// remain frozen.
+    ///
+    /// Returns the mapping from old to new node ids.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn reassign_ids(&mut self, mut new_node_id: T::NewNodeId) -> IdMap<T> {
+        let id_map = self
+            .node_ids_depth_first()
+            .into_iter()
+            .map(|old_node_id| (old_node_id, new_node_id.new_node_id()))
+            .collect::<IdMap<T>>();
+        log::debug!("Reassigning ids of {count} node(s)", count = id_map.len());
+        let remap_node_id = |old_node_id: &T::NodeId| {
+            id_map
+                .get(old_node_id)
+                .cloned()
+                .expect("node id has been reassigned")
+        };
+        let remap_half_edge = |HalfEdgeOwned {
+                                   path_segment,
+                                   node_id,
+                               }: &HalfEdgeOwned<T>| HalfEdgeOwned {
+            path_segment: path_segment.clone(),
+            node_id: remap_node_id(node_id),
+        };
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(old_node_id, old_node)| {
+                let TreeNode {
+                    id: _,
+                    parent,
+                    node,
+                } = &**old_node;
+                let node = match node {
+                    Node::Inner(InnerNode { children, value }) => Node::Inner(InnerNode {
+                        children: children
+                            .iter()
+                            .map(|(child_key, half_edge)| {
+                                (child_key.clone(), remap_half_edge(half_edge))
+                            })
+                            .collect(),
+                        value: value.clone(),
+                    }),
+                    Node::Leaf(leaf) => Node::Leaf(leaf.clone()),
+                };
+                let new_node_id = remap_node_id(old_node_id);
+                let new_node = TreeNode {
+                    id: new_node_id.clone(),
+                    parent: parent.as_ref().map(remap_half_edge),
+                    node,
+                };
+                (new_node_id, Arc::new(new_node))
+            })
+            .collect();
+        *self.nodes_mut() = nodes;
+        self.root_node_id = remap_node_id(&self.root_node_id);
+        self.frozen_node_ids = self.frozen_node_ids.iter().map(remap_node_id).collect();
+        self.new_node_id = new_node_id;
+        id_map
+    }
+
+    fn insert_or_replace_subtree_nodes(
+        &mut self,
+        parent_node: &Arc<TreeNode<T>>,
+        child_path_segment: &T::PathSegment,
+        old_child_path_segment: Option<&T::PathSegment>,
+        mut subtree: Self,
+    ) -> Result<SubtreeInsertedOrReplaced<T>, InsertOrUpdateNodeValueError<T>> {
+        // Initialized with the old node id, which will be replaced with the new node id
+        // after the root node of the subtree has been inserted/replaced.
+        let mut subtree_root_node_id = subtree.root_node_id();
+        let mut subtree_root_parent_updated = None;
+        let mut affected_nodes = 0;
+        {
+            let subtree_node_ids = subtree.node_ids_depth_first();
+            let mut old_to_new_node_id = IdMap::<T>::with_capacity(subtree_node_ids.len());
+            for old_node_id in subtree_node_ids {
+                let old_node = subtree
+                    .nodes
+                    .get(&old_node_id)
+                    .map(Arc::clone)
+                    .expect("node exists");
+                let removed = subtree.nodes.remove_mut(&old_node_id);
+                debug_assert!(removed);
+                // Ideally, the nodes in the subtree are not referenced in the outer
+                // context to avoid cloning them. For most use cases this assumption
+                // should be valid.
+                let TreeNode {
+                    id: _,
+                    parent,
+                    node,
+                } = Arc::unwrap_or_clone(old_node);
+                // TODO: This could be optimized when not reusing insert_or_update_child_node_value()
+                // and instead inserting or replacing the node directly.
+                let (parent_node, child_path_segment, old_child_path_segment) =
+                    if let Some(parent) = parent {
+                        debug_assert!(old_to_new_node_id.contains_key(&parent.node_id));
+                        let parent_node_id = old_to_new_node_id
+                            .get(&parent.node_id)
+                            .cloned()
+                            .expect("parent node has already been inserted");
+                        let parent_node = self
+                            .nodes
+                            .get(&parent_node_id)
+                            .expect("parent node has already been inserted");
+                        (parent_node, parent.path_segment, None)
+                    } else {
+                        // Root node.
+                        debug_assert_eq!(old_node_id, subtree.root_node_id());
+                        (
+                            parent_node,
+                            T::path_segment_to_owned(child_path_segment),
+                            old_child_path_segment,
+                        )
+                    };
+                let node_value = match node {
+                    Node::Inner(inner) => NodeValue::Inner(inner.value),
+                    Node::Leaf(leaf) => NodeValue::Leaf(leaf.value),
+                };
+                let NodeInsertedOrUpdated {
+                    node: child_node,
+                    parent,
+                    affected_nodes: child_affected_nodes,
+                } = self.insert_or_update_child_node_value(
+                    &Arc::clone(parent_node),
+                    child_path_segment.borrow(),
+                    old_child_path_segment,
+                    node_value,
+                )?;
+                let child_node_id = child_node.id.clone();
+                if old_node_id == subtree.root_node_id() {
+                    // Subtree root node inserted/updated.
+                    subtree_root_node_id = child_node_id.clone();
+                    subtree_root_parent_updated = parent;
+                    affected_nodes += child_affected_nodes;
+                } else {
+                    // The parent node is part of the subtree and has already been counted.
+                    affected_nodes += child_affected_nodes - usize::from(parent.is_some());
+                }
+                debug_assert!(!old_to_new_node_id.contains_key(&old_node_id));
+                old_to_new_node_id.insert(old_node_id, child_node_id);
+            }
+        }
+        // The parent node remains unchanged if the value of an existing
+        // child node has been updated in place.
+        let parent = subtree_root_parent_updated.unwrap_or_else(|| ParentNodeUpdated {
+            node: Arc::clone(self.get_node(parent_node.id.clone())),
+            removed_subtree: None,
+        });
+        Ok(SubtreeInsertedOrReplaced {
+            child_node_id: subtree_root_node_id,
+            parent,
+            affected_nodes,
+        })
+    }
+
+    /// Move a node with all its descendants to a different path.
+    ///
+    /// Moves everything under `old_prefix` to `new_prefix`. Missing ancestor
+    /// nodes of `new_prefix` are created with values provided by
+    /// `new_inner_value`. The moved nodes retain both their ids and values.
+    ///
+    /// Returns the ids of all moved nodes, starting with the node that
+    /// has been found at `old_prefix`.
+    ///
+    /// Fails if no node exists at `old_prefix`, if `new_prefix` is
+    /// already occupied, or if a frozen subtree would be modified.
+    /// The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn rewrite_prefix(
+        &mut self,
+        old_prefix: &T::RootPath,
+        new_prefix: &T::RootPath,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<Vec<T::NodeId>, RewritePrefixError<T>> {
+        if old_prefix.is_root() || new_prefix.is_root() {
+            return Err(RewritePrefixError::RootNode);
+        }
+        let node_id = self
+            .find_node(old_prefix)
+            .ok_or(RewritePrefixError::NotFound)?
+            .id
+            .clone();
+        if old_prefix.segments().count() <= new_prefix.segments().count()
+            && old_prefix
+                .segments()
+                .zip(new_prefix.segments())
+                .all(|(old_segment, new_segment)| old_segment == new_segment)
+        {
+            return Err(RewritePrefixError::NestedPrefix);
+        }
+        if let Some(node_id) = self
+            .find_frozen_node_affected_by_removal(self.get_node(node_id.clone()))
+            .or_else(|| self.find_frozen_node_on_path(new_prefix))
+        {
+            return Err(RewritePrefixError::SubtreeFrozen { node_id });
+        }
+        // Restores missing ancestor nodes that might have been created
+        // before detecting a conflict.
+        let moved_node_ids =
+            self.with_rollback(|tree| tree.move_node(node_id, new_prefix, new_inner_value))?;
+        log::debug!(
+            "Rewrote prefix {old_prefix:?} to {new_prefix:?}: moved {moved_nodes_count} node(s)",
+            moved_nodes_count = moved_node_ids.len(),
+        );
+        Ok(moved_node_ids)
+    }
+
+    fn move_node(
+        &mut self,
+        node_id: T::NodeId,
+        new_prefix: &T::RootPath,
+        new_inner_value: &mut impl FnMut() -> T::InnerValue,
+    ) -> Result<Vec<T::NodeId>, RewritePrefixError<T>> {
+        let TreeNodeParentChildContext {
+            parent_node: new_parent_node,
+            child_path_segment: new_child_path_segment,
+            ..
+        } = self
+            .create_missing_ancestor_nodes(
+                new_prefix,
+                || Ok::<_, Infallible>(new_inner_value()),
+                |_| Ok(None),
+            )
+            .map_err(|err| match err {
+                CreateMissingAncestorNodesError::PathConflict(conflict) => {
+                    RewritePrefixError::PathConflict { conflict }
+                }
+                CreateMissingAncestorNodesError::NewValue(source) => match source {},
+            })?;
+        let new_parent_node_id = new_parent_node
+            .expect("parent node is an inner node")
+            .id
+            .clone();
+        let new_child_path_segment = new_child_path_segment.expect("not the root path");
+        if let Some(node_id) = self
+            .get_node(new_parent_node_id.clone())
+            .node
+            .find_child(new_child_path_segment)
+        {
+            return Err(RewritePrefixError::Occupied { node_id });
+        }
+        log_mutation!("Moving node {node_id} to {new_prefix:?}");
+        Ok(self.attach_moved_node(node_id, new_parent_node_id, new_child_path_segment))
+    }
+
+    /// Detach a node from its old parent node and attach it to a new parent node.
+    ///
+    /// The caller is responsible for ensuring that the new parent node is an
+    /// inner node outside of the moved subtree and that the new child path
+    /// segment is vacant.
+    fn attach_moved_node(
+        &mut self,
+        node_id: T::NodeId,
+        new_parent_node_id: T::NodeId,
+        new_child_path_segment: &T::PathSegment,
+    ) -> Vec<T::NodeId> {
+        // Reload all nodes, which might have been updated when creating
+        // the missing ancestor nodes.
+        let node = Arc::clone(self.get_node(node_id.clone()));
+        let old_parent = node.parent.as_ref().expect("has parent");
+        // Detach the node from its old parent node.
+        let old_parent_node = Arc::clone(self.get_node(old_parent.node_id.clone()));
+        let Node::Inner(inner_node) = &old_parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let mut inner_node = inner_node.clone();
+        inner_node
+            .children
+            .remove_mut(&*T::child_key(old_parent.path_segment.borrow()));
+        update_parent_node(
+            self.nodes_mut(),
+            TreeNode {
+                id: old_parent_node.id.clone(),
+                parent: old_parent_node.parent.clone(),
+                node: Node::Inner(inner_node),
+            },
+        );
+        // Attach the node to its new parent node, which might be the same
+        // as the old parent node.
+        let new_parent_node = Arc::clone(self.get_node(new_parent_node_id.clone()));
+        let Node::Inner(inner_node) = &new_parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let mut inner_node = inner_node.clone();
+        inner_node.insert_child(new_child_path_segment, node_id.clone());
+        update_parent_node(
+            self.nodes_mut(),
+            TreeNode {
+                id: new_parent_node.id.clone(),
+                parent: new_parent_node.parent.clone(),
+                node: Node::Inner(inner_node),
+            },
+        );
+        let moved_node = Arc::new(TreeNode {
+            id: node_id.clone(),
+            parent: Some(HalfEdgeOwned {
+                path_segment: T::path_segment_to_owned(new_child_path_segment),
+                node_id: new_parent_node_id,
+            }),
+            node: node.node.clone(),
+        });
+        self.insert_node(Arc::clone(&moved_node));
+        let moved_node_ids = std::iter::once(node_id)
+            .chain(
+                moved_node
+                    .node
+                    .descendants(self)
+                    .map(|HalfEdge { node_id, .. }| node_id),
+            )
+            .collect();
+        moved_node_ids
+    }
+
+    /// Move a subtree to a new parent node.
+    ///
+    /// The subtree is attached as a child of the existing inner node with
+    /// id `new_parent_node_id` at `new_child_path_segment`. In contrast to
+    /// [`Self::remove_subtree_by_id()`] followed by [`Self::insert_or_replace_subtree()`]
+    /// all moved nodes retain their ids. Use [`Self::rewrite_prefix()`] for
+    /// moving a subtree to a path with missing ancestor nodes.
+    ///
+    /// Returns the ids of all moved nodes, starting with the given node.
+    ///
+    /// Fails if any of the nodes doesn't exist, if the new parent node is
+    /// a leaf node or part of the moved subtree, if the new child path
+    /// segment is already occupied, or if a frozen subtree would be modified.
+    /// The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn move_subtree(
+        &mut self,
+        node_id: T::NodeId,
+        new_parent_node_id: T::NodeId,
+        new_child_path_segment: &T::PathSegment,
+    ) -> Result<Vec<T::NodeId>, RewritePrefixError<T>> {
+        if node_id == self.root_node_id {
+            return Err(RewritePrefixError::RootNode);
+        }
+        let node = self
+            .lookup_node(node_id.clone())
+            .ok_or(RewritePrefixError::NotFound)?;
+        let new_parent_node = self
+            .lookup_node(new_parent_node_id.clone())
+            .ok_or(RewritePrefixError::NotFound)?;
+        if new_parent_node.id == node_id
+            || self
+                .ancestor_nodes(new_parent_node)
+                .any(|HalfEdgeTreeNode { node, .. }| node.id == node_id)
+        {
+            return Err(RewritePrefixError::NestedPrefix);
+        }
+        if matches!(new_parent_node.node, Node::Leaf(_)) {
+            return Err(RewritePrefixError::PathConflict {
+                conflict: TreeNodeParentChildPathConflict {
+                    parent_node: Arc::clone(new_parent_node),
+                    child_path_segment: T::path_segment_to_owned(new_child_path_segment),
+                },
+            });
+        }
+        if let Some(node_id) = new_parent_node.node.find_child(new_child_path_segment) {
+            return Err(RewritePrefixError::Occupied { node_id });
+        }
+        if let Some(node_id) = self
+            .find_frozen_node_affected_by_removal(node)
+            .or_else(|| self.find_frozen_ancestor_or_self(new_parent_node))
+        {
+            return Err(RewritePrefixError::SubtreeFrozen { node_id });
+        }
+        log_mutation!(
+            "Moving node {node_id} to {new_child_path_segment:?} below node {new_parent_node_id}"
+        );
+        let moved_node_ids =
+            self.attach_moved_node(node_id, new_parent_node_id, new_child_path_segment);
+        log::debug!(
+            "Moved subtree: {moved_nodes_count} node(s)",
+            moved_nodes_count = moved_node_ids.len(),
+        );
+        Ok(moved_node_ids)
+    }
+
+    /// Exchange the positions of two subtrees.
+    ///
+    /// Each node takes over the parent node and the path segment of the
+    /// other node. In contrast to removing and re-inserting the subtrees
+    /// all nodes retain their ids.
+    ///
+    /// Fails if any of the nodes doesn't exist, if one of them is the root
+    /// node, if the subtrees overlap, i.e. if the nodes are equal or one is
+    /// an ancestor of the other, or if a frozen subtree would be modified.
+    /// The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn swap_subtrees(
+        &mut self,
+        node_id_a: T::NodeId,
+        node_id_b: T::NodeId,
+    ) -> Result<(), RewritePrefixError<T>> {
+        if node_id_a == self.root_node_id || node_id_b == self.root_node_id {
+            return Err(RewritePrefixError::RootNode);
+        }
+        let node_a = self
+            .lookup_node(node_id_a.clone())
+            .map(Arc::clone)
+            .ok_or(RewritePrefixError::NotFound)?;
+        let node_b = self
+            .lookup_node(node_id_b.clone())
+            .map(Arc::clone)
+            .ok_or(RewritePrefixError::NotFound)?;
+        if node_id_a == node_id_b
+            || self
+                .ancestor_nodes(&node_a)
+                .any(|HalfEdgeTreeNode { node, .. }| node.id == node_id_b)
+            || self
+                .ancestor_nodes(&node_b)
+                .any(|HalfEdgeTreeNode { node, .. }| node.id == node_id_a)
+        {
+            return Err(RewritePrefixError::NestedPrefix);
+        }
+        if let Some(node_id) = self
+            .find_frozen_node_affected_by_removal(&node_a)
+            .or_else(|| self.find_frozen_node_affected_by_removal(&node_b))
+        {
+            return Err(RewritePrefixError::SubtreeFrozen { node_id });
+        }
+        log_mutation!("Swapping subtrees of nodes {node_id_a} and {node_id_b}");
+        let parent_a = node_a.parent.clone().expect("has parent");
+        let parent_b = node_b.parent.clone().expect("has parent");
+        // Both parent nodes might be the same node. Reload the parent node
+        // before each update.
+        for (parent, old_node_id, new_node_id) in [
+            (&parent_a, &node_id_a, &node_id_b),
+            (&parent_b, &node_id_b, &node_id_a),
+        ] {
+            let parent_node = Arc::clone(self.get_node(parent.node_id.clone()));
+            let Node::Inner(inner_node) = &parent_node.node else {
+                unreachable!("parent node is an inner node");
+            };
+            let mut inner_node = inner_node.clone();
+            let path_segment = parent.path_segment.borrow();
+            debug_assert_eq!(
+                Some(old_node_id),
+                inner_node.find_child(path_segment).as_ref()
+            );
+            inner_node.replace_child(path_segment, new_node_id.clone());
+            update_parent_node(
+                self.nodes_mut(),
+                TreeNode {
+                    id: parent_node.id.clone(),
+                    parent: parent_node.parent.clone(),
+                    node: Node::Inner(inner_node),
+                },
+            );
+        }
+        for (node, new_parent) in [(node_a, parent_b), (node_b, parent_a)] {
+            let swapped_node = Arc::new(TreeNode {
+                id: node.id.clone(),
+                parent: Some(new_parent),
+                node: node.node.clone(),
+            });
+            self.insert_node(swapped_node);
+        }
+        Ok(())
+    }
+
+    /// Rename a child node without modifying its value.
+    ///
+    /// Only the path segment of the edge between the parent node and the
+    /// child node is replaced. The child node retains its id, value, and
+    /// children. In contrast to [`Self::insert_or_update_child_node_value()`]
+    /// the value doesn't need to be provided.
+    ///
+    /// Renaming a child node to a path segment with the same
+    /// [`PathTreeTypes::ChildKey`] only replaces its path segment.
+    ///
+    /// Returns the renamed child node.
+    ///
+    /// Fails if the parent node or the child node doesn't exist, if the new
+    /// child path segment is occupied by a different node, or if a frozen
+    /// subtree would be modified. The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn rename_child_segment(
+        &mut self,
+        parent_node_id: T::NodeId,
+        old_child_path_segment: &T::PathSegment,
+        new_child_path_segment: &T::PathSegment,
+    ) -> Result<Arc<TreeNode<T>>, RewritePrefixError<T>> {
+        let parent_node = self
+            .lookup_node(parent_node_id.clone())
+            .map(Arc::clone)
+            .ok_or(RewritePrefixError::NotFound)?;
+        let child_node_id = parent_node
+            .node
+            .find_child(old_child_path_segment)
+            .ok_or(RewritePrefixError::NotFound)?;
+        let child_node = Arc::clone(self.get_node(child_node_id.clone()));
+        let child_path_segment = child_node
+            .parent
+            .as_ref()
+            .map(|HalfEdgeOwned { path_segment, .. }| path_segment.borrow())
+            .expect("has parent");
+        if child_path_segment == new_child_path_segment {
+            // Unchanged
+            return Ok(child_node);
+        }
+        if let Some(node_id) = parent_node
+            .node
+            .find_child(new_child_path_segment)
+            .filter(|node_id| *node_id != child_node_id)
+        {
+            return Err(RewritePrefixError::Occupied { node_id });
+        }
+        if let Some(node_id) = self.find_frozen_node_affected_by_removal(&child_node) {
+            return Err(RewritePrefixError::SubtreeFrozen { node_id });
+        }
+        log_mutation!("Renaming child node {child_node_id} from {old_child_path_segment:?} to {new_child_path_segment:?}");
+        let Node::Inner(inner_node) = &parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let mut inner_node = inner_node.clone();
+        if T::child_key(old_child_path_segment) == T::child_key(new_child_path_segment) {
+            inner_node.replace_child(new_child_path_segment, child_node_id.clone());
+        } else {
+            inner_node
+                .children
+                .remove_mut(&*T::child_key(old_child_path_segment));
+            inner_node.insert_child(new_child_path_segment, child_node_id.clone());
+        }
+        update_parent_node(
+            self.nodes_mut(),
+            TreeNode {
+                id: parent_node.id.clone(),
+                parent: parent_node.parent.clone(),
+                node: Node::Inner(inner_node),
+            },
+        );
+        let renamed_child_node = Arc::new(TreeNode {
+            id: child_node_id.clone(),
+            parent: Some(HalfEdgeOwned {
+                path_segment: T::path_segment_to_owned(new_child_path_segment),
+                node_id: parent_node_id,
+            }),
+            node: child_node.node.clone(),
+        });
+        self.insert_node(Arc::clone(&renamed_child_node));
+        Ok(renamed_child_node)
+    }
+
+    /// Remove a single node and promote its children.
+    ///
+    /// The children of the removed node are attached to its parent node
+    /// with their current path segments, i.e. their subtrees move up by
+    /// one level. All promoted nodes retain their ids.
+    ///
+    /// Returns the removed node.
+    ///
+    /// Fails if the node doesn't exist, if it is the root node, if a child
+    /// node collides with a sibling of the removed node, or if a frozen
+    /// subtree would be modified. The tree remains unchanged on error.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn splice_node(
+        &mut self,
+        node_id: T::NodeId,
+    ) -> Result<Arc<TreeNode<T>>, SpliceNodeError<T>> {
+        if node_id == self.root_node_id {
+            return Err(SpliceNodeError::RootNode);
+        }
+        let node = self
+            .lookup_node(node_id.clone())
+            .map(Arc::clone)
+            .ok_or(SpliceNodeError::NotFound)?;
+        if let Some(node_id) = self.find_frozen_node_affected_by_removal(&node) {
+            return Err(SpliceNodeError::SubtreeFrozen { node_id });
+        }
+        let parent = node.parent.as_ref().expect("has parent");
+        let parent_node = Arc::clone(self.get_node(parent.node_id.clone()));
+        let Node::Inner(inner_node) = &parent_node.node else {
+            unreachable!("parent node is an inner node");
+        };
+        let mut inner_node = inner_node.clone();
+        inner_node
+            .children
+            .remove_mut(&*T::child_key(parent.path_segment.borrow()));
+        for HalfEdge {
+            path_segment,
+            child_key: _,
+            node_id: child_node_id,
+        } in node.node.children()
+        {
+            if let Some(node_id) = inner_node.find_child(path_segment) {
+                return Err(SpliceNodeError::Occupied { node_id });
+            }
+            inner_node.insert_child(path_segment, child_node_id);
+        }
+        log_mutation!("Splicing node {node_id}");
+        update_parent_node(
+            self.nodes_mut(),
+            TreeNode {
+                id: parent_node.id.clone(),
+                parent: parent_node.parent.clone(),
+                node: Node::Inner(inner_node),
+            },
+        );
+        for HalfEdge {
+            path_segment,
+            child_key: _,
+            node_id: child_node_id,
+        } in node.node.children()
+        {
+            let child_node = self.get_node(child_node_id.clone());
+            let promoted_node = Arc::new(TreeNode {
+                id: child_node_id.clone(),
+                parent: Some(HalfEdgeOwned {
+                    path_segment: T::path_segment_to_owned(path_segment),
+                    node_id: parent_node.id.clone(),
+                }),
+                node: child_node.node.clone(),
+            });
+            self.insert_node(promoted_node);
+        }
+        let removed = self.remove_node(&node_id);
+        debug_assert!(removed);
+        log::debug!(
+            "Spliced node {node_id}: promoted {children_count} child node(s)",
+            children_count = node.node.children_count(),
+        );
+        Ok(node)
+    }
+
+    /// Remove multiple subtrees by their paths.
+    ///
+    /// All paths are resolved before removing any nodes. Paths that are nested
+    /// under other requested paths are skipped instead of removing them
+    /// separately.
+    ///
+    /// Returns the outcome for each path in the given order.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn remove_paths<'p>(
+        &mut self,
+        paths: impl IntoIterator<Item = &'p T::RootPath>,
+    ) -> Vec<PathRemoved<T>>
+    where
+        T::RootPath: 'p,
+    {
+        let node_ids = paths
+            .into_iter()
+            .map(|path| self.find_node(path).map(|node| node.id.clone()))
+            .collect::<Vec<_>>();
+        // Subtrees that are affected by a frozen subtree are not removed
+        // and don't include any nested paths.
+        let requested_node_ids = node_ids
+            .iter()
+            .flatten()
+            .filter(|node_id| {
+                self.find_frozen_node_affected_by_removal(self.get_node((*node_id).clone()))
+                    .is_none()
+            })
+            .cloned()
+            .collect::<std::collections::HashSet<_>>();
+        let mut removed_node_ids = std::collections::HashSet::new();
+        let removed = node_ids
+            .into_iter()
+            .map(|node_id| {
+                let Some(node_id) = node_id else {
+                    return PathRemoved::NotFound;
+                };
+                if node_id == self.root_node_id {
+                    return PathRemoved::RootNode;
+                }
+                if !removed_node_ids.insert(node_id.clone()) {
+                    return PathRemoved::Included;
+                }
+                let Some(node) = self.lookup_node(node_id.clone()) else {
+                    // Already removed together with an ancestor node.
+                    return PathRemoved::Included;
+                };
+                if self.ancestor_nodes(node).any(|ancestor| {
+                    requested_node_ids.contains(&ancestor.node.id)
+                        && ancestor.node.id != self.root_node_id
+                }) {
+                    return PathRemoved::Included;
+                }
+                if let Some(node_id) = self.find_frozen_node_affected_by_removal(node) {
+                    return PathRemoved::Frozen { node_id };
+                }
+                let removed = self
+                    .remove_subtree_by_id(node_id)
+                    .expect("subtree has been removed");
+                PathRemoved::Removed(removed)
+            })
+            .collect::<Vec<_>>();
+        log::debug!(
+            "Removed {removed_count} of {paths_count} path(s)",
+            removed_count = removed
+                .iter()
+                .filter(|removed| matches!(removed, PathRemoved::Removed(_)))
+                .count(),
+            paths_count = removed.len(),
+        );
+        removed
+    }
+
+    /// Create a projection with the nodes that match the given predicate.
+    ///
+    /// The projection contains all matching nodes together with their
+    /// ancestors, i.e. the paths of matching nodes are preserved. The root
+    /// node is always included. Subtrees without any matching nodes are
+    /// pruned, e.g. for views like "show only modified files".
+    ///
+    /// The source tree remains unchanged. All nodes that are unaffected by
+    /// pruning are shared with the source tree, only the parent nodes of
+    /// pruned subtrees are rewritten. Node ids are retained.
+    ///
+    /// Executed in linear time, i.e. O(n).
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn filter(&self, mut predicate: impl FnMut(&TreeNode<T>) -> bool) -> Self {
+        let root_node = self.root_node();
+        let mut matched_node_ids = std::collections::HashSet::new();
+        for node in self.post_order_nodes(root_node) {
+            let matched = predicate(node)
+                || node
+                    .node
+                    .children()
+                    .any(|HalfEdge { node_id, .. }| matched_node_ids.contains(&node_id));
+            if matched {
+                matched_node_ids.insert(node.id.clone());
+            }
+        }
+        matched_node_ids.insert(root_node.id.clone());
+        // Prune the topmost nodes without any matching descendants.
+        let pruned_node_ids = self
+            .descendant_nodes(root_node)
+            .filter(|HalfEdge { node_id, .. }| !matched_node_ids.contains(node_id))
+            .filter_map(|HalfEdge { node_id, .. }| {
+                let parent = self.get_node(node_id.clone()).parent.as_ref()?;
+                matched_node_ids
+                    .contains(&parent.node_id)
+                    .then_some(node_id)
+            })
+            .collect::<Vec<_>>();
+        let mut projection = self.clone();
+        // The projection is derived from but independent of the source tree.
+        let frozen_node_ids = std::mem::replace(&mut projection.frozen_node_ids, new_hash_set());
+        for node_id in pruned_node_ids {
+            let removed = projection.remove_subtree_by_id(node_id);
+            debug_assert!(removed.is_some());
+        }
+        for node_id in &frozen_node_ids {
+            if matched_node_ids.contains(node_id) {
+                projection.frozen_node_ids.insert_mut(node_id.clone());
+            }
+        }
+        projection
+    }
+
+    /// Create a projection of all nodes that have been removed since an older snapshot.
+    ///
+    /// Contains the nodes of `older` whose ids don't exist in this tree
+    /// anymore, together with their ancestors, e.g. for previewing what
+    /// a pending operation will delete. Nodes are identified by their ids,
+    /// i.e. renamed or moved nodes are not considered as removed.
+    ///
+    /// See also [`Self::filter()`]. The nodes of the projection are shared
+    /// with `older`.
+    #[must_use]
+    pub fn removed_since(&self, older: &Self) -> Self {
+        older.filter(|node| !self.nodes.contains_key(&node.id))
+    }
+
+    /// Retain only the nodes that match the given predicate.
+    ///
+    /// The root node is always retained and cannot be removed. Frozen
+    /// subtrees and their ancestors are retained, see [`Self::freeze_subtree()`].
+    ///
+    /// Returns the number of nodes that have been removed.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn retain_nodes(&mut self, predicate: impl FnMut(&TreeNode<T>) -> bool) {
+        self.retain_nodes_with_scratch(predicate, &mut TraversalScratch::new());
+    }
+
+    /// Retain only the nodes that match the given predicate, reusing temporary buffers.
+    ///
+    /// Same as [`Self::retain_nodes()`].
+    pub fn retain_nodes_with_scratch(
+        &mut self,
+        mut predicate: impl FnMut(&TreeNode<T>) -> bool,
+        scratch: &mut TraversalScratch<T>,
+    ) {
         // TODO: Optimize by traversing the tree structure instead of iterating over
         // all nodes in no particular order. If a subtree is removed then all its
         // children don't need to be visited.
-        let mut node_ids_to_remove = Vec::new();
+        // The buffer is taken temporarily, because the scratch is also needed
+        // for removing the subtrees.
+        let mut node_ids_to_remove = std::mem::take(&mut scratch.candidates);
+        debug_assert!(node_ids_to_remove.is_empty());
         for node in self.nodes() {
             if !predicate(node) && node.id != self.root_node_id() {
-                node_ids_to_remove.push(node.id);
+                node_ids_to_remove.push(node.id.clone());
             }
         }
         // Remove the subtrees in reverse order of the depth of their root node.
         node_ids_to_remove.sort_by(|lhs_id, rhs_id| {
-            let lhs_node = self.get_node(*lhs_id);
-            let rhs_node = self.get_node(*rhs_id);
+            let lhs_node = self.get_node(lhs_id.clone());
+            let rhs_node = self.get_node(rhs_id.clone());
             let lhs_depth = self.ancestor_nodes_count(lhs_node);
             let rhs_depth = self.ancestor_nodes_count(rhs_node);
             lhs_depth.cmp(&rhs_depth)
         });
-        for node_id in node_ids_to_remove {
-            self.remove_subtree_by_id(node_id);
+        let nodes_count_before = self.nodes_count();
+        for node_id in node_ids_to_remove.drain(..) {
+            self.remove_subtree_by_id_with_scratch(node_id, scratch);
+        }
+        scratch.candidates = node_ids_to_remove;
+        log::debug!(
+            "Retained {nodes_count} of {nodes_count_before} node(s)",
+            nodes_count = self.nodes_count(),
+        );
+    }
+
+    /// Transform or remove leaf nodes in a single pass.
+    ///
+    /// Leaf nodes are removed if `map_leaf_value` returns `None`. Otherwise
+    /// their value is replaced by the returned value. Inner nodes that have
+    /// lost all their children are either retained or removed, depending on
+    /// `empty_inner_nodes`.
+    ///
+    /// The root node is neither visited nor removed. Nodes within frozen
+    /// subtrees are neither visited nor removed.
+    ///
+    /// Returns the number of nodes that have been removed.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn filter_map_leaves(
+        &mut self,
+        mut map_leaf_value: impl FnMut(&T::LeafValue) -> Option<T::LeafValue>,
+        empty_inner_nodes: EmptyInnerNodes,
+    ) -> usize {
+        let root_node = self.root_node();
+        let mut removed_node_ids = std::collections::HashSet::new();
+        let mut updated_leaf_values = Vec::new();
+        // Children are visited before their parent node.
+        for node in self.post_order_nodes(root_node) {
+            if node.id == self.root_node_id || self.is_frozen(node) {
+                continue;
+            }
+            let remove = match &node.node {
+                Node::Leaf(leaf) => {
+                    if let Some(new_value) = map_leaf_value(&leaf.value) {
+                        updated_leaf_values.push((node.id.clone(), new_value));
+                        false
+                    } else {
+                        true
+                    }
+                }
+                Node::Inner(inner) => {
+                    empty_inner_nodes == EmptyInnerNodes::Prune
+                        && !inner.children.is_empty()
+                        && inner
+                            .children()
+                            .all(|HalfEdge { node_id, .. }| removed_node_ids.contains(&node_id))
+                }
+            };
+            if remove {
+                removed_node_ids.insert(node.id.clone());
+            }
+        }
+        // Only remove the topmost nodes together with their subtrees.
+        let pruned_node_ids = removed_node_ids
+            .iter()
+            .filter(|node_id| {
+                let parent = self.get_node((*node_id).clone()).parent.as_ref();
+                parent.map_or(true, |parent| !removed_node_ids.contains(&parent.node_id))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for node_id in pruned_node_ids {
+            let removed = self.remove_subtree_by_id(node_id);
+            debug_assert!(removed.is_some());
         }
+        for (node_id, new_value) in updated_leaf_values {
+            let node = Arc::clone(self.get_node(node_id));
+            let updated = self.update_node_value(&node, NodeValue::Leaf(new_value));
+            debug_assert!(updated.is_ok());
+        }
+        log::debug!(
+            "Removed {removed_count} node(s) while mapping leaf values",
+            removed_count = removed_node_ids.len(),
+        );
+        removed_node_ids.len()
     }
 
     /// All nodes in no particular order.
@@ -910,6 +2855,93 @@ impl<T: PathTreeTypes> PathTree<T> {
         self.nodes.values()
     }
 
+    /// All leaf nodes in no particular order.
+    ///
+    /// Inner nodes are skipped. The path segment of each leaf node is
+    /// available through its parent edge, the full path through
+    /// [`Self::ancestor_nodes()`].
+    pub fn leaf_nodes(&self) -> impl Iterator<Item = &Arc<TreeNode<T>>> {
+        self.nodes()
+            .filter(|node| matches!(node.node, Node::Leaf(_)))
+    }
+
+    /// All leaf nodes below the given node.
+    ///
+    /// Visits the descendants in the same order as [`Self::descendant_nodes()`]
+    /// and only yields leaf nodes together with the path segment from their
+    /// parent node. Use [`Self::nodes_with_paths()`] for obtaining the full
+    /// paths.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn leaf_nodes_below<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> impl Iterator<Item = HalfEdgeTreeNode<'a, T>> {
+        self.descendant_nodes(node).filter_map(
+            |HalfEdge {
+                 path_segment,
+                 child_key: _,
+                 node_id,
+             }| {
+                let node = self.get_node(node_id);
+                matches!(node.node, Node::Leaf(_))
+                    .then_some(HalfEdgeTreeNode { path_segment, node })
+            },
+        )
+    }
+
+    /// Strong reference counts of all nodes in no particular order.
+    ///
+    /// Intended for debugging purposes, e.g. for finding nodes that are
+    /// retained by external consumers. The count includes the reference
+    /// held by this tree. Snapshots created by cloning the tree share the
+    /// same reference for all nodes that have not been modified since.
+    pub fn node_refcounts(&self) -> impl ExactSizeIterator<Item = (T::NodeId, usize)> + '_ {
+        self.nodes
+            .iter()
+            .map(|(node_id, node)| (node_id.clone(), Arc::strong_count(node)))
+    }
+
+    /// Number of nodes that differ from a previous snapshot of the tree.
+    ///
+    /// Counts all nodes that have been inserted, updated, or removed since
+    /// the snapshot has been cloned from this tree, i.e. the length of the
+    /// [`TreeDiff`](crate::TreeDiff) returned by [`Self::diff()`]. Unmodified
+    /// nodes are shared between both trees and are not counted.
+    ///
+    /// Executed in linear time, i.e. O(n).
+    #[must_use]
+    pub fn rewritten_nodes_count(&self, snapshot: &Self) -> usize {
+        snapshot.diff(self).len()
+    }
+
+    /// Verify that an operation rewrites a bounded number of nodes.
+    ///
+    /// Executes the operation and returns its result.
+    ///
+    /// Intended for testing code that relies on the structural sharing between
+    /// snapshots, see [`Self::rewritten_nodes_count()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `max_nodes` have been rewritten.
+    #[track_caller]
+    pub fn assert_write_amplification<R>(
+        &mut self,
+        max_nodes: usize,
+        operation: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let snapshot = self.clone();
+        let result = operation(self);
+        let rewritten_nodes_count = self.rewritten_nodes_count(&snapshot);
+        assert!(
+            rewritten_nodes_count <= max_nodes,
+            "rewritten {rewritten_nodes_count} nodes instead of at most {max_nodes}"
+        );
+        result
+    }
+
     /// Total number of nodes in the tree.
     ///
     /// Executed in constant time, i.e. O(1). But only if not both
@@ -936,6 +2968,42 @@ impl<T: PathTreeTypes> PathTree<T> {
         }
     }
 
+    /// Check if the tree only consists of the root node.
+    ///
+    /// In constant time, i.e. O(1).
+    #[must_use]
+    pub fn is_root_only(&self) -> bool {
+        self.nodes.size() == 1
+    }
+
+    /// The sole leaf node of a tree that consists of the root node and
+    /// a single leaf node.
+    ///
+    /// Returns `None` for all other trees, including trees that only
+    /// consist of a root node with a leaf value.
+    ///
+    /// In constant time, i.e. O(1).
+    #[must_use]
+    pub fn single_leaf(&self) -> Option<&Arc<TreeNode<T>>> {
+        if self.nodes.size() != 2 {
+            return None;
+        }
+        let HalfEdge { node_id, .. } = self.root_node().node.children().next()?;
+        let node = self.get_node(node_id);
+        matches!(node.node, Node::Leaf(_)).then_some(node)
+    }
+
+    /// Check if all nodes except the root node are children of the root node.
+    ///
+    /// Also `true` if the tree only consists of the root node, see
+    /// [`Self::is_root_only()`].
+    ///
+    /// In constant time, i.e. O(1).
+    #[must_use]
+    pub fn is_flat(&self) -> bool {
+        self.root_node().node.children_count() + 1 == self.nodes.size()
+    }
+
     /// Iterator over all ancestor nodes of the given node.
     ///
     /// Returns the parent node and the respective path segment from the child node
@@ -943,16 +3011,67 @@ impl<T: PathTreeTypes> PathTree<T> {
     pub fn ancestor_nodes<'a>(
         &'a self,
         node: &'a Arc<TreeNode<T>>,
-    ) -> impl Iterator<Item = HalfEdgeTreeNode<'_, T>> + Clone {
+    ) -> impl Iterator<Item = HalfEdgeTreeNode<'a, T>> + Clone {
         AncestorTreeNodeIter::new(self, node)
     }
 
+    /// Returns the path from the root node down to the given node
+    ///
+    /// Yields the nodes on the path together with their path segment in
+    /// top-down order, e.g. for rendering breadcrumbs. Complements
+    /// [`Self::ancestor_nodes()`]. The root node is excluded, because it is
+    /// not addressed by a path segment. The given node is yielded last.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn path_to_node<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> impl DoubleEndedIterator<Item = HalfEdgeTreeNode<'a, T>> + ExactSizeIterator {
+        debug_assert!(self.contains_node(node));
+        let mut path = Vec::new();
+        let mut next_node = node;
+        while let Some(parent) = &next_node.parent {
+            path.push(HalfEdgeTreeNode {
+                path_segment: parent.path_segment.borrow(),
+                node: next_node,
+            });
+            next_node = self.get_node(parent.node_id.clone());
+        }
+        path.into_iter().rev()
+    }
+
     /// The number of parent nodes of the given node up to the root node.
     #[must_use]
     pub fn ancestor_nodes_count(&self, node: &Arc<TreeNode<T>>) -> usize {
         self.ancestor_nodes(node).count()
     }
 
+    /// Find the nearest ancestor node that matches a predicate.
+    ///
+    /// Visits the ancestor nodes in bottom-up order, starting with the parent
+    /// node, and stops at the first match, e.g. for finding the nearest
+    /// ancestor with a marker child or an overriding value. The given node
+    /// itself is not visited.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn find_ancestor<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        mut predicate: impl FnMut(&Arc<TreeNode<T>>) -> bool,
+    ) -> Option<&'a Arc<TreeNode<T>>> {
+        debug_assert!(self.contains_node(node));
+        let mut next_node = node;
+        while let Some(parent) = &next_node.parent {
+            next_node = self.get_node(parent.node_id.clone());
+            if predicate(next_node) {
+                return Some(next_node);
+            }
+        }
+        None
+    }
+
     /// Returns an iterator over all descendants of this node
     ///
     /// Recursively traverses the subtree.
@@ -967,12 +3086,374 @@ impl<T: PathTreeTypes> PathTree<T> {
         node.node.descendants(self)
     }
 
+    /// Returns an iterator over all descendants of this node with a known length
+    ///
+    /// Same as [`Self::descendant_nodes()`], but counts the descendants upfront
+    /// in linear time with respect to the size of the subtree. The returned
+    /// iterator implements [`ExactSizeIterator`].
+    pub fn descendant_nodes_counted<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> CountedDescendantsIter<'a, T> {
+        debug_assert!(self.contains_node(node));
+        CountedDescendantsIter::new(&node.node, self)
+    }
+
+    /// Returns an iterator over all descendants of this node in reverse order
+    ///
+    /// Yields the nodes of [`Self::descendant_nodes()`] in reverse order,
+    /// e.g. for taking the last N nodes without visiting all preceding nodes.
+    pub fn descendant_nodes_rev<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> ReverseDescendantsIter<'a, T> {
+        debug_assert!(self.contains_node(node));
+        ReverseDescendantsIter::new(&node.node, self)
+    }
+
+    /// Returns an iterator over the descendants of this node up to a maximum depth
+    ///
+    /// Yields each descendant together with its depth relative to the given
+    /// node, i.e. 1 for the direct children. Descendants below `max_depth`
+    /// are not visited at all, e.g. for expanding a tree view level by level.
+    ///
+    /// The nodes are visited depth-first in pre-order. The ordering of siblings
+    /// is undefined like for [`Self::descendant_nodes()`].
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn descendant_nodes_within_depth<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        max_depth: usize,
+    ) -> impl Iterator<Item = (usize, HalfEdgeTreeNode<'a, T>)> {
+        debug_assert!(self.contains_node(node));
+        let mut pending = if max_depth > 0 {
+            self.child_nodes(node).map(|child| (1, child)).collect()
+        } else {
+            Vec::new()
+        };
+        std::iter::from_fn(move || {
+            let (depth, next) = pending.pop()?;
+            if depth < max_depth {
+                pending.extend(self.child_nodes(next.node).map(|child| (depth + 1, child)));
+            }
+            Some((depth, next))
+        })
+    }
+
+    /// All nodes at the given distance from the root node
+    ///
+    /// Yields only the root node for depth 0. Nodes below the given depth
+    /// are not visited. The ordering of nodes is undefined.
+    pub fn nodes_at_depth(&self, depth: usize) -> impl Iterator<Item = &Arc<TreeNode<T>>> {
+        let root_node = (depth == 0).then(|| self.root_node());
+        let descendants = self
+            .descendant_nodes_within_depth(self.root_node(), depth)
+            .filter_map(move |(node_depth, HalfEdgeTreeNode { node, .. })| {
+                (node_depth == depth).then_some(node)
+            });
+        root_node.into_iter().chain(descendants)
+    }
+
+    /// Returns an iterator over all descendants of this node in a deterministic order
+    ///
+    /// Recursively traverses the subtree in depth-first pre-order. The children
+    /// of each node are visited in the order of their path segments. In contrast
+    /// to [`Self::descendant_nodes()`] the order is reproducible across runs,
+    /// e.g. for exports and tests.
+    ///
+    /// Sorting the children of each node requires additional time, i.e.
+    /// O(n log k) with n the number of descendants and k the maximum number
+    /// of children.
+    pub fn descendant_nodes_ordered<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> OrderedDescendantsIter<'a, T>
+    where
+        T::PathSegment: Ord,
+    {
+        self.descendant_nodes_sorted_by(node, Ord::cmp as PathSegmentCmp<T>)
+    }
+
+    /// All descendants of a node in the given export order.
+    pub(crate) fn descendant_nodes_for_export<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        export_order: ExportOrder<T>,
+    ) -> Box<dyn Iterator<Item = HalfEdge<'a, T>> + 'a> {
+        match export_order {
+            ExportOrder::SortedBy(compare) => {
+                Box::new(self.descendant_nodes_sorted_by(node, compare))
+            }
+            ExportOrder::Unordered => Box::new(self.descendant_nodes(node)),
+        }
+    }
+
+    /// Returns an iterator over all descendants of this node in a custom order
+    ///
+    /// Like [`Self::descendant_nodes_ordered()`], but the children of each node
+    /// are visited in the order defined by `compare`, e.g. [`natural_cmp()`](crate::natural_cmp).
+    pub fn descendant_nodes_sorted_by<'a, C>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        compare: C,
+    ) -> OrderedDescendantsIter<'a, T, C>
+    where
+        C: FnMut(&T::PathSegment, &T::PathSegment) -> Ordering,
+    {
+        debug_assert!(self.contains_node(node));
+        OrderedDescendantsIter::new(self, &node.node, compare)
+    }
+
+    /// Direct children of a node with their path segments
+    ///
+    /// Resolves the ids of the child nodes. In the same arbitrary but stable
+    /// ordering as [`Node::children()`]. Yields nothing for leaf nodes.
+    ///
+    /// Undefined behavior if the given node does not belong to the tree.
+    /// This precondition is only checked by debug assertions.
+    pub fn child_nodes<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> impl ExactSizeIterator<Item = HalfEdgeTreeNode<'a, T>> {
+        debug_assert!(self.contains_node(node));
+        node.node.children().map(
+            |HalfEdge {
+                 path_segment,
+                 child_key: _,
+                 node_id,
+             }| HalfEdgeTreeNode {
+                path_segment,
+                node: self.get_node(node_id),
+            },
+        )
+    }
+
+    /// Edges to the children of a node in a custom order
+    ///
+    /// The children are sorted by their path segments according to `compare`.
+    #[must_use]
+    pub fn child_nodes_sorted_by<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+        mut compare: impl FnMut(&T::PathSegment, &T::PathSegment) -> Ordering,
+    ) -> Vec<HalfEdge<'a, T>> {
+        debug_assert!(self.contains_node(node));
+        let mut children = node.node.children().collect::<Vec<_>>();
+        children.sort_by(|lhs, rhs| compare(lhs.path_segment, rhs.path_segment));
+        children
+    }
+
+    /// Edges to the children of a node in natural order
+    ///
+    /// Shortcut for [`Self::child_nodes_sorted_by()`] with [`natural_cmp()`](crate::natural_cmp),
+    /// e.g. for listing files in a user interface.
+    #[must_use]
+    pub fn child_nodes_sorted_naturally<'a>(
+        &'a self,
+        node: &'a Arc<TreeNode<T>>,
+    ) -> Vec<HalfEdge<'a, T>>
+    where
+        T::PathSegment: AsRef<str>,
+    {
+        self.child_nodes_sorted_by(node, |lhs, rhs| {
+            crate::natural_cmp(lhs.as_ref(), rhs.as_ref())
+        })
+    }
+
     /// Number of child nodes of the given node (recursively).
     #[must_use]
     pub fn descendant_nodes_count(&self, node: &Arc<TreeNode<T>>) -> usize {
         debug_assert!(self.contains_node(node));
         node.node.descendants_count(self)
     }
+
+    /// Accumulated weight of all values in a subtree.
+    ///
+    /// Sums up the weights of the values of the given node and all its
+    /// descendants, see [`PathTreeTypes::inner_value_weight()`] and
+    /// [`PathTreeTypes::leaf_value_weight()`]. Intended for disk-usage style
+    /// reporting.
+    ///
+    /// Returns `None` if the node does not exist.
+    ///
+    /// Computed on demand in linear time with respect to the size of the
+    /// subtree. Use a [`SubtreeWeightIndex`](crate::SubtreeWeightIndex) for
+    /// repeated queries while the tree is modified.
+    #[must_use]
+    pub fn subtree_weight_bytes(&self, node_id: T::NodeId) -> Option<usize> {
+        let node = self.lookup_node(node_id)?;
+        let weight = std::iter::once(node)
+            .chain(
+                self.descendant_nodes(node)
+                    .map(|child| self.get_node(child.node_id)),
+            )
+            .map(|node| node.node.value_weight())
+            .sum();
+        Some(weight)
+    }
+
+    /// Clone the tree without any structural sharing.
+    ///
+    /// All nodes and their children are copied into new allocations.
+    /// The node ids remain unchanged.
+    ///
+    /// Executed in linear time, i.e. O(n) with n the number of nodes.
+    /// Prefer [`Clone::clone()`] unless the reference counts of the shared
+    /// nodes must not be affected by the copy.
+    #[must_use]
+    pub fn deep_clone(&self) -> Self {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(node_id, node)| {
+                let TreeNode { id, parent, node } = &**node;
+                let node = match node {
+                    Node::Inner(InnerNode { children, value }) => Node::Inner(InnerNode {
+                        children: children
+                            .iter()
+                            .map(|(child_key, child)| (child_key.clone(), child.clone()))
+                            .collect(),
+                        value: value.clone(),
+                    }),
+                    Node::Leaf(LeafNode { value }) => Node::Leaf(LeafNode::new(value.clone())),
+                };
+                let node = TreeNode {
+                    id: id.clone(),
+                    parent: parent.clone(),
+                    node,
+                };
+                (node_id.clone(), Arc::new(node))
+            })
+            .collect();
+        Self {
+            root_node_id: self.root_node_id.clone(),
+            nodes,
+            new_node_id: self.new_node_id.clone(),
+            provenance: self.provenance.clone(),
+            frozen_node_ids: self.frozen_node_ids.clone(),
+            snapshot_token: self.snapshot_token,
+            snapshot_lineage: Arc::clone(&self.snapshot_lineage),
+            root_path_policy: self.root_path_policy,
+            _types: PhantomData,
+        }
+    }
+
+    /// Convert all values into a tree with different value types.
+    ///
+    /// The structure of the tree, all node ids, and all path segments are
+    /// preserved. Only the types of the values may differ. Nodes that are
+    /// not shared with other trees are consumed without cloning their values.
+    ///
+    /// Frozen subtrees remain frozen.
+    #[must_use]
+    pub fn map_values<U>(
+        self,
+        mut map_inner_value: impl FnMut(T::InnerValue) -> U::InnerValue,
+        mut map_leaf_value: impl FnMut(T::LeafValue) -> U::LeafValue,
+    ) -> PathTree<U>
+    where
+        U: PathTreeTypes<
+            NodeId = T::NodeId,
+            NewNodeId = T::NewNodeId,
+            PathSegment = T::PathSegment,
+            PathSegmentOwned = T::PathSegmentOwned,
+            RootPath = T::RootPath,
+            ChildKey = T::ChildKey,
+            ChildKeyOwned = T::ChildKeyOwned,
+        >,
+    {
+        let Self {
+            root_node_id,
+            nodes,
+            new_node_id,
+            provenance,
+            frozen_node_ids,
+            snapshot_token: _,
+            snapshot_lineage: _,
+            root_path_policy,
+            _types,
+        } = self;
+        let old_nodes = nodes.values().map(Arc::clone).collect::<Vec<_>>();
+        // Release the references of the tree before consuming the nodes.
+        drop(nodes);
+        let map_half_edge = |HalfEdgeOwned {
+                                 path_segment,
+                                 node_id,
+                             }: HalfEdgeOwned<T>| HalfEdgeOwned::<U> {
+            path_segment,
+            node_id,
+        };
+        let nodes = old_nodes
+            .into_iter()
+            .map(|old_node| {
+                let TreeNode { id, parent, node } = Arc::unwrap_or_clone(old_node);
+                let node = match node {
+                    Node::Inner(InnerNode { children, value }) => Node::Inner(InnerNode {
+                        children: children
+                            .iter()
+                            .map(|(child_key, child)| {
+                                (child_key.clone(), map_half_edge(child.clone()))
+                            })
+                            .collect(),
+                        value: map_inner_value(value),
+                    }),
+                    Node::Leaf(LeafNode { value }) => {
+                        Node::Leaf(LeafNode::new(U::intern_leaf_value(map_leaf_value(value))))
+                    }
+                };
+                let node = TreeNode {
+                    id: id.clone(),
+                    parent: parent.map(map_half_edge),
+                    node,
+                };
+                (id, Arc::new(node))
+            })
+            .collect();
+        PathTree {
+            root_node_id,
+            nodes,
+            new_node_id,
+            provenance,
+            frozen_node_ids,
+            snapshot_token: SnapshotToken::next(),
+            snapshot_lineage: Arc::new(SnapshotLineage::new()),
+            root_path_policy,
+            _types: PhantomData,
+        }
+    }
+
+    /// Estimated size of a clone in bytes.
+    ///
+    /// A clone with [`Clone::clone()`] only copies the tree handle while
+    /// sharing all nodes.
+    #[must_use]
+    pub const fn shallow_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    /// Estimated size of a deep clone in bytes.
+    ///
+    /// A clone with [`Self::deep_clone()`] copies all nodes. The estimate
+    /// only accounts for the inline size of the nodes and their children.
+    /// Heap allocations owned by values or path segments and the internal
+    /// overhead of the hash maps are not included.
+    #[must_use]
+    pub fn deep_size(&self) -> usize {
+        // Each node is stored in a reference-counted allocation with strong and weak counts.
+        let node_size = std::mem::size_of::<T::NodeId>()
+            + std::mem::size_of::<Arc<TreeNode<T>>>()
+            + 2 * std::mem::size_of::<usize>()
+            + std::mem::size_of::<TreeNode<T>>();
+        // Children are mapped by their child key to the edge with the path segment and node id.
+        let child_size =
+            std::mem::size_of::<T::ChildKeyOwned>() + std::mem::size_of::<HalfEdgeOwned<T>>();
+        let nodes_count = self.nodes_count().get();
+        // All nodes except the root node are referenced as a child by their parent node.
+        let children_count = nodes_count - 1;
+        self.shallow_size() + nodes_count * node_size + children_count * child_size
+    }
 }
 
 /// Immutable node in the tree.
@@ -1017,7 +3498,7 @@ impl<T: PathTreeTypes> TreeNode<T> {
                     NodeValue::Inner(new_value) => {
                         // Remains an inner node with the current children and the new value.
                         Self {
-                            id: self.id,
+                            id: self.id.clone(),
                             parent: new_parent.or_else(|| self.parent.clone()),
                             node: Node::Inner(InnerNode {
                                 children: children.clone(),
@@ -1032,7 +3513,7 @@ impl<T: PathTreeTypes> TreeNode<T> {
                             });
                         }
                         Self {
-                            id: self.id,
+                            id: self.id.clone(),
                             parent: new_parent.or_else(|| self.parent.clone()),
                             node: Node::from_value_without_children(new_value),
                         }
@@ -1042,7 +3523,7 @@ impl<T: PathTreeTypes> TreeNode<T> {
             Node::Leaf(_) => {
                 // Leaf node values could be replaced by both leaf and inner node values.
                 Self {
-                    id: self.id,
+                    id: self.id.clone(),
                     parent: new_parent.or_else(|| self.parent.clone()),
                     node: Node::from_value_without_children(new_value),
                 }
@@ -1059,41 +3540,45 @@ impl<T: PathTreeTypes> TreeNode<T> {
     }
 }
 
-fn try_replace_leaf_with_inner_node<T: PathTreeTypes>(
+/// Returns the unmodified leaf node as an error if it could not be replaced.
+///
+/// The outer error is returned if the new inner value could not be created.
+#[allow(clippy::type_complexity)]
+fn try_replace_leaf_with_inner_node<T: PathTreeTypes, E>(
     nodes: &mut HashMap<T::NodeId, Arc<TreeNode<T>>>,
     node: Arc<TreeNode<T>>,
     try_clone_leaf_into_inner_value: &mut Option<
-        impl FnOnce(&T::LeafValue) -> Option<T::InnerValue>,
+        impl FnOnce(&T::LeafValue) -> Result<Option<T::InnerValue>, E>,
     >,
-) -> Result<Arc<TreeNode<T>>, Arc<TreeNode<T>>> {
+) -> Result<Result<Arc<TreeNode<T>>, Arc<TreeNode<T>>>, E> {
     let TreeNode {
         id,
         parent,
         node: Node::Leaf(LeafNode { value: leaf_value }),
     } = &*node
     else {
-        return Ok(node);
+        return Ok(Ok(node));
     };
     let try_clone_leaf_into_inner_value = try_clone_leaf_into_inner_value
         .take()
         .expect("consumed at most once");
-    let Some(inner_value) = try_clone_leaf_into_inner_value(leaf_value) else {
+    let Some(inner_value) = try_clone_leaf_into_inner_value(leaf_value)? else {
         // Keep this leaf node
-        return Err(node);
+        return Ok(Err(node));
     };
     // Replace leaf node with empty inner node
     let inner_node = TreeNode {
-        id: *id,
+        id: id.clone(),
         parent: parent.clone(),
         node: InnerNode::new(inner_value).into(),
     };
-    log::debug!(
+    log_mutation!(
         "Replacing leaf node {leaf_node:?} with inner node {inner_node:?}",
         leaf_node = *node
     );
     let inner_node = Arc::new(inner_node);
-    nodes.insert_mut(inner_node.id, Arc::clone(&inner_node));
-    Ok(inner_node)
+    nodes.insert_mut(inner_node.id.clone(), Arc::clone(&inner_node));
+    Ok(Ok(inner_node))
 }
 
 /// Iterator over all ancestor nodes of the given node.
@@ -1125,7 +3610,7 @@ impl<'a, T: PathTreeTypes> Iterator for AncestorTreeNodeIter<'a, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let parent = self.next_node.as_ref()?.parent.as_ref()?;
-        self.next_node = self.tree.lookup_node(parent.node_id);
+        self.next_node = self.tree.lookup_node(parent.node_id.clone());
         self.next_node.map(|node| HalfEdgeTreeNode {
             path_segment: parent.path_segment.borrow(),
             node,
@@ -1138,11 +3623,11 @@ fn update_parent_node<T: PathTreeTypes>(
     parent_node: TreeNode<T>,
 ) -> Arc<TreeNode<T>> {
     debug_assert!(matches!(parent_node.node, Node::Inner(_)));
-    let parent_node_id = parent_node.id;
+    let parent_node_id = parent_node.id.clone();
     let new_parent_node = Arc::new(parent_node);
-    debug_assert!(nodes.contains_key(&parent_node_id));
+    strict_assert!(nodes.contains_key(&parent_node_id));
     nodes.insert_mut(parent_node_id, Arc::clone(&new_parent_node));
-    log::debug!(
+    log_mutation!(
         "Updated parent node {new_parent_node:?}",
         new_parent_node = *new_parent_node,
     );
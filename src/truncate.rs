@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::ops::Range;
+
+use crate::{HalfEdgeTreeNode, PathTree, PathTreeTypes};
+
+const SEPARATOR: char = '/';
+
+/// Replaces all elided path segments.
+const ELLIPSIS: &str = "…";
+
+/// Which path segments to elide when a path is too long.
+///
+/// See [`PathTree::format_node_path_truncated()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TruncationStrategy {
+    /// Elide segments in the middle, e.g. `/home/…/docs/file.txt`.
+    ///
+    /// The last segment is always retained.
+    #[default]
+    Middle,
+
+    /// Elide leading segments, e.g. `/…/docs/file.txt`.
+    ///
+    /// The last segment is always retained.
+    Head,
+
+    /// Elide trailing segments, e.g. `/home/user/…`.
+    Tail,
+}
+
+/// A formatted path with elided segments.
+///
+/// Returned by [`PathTree::format_node_path_truncated()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedNodePath {
+    /// The formatted path.
+    pub path: String,
+
+    /// The indices of all segments that have been replaced by an ellipsis.
+    ///
+    /// Elided segments are always contiguous. Empty if the path has not
+    /// been truncated.
+    pub elided_segments: Range<usize>,
+}
+
+impl TruncatedNodePath {
+    /// Check if any segments have been elided.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        !self.elided_segments.is_empty()
+    }
+}
+
+/// The length of a formatted segment including its separator.
+fn segment_len(path_segment: &str) -> usize {
+    1 + path_segment.chars().count()
+}
+
+/// The number of leading and trailing segments to retain.
+///
+/// At least one segment is elided. The result might exceed `max_len`
+/// if the mandatory segments do not fit.
+fn retained_segments_count(
+    segment_lens: &[usize],
+    max_len: usize,
+    strategy: TruncationStrategy,
+) -> (usize, usize) {
+    debug_assert!(!segment_lens.is_empty());
+    let segments_count = segment_lens.len();
+    let (mut head_count, mut tail_count) = match strategy {
+        TruncationStrategy::Middle | TruncationStrategy::Head => (0, 1),
+        TruncationStrategy::Tail => (0, 0),
+    };
+    // The ellipsis is prefixed by a separator like a segment.
+    let mut len = segment_len(ELLIPSIS)
+        + segment_lens[segments_count - tail_count..]
+            .iter()
+            .sum::<usize>();
+    let (mut grow_head, mut grow_tail) = match strategy {
+        TruncationStrategy::Middle => (true, true),
+        TruncationStrategy::Head => (false, true),
+        TruncationStrategy::Tail => (true, false),
+    };
+    // Alternate between both ends until nothing fits anymore.
+    while (grow_head || grow_tail) && head_count + tail_count + 1 < segments_count {
+        if grow_head {
+            let next_len = len + segment_lens[head_count];
+            if next_len <= max_len {
+                len = next_len;
+                head_count += 1;
+            } else {
+                grow_head = false;
+            }
+        }
+        if grow_tail && head_count + tail_count + 1 < segments_count {
+            let next_len = len + segment_lens[segments_count - tail_count - 1];
+            if next_len <= max_len {
+                len = next_len;
+                tail_count += 1;
+            } else {
+                grow_tail = false;
+            }
+        }
+    }
+    (head_count, tail_count)
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Format the path of a node with at most `max_len` characters.
+    ///
+    /// Path segments are prefixed by a slash, the root node has the path `/`.
+    /// If the full path is too long then whole segments are replaced by a
+    /// single ellipsis `…` according to `strategy`. Segments are never cut,
+    /// i.e. the result might exceed `max_len` if the segments that are always
+    /// retained do not fit. Separators in segments are not escaped.
+    ///
+    /// The length is measured in Unicode scalar values, i.e. [`char`]s,
+    /// and not in bytes. The displayed width might still differ, e.g. for
+    /// combining characters or wide glyphs.
+    ///
+    /// Returns `None` if the node does not exist.
+    #[must_use]
+    pub fn format_node_path_truncated(
+        &self,
+        node_id: T::NodeId,
+        max_len: usize,
+        strategy: TruncationStrategy,
+    ) -> Option<TruncatedNodePath>
+    where
+        T::PathSegment: AsRef<str>,
+    {
+        let node = self.lookup_node(node_id)?;
+        let path_segments = self
+            .path_to_node(node)
+            .map(|HalfEdgeTreeNode { path_segment, .. }| path_segment.as_ref())
+            .collect::<Vec<_>>();
+        let segment_lens = path_segments
+            .iter()
+            .map(|path_segment| segment_len(path_segment))
+            .collect::<Vec<_>>();
+        let elided_segments =
+            if path_segments.is_empty() || segment_lens.iter().sum::<usize>() <= max_len {
+                let segments_count = path_segments.len();
+                segments_count..segments_count
+            } else {
+                let (head_count, tail_count) =
+                    retained_segments_count(&segment_lens, max_len, strategy);
+                head_count..path_segments.len() - tail_count
+            };
+        let mut path = String::new();
+        for (index, path_segment) in path_segments.iter().enumerate() {
+            if elided_segments.contains(&index) {
+                if index == elided_segments.start {
+                    path.push(SEPARATOR);
+                    path.push_str(ELLIPSIS);
+                }
+                continue;
+            }
+            path.push(SEPARATOR);
+            path.push_str(path_segment);
+        }
+        if path.is_empty() {
+            // Root node
+            path.push(SEPARATOR);
+        }
+        Some(TruncatedNodePath {
+            path,
+            elided_segments,
+        })
+    }
+}
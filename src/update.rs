@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{ops::ControlFlow, sync::Arc};
+
+use crate::{HalfEdge, Node, NodeValue, PathTree, PathTreeTypes};
+
+/// Treatment of preceding updates when stopping early.
+///
+/// See [`PathTree::try_update_leaves_in_subtree()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BreakPolicy {
+    /// Keep all updates that have been made before stopping.
+    #[default]
+    KeepUpdates,
+
+    /// Discard all updates and restore the tree.
+    RollBack,
+}
+
+/// Outcome of [`PathTree::try_update_leaves_in_subtree()`].
+#[derive(Debug, Clone)]
+pub struct LeavesUpdated<T, B>
+where
+    T: PathTreeTypes,
+{
+    /// The number of leaf nodes that have been passed to the closure.
+    pub visited_count: usize,
+
+    /// The number of leaf nodes that have been updated.
+    ///
+    /// Includes updates that have been rolled back.
+    pub updated_count: usize,
+
+    /// The id of the leaf node and the value returned by the closure,
+    /// if stopped early.
+    pub stopped: Option<(T::NodeId, B)>,
+
+    /// `true` if all updates have been discarded.
+    pub rolled_back: bool,
+}
+
+impl<T, B> LeavesUpdated<T, B>
+where
+    T: PathTreeTypes,
+{
+    /// Check if all leaf nodes have been visited.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.stopped.is_none()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Update the values of all leaf nodes in a subtree with the option to stop early.
+    ///
+    /// The closure returns [`ControlFlow::Continue`] with `Some` new value
+    /// for updating a leaf node or with `None` for keeping its value.
+    /// Returning [`ControlFlow::Break`] stops the traversal. All preceding
+    /// updates are then either kept or discarded according to the `policy`.
+    ///
+    /// Leaf nodes are visited in the same, stable order as by
+    /// [`Self::descendant_nodes()`]. Resuming a migration after stopping
+    /// is possible by skipping leaf nodes that have already been migrated.
+    ///
+    /// Returns `None` if the node does not exist.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn try_update_leaves_in_subtree<B>(
+        &mut self,
+        node_id: T::NodeId,
+        policy: BreakPolicy,
+        mut update_value: impl FnMut(&T::NodeId, &T::LeafValue) -> ControlFlow<B, Option<T::LeafValue>>,
+    ) -> Option<LeavesUpdated<T, B>> {
+        let node = self.lookup_node(node_id)?;
+        let leaf_node_ids = std::iter::once(node.id.clone())
+            .chain(
+                self.descendant_nodes(node)
+                    .map(|HalfEdge { node_id, .. }| node_id),
+            )
+            .filter(|node_id| matches!(self.get_node(node_id.clone()).node, Node::Leaf(_)))
+            .collect::<Vec<_>>();
+        let tree_before = matches!(policy, BreakPolicy::RollBack).then(|| self.clone());
+        let mut updated = LeavesUpdated {
+            visited_count: 0,
+            updated_count: 0,
+            stopped: None,
+            rolled_back: false,
+        };
+        for node_id in leaf_node_ids {
+            let node = Arc::clone(self.get_node(node_id.clone()));
+            let Node::Leaf(leaf) = &node.node else {
+                unreachable!("leaf node");
+            };
+            updated.visited_count += 1;
+            match update_value(&node_id, &leaf.value) {
+                ControlFlow::Continue(None) => (),
+                ControlFlow::Continue(Some(new_value)) => {
+                    self.update_node_value(&node, NodeValue::Leaf(new_value))
+                        .expect("leaf value");
+                    updated.updated_count += 1;
+                }
+                ControlFlow::Break(value) => {
+                    updated.stopped = Some((node_id, value));
+                    break;
+                }
+            }
+        }
+        if let (Some(tree_before), Some(_)) = (tree_before, &updated.stopped) {
+            log::debug!(
+                "Rolling back {updated_count} update(s)",
+                updated_count = updated.updated_count
+            );
+            *self = tree_before;
+            updated.rolled_back = true;
+        }
+        Some(updated)
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, collections::HashSet};
+
+use derive_more::{Display, Error};
+
+use crate::{HalfEdge, HalfEdgeOwned, PathTree, PathTreeTypes};
+
+/// A violated structural invariant of a [`PathTree`].
+///
+/// Indicates a bug in this crate.
+#[derive(Debug, Display, Error)]
+pub enum TreeValidationError<T>
+where
+    T: PathTreeTypes,
+{
+    #[display("root node {node_id} not found")]
+    RootNodeNotFound {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("root node {node_id} has a parent node")]
+    RootNodeWithParent {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("node {node_id} has no parent node")]
+    ParentNodeMissing {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("parent node of node {node_id} not found")]
+    ParentNodeNotFound {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("node {node_id} is not a child of its parent node")]
+    ChildEdgeMissing {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("child node {node_id} not found")]
+    ChildNodeNotFound {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+    #[display("node {node_id} is unreachable from the root node")]
+    Unreachable {
+        #[error(not(source))]
+        node_id: T::NodeId,
+    },
+}
+
+/// Outcome of [`PathTree::verify_id_set()`].
+#[derive(Debug, Clone)]
+pub struct IdAudit<T>
+where
+    T: PathTreeTypes,
+{
+    /// External ids that do not refer to a node in the tree.
+    ///
+    /// In the order of occurrence without duplicates.
+    pub missing_ids: Vec<T::NodeId>,
+
+    /// Ids of nodes in the tree that are not contained in the external ids.
+    ///
+    /// In no particular order.
+    pub uncovered_node_ids: Vec<T::NodeId>,
+}
+
+impl<T> IdAudit<T>
+where
+    T: PathTreeTypes,
+{
+    /// Check if the external ids match the node ids of the tree exactly.
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        let Self {
+            missing_ids,
+            uncovered_node_ids,
+        } = self;
+        missing_ids.is_empty() && uncovered_node_ids.is_empty()
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Check the structural invariants of the tree.
+    ///
+    /// Verifies that all parent and child edges are consistent and
+    /// that all nodes are reachable from the root node.
+    ///
+    /// All public operations are supposed to preserve these invariants.
+    /// Intended for testing and fuzzing. Executed in linear time, i.e. O(n).
+    pub fn validate(&self) -> Result<(), TreeValidationError<T>> {
+        let root_node_id = self.root_node_id();
+        let root_node = self.lookup_node(root_node_id.clone()).ok_or_else(|| {
+            TreeValidationError::RootNodeNotFound {
+                node_id: root_node_id.clone(),
+            }
+        })?;
+        if root_node.parent.is_some() {
+            return Err(TreeValidationError::RootNodeWithParent {
+                node_id: root_node_id,
+            });
+        }
+        for node in self.nodes() {
+            let node_id = node.id.clone();
+            for HalfEdge {
+                path_segment: _,
+                child_key: _,
+                node_id: child_node_id,
+            } in node.node.children()
+            {
+                let child_node = self.lookup_node(child_node_id.clone()).ok_or_else(|| {
+                    TreeValidationError::ChildNodeNotFound {
+                        node_id: child_node_id.clone(),
+                    }
+                })?;
+                if child_node
+                    .parent
+                    .as_ref()
+                    .map_or(true, |parent| parent.node_id != node_id)
+                {
+                    return Err(TreeValidationError::ChildEdgeMissing {
+                        node_id: child_node_id,
+                    });
+                }
+            }
+            if node_id == root_node_id {
+                continue;
+            }
+            let HalfEdgeOwned {
+                path_segment,
+                node_id: parent_node_id,
+            } = node
+                .parent
+                .as_ref()
+                .ok_or_else(|| TreeValidationError::ParentNodeMissing {
+                    node_id: node_id.clone(),
+                })?;
+            let parent_node = self.lookup_node(parent_node_id.clone()).ok_or_else(|| {
+                TreeValidationError::ParentNodeNotFound {
+                    node_id: node_id.clone(),
+                }
+            })?;
+            if parent_node.node.find_child(path_segment.borrow()).as_ref() != Some(&node_id) {
+                return Err(TreeValidationError::ChildEdgeMissing { node_id });
+            }
+        }
+        // All edges are consistent and could be traversed safely.
+        let reachable_node_ids = std::iter::once(root_node_id.clone())
+            .chain(
+                self.descendant_nodes(root_node)
+                    .map(|HalfEdge { node_id, .. }| node_id),
+            )
+            .collect::<HashSet<_>>();
+        if let Some(node) = self
+            .nodes()
+            .find(|node| !reachable_node_ids.contains(&node.id))
+        {
+            return Err(TreeValidationError::Unreachable {
+                node_id: node.id.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Compare the node ids of the tree with an external set of ids.
+    ///
+    /// Intended for validating the consistency of external indexes that
+    /// refer to nodes by their id, e.g. after recovering from a crash.
+    /// Executed in linear time, i.e. O(n + m) with n the number of nodes
+    /// and m the number of external ids.
+    pub fn verify_id_set(&self, ids: impl IntoIterator<Item = T::NodeId>) -> IdAudit<T> {
+        let mut covered_node_ids = HashSet::new();
+        let mut missing_ids = Vec::new();
+        let mut missing_ids_seen = HashSet::new();
+        for id in ids {
+            if self.lookup_node(id.clone()).is_some() {
+                covered_node_ids.insert(id);
+            } else if missing_ids_seen.insert(id.clone()) {
+                missing_ids.push(id);
+            }
+        }
+        let uncovered_node_ids = self
+            .nodes()
+            .map(|node| node.id.clone())
+            .filter(|node_id| !covered_node_ids.contains(node_id))
+            .collect();
+        IdAudit {
+            missing_ids,
+            uncovered_node_ids,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{any::Any, borrow::Cow, sync::Arc};
+
+use crate::{HalfEdge, Node, PathTree, PathTreeTypes, TreeNode};
+
+/// Type-erased value of a visited node.
+#[derive(Debug, Clone, Copy)]
+pub enum VisitedValue<'a> {
+    Inner(&'a dyn Any),
+    Leaf(&'a dyn Any),
+}
+
+impl<'a> VisitedValue<'a> {
+    /// The value, regardless of the node kind.
+    #[must_use]
+    pub const fn as_any(&self) -> &'a dyn Any {
+        match self {
+            Self::Inner(value) | Self::Leaf(value) => *value,
+        }
+    }
+
+    /// Downcast the value to a concrete type.
+    #[must_use]
+    pub fn downcast_ref<V: Any>(&self) -> Option<&'a V> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// A node that is passed to a [`NodeValueVisitor`].
+#[derive(Debug, Clone, Copy)]
+pub struct VisitedNode<'a> {
+    /// The number of ancestor nodes, i.e. 0 for the root node.
+    pub depth: usize,
+
+    /// The path segment from the parent node.
+    ///
+    /// `None` for the root node.
+    pub path_segment: Option<&'a dyn Any>,
+
+    pub value: VisitedValue<'a>,
+
+    pub children_count: usize,
+}
+
+impl<'a> VisitedNode<'a> {
+    /// The path segment as a string slice.
+    ///
+    /// Supports path segments of type `String`, `Cow<'static, str>`,
+    /// `Arc<str>`, `Box<str>`, and `&'static str`.
+    #[must_use]
+    pub fn path_segment_str(&self) -> Option<&'a str> {
+        let path_segment = self.path_segment?;
+        if let Some(path_segment) = path_segment.downcast_ref::<String>() {
+            return Some(path_segment);
+        }
+        if let Some(path_segment) = path_segment.downcast_ref::<Cow<'static, str>>() {
+            return Some(path_segment);
+        }
+        if let Some(path_segment) = path_segment.downcast_ref::<Arc<str>>() {
+            return Some(path_segment);
+        }
+        if let Some(path_segment) = path_segment.downcast_ref::<Box<str>>() {
+            return Some(path_segment);
+        }
+        path_segment.downcast_ref::<&'static str>().copied()
+    }
+}
+
+/// Object-safe visitor of nodes and their values.
+///
+/// Processes trees without knowing the concrete [`PathTreeTypes`],
+/// e.g. across a `dyn` boundary.
+pub trait NodeValueVisitor {
+    fn visit_node(&mut self, node: VisitedNode<'_>);
+}
+
+/// Decision of a [`Visit`] implementation after visiting a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitControl {
+    /// Continue with the children of the node.
+    #[default]
+    Continue,
+
+    /// Skip all descendants of the node and continue with its siblings.
+    SkipSubtree,
+
+    /// Stop the traversal.
+    Stop,
+}
+
+/// Typed visitor that controls the traversal.
+///
+/// In contrast to iterators the visitor could prune subtrees without
+/// descending into them.
+///
+/// Implemented for all closures with a matching signature.
+pub trait Visit<T>
+where
+    T: PathTreeTypes,
+{
+    /// Visit a node before its descendants.
+    ///
+    /// `depth` is the number of ancestor nodes, i.e. 0 for the root node.
+    fn visit(&mut self, depth: usize, node: &Arc<TreeNode<T>>) -> VisitControl;
+}
+
+impl<T, F> Visit<T> for F
+where
+    T: PathTreeTypes,
+    F: FnMut(usize, &Arc<TreeNode<T>>) -> VisitControl,
+{
+    fn visit(&mut self, depth: usize, node: &Arc<TreeNode<T>>) -> VisitControl {
+        self(depth, node)
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Visit all nodes of the tree.
+    ///
+    /// Nodes are visited depth-first, starting at the root node. Parent nodes
+    /// are visited before their children. The order of siblings is undefined.
+    pub fn accept(&self, visitor: &mut dyn NodeValueVisitor)
+    where
+        T::PathSegmentOwned: Any,
+        T::InnerValue: Any,
+        T::LeafValue: Any,
+    {
+        let mut stack = vec![(self.root_node(), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let value = match &node.node {
+                Node::Inner(inner) => {
+                    stack.extend(
+                        inner
+                            .children()
+                            .filter_map(|child| self.lookup_node(child.node_id))
+                            .map(|child_node| (child_node, depth + 1)),
+                    );
+                    VisitedValue::Inner(&inner.value)
+                }
+                Node::Leaf(leaf) => VisitedValue::Leaf(&leaf.value),
+            };
+            visitor.visit_node(VisitedNode {
+                depth,
+                path_segment: node
+                    .parent
+                    .as_ref()
+                    .map(|parent| &parent.path_segment as &dyn Any),
+                value,
+                children_count: node.node.children_count(),
+            });
+        }
+    }
+
+    /// Visit all nodes of the tree with pruning.
+    ///
+    /// Nodes are visited depth-first in pre-order, starting at the root node.
+    /// Siblings are visited in the same order as by
+    /// [`Self::depth_first_traversal()`].
+    ///
+    /// Returns `false` if the traversal has been stopped by the visitor.
+    pub fn visit(&self, visitor: &mut impl Visit<T>) -> bool {
+        let mut stack = vec![(self.root_node_id(), 0)];
+        while let Some((node_id, depth)) = stack.pop() {
+            let node = self.get_node(node_id);
+            match visitor.visit(depth, node) {
+                VisitControl::Continue => (),
+                VisitControl::SkipSubtree => continue,
+                VisitControl::Stop => return false,
+            }
+            let len_before = stack.len();
+            stack.extend(
+                node.node
+                    .children()
+                    .map(|HalfEdge { node_id, .. }| (node_id, depth + 1)),
+            );
+            // Reverse the order of children so that the first child ends up at the top of the stack.
+            stack[len_before..].reverse();
+        }
+        true
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{HalfEdge, PathTree, PathTreeTypes, SubtreeKindCounts, TreeNode};
+
+/// Aggregated values of a subtree.
+#[derive(Debug, Clone, Copy, Default)]
+struct SubtreeAggregate {
+    weight_bytes: usize,
+    kind_counts: SubtreeKindCounts,
+}
+
+impl SubtreeAggregate {
+    fn add(&mut self, other: &Self) {
+        let Self {
+            weight_bytes,
+            kind_counts,
+        } = other;
+        self.weight_bytes += weight_bytes;
+        self.kind_counts.add_counts(kind_counts);
+    }
+}
+
+/// An index of the accumulated value weights and node counts of all subtrees.
+///
+/// Caches the results of [`PathTree::subtree_weight_bytes()`] and
+/// [`PathTree::subtree_kind_counts()`] for every node, e.g. for displaying
+/// badges of folders without traversing their subtrees. Caching the
+/// aggregates in the nodes themselves would require to rewrite all ancestor
+/// nodes on each modification.
+///
+/// The index is created for a tree and must be synchronized explicitly after
+/// the tree has been modified. Only the aggregates of modified nodes and their
+/// ancestors are recomputed.
+#[derive(Debug, Clone)]
+pub struct SubtreeWeightIndex<T>
+where
+    T: PathTreeTypes,
+{
+    snapshot: PathTree<T>,
+    aggregates: HashMap<T::NodeId, SubtreeAggregate>,
+}
+
+impl<T> SubtreeWeightIndex<T>
+where
+    T: PathTreeTypes,
+{
+    /// Create an index for a tree.
+    ///
+    /// Executed in linear time, i.e. O(n) with n the number of nodes.
+    #[must_use]
+    pub fn new(tree: &PathTree<T>) -> Self {
+        let mut index = Self {
+            snapshot: tree.clone(),
+            aggregates: HashMap::new(),
+        };
+        for node in tree.post_order_nodes(tree.root_node()) {
+            index.update_aggregate(node);
+        }
+        index
+    }
+
+    /// Recompute the aggregate of a node from the aggregates of its children.
+    fn update_aggregate(&mut self, node: &TreeNode<T>) {
+        let mut aggregate = SubtreeAggregate {
+            weight_bytes: node.node.value_weight(),
+            kind_counts: SubtreeKindCounts::default(),
+        };
+        aggregate.kind_counts.add(&node.node);
+        for HalfEdge { node_id, .. } in node.node.children() {
+            aggregate.add(&self.aggregates[&node_id]);
+        }
+        self.aggregates.insert(node.id.clone(), aggregate);
+    }
+
+    /// Synchronize the index with a modified tree.
+    ///
+    /// The tree must be a descendant of the tree for which the index has been
+    /// created or synchronized, i.e. it must share the same history.
+    ///
+    /// Detecting the modified nodes is executed in linear time, i.e. O(n)
+    /// with n the number of nodes, but without accessing any values. The
+    /// aggregates are then only recomputed for the k modified nodes and their
+    /// ancestors, i.e. in O(k·d) with d the depth of the tree.
+    ///
+    /// Returns the number of nodes with recomputed aggregates.
+    pub fn sync(&mut self, tree: &PathTree<T>) -> usize {
+        let diff = self.snapshot.diff(tree);
+        for node_id in &diff.removed {
+            self.aggregates.remove(node_id);
+        }
+        let mut dirty_node_ids = HashSet::new();
+        for node_id in diff.inserted.into_iter().chain(diff.updated) {
+            let mut next_node_id = Some(node_id);
+            while let Some(node_id) = next_node_id.take() {
+                if !dirty_node_ids.insert(node_id.clone()) {
+                    // All ancestors have already been visited.
+                    break;
+                }
+                next_node_id = tree
+                    .get_node(node_id)
+                    .parent
+                    .as_ref()
+                    .map(|parent| parent.node_id.clone());
+            }
+        }
+        // Recompute all children before their parent nodes.
+        let mut dirty_nodes = dirty_node_ids
+            .into_iter()
+            .map(|node_id| {
+                let node = tree.get_node(node_id);
+                (tree.ancestor_nodes_count(node), node)
+            })
+            .collect::<Vec<_>>();
+        dirty_nodes.sort_unstable_by(|(lhs_depth, _), (rhs_depth, _)| rhs_depth.cmp(lhs_depth));
+        for (_, node) in &dirty_nodes {
+            self.update_aggregate(node);
+        }
+        self.snapshot = tree.clone();
+        dirty_nodes.len()
+    }
+
+    /// The accumulated weight of all values in a subtree.
+    ///
+    /// Returns `None` if the node does not exist.
+    ///
+    /// See also [`PathTree::subtree_weight_bytes()`].
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn subtree_weight_bytes(&self, node_id: T::NodeId) -> Option<usize> {
+        self.aggregates
+            .get(&node_id)
+            .map(|aggregate| aggregate.weight_bytes)
+    }
+
+    /// The number of inner and leaf nodes in a subtree.
+    ///
+    /// Returns `None` if the node does not exist.
+    ///
+    /// See also [`PathTree::subtree_kind_counts()`].
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Node ids are passed by value
+    pub fn subtree_kind_counts(&self, node_id: T::NodeId) -> Option<SubtreeKindCounts> {
+        self.aggregates
+            .get(&node_id)
+            .map(|aggregate| aggregate.kind_counts)
+    }
+}
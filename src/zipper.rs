@@ -0,0 +1,279 @@
+// SPDX-FileCopyrightText: The rpds-pathtree authors
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{borrow::Borrow as _, sync::Arc};
+
+use crate::{
+    InsertOrUpdateNodeValueError, NodeValue, PathTree, PathTreeTypes, SubtreeRemoved, TreeNode,
+    UpdateNodeValueError,
+};
+
+/// Navigates through a tree and remembers the path of the current node.
+///
+/// Read-only. Use [`Self::to_zipper()`] for editing the tree at the
+/// current position.
+///
+/// Created by [`PathTree::cursor()`].
+#[derive(Debug, Clone)]
+pub struct TreeCursor<'a, T>
+where
+    T: PathTreeTypes,
+{
+    tree: &'a PathTree<T>,
+    node: &'a Arc<TreeNode<T>>,
+    path: Vec<&'a T::PathSegment>,
+}
+
+impl<'a, T> TreeCursor<'a, T>
+where
+    T: PathTreeTypes,
+{
+    #[must_use]
+    pub const fn tree(&self) -> &'a PathTree<T> {
+        self.tree
+    }
+
+    /// The current node.
+    #[must_use]
+    pub const fn node(&self) -> &'a Arc<TreeNode<T>> {
+        self.node
+    }
+
+    /// The path of the current node.
+    #[must_use]
+    pub fn path(&self) -> &[&'a T::PathSegment] {
+        &self.path
+    }
+
+    /// The depth of the current node, i.e. 0 for the root node.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Move to the parent node.
+    ///
+    /// Returns `false` and stays at the root node.
+    pub fn move_up(&mut self) -> bool {
+        let Some(parent) = &self.node.parent else {
+            return false;
+        };
+        self.node = self.tree.get_node(parent.node_id.clone());
+        self.path.pop();
+        true
+    }
+
+    /// Move to a child node.
+    ///
+    /// Returns `false` and stays at the current node if the child node
+    /// does not exist.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn move_down(&mut self, child_path_segment: &T::PathSegment) -> bool {
+        let Some(child_node_id) = self.node.node.find_child(child_path_segment) else {
+            return false;
+        };
+        let child_node = self.tree.get_node(child_node_id);
+        let parent = child_node.parent.as_ref().expect("has parent");
+        self.node = child_node;
+        self.path.push(parent.path_segment.borrow());
+        true
+    }
+
+    /// Move to a sibling node.
+    ///
+    /// Returns `false` and stays at the current node if the sibling node
+    /// does not exist or if the current node is the root node.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn move_to_sibling(&mut self, sibling_path_segment: &T::PathSegment) -> bool {
+        let Some(parent) = &self.node.parent else {
+            return false;
+        };
+        let parent_node = self.tree.get_node(parent.node_id.clone());
+        let Some(sibling_node_id) = parent_node.node.find_child(sibling_path_segment) else {
+            return false;
+        };
+        let sibling_node = self.tree.get_node(sibling_node_id);
+        let parent = sibling_node.parent.as_ref().expect("has parent");
+        self.node = sibling_node;
+        *self.path.last_mut().expect("not the root node") = parent.path_segment.borrow();
+        true
+    }
+
+    /// Start editing the tree at the current node.
+    ///
+    /// The tree is cloned, which is cheap.
+    #[must_use]
+    pub fn to_zipper(&self) -> TreeZipper<T> {
+        TreeZipper {
+            tree: self.tree.clone(),
+            node_id: self.node.id.clone(),
+            path: self
+                .path
+                .iter()
+                .map(|path_segment| T::path_segment_to_owned(path_segment))
+                .collect(),
+        }
+    }
+}
+
+/// Navigates through an owned tree and edits nodes locally.
+///
+/// Edits are applied to the owned tree, which is returned by [`Self::commit()`].
+/// The original tree remains unchanged.
+///
+/// Created by [`PathTree::into_zipper()`] or [`TreeCursor::to_zipper()`].
+#[derive(Debug, Clone)]
+pub struct TreeZipper<T>
+where
+    T: PathTreeTypes,
+{
+    tree: PathTree<T>,
+    node_id: T::NodeId,
+    path: Vec<T::PathSegmentOwned>,
+}
+
+impl<T> TreeZipper<T>
+where
+    T: PathTreeTypes,
+{
+    /// The edited tree.
+    #[must_use]
+    pub const fn tree(&self) -> &PathTree<T> {
+        &self.tree
+    }
+
+    /// The current node.
+    #[must_use]
+    pub fn node(&self) -> &Arc<TreeNode<T>> {
+        self.tree.get_node(self.node_id.clone())
+    }
+
+    /// The path of the current node.
+    #[must_use]
+    pub fn path(&self) -> &[T::PathSegmentOwned] {
+        &self.path
+    }
+
+    /// The depth of the current node, i.e. 0 for the root node.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Move to the parent node.
+    ///
+    /// Returns `false` and stays at the root node.
+    pub fn move_up(&mut self) -> bool {
+        let Some(parent) = &self.node().parent else {
+            return false;
+        };
+        self.node_id = parent.node_id.clone();
+        self.path.pop();
+        true
+    }
+
+    /// Move to a child node.
+    ///
+    /// Returns `false` and stays at the current node if the child node
+    /// does not exist.
+    pub fn move_down(&mut self, child_path_segment: &T::PathSegment) -> bool {
+        let Some(child_node_id) = self.node().node.find_child(child_path_segment) else {
+            return false;
+        };
+        self.node_id = child_node_id;
+        self.path.push(T::path_segment_to_owned(child_path_segment));
+        true
+    }
+
+    /// Move to a sibling node.
+    ///
+    /// Returns `false` and stays at the current node if the sibling node
+    /// does not exist or if the current node is the root node.
+    #[allow(clippy::missing_panics_doc)] // Never panics
+    pub fn move_to_sibling(&mut self, sibling_path_segment: &T::PathSegment) -> bool {
+        let Some(parent) = &self.node().parent else {
+            return false;
+        };
+        let Some(sibling_node_id) = self
+            .tree
+            .get_node(parent.node_id.clone())
+            .node
+            .find_child(sibling_path_segment)
+        else {
+            return false;
+        };
+        self.node_id = sibling_node_id;
+        *self.path.last_mut().expect("not the root node") =
+            T::path_segment_to_owned(sibling_path_segment);
+        true
+    }
+
+    /// Replace the value of the current node.
+    ///
+    /// See also [`PathTree::update_node_value()`].
+    pub fn set_value(&mut self, new_value: NodeValue<T>) -> Result<(), UpdateNodeValueError<T>> {
+        let node = Arc::clone(self.node());
+        self.tree.update_node_value(&node, new_value)?;
+        Ok(())
+    }
+
+    /// Insert or update a child node of the current node.
+    ///
+    /// Stays at the current node.
+    ///
+    /// See also [`PathTree::insert_or_update_child_node_value()`].
+    pub fn insert_or_update_child(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+        new_value: NodeValue<T>,
+    ) -> Result<(), InsertOrUpdateNodeValueError<T>> {
+        let node = Arc::clone(self.node());
+        self.tree
+            .insert_or_update_child_node_value(&node, child_path_segment, None, new_value)?;
+        Ok(())
+    }
+
+    /// Remove a child node of the current node including all its descendants.
+    ///
+    /// Stays at the current node. Returns `None` if the child node does
+    /// not exist or if a frozen subtree would be affected, see
+    /// [`PathTree::remove_subtree_by_id()`].
+    pub fn remove_child(
+        &mut self,
+        child_path_segment: &T::PathSegment,
+    ) -> Option<SubtreeRemoved<T>> {
+        let child_node_id = self.node().node.find_child(child_path_segment)?;
+        self.tree.remove_subtree_by_id(child_node_id)
+    }
+
+    /// Finish editing and return the edited tree.
+    #[must_use]
+    pub fn commit(self) -> PathTree<T> {
+        self.tree
+    }
+}
+
+impl<T> PathTree<T>
+where
+    T: PathTreeTypes,
+{
+    /// Navigate through the tree, starting at the root node.
+    #[must_use]
+    pub fn cursor(&self) -> TreeCursor<'_, T> {
+        TreeCursor {
+            tree: self,
+            node: self.root_node(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Navigate through and edit the tree, starting at the root node.
+    #[must_use]
+    pub fn into_zipper(self) -> TreeZipper<T> {
+        TreeZipper {
+            node_id: self.root_node_id(),
+            tree: self,
+            path: Vec::new(),
+        }
+    }
+}